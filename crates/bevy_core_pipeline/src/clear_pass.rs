@@ -88,10 +88,15 @@ impl Node for ClearPassNode {
             if cleared_windows.contains(&window.id) {
                 continue;
             }
+            // skip windows that couldn't acquire a swap chain texture this frame
+            let swap_chain_texture = match &window.swap_chain_texture {
+                Some(swap_chain_texture) => swap_chain_texture,
+                None => continue,
+            };
             let pass_descriptor = RenderPassDescriptor {
                 label: Some("clear_pass"),
                 color_attachments: &[RenderPassColorAttachment {
-                    view: window.swap_chain_texture.as_ref().unwrap(),
+                    view: swap_chain_texture,
                     resolve_target: None,
                     ops: Operations {
                         load: LoadOp::Clear(clear_color.0.into()),
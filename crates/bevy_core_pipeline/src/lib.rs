@@ -395,6 +395,7 @@ pub fn prepare_core_views_system(
         commands.entity(entity).insert(ViewDepthTexture {
             texture: cached_texture.texture,
             view: cached_texture.default_view,
+            sample_count: msaa.samples,
         });
     }
 }
@@ -4,8 +4,8 @@ use bevy_render::{
     render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
     render_phase::{DrawFunctions, RenderPhase, TrackedRenderPass},
     render_resource::{LoadOp, Operations, RenderPassDescriptor},
-    renderer::RenderContext,
-    view::{ExtractedView, ViewTarget},
+    renderer::{validate_sample_counts, RenderContext},
+    view::{ExtractedView, Msaa, ViewTarget},
 };
 
 pub struct MainPass2dNode {
@@ -39,10 +39,13 @@ impl Node for MainPass2dNode {
         world: &World,
     ) -> Result<(), NodeRunError> {
         let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
-        let (transparent_phase, target) = self
-            .query
-            .get_manual(world, view_entity)
-            .expect("view entity should exist");
+        let (transparent_phase, target) = match self.query.get_manual(world, view_entity) {
+            Ok(query) => query,
+            Err(_) => return Ok(()), // No window
+        };
+        // The 2D pipelines are specialized for the `Msaa` sample count
+        let msaa = world.get_resource::<Msaa>().unwrap();
+        validate_sample_counts(&[target.sample_count], None, msaa.samples)?;
 
         let pass_descriptor = RenderPassDescriptor {
             label: Some("main_pass_2d"),
@@ -70,3 +73,33 @@ impl Node for MainPass2dNode {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_of_a_skipped_window_has_nothing_to_draw() {
+        let mut world = World::new();
+        // `prepare_windows` skips the frame of a window whose swap chain texture can't be
+        // acquired, so its views don't get a `ViewTarget`.
+        let view = world
+            .spawn()
+            .insert_bundle((
+                ExtractedView {
+                    projection: Default::default(),
+                    transform: Default::default(),
+                    width: 800,
+                    height: 600,
+                    near: 0.0,
+                    far: 1000.0,
+                },
+                RenderPhase::<Transparent2d>::default(),
+            ))
+            .id();
+
+        let mut node = MainPass2dNode::new(&mut world);
+        node.update(&mut world);
+        assert!(node.query.get_manual(&world, view).is_err());
+    }
+}
@@ -4,8 +4,8 @@ use bevy_render::{
     render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
     render_phase::{DrawFunctions, RenderPhase, TrackedRenderPass},
     render_resource::{LoadOp, Operations, RenderPassDepthStencilAttachment, RenderPassDescriptor},
-    renderer::RenderContext,
-    view::{ExtractedView, ViewDepthTexture, ViewTarget},
+    renderer::{validate_sample_counts, RenderContext},
+    view::{ExtractedView, Msaa, ViewDepthTexture, ViewTarget},
 };
 
 pub struct MainPass3dNode {
@@ -52,6 +52,13 @@ impl Node for MainPass3dNode {
                 Ok(query) => query,
                 Err(_) => return Ok(()), // No window
             };
+        // The 3D pipelines are specialized for the `Msaa` sample count
+        let msaa = world.get_resource::<Msaa>().unwrap();
+        validate_sample_counts(
+            &[target.sample_count],
+            Some(depth.sample_count),
+            msaa.samples,
+        )?;
 
         {
             // Run the opaque pass, sorted front-to-back
@@ -11,6 +11,18 @@ pub enum AlphaMode {
     /// will be fully opaque and < will be fully transparent
     Mask(f32),
     Blend,
+    /// Alpha blended like [`AlphaMode::Blend`], but drawing both faces of the mesh: the back faces
+    /// first, then the front faces, so that the back of a closed transparent surface (e.g. glass
+    /// or leaves) shows through its front without having to sort its triangles.
+    DoubleSidedTransparent,
+}
+
+impl AlphaMode {
+    /// Returns `true` if the mode blends with the geometry behind, so that meshes using it are
+    /// drawn back to front in the transparent pass.
+    pub fn is_transparent(&self) -> bool {
+        matches!(self, AlphaMode::Blend | AlphaMode::DoubleSidedTransparent)
+    }
 }
 
 impl Eq for AlphaMode {}
@@ -1,3 +1,8 @@
+pub mod morph;
+pub mod outline;
+pub mod particles;
+pub mod skybox;
+pub mod topology_override;
 pub mod wireframe;
 
 mod alpha;
@@ -29,6 +34,8 @@ pub mod draw_3d_graph {
     pub mod node {
         /// Label for the shadow pass node.
         pub const SHADOW_PASS: &str = "shadow_pass";
+        /// Label for the selection outline pass node.
+        pub const OUTLINE_PASS: &str = "outline_pass";
     }
 }
 
@@ -162,6 +162,11 @@ impl Default for DirectionalLightShadowMap {
 }
 
 /// An ambient light, which lights the entire scene equally.
+///
+/// It is uploaded every frame as the `ambient_color` of the `lights` uniform in the
+/// `bevy_pbr::mesh_view_bind_group` shader import, which is bound to every material pipeline. Custom
+/// material shaders importing it can read `lights.ambient_color.rgb`, already scaled by the
+/// `brightness`.
 #[derive(Debug)]
 pub struct AmbientLight {
     pub color: Color,
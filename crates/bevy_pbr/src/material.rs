@@ -1,4 +1,5 @@
 use crate::{
+    topology_override::{TopologyOverride, TopologyOverrideConfig},
     AlphaMode, DrawMesh, MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup,
     SetMeshViewBindGroup,
 };
@@ -269,6 +270,22 @@ impl<M: SpecializedMaterial, const I: usize> EntityRenderCommand for SetMaterial
     }
 }
 
+/// Returns the keys of the draws of a mesh with `alpha_mode` and `mesh_key`, in drawing order.
+///
+/// Meshes are drawn once, except with [`AlphaMode::DoubleSidedTransparent`], which draws the back
+/// faces of the mesh first and then its front faces.
+pub fn alpha_mode_draw_keys(
+    alpha_mode: AlphaMode,
+    mut mesh_key: MeshPipelineKey,
+) -> impl Iterator<Item = MeshPipelineKey> {
+    if alpha_mode.is_transparent() {
+        mesh_key |= MeshPipelineKey::TRANSPARENT_MAIN_PASS;
+    }
+    let back_faces = matches!(alpha_mode, AlphaMode::DoubleSidedTransparent)
+        .then(|| mesh_key | MeshPipelineKey::CULL_FRONT);
+    back_faces.into_iter().chain(std::iter::once(mesh_key))
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn queue_material_meshes<M: SpecializedMaterial>(
     opaque_draw_functions: Res<DrawFunctions<Opaque3d>>,
@@ -280,7 +297,13 @@ pub fn queue_material_meshes<M: SpecializedMaterial>(
     msaa: Res<Msaa>,
     render_meshes: Res<RenderAssets<Mesh>>,
     render_materials: Res<RenderAssets<M>>,
-    material_meshes: Query<(&Handle<M>, &Handle<Mesh>, &MeshUniform)>,
+    topology_override: Option<Res<TopologyOverrideConfig>>,
+    material_meshes: Query<(
+        &Handle<M>,
+        &Handle<Mesh>,
+        &MeshUniform,
+        Option<&TopologyOverride>,
+    )>,
     mut views: Query<(
         &ExtractedView,
         &VisibleEntities,
@@ -310,7 +333,7 @@ pub fn queue_material_meshes<M: SpecializedMaterial>(
         let mesh_key = MeshPipelineKey::from_msaa_samples(msaa.samples);
 
         for visible_entity in &visible_entities.entities {
-            if let Ok((material_handle, mesh_handle, mesh_uniform)) =
+            if let Ok((material_handle, mesh_handle, mesh_uniform, tagged)) =
                 material_meshes.get(*visible_entity)
             {
                 if let Some(material) = render_materials.get(material_handle) {
@@ -319,60 +342,66 @@ pub fn queue_material_meshes<M: SpecializedMaterial>(
                         if mesh.has_tangents {
                             mesh_key |= MeshPipelineKey::VERTEX_TANGENTS;
                         }
+                        let topology = match &topology_override {
+                            Some(config) => {
+                                config.topology(mesh.primitive_topology, tagged.is_some())
+                            }
+                            None => mesh.primitive_topology,
+                        };
+                        mesh_key |= MeshPipelineKey::from_primitive_topology(topology);
                         mesh_key |=
-                            MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+                            MeshPipelineKey::from_index_format(topology, mesh.index_format());
                     }
                     let alpha_mode = M::alpha_mode(material);
-                    if let AlphaMode::Blend = alpha_mode {
-                        mesh_key |= MeshPipelineKey::TRANSPARENT_MAIN_PASS;
-                    }
-
-                    let specialized_key = M::key(material);
-                    let pipeline_id = pipelines.specialize(
-                        &mut pipeline_cache,
-                        &material_pipeline,
-                        (mesh_key, specialized_key),
-                    );
-
                     // NOTE: row 2 of the inverse view matrix dotted with column 3 of the model matrix
                     // gives the z component of translation of the mesh in view space
                     let mesh_z = inverse_view_row_2.dot(mesh_uniform.transform.col(3));
-                    match alpha_mode {
-                        AlphaMode::Opaque => {
-                            opaque_phase.add(Opaque3d {
-                                entity: *visible_entity,
-                                draw_function: draw_opaque_pbr,
-                                pipeline: pipeline_id,
-                                // NOTE: Front-to-back ordering for opaque with ascending sort means near should have the
-                                // lowest sort key and getting further away should increase. As we have
-                                // -z in front of the camera, values in view space decrease away from the
-                                // camera. Flipping the sign of mesh_z results in the correct front-to-back ordering
-                                distance: -mesh_z,
-                            });
-                        }
-                        AlphaMode::Mask(_) => {
-                            alpha_mask_phase.add(AlphaMask3d {
-                                entity: *visible_entity,
-                                draw_function: draw_alpha_mask_pbr,
-                                pipeline: pipeline_id,
-                                // NOTE: Front-to-back ordering for alpha mask with ascending sort means near should have the
-                                // lowest sort key and getting further away should increase. As we have
-                                // -z in front of the camera, values in view space decrease away from the
-                                // camera. Flipping the sign of mesh_z results in the correct front-to-back ordering
-                                distance: -mesh_z,
-                            });
-                        }
-                        AlphaMode::Blend => {
-                            transparent_phase.add(Transparent3d {
-                                entity: *visible_entity,
-                                draw_function: draw_transparent_pbr,
-                                pipeline: pipeline_id,
-                                // NOTE: Back-to-front ordering for transparent with ascending sort means far should have the
-                                // lowest sort key and getting closer should increase. As we have
-                                // -z in front of the camera, the largest distance is -far with values increasing toward the
-                                // camera. As such we can just use mesh_z as the distance
-                                distance: mesh_z,
-                            });
+                    for mesh_key in alpha_mode_draw_keys(alpha_mode, mesh_key) {
+                        let pipeline_id = pipelines.specialize(
+                            &mut pipeline_cache,
+                            &material_pipeline,
+                            (mesh_key, M::key(material)),
+                        );
+
+                        match alpha_mode {
+                            AlphaMode::Opaque => {
+                                opaque_phase.add(Opaque3d {
+                                    entity: *visible_entity,
+                                    draw_function: draw_opaque_pbr,
+                                    pipeline: pipeline_id,
+                                    // NOTE: Front-to-back ordering for opaque with ascending sort means near should have the
+                                    // lowest sort key and getting further away should increase. As we have
+                                    // -z in front of the camera, values in view space decrease away from the
+                                    // camera. Flipping the sign of mesh_z results in the correct front-to-back ordering
+                                    distance: -mesh_z,
+                                });
+                            }
+                            AlphaMode::Mask(_) => {
+                                alpha_mask_phase.add(AlphaMask3d {
+                                    entity: *visible_entity,
+                                    draw_function: draw_alpha_mask_pbr,
+                                    pipeline: pipeline_id,
+                                    // NOTE: Front-to-back ordering for alpha mask with ascending sort means near should have the
+                                    // lowest sort key and getting further away should increase. As we have
+                                    // -z in front of the camera, values in view space decrease away from the
+                                    // camera. Flipping the sign of mesh_z results in the correct front-to-back ordering
+                                    distance: -mesh_z,
+                                });
+                            }
+                            // Both draws of a double sided mesh have the same distance, and the
+                            // phase is sorted with a stable sort, so its back faces are drawn first.
+                            AlphaMode::Blend | AlphaMode::DoubleSidedTransparent => {
+                                transparent_phase.add(Transparent3d {
+                                    entity: *visible_entity,
+                                    draw_function: draw_transparent_pbr,
+                                    pipeline: pipeline_id,
+                                    // NOTE: Back-to-front ordering for transparent with ascending sort means far should have the
+                                    // lowest sort key and getting closer should increase. As we have
+                                    // -z in front of the camera, the largest distance is -far with values increasing toward the
+                                    // camera. As such we can just use mesh_z as the distance
+                                    distance: mesh_z,
+                                });
+                            }
                         }
                     }
                 }
@@ -380,3 +409,35 @@ pub fn queue_material_meshes<M: SpecializedMaterial>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_render::render_resource::Face;
+
+    #[test]
+    fn double_sided_transparent_draws_back_faces_first() {
+        let mesh_key = MeshPipelineKey::from_msaa_samples(4);
+        let draws = |alpha_mode| alpha_mode_draw_keys(alpha_mode, mesh_key).collect::<Vec<_>>();
+
+        let double_sided = draws(AlphaMode::DoubleSidedTransparent);
+        assert_eq!(
+            double_sided
+                .iter()
+                .map(MeshPipelineKey::cull_mode)
+                .collect::<Vec<_>>(),
+            vec![Face::Front, Face::Back]
+        );
+        for key in &double_sided {
+            assert!(key.contains(MeshPipelineKey::TRANSPARENT_MAIN_PASS));
+            assert_eq!(key.msaa_samples(), 4);
+        }
+
+        assert_eq!(
+            draws(AlphaMode::Blend),
+            vec![mesh_key | MeshPipelineKey::TRANSPARENT_MAIN_PASS]
+        );
+        assert_eq!(draws(AlphaMode::Opaque), vec![mesh_key]);
+        assert_eq!(draws(AlphaMode::Opaque)[0].cull_mode(), Face::Back);
+    }
+}
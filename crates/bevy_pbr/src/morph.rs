@@ -0,0 +1,325 @@
+use bevy_app::Plugin;
+use bevy_asset::Handle;
+use bevy_ecs::{
+    prelude::*,
+    system::{lifetimeless::*, SystemParamItem},
+};
+use bevy_math::Vec4;
+use bevy_render::{
+    mesh::Mesh,
+    render_asset::RenderAssets,
+    render_component::{ComponentUniforms, DynamicUniformIndex, UniformComponentPlugin},
+    render_phase::{EntityRenderCommand, RenderCommandResult, TrackedRenderPass},
+    render_resource::{std140::AsStd140, *},
+    renderer::RenderDevice,
+    view::ComputedVisibility,
+    RenderApp, RenderStage,
+};
+use bevy_utils::tracing::warn;
+
+/// The maximum number of [`MorphTargets`] blended on a mesh.
+pub const MAX_MORPH_TARGETS: usize = 4;
+
+/// Uploads the [`MorphTargets`] of meshes, so that custom mesh pipelines can blend them.
+#[derive(Debug, Default)]
+pub struct MorphTargetsPlugin;
+
+impl Plugin for MorphTargetsPlugin {
+    fn build(&self, app: &mut bevy_app::App) {
+        app.add_plugin(UniformComponentPlugin::<MorphWeights>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<MorphPipeline>()
+                .add_system_to_stage(RenderStage::Extract, extract_morph_targets)
+                .add_system_to_stage(RenderStage::Queue, queue_morph_weights_bind_group);
+        }
+    }
+}
+
+/// Morph targets, or blend shapes, deforming the [`Mesh`] of an entity, e.g. for facial
+/// animation.
+///
+/// Each target is a mesh holding only the position deltas of the target, as a
+/// [`Mesh::ATTRIBUTE_POSITION`] with as many vertices as the base mesh. The vertex buffer of the
+/// target at `index` is bound to the slot `FIRST_SLOT + index` by
+/// [`SetMorphTargetVertexBuffers`], and the weights are uploaded to the [`MorphWeights`] uniform
+/// every frame, which [`SetMorphWeightsBindGroup`] binds.
+///
+/// By convention, the vertex shader adds each delta to the base position, scaled by the weight of
+/// its target: `position + weights.x * delta_0 + weights.y * delta_1 + ...`.
+#[derive(Component, Debug, Clone, Default)]
+pub struct MorphTargets {
+    /// The meshes holding the position deltas of each target, at most [`MAX_MORPH_TARGETS`]
+    pub targets: Vec<Handle<Mesh>>,
+    /// The weight of each target. Targets without a weight aren't blended.
+    pub weights: Vec<f32>,
+}
+
+/// The weights of the [`MorphTargets`] of a mesh, as uploaded to the GPU.
+#[derive(Component, AsStd140, Debug, Clone, PartialEq)]
+pub struct MorphWeights {
+    /// The weight of each target, in order. Unused components are `0.0`.
+    pub weights: Vec4,
+    /// The number of targets bound to the mesh
+    pub target_count: u32,
+}
+
+impl MorphWeights {
+    /// Packs the weights of `morph_targets`, dropping the targets past [`MAX_MORPH_TARGETS`].
+    pub fn new(morph_targets: &MorphTargets) -> Self {
+        let target_count = morph_targets.targets.len().min(MAX_MORPH_TARGETS);
+        let mut weights = [0.0; MAX_MORPH_TARGETS];
+        for (weight, target_weight) in weights
+            .iter_mut()
+            .zip(&morph_targets.weights)
+            .take(target_count)
+        {
+            *weight = *target_weight;
+        }
+        MorphWeights {
+            weights: Vec4::from(weights),
+            target_count: target_count as u32,
+        }
+    }
+}
+
+/// The target meshes of the [`MorphTargets`] of an entity, in the render world.
+#[derive(Component, Debug, Clone)]
+pub struct ExtractedMorphTargets {
+    pub targets: Vec<Handle<Mesh>>,
+}
+
+pub fn extract_morph_targets(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    query: Query<(Entity, &ComputedVisibility, &MorphTargets)>,
+) {
+    let mut values = Vec::with_capacity(*previous_len);
+    for (entity, computed_visibility, morph_targets) in query.iter() {
+        if !computed_visibility.is_visible {
+            continue;
+        }
+        if morph_targets.targets.len() > MAX_MORPH_TARGETS {
+            warn!(
+                "Mesh entity {:?} has {} morph targets, only the first {} are blended",
+                entity,
+                morph_targets.targets.len(),
+                MAX_MORPH_TARGETS
+            );
+        }
+        let targets = morph_targets
+            .targets
+            .iter()
+            .take(MAX_MORPH_TARGETS)
+            .map(Handle::clone_weak)
+            .collect();
+        values.push((
+            entity,
+            (
+                ExtractedMorphTargets { targets },
+                MorphWeights::new(morph_targets),
+            ),
+        ));
+    }
+    *previous_len = values.len();
+    commands.insert_or_spawn_batch(values);
+}
+
+pub struct MorphPipeline {
+    /// The layout of the bind group of the [`MorphWeights`] uniform, visible to the vertex stage
+    pub weights_layout: BindGroupLayout,
+}
+
+impl FromWorld for MorphPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.get_resource::<RenderDevice>().unwrap();
+        let weights_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: BufferSize::new(MorphWeights::std140_size_static() as u64),
+                },
+                count: None,
+            }],
+            label: Some("morph_weights_layout"),
+        });
+        MorphPipeline { weights_layout }
+    }
+}
+
+impl MorphPipeline {
+    /// The layouts of the vertex buffers of `target_count` morph targets, holding a
+    /// [`Float32x3`](VertexFormat::Float32x3) position delta per vertex each, read from the
+    /// shader locations `first_location..first_location + target_count`.
+    pub fn target_vertex_buffer_layouts(
+        target_count: usize,
+        first_location: u32,
+    ) -> Vec<VertexBufferLayout> {
+        (0..target_count.min(MAX_MORPH_TARGETS) as u32)
+            .map(|index| VertexBufferLayout {
+                array_stride: VertexFormat::Float32x3.size(),
+                step_mode: VertexStepMode::Vertex,
+                attributes: vec![VertexAttribute {
+                    format: VertexFormat::Float32x3,
+                    offset: 0,
+                    shader_location: first_location + index,
+                }],
+            })
+            .collect()
+    }
+}
+
+pub struct MorphWeightsBindGroup {
+    pub value: BindGroup,
+}
+
+pub fn queue_morph_weights_bind_group(
+    mut commands: Commands,
+    morph_pipeline: Res<MorphPipeline>,
+    render_device: Res<RenderDevice>,
+    morph_weights: Res<ComponentUniforms<MorphWeights>>,
+) {
+    if let Some(binding) = morph_weights.uniforms().binding() {
+        commands.insert_resource(MorphWeightsBindGroup {
+            value: render_device.create_bind_group(&BindGroupDescriptor {
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: binding,
+                }],
+                label: Some("morph_weights_bind_group"),
+                layout: &morph_pipeline.weights_layout,
+            }),
+        });
+    }
+}
+
+pub struct SetMorphWeightsBindGroup<const I: usize>;
+impl<const I: usize> EntityRenderCommand for SetMorphWeightsBindGroup<I> {
+    type Param = (
+        SRes<MorphWeightsBindGroup>,
+        SQuery<Read<DynamicUniformIndex<MorphWeights>>>,
+    );
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (bind_group, weights_query): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        match weights_query.get(item) {
+            Ok(weights_index) => {
+                pass.set_bind_group(I, &bind_group.into_inner().value, &[weights_index.index()]);
+                RenderCommandResult::Success
+            }
+            Err(_) => RenderCommandResult::Failure,
+        }
+    }
+}
+
+/// Pairs the vertex buffer of each of `targets`, as returned by `vertex_buffer`, with the slot it
+/// is bound to, starting at `first_slot`.
+///
+/// Returns `None` if a target has no vertex buffer, e.g. because its mesh isn't uploaded yet.
+pub fn morph_target_vertex_buffers<B>(
+    first_slot: usize,
+    targets: &[Handle<Mesh>],
+    mut vertex_buffer: impl FnMut(&Handle<Mesh>) -> Option<B>,
+) -> Option<Vec<(usize, B)>> {
+    targets
+        .iter()
+        .enumerate()
+        .map(|(index, target)| Some((first_slot + index, vertex_buffer(target)?)))
+        .collect()
+}
+
+/// Binds the vertex buffers of the [`MorphTargets`] of the item, starting at the slot
+/// `FIRST_SLOT`.
+pub struct SetMorphTargetVertexBuffers<const FIRST_SLOT: usize>;
+impl<const FIRST_SLOT: usize> EntityRenderCommand for SetMorphTargetVertexBuffers<FIRST_SLOT> {
+    type Param = (
+        SRes<RenderAssets<Mesh>>,
+        SQuery<Read<ExtractedMorphTargets>>,
+    );
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (meshes, morph_query): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let morph_targets = match morph_query.get(item) {
+            Ok(morph_targets) => morph_targets,
+            Err(_) => return RenderCommandResult::Failure,
+        };
+        let meshes = meshes.into_inner();
+        let buffers = morph_target_vertex_buffers(FIRST_SLOT, &morph_targets.targets, |target| {
+            meshes.get(target).map(|gpu_mesh| &gpu_mesh.vertex_buffer)
+        });
+        match buffers {
+            Some(buffers) => {
+                for (slot, buffer) in buffers {
+                    pass.set_vertex_buffer(slot, buffer.slice(..));
+                }
+                RenderCommandResult::Success
+            }
+            None => RenderCommandResult::Failure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_asset::HandleId;
+    use bevy_ecs::schedule::{Stage, SystemStage};
+
+    #[test]
+    fn morph_weights_are_extracted_and_delta_buffers_bound() {
+        let smile = Handle::<Mesh>::weak(HandleId::random::<Mesh>());
+        let blink = Handle::<Mesh>::weak(HandleId::random::<Mesh>());
+
+        let mut world = World::default();
+        let face = world
+            .spawn()
+            .insert_bundle((
+                ComputedVisibility::default(),
+                MorphTargets {
+                    targets: vec![smile.clone(), blink.clone()],
+                    weights: vec![0.75, 0.25],
+                },
+            ))
+            .id();
+
+        SystemStage::single(extract_morph_targets).run(&mut world);
+
+        assert_eq!(
+            world.get::<MorphWeights>(face),
+            Some(&MorphWeights {
+                weights: Vec4::new(0.75, 0.25, 0.0, 0.0),
+                target_count: 2,
+            })
+        );
+        let extracted = world.get::<ExtractedMorphTargets>(face).unwrap();
+        assert_eq!(extracted.targets, vec![smile.clone(), blink.clone()]);
+
+        // Slot 0 holds the base mesh, the deltas follow it.
+        let buffer_ids = |target: &Handle<Mesh>| {
+            [&smile, &blink]
+                .iter()
+                .position(|handle| *handle == target)
+                .map(|index| 10 + index)
+        };
+        assert_eq!(
+            morph_target_vertex_buffers(1, &extracted.targets, buffer_ids),
+            Some(vec![(1, 10), (2, 11)])
+        );
+        assert_eq!(
+            morph_target_vertex_buffers(1, &extracted.targets, |_| None::<usize>),
+            None
+        );
+    }
+}
@@ -0,0 +1,575 @@
+use crate::{
+    DrawMesh, MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup,
+};
+use bevy_app::Plugin;
+use bevy_asset::{Assets, Handle, HandleUntyped};
+use bevy_core::FloatOrd;
+use bevy_ecs::{
+    prelude::*,
+    reflect::ReflectComponent,
+    system::{lifetimeless::*, SystemParamItem},
+};
+use bevy_math::Vec4;
+use bevy_reflect::{Reflect, TypeUuid};
+use bevy_render::{
+    camera::{ActiveCameras, CameraPlugin},
+    color::Color,
+    mesh::Mesh,
+    render_asset::RenderAssets,
+    render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType},
+    render_phase::{
+        AddRenderCommand, CachedPipelinePhaseItem, DrawFunctionId, DrawFunctions, EntityPhaseItem,
+        EntityRenderCommand, PhaseItem, RenderCommandResult, RenderPhase, SetItemPipeline,
+        TrackedRenderPass,
+    },
+    render_resource::{std140::AsStd140, *},
+    renderer::{RenderContext, RenderDevice, RenderQueue},
+    texture::TextureCache,
+    view::{ExtractedView, Msaa, ViewTarget},
+    RenderApp, RenderStage,
+};
+
+pub const OUTLINE_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 7193824750186423119);
+
+/// The format of the per-view stencil buffer the selection outline is rendered with.
+pub const OUTLINE_STENCIL_FORMAT: TextureFormat = TextureFormat::Depth24PlusStencil8;
+
+/// The stencil value written for the silhouettes of [`Selected`] entities.
+pub const OUTLINE_STENCIL_REFERENCE: u32 = 1;
+
+/// Draws an outline around all [`Selected`] entities, as configured by [`SelectionOutline`].
+///
+/// The outline is rendered in two passes over the selected meshes: the first writes their
+/// silhouettes to a stencil buffer, the second draws the meshes extruded by
+/// [`SelectionOutline::width`] wherever the stencil has not been written.
+#[derive(Debug, Default)]
+pub struct SelectionOutlinePlugin;
+
+impl Plugin for SelectionOutlinePlugin {
+    fn build(&self, app: &mut bevy_app::App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            OUTLINE_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("render/outline.wgsl")),
+        );
+
+        app.register_type::<Selected>()
+            .init_resource::<SelectionOutline>();
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(render_app) => render_app,
+            Err(_) => return,
+        };
+
+        render_app
+            .init_resource::<DrawFunctions<Outline3d>>()
+            .add_render_command::<Outline3d, DrawOutline>()
+            .init_resource::<OutlinePipeline>()
+            .init_resource::<OutlineMeta>()
+            .init_resource::<SpecializedPipelines<OutlinePipeline>>()
+            .add_system_to_stage(RenderStage::Extract, extract_selected)
+            .add_system_to_stage(RenderStage::Extract, extract_selection_outline)
+            .add_system_to_stage(RenderStage::Extract, extract_outline_camera_phases)
+            .add_system_to_stage(RenderStage::Prepare, prepare_outline)
+            .add_system_to_stage(RenderStage::Queue, queue_outline_bind_group)
+            .add_system_to_stage(RenderStage::Queue, queue_outlines)
+            .add_system_to_stage(
+                RenderStage::PhaseSort,
+                bevy_render::render_phase::sort_phase_system::<Outline3d>,
+            );
+
+        let outline_pass_node = OutlinePassNode::new(&mut render_app.world);
+        let mut graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+        let draw_3d_graph = graph
+            .get_sub_graph_mut(bevy_core_pipeline::draw_3d_graph::NAME)
+            .unwrap();
+        draw_3d_graph.add_node(crate::draw_3d_graph::node::OUTLINE_PASS, outline_pass_node);
+        draw_3d_graph
+            .add_node_edge(
+                bevy_core_pipeline::draw_3d_graph::node::MAIN_PASS,
+                crate::draw_3d_graph::node::OUTLINE_PASS,
+            )
+            .unwrap();
+        draw_3d_graph
+            .add_slot_edge(
+                draw_3d_graph.input_node().unwrap().id,
+                bevy_core_pipeline::draw_3d_graph::input::VIEW_ENTITY,
+                crate::draw_3d_graph::node::OUTLINE_PASS,
+                OutlinePassNode::IN_VIEW,
+            )
+            .unwrap();
+    }
+}
+
+/// Marks an entity to be outlined by the [`SelectionOutlinePlugin`].
+#[derive(Component, Debug, Clone, Default, Reflect)]
+#[reflect(Component)]
+pub struct Selected;
+
+/// Configures the outline drawn around [`Selected`] entities.
+#[derive(Debug, Clone)]
+pub struct SelectionOutline {
+    /// The color of the outline
+    pub color: Color,
+    /// The width of the outline, in pixels
+    pub width: f32,
+}
+
+impl Default for SelectionOutline {
+    fn default() -> Self {
+        Self {
+            color: Color::ORANGE,
+            width: 4.0,
+        }
+    }
+}
+
+fn extract_selection_outline(mut commands: Commands, outline: Res<SelectionOutline>) {
+    if outline.is_added() || outline.is_changed() {
+        commands.insert_resource(outline.into_inner().clone());
+    }
+}
+
+fn extract_selected(mut commands: Commands, query: Query<Entity, With<Selected>>) {
+    for entity in query.iter() {
+        commands.get_or_spawn(entity).insert(Selected);
+    }
+}
+
+fn extract_outline_camera_phases(mut commands: Commands, active_cameras: Res<ActiveCameras>) {
+    if let Some(camera_3d) = active_cameras.get(CameraPlugin::CAMERA_3D) {
+        if let Some(entity) = camera_3d.entity {
+            commands
+                .get_or_spawn(entity)
+                .insert(RenderPhase::<Outline3d>::default());
+        }
+    }
+}
+
+/// One of the two passes the selection outline is drawn with.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OutlinePass {
+    /// Writes [`OUTLINE_STENCIL_REFERENCE`] to the stencil buffer for every covered pixel.
+    Mask,
+    /// Draws the extruded mesh wherever the stencil is not [`OUTLINE_STENCIL_REFERENCE`].
+    Outline,
+}
+
+impl OutlinePass {
+    /// Returns the stencil state used by this pass.
+    ///
+    /// Both passes are drawn with [`OUTLINE_STENCIL_REFERENCE`] as the stencil reference.
+    pub fn stencil_state(&self) -> StencilState {
+        let face = match self {
+            OutlinePass::Mask => StencilFaceState {
+                compare: CompareFunction::Always,
+                fail_op: StencilOperation::Keep,
+                depth_fail_op: StencilOperation::Keep,
+                pass_op: StencilOperation::Replace,
+            },
+            OutlinePass::Outline => StencilFaceState {
+                compare: CompareFunction::NotEqual,
+                fail_op: StencilOperation::Keep,
+                depth_fail_op: StencilOperation::Keep,
+                pass_op: StencilOperation::Keep,
+            },
+        };
+        StencilState {
+            front: face,
+            back: face,
+            read_mask: !0,
+            write_mask: match self {
+                OutlinePass::Mask => !0,
+                OutlinePass::Outline => 0,
+            },
+        }
+    }
+
+    /// Returns the depth/stencil state used by this pass. The outline is drawn on top of the
+    /// scene, so depth is neither tested nor written.
+    pub fn depth_stencil_state(&self) -> DepthStencilState {
+        DepthStencilState {
+            format: OUTLINE_STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: CompareFunction::Always,
+            stencil: self.stencil_state(),
+            bias: DepthBiasState {
+                constant: 0,
+                slope_scale: 0.0,
+                clamp: 0.0,
+            },
+        }
+    }
+}
+
+#[derive(Clone, AsStd140)]
+pub struct OutlineUniform {
+    color: Vec4,
+    width: f32,
+}
+
+#[derive(Default)]
+pub struct OutlineMeta {
+    pub uniforms: UniformVec<OutlineUniform>,
+    pub bind_group: Option<BindGroup>,
+}
+
+pub struct OutlinePipeline {
+    mesh_pipeline: MeshPipeline,
+    outline_layout: BindGroupLayout,
+    shader: Handle<Shader>,
+}
+
+impl FromWorld for OutlinePipeline {
+    fn from_world(render_world: &mut World) -> Self {
+        let render_device = render_world.get_resource::<RenderDevice>().unwrap();
+        let outline_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(OutlineUniform::std140_size_static() as u64),
+                },
+                count: None,
+            }],
+            label: Some("outline_layout"),
+        });
+
+        OutlinePipeline {
+            mesh_pipeline: render_world.get_resource::<MeshPipeline>().unwrap().clone(),
+            outline_layout,
+            shader: OUTLINE_SHADER_HANDLE.typed(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct OutlinePipelineKey {
+    pub mesh_key: MeshPipelineKey,
+    pub pass: OutlinePass,
+}
+
+impl SpecializedPipeline for OutlinePipeline {
+    type Key = OutlinePipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut descriptor = self.mesh_pipeline.specialize(key.mesh_key);
+        descriptor.vertex.shader = self.shader.clone_weak();
+        descriptor.vertex.entry_point = match key.pass {
+            OutlinePass::Mask => "mask_vertex".into(),
+            OutlinePass::Outline => "outline_vertex".into(),
+        };
+        let fragment = descriptor.fragment.as_mut().unwrap();
+        fragment.shader = self.shader.clone_weak();
+        let target = &mut fragment.targets[0];
+        target.blend = Some(BlendState::ALPHA_BLENDING);
+        if key.pass == OutlinePass::Mask {
+            target.write_mask = ColorWrites::empty();
+        }
+        descriptor
+            .layout
+            .as_mut()
+            .unwrap()
+            .push(self.outline_layout.clone());
+        descriptor.depth_stencil = Some(key.pass.depth_stencil_state());
+        descriptor.label = Some(match key.pass {
+            OutlinePass::Mask => "outline_mask_pipeline".into(),
+            OutlinePass::Outline => "outline_pipeline".into(),
+        });
+        descriptor
+    }
+}
+
+/// The stencil buffer a view's selection outline is rendered with.
+#[derive(Component)]
+pub struct ViewOutlineStencilTexture {
+    pub texture: Texture,
+    pub view: TextureView,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_outline(
+    mut commands: Commands,
+    mut texture_cache: ResMut<TextureCache>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    msaa: Res<Msaa>,
+    outline: Res<SelectionOutline>,
+    mut outline_meta: ResMut<OutlineMeta>,
+    views: Query<(Entity, &ExtractedView), With<RenderPhase<Outline3d>>>,
+) {
+    outline_meta.uniforms.clear();
+    outline_meta.uniforms.push(OutlineUniform {
+        color: Vec4::from_slice(&outline.color.as_linear_rgba_f32()),
+        width: outline.width,
+    });
+    outline_meta
+        .uniforms
+        .write_buffer(&render_device, &render_queue);
+
+    for (entity, view) in views.iter() {
+        let cached_texture = texture_cache.get(
+            &render_device,
+            TextureDescriptor {
+                label: Some("view_outline_stencil_texture"),
+                size: Extent3d {
+                    depth_or_array_layers: 1,
+                    width: view.width,
+                    height: view.height,
+                },
+                mip_level_count: 1,
+                sample_count: msaa.samples,
+                dimension: TextureDimension::D2,
+                format: OUTLINE_STENCIL_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+            },
+        );
+        commands.entity(entity).insert(ViewOutlineStencilTexture {
+            texture: cached_texture.texture,
+            view: cached_texture.default_view,
+        });
+    }
+}
+
+fn queue_outline_bind_group(
+    render_device: Res<RenderDevice>,
+    outline_pipeline: Res<OutlinePipeline>,
+    mut outline_meta: ResMut<OutlineMeta>,
+) {
+    let bind_group = outline_meta.uniforms.binding().map(|binding| {
+        render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: binding,
+            }],
+            label: Some("outline_bind_group"),
+            layout: &outline_pipeline.outline_layout,
+        })
+    });
+    outline_meta.bind_group = bind_group;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_outlines(
+    outline_draw_functions: Res<DrawFunctions<Outline3d>>,
+    render_meshes: Res<RenderAssets<Mesh>>,
+    outline_pipeline: Res<OutlinePipeline>,
+    mut pipeline_cache: ResMut<RenderPipelineCache>,
+    mut specialized_pipelines: ResMut<SpecializedPipelines<OutlinePipeline>>,
+    msaa: Res<Msaa>,
+    selected_meshes: Query<(Entity, &Handle<Mesh>, &MeshUniform), With<Selected>>,
+    mut views: Query<(&ExtractedView, &mut RenderPhase<Outline3d>)>,
+) {
+    let draw_outline = outline_draw_functions
+        .read()
+        .get_id::<DrawOutline>()
+        .unwrap();
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples);
+    for (view, mut outline_phase) in views.iter_mut() {
+        let view_matrix = view.transform.compute_matrix();
+        let view_row_2 = view_matrix.row(2);
+        for (entity, mesh_handle, mesh_uniform) in selected_meshes.iter() {
+            let mesh = match render_meshes.get(mesh_handle) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let mut mesh_key = msaa_key
+                | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology)
+                | MeshPipelineKey::from_index_format(mesh.primitive_topology, mesh.index_format());
+            if mesh.has_tangents {
+                mesh_key |= MeshPipelineKey::VERTEX_TANGENTS;
+            }
+            for pass in [OutlinePass::Mask, OutlinePass::Outline] {
+                outline_phase.add(Outline3d {
+                    pass,
+                    distance: view_row_2.dot(mesh_uniform.transform.col(3)),
+                    pipeline: specialized_pipelines.specialize(
+                        &mut pipeline_cache,
+                        &outline_pipeline,
+                        OutlinePipelineKey { mesh_key, pass },
+                    ),
+                    entity,
+                    draw_function: draw_outline,
+                });
+            }
+        }
+    }
+}
+
+/// A selected mesh drawn in one of the [`OutlinePass`]es.
+///
+/// Items are sorted so that all [`OutlinePass::Mask`] draws happen before any
+/// [`OutlinePass::Outline`] draw.
+pub struct Outline3d {
+    pub pass: OutlinePass,
+    pub distance: f32,
+    pub pipeline: CachedPipelineId,
+    pub entity: Entity,
+    pub draw_function: DrawFunctionId,
+}
+
+impl PhaseItem for Outline3d {
+    type SortKey = (OutlinePass, FloatOrd);
+
+    #[inline]
+    fn sort_key(&self) -> Self::SortKey {
+        (self.pass, FloatOrd(self.distance))
+    }
+
+    #[inline]
+    fn draw_function(&self) -> DrawFunctionId {
+        self.draw_function
+    }
+}
+
+impl EntityPhaseItem for Outline3d {
+    #[inline]
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+impl CachedPipelinePhaseItem for Outline3d {
+    #[inline]
+    fn cached_pipeline(&self) -> CachedPipelineId {
+        self.pipeline
+    }
+}
+
+pub struct SetOutlineBindGroup<const I: usize>;
+impl<const I: usize> EntityRenderCommand for SetOutlineBindGroup<I> {
+    type Param = SRes<OutlineMeta>;
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        _item: Entity,
+        outline_meta: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        match &outline_meta.into_inner().bind_group {
+            Some(bind_group) => {
+                pass.set_bind_group(I, bind_group, &[]);
+                RenderCommandResult::Success
+            }
+            None => RenderCommandResult::Failure,
+        }
+    }
+}
+
+type DrawOutline = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    SetOutlineBindGroup<2>,
+    DrawMesh,
+);
+
+pub struct OutlinePassNode {
+    query: QueryState<
+        (
+            &'static RenderPhase<Outline3d>,
+            &'static ViewTarget,
+            &'static ViewOutlineStencilTexture,
+        ),
+        With<ExtractedView>,
+    >,
+}
+
+impl OutlinePassNode {
+    pub const IN_VIEW: &'static str = "view";
+
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            query: QueryState::new(world),
+        }
+    }
+}
+
+impl Node for OutlinePassNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(OutlinePassNode::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
+        let (outline_phase, target, stencil) = match self.query.get_manual(world, view_entity) {
+            Ok(query) => query,
+            Err(_) => return Ok(()), // No window
+        };
+        if outline_phase.items.is_empty() {
+            return Ok(());
+        }
+
+        let pass_descriptor = RenderPassDescriptor {
+            label: Some("outline_pass_3d"),
+            color_attachments: &[target.get_color_attachment(Operations {
+                load: LoadOp::Load,
+                store: true,
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &stencil.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(0.0),
+                    store: false,
+                }),
+                stencil_ops: Some(Operations {
+                    load: LoadOp::Clear(0),
+                    store: false,
+                }),
+            }),
+        };
+
+        let draw_functions = world.get_resource::<DrawFunctions<Outline3d>>().unwrap();
+        let render_pass = render_context
+            .command_encoder
+            .begin_render_pass(&pass_descriptor);
+        let mut draw_functions = draw_functions.write();
+        let mut tracked_pass = TrackedRenderPass::new(render_pass);
+        tracked_pass.set_stencil_reference(OUTLINE_STENCIL_REFERENCE);
+        for item in &outline_phase.items {
+            let draw_function = draw_functions.get_mut(item.draw_function).unwrap();
+            draw_function.draw(world, &mut tracked_pass, view_entity, item);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outline_passes_share_the_stencil_reference() {
+        let mask = OutlinePass::Mask.stencil_state();
+        assert_eq!(mask.front.compare, CompareFunction::Always);
+        assert_eq!(mask.front.pass_op, StencilOperation::Replace);
+        assert_eq!(mask.front, mask.back);
+        assert_ne!(mask.write_mask & OUTLINE_STENCIL_REFERENCE, 0);
+
+        let outline = OutlinePass::Outline.stencil_state();
+        assert_eq!(outline.front.compare, CompareFunction::NotEqual);
+        assert_eq!(outline.front.pass_op, StencilOperation::Keep);
+        assert_eq!(outline.front, outline.back);
+        assert_ne!(outline.read_mask & OUTLINE_STENCIL_REFERENCE, 0);
+        assert_eq!(outline.write_mask, 0);
+
+        for pass in [OutlinePass::Mask, OutlinePass::Outline] {
+            assert_eq!(pass.depth_stencil_state().format, OUTLINE_STENCIL_FORMAT);
+        }
+        // The mask has to be complete before any outline is drawn
+        assert!(OutlinePass::Mask < OutlinePass::Outline);
+    }
+}
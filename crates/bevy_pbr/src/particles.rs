@@ -0,0 +1,403 @@
+use crate::{MeshPipeline, SetMeshViewBindGroup};
+use bevy_app::Plugin;
+use bevy_asset::{Assets, Handle, HandleUntyped};
+use bevy_core_pipeline::Transparent3d;
+use bevy_ecs::{
+    prelude::*,
+    system::{lifetimeless::*, SystemParamItem},
+};
+use bevy_math::Vec3;
+use bevy_reflect::TypeUuid;
+use bevy_render::{
+    color::Color,
+    render_phase::{
+        AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase,
+        SetItemPipeline, TrackedRenderPass,
+    },
+    render_resource::*,
+    renderer::{RenderDevice, RenderQueue},
+    texture::BevyDefault,
+    view::{ExtractedView, Msaa, Visibility},
+    RenderApp, RenderStage,
+};
+use bevy_transform::components::GlobalTransform;
+use bytemuck::{Pod, Zeroable};
+use std::ops::Range;
+
+pub const PARTICLE_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 2947286519082465913);
+
+/// The number of vertices of the quad each particle is drawn as.
+pub const PARTICLE_QUAD_VERTICES: u32 = 6;
+
+/// Draws every [`ParticleBatch`] as camera-facing quads, using one instanced draw per batch.
+#[derive(Debug, Default)]
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut bevy_app::App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            PARTICLE_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("render/particles.wgsl")),
+        );
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .add_render_command::<Transparent3d, DrawParticles>()
+                .init_resource::<ParticlePipeline>()
+                .init_resource::<ParticleMeta>()
+                .init_resource::<SpecializedPipelines<ParticlePipeline>>()
+                .add_system_to_stage(RenderStage::Extract, extract_particle_batches)
+                .add_system_to_stage(RenderStage::Prepare, prepare_particle_batches)
+                .add_system_to_stage(RenderStage::Queue, queue_particle_batches);
+        }
+    }
+}
+
+/// A single particle of a [`ParticleBatch`].
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    /// The position of the particle, relative to the entity holding the [`ParticleBatch`]
+    pub position: Vec3,
+    /// The width and height of the quad the particle is drawn as, in world units
+    pub size: f32,
+    /// The color of the particle
+    pub color: Color,
+}
+
+/// A set of particles drawn together by the [`ParticlePlugin`].
+///
+/// Particles are far too numerous to be entities of their own, so a whole batch is uploaded to
+/// a single instance buffer and drawn with one instanced draw call.
+#[derive(Component, Debug, Clone, Default)]
+pub struct ParticleBatch {
+    pub particles: Vec<Particle>,
+}
+
+/// The per-instance vertex data of a particle.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable)]
+pub struct ParticleInstance {
+    pub position: [f32; 3],
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct ExtractedParticleBatch {
+    pub instances: Vec<ParticleInstance>,
+    /// The world space origin of the batch, used to sort it against other transparent items
+    pub origin: Vec3,
+}
+
+impl ExtractedParticleBatch {
+    pub fn new(batch: &ParticleBatch, transform: &GlobalTransform) -> Self {
+        let matrix = transform.compute_matrix();
+        Self {
+            instances: batch
+                .particles
+                .iter()
+                .map(|particle| ParticleInstance {
+                    position: matrix.transform_point3(particle.position).into(),
+                    size: particle.size,
+                    color: particle.color.as_linear_rgba_f32(),
+                })
+                .collect(),
+            origin: transform.translation,
+        }
+    }
+}
+
+/// The instanced draw of one [`ExtractedParticleBatch`].
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct ParticleDraw {
+    pub vertices: Range<u32>,
+    pub instances: Range<u32>,
+}
+
+/// Appends the instances of `batch` to `instances`, returning the draw that renders them.
+pub fn push_particle_instances(
+    instances: &mut BufferVec<ParticleInstance>,
+    batch: &ExtractedParticleBatch,
+) -> ParticleDraw {
+    let start = instances.len() as u32;
+    for instance in &batch.instances {
+        instances.push(*instance);
+    }
+    ParticleDraw {
+        vertices: 0..PARTICLE_QUAD_VERTICES,
+        instances: start..instances.len() as u32,
+    }
+}
+
+pub struct ParticleMeta {
+    pub instances: BufferVec<ParticleInstance>,
+}
+
+impl Default for ParticleMeta {
+    fn default() -> Self {
+        Self {
+            instances: BufferVec::new(BufferUsages::VERTEX),
+        }
+    }
+}
+
+fn extract_particle_batches(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    query: Query<(Entity, &Visibility, &GlobalTransform, &ParticleBatch)>,
+) {
+    let mut values = Vec::with_capacity(*previous_len);
+    for (entity, visibility, transform, batch) in query.iter() {
+        if !visibility.is_visible || batch.particles.is_empty() {
+            continue;
+        }
+        values.push((entity, (ExtractedParticleBatch::new(batch, transform),)));
+    }
+    *previous_len = values.len();
+    commands.insert_or_spawn_batch(values);
+}
+
+fn prepare_particle_batches(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut particle_meta: ResMut<ParticleMeta>,
+    batches: Query<(Entity, &ExtractedParticleBatch)>,
+) {
+    particle_meta.instances.clear();
+    for (entity, batch) in batches.iter() {
+        let draw = push_particle_instances(&mut particle_meta.instances, batch);
+        commands.entity(entity).insert(draw);
+    }
+    particle_meta
+        .instances
+        .write_buffer(&render_device, &render_queue);
+}
+
+pub struct ParticlePipeline {
+    view_layout: BindGroupLayout,
+    shader: Handle<Shader>,
+}
+
+impl FromWorld for ParticlePipeline {
+    fn from_world(render_world: &mut World) -> Self {
+        let mesh_pipeline = render_world.get_resource::<MeshPipeline>().unwrap();
+        ParticlePipeline {
+            view_layout: mesh_pipeline.view_layout.clone(),
+            shader: PARTICLE_SHADER_HANDLE.typed(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct ParticlePipelineKey {
+    pub msaa_samples: u32,
+}
+
+impl SpecializedPipeline for ParticlePipeline {
+    type Key = ParticlePipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let instance_buffer_layout = VertexBufferLayout {
+            array_stride: std::mem::size_of::<ParticleInstance>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: vec![
+                // Position
+                VertexAttribute {
+                    format: VertexFormat::Float32x3,
+                    offset: 0,
+                    shader_location: 0,
+                },
+                // Size
+                VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: 12,
+                    shader_location: 1,
+                },
+                // Color
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: 16,
+                    shader_location: 2,
+                },
+            ],
+        };
+
+        RenderPipelineDescriptor {
+            vertex: VertexState {
+                shader: self.shader.clone_weak(),
+                entry_point: "vertex".into(),
+                shader_defs: Vec::new(),
+                buffers: vec![instance_buffer_layout],
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone_weak(),
+                shader_defs: Vec::new(),
+                entry_point: "fragment".into(),
+                targets: vec![ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            layout: Some(vec![self.view_layout.clone()]),
+            primitive: PrimitiveState {
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Greater,
+                stencil: StencilState {
+                    front: StencilFaceState::IGNORE,
+                    back: StencilFaceState::IGNORE,
+                    read_mask: 0,
+                    write_mask: 0,
+                },
+                bias: DepthBiasState {
+                    constant: 0,
+                    slope_scale: 0.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: MultisampleState {
+                count: key.msaa_samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            label: Some("particle_pipeline".into()),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_particle_batches(
+    transparent_draw_functions: Res<DrawFunctions<Transparent3d>>,
+    particle_pipeline: Res<ParticlePipeline>,
+    mut pipeline_cache: ResMut<RenderPipelineCache>,
+    mut specialized_pipelines: ResMut<SpecializedPipelines<ParticlePipeline>>,
+    msaa: Res<Msaa>,
+    batches: Query<(Entity, &ExtractedParticleBatch)>,
+    mut views: Query<(&ExtractedView, &mut RenderPhase<Transparent3d>)>,
+) {
+    let draw_particles = transparent_draw_functions
+        .read()
+        .get_id::<DrawParticles>()
+        .unwrap();
+    let pipeline = specialized_pipelines.specialize(
+        &mut pipeline_cache,
+        &particle_pipeline,
+        ParticlePipelineKey {
+            msaa_samples: msaa.samples,
+        },
+    );
+    for (view, mut transparent_phase) in views.iter_mut() {
+        let view_row_2 = view.transform.compute_matrix().row(2);
+        for (entity, batch) in batches.iter() {
+            transparent_phase.add(Transparent3d {
+                distance: view_row_2.dot(batch.origin.extend(1.0)),
+                pipeline,
+                entity,
+                draw_function: draw_particles,
+            });
+        }
+    }
+}
+
+pub struct DrawParticleBatch;
+impl EntityRenderCommand for DrawParticleBatch {
+    type Param = (SRes<ParticleMeta>, SQuery<Read<ParticleDraw>>);
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (particle_meta, draw_query): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let draw = match draw_query.get(item) {
+            Ok(draw) => draw,
+            Err(_) => return RenderCommandResult::Failure,
+        };
+        match particle_meta.into_inner().instances.buffer() {
+            Some(buffer) => {
+                pass.set_vertex_buffer(0, buffer.slice(..));
+                pass.draw(draw.vertices.clone(), draw.instances.clone());
+                RenderCommandResult::Success
+            }
+            None => RenderCommandResult::Failure,
+        }
+    }
+}
+
+type DrawParticles = (SetItemPipeline, SetMeshViewBindGroup<0>, DrawParticleBatch);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_is_a_single_instanced_draw() {
+        let batch = ParticleBatch {
+            particles: (0..100)
+                .map(|i| Particle {
+                    position: Vec3::new(i as f32, 0.0, 0.0),
+                    size: 0.5,
+                    color: Color::WHITE,
+                })
+                .collect(),
+        };
+        let extracted =
+            ExtractedParticleBatch::new(&batch, &GlobalTransform::from_xyz(0.0, 1.0, 0.0));
+        assert_eq!(extracted.instances[3].position, [3.0, 1.0, 0.0]);
+
+        let mut instances = BufferVec::new(BufferUsages::VERTEX);
+        let draw = push_particle_instances(&mut instances, &extracted);
+        assert_eq!(instances.len(), 100);
+        assert_eq!(
+            draw,
+            ParticleDraw {
+                vertices: 0..PARTICLE_QUAD_VERTICES,
+                instances: 0..100,
+            }
+        );
+
+        let second = push_particle_instances(&mut instances, &extracted);
+        assert_eq!(second.instances, 100..200);
+    }
+
+    #[test]
+    fn quads_face_a_rotated_camera() {
+        let shader = include_str!("render/particles.wgsl");
+        assert!(shader.contains("let right = view.view[0].xyz;"));
+        assert!(shader.contains("let up = view.view[1].xyz;"));
+
+        // The `view` matrix of the view uniform, as written by `prepare_view_uniforms`
+        let camera = GlobalTransform::from_xyz(4.0, 3.0, 5.0).looking_at(Vec3::ZERO, Vec3::Y);
+        let view = camera.compute_matrix();
+        let right = view.col(0).truncate();
+        let up = view.col(1).truncate();
+        assert!(right.abs_diff_eq(camera.rotation * Vec3::X, 1e-5));
+        assert!(up.abs_diff_eq(camera.rotation * Vec3::Y, 1e-5));
+
+        // All corners of a quad are at the same depth in view space, so it faces the camera.
+        let world_to_view = view.inverse();
+        let position = Vec3::new(1.0, 0.5, -1.0);
+        let depth = |world: Vec3| world_to_view.transform_point3(world).z;
+        for (x, y) in [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)] {
+            let corner = position + (right * x + up * y) * 2.0;
+            assert!((depth(corner) - depth(position)).abs() < 1e-5);
+        }
+
+        // The columns of the world to view matrix don't span a camera-facing plane.
+        let corner = position + world_to_view.col(0).truncate() + world_to_view.col(1).truncate();
+        assert!((depth(corner) - depth(position)).abs() > 0.1);
+    }
+}
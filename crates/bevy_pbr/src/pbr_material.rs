@@ -244,6 +244,11 @@ impl RenderAsset for StandardMaterial {
                 flags |= StandardMaterialFlags::ALPHA_MODE_MASK;
             }
             AlphaMode::Blend => flags |= StandardMaterialFlags::ALPHA_MODE_BLEND,
+            // The back faces need their normals flipped, as for double sided materials.
+            AlphaMode::DoubleSidedTransparent => {
+                flags |=
+                    StandardMaterialFlags::ALPHA_MODE_BLEND | StandardMaterialFlags::DOUBLE_SIDED;
+            }
         };
 
         let value = StandardMaterialUniformData {
@@ -24,13 +24,12 @@ use bevy_render::{
         TrackedRenderPass,
     },
     render_resource::{std140::AsStd140, *},
-    renderer::{RenderContext, RenderDevice, RenderQueue},
+    renderer::{validate_sample_counts, RenderContext, RenderDevice, RenderQueue},
     texture::*,
     view::{ExtractedView, ViewUniform, ViewUniformOffset, ViewUniforms, VisibleEntities},
 };
 use bevy_transform::components::GlobalTransform;
 use bevy_utils::{tracing::warn, HashMap};
-use std::num::NonZeroU32;
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel)]
 pub enum RenderLightSystems {
@@ -46,6 +45,13 @@ pub struct ExtractedAmbientLight {
     brightness: f32,
 }
 
+impl ExtractedAmbientLight {
+    /// The linear ambient color, scaled by the brightness, as uploaded to `lights.ambient_color`
+    pub fn gpu_color(&self) -> Vec4 {
+        Vec4::from_slice(&self.color.as_linear_rgba_f32()) * self.brightness
+    }
+}
+
 #[derive(Component)]
 pub struct ExtractedPointLight {
     color: Color,
@@ -151,6 +157,8 @@ pub const MAX_DIRECTIONAL_LIGHTS: usize = 1;
 pub const POINT_SHADOW_LAYERS: u32 = (6 * MAX_POINT_LIGHT_SHADOW_MAPS) as u32;
 pub const DIRECTIONAL_SHADOW_LAYERS: u32 = MAX_DIRECTIONAL_LIGHTS as u32;
 pub const SHADOW_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+/// The sample count of the shadow maps and of the pipelines drawing into them
+pub const SHADOW_SAMPLE_COUNT: u32 = 1;
 
 pub struct ShadowPipeline {
     pub view_layout: BindGroupLayout,
@@ -216,6 +224,7 @@ bitflags::bitflags! {
     pub struct ShadowPipelineKey: u32 {
         const NONE               = 0;
         const VERTEX_TANGENTS    = (1 << 0);
+        const INDEX_FORMAT_UINT32 = (1 << 1);
         const PRIMITIVE_TOPOLOGY_RESERVED_BITS = ShadowPipelineKey::PRIMITIVE_TOPOLOGY_MASK_BITS << ShadowPipelineKey::PRIMITIVE_TOPOLOGY_SHIFT_BITS;
     }
 }
@@ -243,6 +252,33 @@ impl ShadowPipelineKey {
             _ => PrimitiveTopology::default(),
         }
     }
+
+    /// See [`MeshPipelineKey::from_index_format`](crate::MeshPipelineKey::from_index_format).
+    pub fn from_index_format(
+        primitive_topology: PrimitiveTopology,
+        index_format: Option<IndexFormat>,
+    ) -> Self {
+        match (primitive_topology, index_format) {
+            (
+                PrimitiveTopology::LineStrip | PrimitiveTopology::TriangleStrip,
+                Some(IndexFormat::Uint32),
+            ) => ShadowPipelineKey::INDEX_FORMAT_UINT32,
+            _ => ShadowPipelineKey::NONE,
+        }
+    }
+
+    pub fn strip_index_format(&self) -> Option<IndexFormat> {
+        match self.primitive_topology() {
+            PrimitiveTopology::LineStrip | PrimitiveTopology::TriangleStrip => {
+                if self.contains(ShadowPipelineKey::INDEX_FORMAT_UINT32) {
+                    Some(IndexFormat::Uint32)
+                } else {
+                    Some(IndexFormat::Uint16)
+                }
+            }
+            _ => None,
+        }
+    }
 }
 
 impl SpecializedPipeline for ShadowPipeline {
@@ -305,8 +341,9 @@ impl SpecializedPipeline for ShadowPipeline {
                     ],
                 )
             };
-        RenderPipelineDescriptor {
-            vertex: VertexState {
+        let mut descriptor = RenderPipelineDescriptor::depth_only(
+            "shadow_pipeline",
+            VertexState {
                 shader: SHADOW_SHADER_HANDLE.typed::<Shader>(),
                 entry_point: "vertex".into(),
                 shader_defs: vec![],
@@ -316,36 +353,22 @@ impl SpecializedPipeline for ShadowPipeline {
                     attributes: vertex_attributes,
                 }],
             },
-            fragment: None,
-            layout: Some(vec![self.view_layout.clone(), self.mesh_layout.clone()]),
-            primitive: PrimitiveState {
-                topology: key.primitive_topology(),
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: None,
-                unclipped_depth: false,
-                polygon_mode: PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: Some(DepthStencilState {
-                format: SHADOW_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: CompareFunction::GreaterEqual,
-                stencil: StencilState {
-                    front: StencilFaceState::IGNORE,
-                    back: StencilFaceState::IGNORE,
-                    read_mask: 0,
-                    write_mask: 0,
-                },
-                bias: DepthBiasState {
-                    constant: 0,
-                    slope_scale: 0.0,
-                    clamp: 0.0,
-                },
-            }),
-            multisample: MultisampleState::default(),
-            label: Some("shadow_pipeline".into()),
-        }
+            None,
+            SHADOW_FORMAT,
+            CompareFunction::GreaterEqual,
+        );
+        descriptor.layout = Some(vec![self.view_layout.clone(), self.mesh_layout.clone()]);
+        descriptor.multisample.count = SHADOW_SAMPLE_COUNT;
+        descriptor.primitive = PrimitiveState {
+            topology: key.primitive_topology(),
+            strip_index_format: key.strip_index_format(),
+            front_face: FrontFace::Ccw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        };
+        descriptor
     }
 }
 
@@ -531,6 +554,8 @@ fn face_index_to_name(face_index: usize) -> &'static str {
 pub struct ShadowView {
     pub depth_texture_view: TextureView,
     pub pass_name: String,
+    /// The sample count of the shadow map behind `depth_texture_view`
+    pub sample_count: u32,
 }
 
 #[derive(Component)]
@@ -590,6 +615,50 @@ pub fn calculate_cluster_factors(
     }
 }
 
+/// Describes the array texture holding the depth cube maps of all point lights casting shadows,
+/// with sides of `size` texels.
+pub fn point_light_shadow_map_descriptor(size: u32) -> TextureDescriptor<'static> {
+    TextureDescriptor {
+        size: Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: POINT_SHADOW_LAYERS,
+        },
+        mip_level_count: 1,
+        sample_count: SHADOW_SAMPLE_COUNT,
+        dimension: TextureDimension::D2,
+        format: SHADOW_FORMAT,
+        label: Some("point_light_shadow_map_texture"),
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    }
+}
+
+/// Describes the array texture holding the depth maps of all directional lights, with sides of
+/// `size` texels.
+pub fn directional_light_shadow_map_descriptor(size: u32) -> TextureDescriptor<'static> {
+    TextureDescriptor {
+        size: Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: DIRECTIONAL_SHADOW_LAYERS,
+        },
+        mip_level_count: 1,
+        sample_count: SHADOW_SAMPLE_COUNT,
+        dimension: TextureDimension::D2,
+        format: SHADOW_FORMAT,
+        label: Some("directional_light_shadow_map_texture"),
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    }
+}
+
+/// Returns the view matrix the shadow map of a directional light shining along `direction` is
+/// rendered with.
+pub fn directional_light_view(direction: Vec3) -> Mat4 {
+    // NOTE: A directional light seems to have to have an eye position on the line along the direction of the light
+    // through the world origin. I (Rob Swain) do not yet understand why it cannot be translated away from this.
+    Mat4::look_at_rh(Vec3::ZERO, direction, Vec3::Y)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn prepare_lights(
     mut commands: Commands,
@@ -676,40 +745,17 @@ pub fn prepare_lights(
 
     // set up light data for each view
     for (entity, extracted_view, clusters) in views.iter() {
-        let point_light_depth_texture = texture_cache.get(
-            &render_device,
-            TextureDescriptor {
-                size: Extent3d {
-                    width: point_light_shadow_map.size as u32,
-                    height: point_light_shadow_map.size as u32,
-                    depth_or_array_layers: POINT_SHADOW_LAYERS,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: TextureDimension::D2,
-                format: SHADOW_FORMAT,
-                label: Some("point_light_shadow_map_texture"),
-                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
-            },
-        );
-        let directional_light_depth_texture = texture_cache.get(
-            &render_device,
-            TextureDescriptor {
-                size: Extent3d {
-                    width: (directional_light_shadow_map.size as u32)
-                        .min(wgpu_options.limits.max_texture_dimension_2d),
-                    height: (directional_light_shadow_map.size as u32)
-                        .min(wgpu_options.limits.max_texture_dimension_2d),
-                    depth_or_array_layers: DIRECTIONAL_SHADOW_LAYERS,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: TextureDimension::D2,
-                format: SHADOW_FORMAT,
-                label: Some("directional_light_shadow_map_texture"),
-                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
-            },
+        let point_light_descriptor =
+            point_light_shadow_map_descriptor(point_light_shadow_map.size as u32);
+        let point_light_sample_count = point_light_descriptor.sample_count;
+        let point_light_depth_texture = texture_cache.get(&render_device, point_light_descriptor);
+        let directional_light_descriptor = directional_light_shadow_map_descriptor(
+            (directional_light_shadow_map.size as u32)
+                .min(wgpu_options.limits.max_texture_dimension_2d),
         );
+        let directional_light_sample_count = directional_light_descriptor.sample_count;
+        let directional_light_depth_texture =
+            texture_cache.get(&render_device, directional_light_descriptor);
         let mut view_lights = Vec::new();
 
         let is_orthographic = extracted_view.projection.w_axis.w == 1.0;
@@ -723,8 +769,7 @@ pub fn prepare_lights(
         let n_clusters = clusters.axis_slices.x * clusters.axis_slices.y * clusters.axis_slices.z;
         let mut gpu_lights = GpuLights {
             directional_lights: [GpuDirectionalLight::default(); MAX_DIRECTIONAL_LIGHTS],
-            ambient_color: Vec4::from_slice(&ambient_light.color.as_linear_rgba_f32())
-                * ambient_light.brightness,
+            ambient_color: ambient_light.gpu_color(),
             cluster_factors: Vec4::new(
                 clusters.axis_slices.x as f32 / extracted_view.width as f32,
                 clusters.axis_slices.y as f32 / extracted_view.height as f32,
@@ -752,19 +797,13 @@ pub fn prepare_lights(
             let view_translation = GlobalTransform::from_translation(light.transform.translation);
 
             for (face_index, view_rotation) in cube_face_rotations.iter().enumerate() {
-                let depth_texture_view =
-                    point_light_depth_texture
-                        .texture
-                        .create_view(&TextureViewDescriptor {
-                            label: Some("point_light_shadow_map_texture_view"),
-                            format: None,
-                            dimension: Some(TextureViewDimension::D2),
-                            aspect: TextureAspect::All,
-                            base_mip_level: 0,
-                            mip_level_count: None,
-                            base_array_layer: (light_index * 6 + face_index) as u32,
-                            array_layer_count: NonZeroU32::new(1),
-                        });
+                let depth_texture_view = point_light_depth_texture
+                    .texture
+                    .create_layer_attachment_view(
+                        Some("point_light_shadow_map_texture_view"),
+                        (light_index * 6 + face_index) as u32,
+                        0,
+                    );
 
                 let view_light_entity = commands
                     .spawn()
@@ -776,6 +815,7 @@ pub fn prepare_lights(
                                 light_index,
                                 face_index_to_name(face_index)
                             ),
+                            sample_count: point_light_sample_count,
                         },
                         ExtractedView {
                             width: point_light_shadow_map.size as u32,
@@ -817,9 +857,7 @@ pub fn prepare_lights(
             let exposure = 1.0 / (f32::powf(2.0, ev100) * 1.2);
             let intensity = light.illuminance * exposure;
 
-            // NOTE: A directional light seems to have to have an eye position on the line along the direction of the light
-            // through the world origin. I (Rob Swain) do not yet understand why it cannot be translated away from this.
-            let view = Mat4::look_at_rh(Vec3::ZERO, light.direction, Vec3::Y);
+            let view = directional_light_view(light.direction);
             // NOTE: This orthographic projection defines the volume within which shadows from a directional light can be cast
             let projection = light.projection;
 
@@ -841,19 +879,13 @@ pub fn prepare_lights(
             };
 
             if light.shadows_enabled {
-                let depth_texture_view =
-                    directional_light_depth_texture
-                        .texture
-                        .create_view(&TextureViewDescriptor {
-                            label: Some("directional_light_shadow_map_texture_view"),
-                            format: None,
-                            dimension: Some(TextureViewDimension::D2),
-                            aspect: TextureAspect::All,
-                            base_mip_level: 0,
-                            mip_level_count: None,
-                            base_array_layer: i as u32,
-                            array_layer_count: NonZeroU32::new(1),
-                        });
+                let depth_texture_view = directional_light_depth_texture
+                    .texture
+                    .create_layer_attachment_view(
+                        Some("directional_light_shadow_map_texture_view"),
+                        i as u32,
+                        0,
+                    );
 
                 let view_light_entity = commands
                     .spawn()
@@ -861,6 +893,7 @@ pub fn prepare_lights(
                         ShadowView {
                             depth_texture_view,
                             pass_name: format!("shadow pass directional light {}", i),
+                            sample_count: directional_light_sample_count,
                         },
                         ExtractedView {
                             width: directional_light_shadow_map.size as u32,
@@ -1129,6 +1162,10 @@ pub fn queue_shadows(
                             key |= ShadowPipelineKey::VERTEX_TANGENTS;
                         }
                         key |= ShadowPipelineKey::from_primitive_topology(mesh.primitive_topology);
+                        key |= ShadowPipelineKey::from_index_format(
+                            mesh.primitive_topology,
+                            mesh.index_format(),
+                        );
                     }
                     let pipeline_id =
                         pipelines.specialize(&mut pipeline_cache, &shadow_pipeline, key);
@@ -1218,6 +1255,7 @@ impl Node for ShadowPassNode {
                     .view_light_query
                     .get_manual(world, view_light_entity)
                     .unwrap();
+                validate_sample_counts(&[], Some(view_light.sample_count), SHADOW_SAMPLE_COUNT)?;
                 let pass_descriptor = RenderPassDescriptor {
                     label: Some(&view_light.pass_name),
                     color_attachments: &[],
@@ -1279,3 +1317,94 @@ impl<const I: usize> EntityRenderCommand for SetShadowViewBindGroup<I> {
         RenderCommandResult::Success
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shadow_maps_are_depth_array_textures() {
+        let point = point_light_shadow_map_descriptor(1024);
+        assert_eq!(point.format, SHADOW_FORMAT);
+        assert_eq!(point.size.width, 1024);
+        assert_eq!(point.size.height, 1024);
+        assert_eq!(
+            point.size.depth_or_array_layers,
+            6 * MAX_POINT_LIGHT_SHADOW_MAPS as u32
+        );
+        assert!(point
+            .usage
+            .contains(TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING));
+
+        let directional = directional_light_shadow_map_descriptor(2048);
+        assert_eq!(directional.format, SHADOW_FORMAT);
+        assert_eq!(directional.size.width, 2048);
+        assert_eq!(
+            directional.size.depth_or_array_layers,
+            MAX_DIRECTIONAL_LIGHTS as u32
+        );
+        assert!(directional
+            .usage
+            .contains(TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING));
+    }
+
+    #[test]
+    fn shadow_passes_match_the_shadow_pipeline_sample_count() {
+        for descriptor in [
+            point_light_shadow_map_descriptor(1024),
+            directional_light_shadow_map_descriptor(1024),
+        ] {
+            assert_eq!(
+                validate_sample_counts(&[], Some(descriptor.sample_count), SHADOW_SAMPLE_COUNT),
+                Ok(())
+            );
+        }
+        // The shadow pass errors before wgpu panics on a multisampled shadow map
+        assert!(validate_sample_counts(&[], Some(4), SHADOW_SAMPLE_COUNT).is_err());
+    }
+
+    #[test]
+    fn directional_light_view_looks_along_light() {
+        let direction = Vec3::new(1.0, -1.0, 0.0).normalize();
+        let view = directional_light_view(direction);
+
+        // Points further along the light direction are further in front of the light, which
+        // looks down -Z in view space.
+        let near = view.transform_point3(direction);
+        let far = view.transform_point3(direction * 10.0);
+        assert!(near.truncate().length() < 1e-5);
+        assert!((near.z + 1.0).abs() < 1e-5);
+        assert!((far.z + 10.0).abs() < 1e-5);
+
+        // The view is a rigid transform at the origin.
+        assert!(view.transform_point3(Vec3::ZERO).length() < 1e-5);
+        assert!((view.transform_vector3(Vec3::X).length() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ambient_light_is_uploaded_to_lights_uniform() {
+        let ambient_light = ExtractedAmbientLight {
+            color: Color::rgb_linear(0.2, 0.4, 1.0),
+            brightness: 0.5,
+        };
+        let gpu_lights = GpuLights {
+            directional_lights: [GpuDirectionalLight::default(); MAX_DIRECTIONAL_LIGHTS],
+            ambient_color: ambient_light.gpu_color(),
+            cluster_dimensions: UVec4::ZERO,
+            cluster_factors: Vec4::ZERO,
+            n_directional_lights: 0,
+        };
+
+        let std140 = gpu_lights.as_std140();
+        let ambient_color = std140.ambient_color;
+        assert_eq!(
+            [
+                ambient_color.x,
+                ambient_color.y,
+                ambient_color.z,
+                ambient_color.w
+            ],
+            [0.1, 0.2, 0.5, 0.5]
+        );
+    }
+}
@@ -11,7 +11,7 @@ use bevy_ecs::{
 use bevy_math::{Mat4, Size};
 use bevy_reflect::TypeUuid;
 use bevy_render::{
-    mesh::{GpuBufferInfo, Mesh},
+    mesh::{GpuBufferInfo, GpuMesh, Mesh},
     render_asset::RenderAssets,
     render_component::{ComponentUniforms, DynamicUniformIndex, UniformComponentPlugin},
     render_phase::{EntityRenderCommand, RenderCommandResult, TrackedRenderPass},
@@ -21,7 +21,10 @@ use bevy_render::{
     view::{ComputedVisibility, ViewUniform, ViewUniformOffset, ViewUniforms},
     RenderApp, RenderStage,
 };
-use bevy_transform::components::GlobalTransform;
+use bevy_transform::components::{
+    GlobalTransform, PreviousGlobalTransform, TransformInterpolation,
+};
+use bevy_utils::{tracing::warn, HashSet};
 
 #[derive(Default)]
 pub struct MeshRenderPlugin;
@@ -70,6 +73,30 @@ pub struct MeshUniform {
     pub flags: u32,
 }
 
+impl MeshUniform {
+    /// Computes the uniform of a mesh placed by `transform`, or `None` if the transform is not
+    /// finite, e.g. after a division by zero.
+    ///
+    /// Uploading a non-finite matrix draws garbage at best, and can hang the GPU driver. A
+    /// transform that is finite but not invertible, like a zero scale used to hide or flatten a
+    /// mesh, is still valid: its normals are then only rotated.
+    pub fn from_transform(transform: &GlobalTransform, flags: u32) -> Option<Self> {
+        let matrix = transform.compute_matrix();
+        if !matrix.is_finite() {
+            return None;
+        }
+        let mut inverse_transpose_model = matrix.inverse().transpose();
+        if !inverse_transpose_model.is_finite() {
+            inverse_transpose_model = Mat4::from_quat(transform.rotation);
+        }
+        Some(MeshUniform {
+            transform: matrix,
+            inverse_transpose_model,
+            flags,
+        })
+    }
+}
+
 // NOTE: These must match the bit flags in bevy_pbr2/src/render/mesh.wgsl!
 bitflags::bitflags! {
     #[repr(transparent)]
@@ -84,11 +111,13 @@ pub fn extract_meshes(
     mut commands: Commands,
     mut previous_caster_len: Local<usize>,
     mut previous_not_caster_len: Local<usize>,
+    mut warned: Local<HashSet<Entity>>,
     caster_query: Query<
         (
             Entity,
             &ComputedVisibility,
             &GlobalTransform,
+            Option<&PreviousGlobalTransform>,
             &Handle<Mesh>,
             Option<&NotShadowReceiver>,
         ),
@@ -99,64 +128,93 @@ pub fn extract_meshes(
             Entity,
             &ComputedVisibility,
             &GlobalTransform,
+            Option<&PreviousGlobalTransform>,
             &Handle<Mesh>,
             Option<&NotShadowReceiver>,
         ),
         With<NotShadowCaster>,
     >,
+    interpolation: Option<Res<TransformInterpolation>>,
 ) {
+    let alpha = interpolation.map_or(1.0, |interpolation| interpolation.alpha);
     let mut caster_values = Vec::with_capacity(*previous_caster_len);
-    for (entity, computed_visibility, transform, handle, not_receiver) in caster_query.iter() {
+    for (entity, computed_visibility, transform, previous, handle, not_receiver) in
+        caster_query.iter()
+    {
         if !computed_visibility.is_visible {
             continue;
         }
-        let transform = transform.compute_matrix();
-        caster_values.push((
+        let mesh_uniform = match extract_mesh_uniform(
             entity,
-            (
-                handle.clone_weak(),
-                MeshUniform {
-                    flags: if not_receiver.is_some() {
-                        MeshFlags::empty().bits
-                    } else {
-                        MeshFlags::SHADOW_RECEIVER.bits
-                    },
-                    transform,
-                    inverse_transpose_model: transform.inverse().transpose(),
-                },
-            ),
-        ));
+            transform,
+            previous,
+            alpha,
+            not_receiver,
+            &mut warned,
+        ) {
+            Some(mesh_uniform) => mesh_uniform,
+            None => continue,
+        };
+        caster_values.push((entity, (handle.clone_weak(), mesh_uniform)));
     }
     *previous_caster_len = caster_values.len();
     commands.insert_or_spawn_batch(caster_values);
 
     let mut not_caster_values = Vec::with_capacity(*previous_not_caster_len);
-    for (entity, computed_visibility, transform, handle, not_receiver) in not_caster_query.iter() {
+    for (entity, computed_visibility, transform, previous, handle, not_receiver) in
+        not_caster_query.iter()
+    {
         if !computed_visibility.is_visible {
             continue;
         }
-        let transform = transform.compute_matrix();
-        not_caster_values.push((
+        let mesh_uniform = match extract_mesh_uniform(
             entity,
-            (
-                handle.clone_weak(),
-                MeshUniform {
-                    flags: if not_receiver.is_some() {
-                        MeshFlags::empty().bits
-                    } else {
-                        MeshFlags::SHADOW_RECEIVER.bits
-                    },
-                    transform,
-                    inverse_transpose_model: transform.inverse().transpose(),
-                },
-                NotShadowCaster,
-            ),
-        ));
+            transform,
+            previous,
+            alpha,
+            not_receiver,
+            &mut warned,
+        ) {
+            Some(mesh_uniform) => mesh_uniform,
+            None => continue,
+        };
+        not_caster_values.push((entity, (handle.clone_weak(), mesh_uniform, NotShadowCaster)));
     }
     *previous_not_caster_len = not_caster_values.len();
     commands.insert_or_spawn_batch(not_caster_values);
 }
 
+fn extract_mesh_uniform(
+    entity: Entity,
+    transform: &GlobalTransform,
+    previous: Option<&PreviousGlobalTransform>,
+    alpha: f32,
+    not_receiver: Option<&NotShadowReceiver>,
+    warned: &mut HashSet<Entity>,
+) -> Option<MeshUniform> {
+    // Entities moved by a fixed timestep simulation are drawn in between their last two steps.
+    let transform = match previous {
+        Some(previous) => previous.interpolate(transform, alpha),
+        None => *transform,
+    };
+    let flags = if not_receiver.is_some() {
+        MeshFlags::empty().bits
+    } else {
+        MeshFlags::SHADOW_RECEIVER.bits
+    };
+    let mesh_uniform = MeshUniform::from_transform(&transform, flags);
+    // Only warn when an entity's transform breaks, not on every frame it stays broken.
+    if mesh_uniform.is_some() {
+        warned.remove(&entity);
+    } else if warned.insert(entity) {
+        warn!(
+            "Mesh entity {:?} is not drawn: its GlobalTransform is not finite",
+            entity
+        );
+    }
+    mesh_uniform
+}
+
 #[derive(Clone)]
 pub struct MeshPipeline {
     pub view_layout: BindGroupLayout,
@@ -370,6 +428,8 @@ bitflags::bitflags! {
         const NONE                        = 0;
         const VERTEX_TANGENTS             = (1 << 0);
         const TRANSPARENT_MAIN_PASS       = (1 << 1);
+        const INDEX_FORMAT_UINT32         = (1 << 2);
+        const CULL_FRONT                  = (1 << 3);
         const MSAA_RESERVED_BITS          = MeshPipelineKey::MSAA_MASK_BITS << MeshPipelineKey::MSAA_SHIFT_BITS;
         const PRIMITIVE_TOPOLOGY_RESERVED_BITS = MeshPipelineKey::PRIMITIVE_TOPOLOGY_MASK_BITS << MeshPipelineKey::PRIMITIVE_TOPOLOGY_SHIFT_BITS;
     }
@@ -397,6 +457,16 @@ impl MeshPipelineKey {
         MeshPipelineKey::from_bits(primitive_topology_bits).unwrap()
     }
 
+    /// The faces culled by pipelines specialized for this key: back faces, unless
+    /// [`MeshPipelineKey::CULL_FRONT`] is set.
+    pub fn cull_mode(&self) -> Face {
+        if self.contains(MeshPipelineKey::CULL_FRONT) {
+            Face::Front
+        } else {
+            Face::Back
+        }
+    }
+
     pub fn primitive_topology(&self) -> PrimitiveTopology {
         let primitive_topology_bits =
             (self.bits >> Self::PRIMITIVE_TOPOLOGY_SHIFT_BITS) & Self::PRIMITIVE_TOPOLOGY_MASK_BITS;
@@ -409,6 +479,38 @@ impl MeshPipelineKey {
             _ => PrimitiveTopology::default(),
         }
     }
+
+    /// Selects the index format the pipeline is specialized for. Only strip topologies depend on
+    /// it, as their primitive restart value is the maximum index of the format, so list
+    /// topologies share one pipeline whatever their index format.
+    pub fn from_index_format(
+        primitive_topology: PrimitiveTopology,
+        index_format: Option<IndexFormat>,
+    ) -> Self {
+        match (primitive_topology, index_format) {
+            (
+                PrimitiveTopology::LineStrip | PrimitiveTopology::TriangleStrip,
+                Some(IndexFormat::Uint32),
+            ) => MeshPipelineKey::INDEX_FORMAT_UINT32,
+            _ => MeshPipelineKey::NONE,
+        }
+    }
+
+    /// Returns the `strip_index_format` of the pipeline's
+    /// [`PrimitiveState`](bevy_render::render_resource::PrimitiveState), which must be set for
+    /// strip topologies and unset for all others.
+    pub fn strip_index_format(&self) -> Option<IndexFormat> {
+        match self.primitive_topology() {
+            PrimitiveTopology::LineStrip | PrimitiveTopology::TriangleStrip => {
+                if self.contains(MeshPipelineKey::INDEX_FORMAT_UINT32) {
+                    Some(IndexFormat::Uint32)
+                } else {
+                    Some(IndexFormat::Uint16)
+                }
+            }
+            _ => None,
+        }
+    }
 }
 
 impl SpecializedPipeline for MeshPipeline {
@@ -519,12 +621,12 @@ impl SpecializedPipeline for MeshPipeline {
             layout: Some(vec![self.view_layout.clone(), self.mesh_layout.clone()]),
             primitive: PrimitiveState {
                 front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
+                cull_mode: Some(key.cull_mode()),
                 unclipped_depth: false,
                 polygon_mode: PolygonMode::Fill,
                 conservative: false,
                 topology: key.primitive_topology(),
-                strip_index_format: None,
+                strip_index_format: key.strip_index_format(),
             },
             depth_stencil: Some(DepthStencilState {
                 format: TextureFormat::Depth32Float,
@@ -719,35 +821,135 @@ impl EntityRenderCommand for DrawMesh {
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
         let mesh_handle = mesh_query.get(item).unwrap();
-        if let Some(gpu_mesh) = meshes.into_inner().get(mesh_handle) {
-            pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
-            match &gpu_mesh.buffer_info {
-                GpuBufferInfo::Indexed {
-                    buffer,
-                    index_format,
-                    count,
-                } => {
-                    pass.set_index_buffer(buffer.slice(..), 0, *index_format);
-                    pass.draw_indexed(0..*count, 0, 0..1);
-                }
-                GpuBufferInfo::NonIndexed { vertex_count } => {
-                    pass.draw(0..*vertex_count, 0..1);
-                }
+        let gpu_mesh = match GpuMesh::get(meshes.into_inner(), mesh_handle) {
+            Ok(gpu_mesh) => gpu_mesh,
+            Err(_) => return RenderCommandResult::Failure,
+        };
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..1);
+            }
+            GpuBufferInfo::NonIndexed { vertex_count } => {
+                pass.draw(0..*vertex_count, 0..1);
             }
-            RenderCommandResult::Success
-        } else {
-            RenderCommandResult::Failure
         }
+        RenderCommandResult::Success
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::MeshPipelineKey;
+    use super::{extract_meshes, MeshPipelineKey, MeshUniform};
+    use bevy_asset::Handle;
+    use bevy_ecs::{
+        schedule::{Stage, SystemStage},
+        world::World,
+    };
+    use bevy_math::Vec3;
+    use bevy_render::{
+        mesh::{Indices, Mesh},
+        render_resource::{IndexFormat, PrimitiveTopology},
+        view::ComputedVisibility,
+    };
+    use bevy_transform::components::{
+        GlobalTransform, PreviousGlobalTransform, TransformInterpolation,
+    };
+
     #[test]
     fn mesh_key_msaa_samples() {
         for i in 1..=64 {
             assert_eq!(MeshPipelineKey::from_msaa_samples(i).msaa_samples(), i);
         }
     }
+
+    #[test]
+    fn mesh_key_index_format() {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleStrip);
+        mesh.set_indices(Some(Indices::U32(vec![0, 1, 2, u32::MAX, 2, 1, 3])));
+        let uint32_key = MeshPipelineKey::from_primitive_topology(mesh.primitive_topology())
+            | MeshPipelineKey::from_index_format(
+                mesh.primitive_topology(),
+                mesh.indices().map(IndexFormat::from),
+            );
+        let uint16_key = MeshPipelineKey::from_primitive_topology(mesh.primitive_topology())
+            | MeshPipelineKey::from_index_format(
+                mesh.primitive_topology(),
+                Some(IndexFormat::Uint16),
+            );
+
+        assert_eq!(uint32_key.strip_index_format(), Some(IndexFormat::Uint32));
+        assert_eq!(uint16_key.strip_index_format(), Some(IndexFormat::Uint16));
+        assert_ne!(uint32_key, uint16_key);
+
+        // List topologies don't use a strip index format, so they share one pipeline whatever
+        // the index buffer holds.
+        let list_key = |index_format| {
+            MeshPipelineKey::from_primitive_topology(PrimitiveTopology::TriangleList)
+                | MeshPipelineKey::from_index_format(PrimitiveTopology::TriangleList, index_format)
+        };
+        assert_eq!(
+            list_key(Some(IndexFormat::Uint32)).strip_index_format(),
+            None
+        );
+        assert_eq!(
+            list_key(Some(IndexFormat::Uint32)),
+            list_key(Some(IndexFormat::Uint16))
+        );
+        assert_eq!(list_key(Some(IndexFormat::Uint32)), list_key(None));
+    }
+
+    #[test]
+    fn non_finite_transforms_are_not_uploaded() {
+        let mut world = World::default();
+        let mut spawn_mesh = |transform: GlobalTransform| {
+            world
+                .spawn()
+                .insert_bundle((
+                    ComputedVisibility::default(),
+                    transform,
+                    Handle::<Mesh>::default(),
+                ))
+                .id()
+        };
+        let valid = spawn_mesh(GlobalTransform::from_xyz(1.0, 2.0, 3.0));
+        let nan = spawn_mesh(GlobalTransform::from_xyz(f32::NAN, 0.0, 0.0));
+        let flattened = spawn_mesh(GlobalTransform::from_scale(Vec3::new(1.0, 0.0, 1.0)));
+
+        SystemStage::single(extract_meshes).run(&mut world);
+
+        assert!(world.get::<MeshUniform>(valid).is_some());
+        assert!(world.get::<MeshUniform>(nan).is_none());
+        // A zero scale is a common way to hide or flatten a mesh, so it is still drawn.
+        let flattened = world.get::<MeshUniform>(flattened).unwrap();
+        assert!(flattened.inverse_transpose_model.is_finite());
+    }
+
+    #[test]
+    fn fixed_timestep_meshes_are_interpolated() {
+        let mut world = World::default();
+        world.insert_resource(TransformInterpolation { alpha: 0.5 });
+        let mesh = world
+            .spawn()
+            .insert_bundle((
+                ComputedVisibility::default(),
+                GlobalTransform::from_xyz(4.0, 0.0, 2.0),
+                PreviousGlobalTransform(GlobalTransform::from_xyz(2.0, 0.0, 0.0)),
+                Handle::<Mesh>::default(),
+            ))
+            .id();
+
+        SystemStage::single(extract_meshes).run(&mut world);
+
+        let mesh_uniform = world.get::<MeshUniform>(mesh).unwrap();
+        assert_eq!(
+            mesh_uniform.transform,
+            GlobalTransform::from_xyz(3.0, 0.0, 1.0).compute_matrix()
+        );
+    }
 }
@@ -0,0 +1,51 @@
+#import bevy_pbr::mesh_view_bind_group
+#import bevy_pbr::mesh_struct
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> mesh: Mesh;
+
+struct SelectionOutline {
+    color: vec4<f32>;
+    width: f32;
+};
+
+[[group(2), binding(0)]]
+var<uniform> outline: SelectionOutline;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+};
+
+// Writes the silhouette of the selected mesh to the stencil buffer.
+[[stage(vertex)]]
+fn mask_vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * mesh.model * vec4<f32>(vertex.position, 1.0);
+    return out;
+}
+
+// Pushes the mesh outwards by `outline.width` pixels along its screen space normal.
+[[stage(vertex)]]
+fn outline_vertex(vertex: Vertex) -> VertexOutput {
+    let clip_position = view.view_proj * mesh.model * vec4<f32>(vertex.position, 1.0);
+    let world_normal = (mesh.inverse_transpose_model * vec4<f32>(vertex.normal, 0.0)).xyz;
+    let clip_normal = (view.view_proj * vec4<f32>(world_normal, 0.0)).xy;
+
+    var out: VertexOutput;
+    out.clip_position = clip_position;
+    if (length(clip_normal) > 0.0) {
+        let offset = normalize(clip_normal) * outline.width * 2.0 / vec2<f32>(view.width, view.height);
+        out.clip_position = vec4<f32>(clip_position.xy + offset * clip_position.w, clip_position.zw);
+    }
+    return out;
+}
+
+[[stage(fragment)]]
+fn fragment() -> [[location(0)]] vec4<f32> {
+    return outline.color;
+}
@@ -0,0 +1,40 @@
+#import bevy_pbr::mesh_view_bind_group
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+// Expands each particle instance into a camera-facing quad made of two triangles.
+[[stage(vertex)]]
+fn vertex(
+    [[builtin(vertex_index)]] vertex_index: u32,
+    [[location(0)]] position: vec3<f32>,
+    [[location(1)]] size: f32,
+    [[location(2)]] color: vec4<f32>,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, 0.5),
+    );
+    let corner = corners[vertex_index];
+    // `view.view` is the world transform of the camera, so its columns are the world space right
+    // and up vectors of the camera.
+    let right = view.view[0].xyz;
+    let up = view.view[1].xyz;
+    let world_position = position + (right * corner.x + up * corner.y) * size;
+
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * vec4<f32>(world_position, 1.0);
+    out.color = color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,37 @@
+#import bevy_pbr::mesh_view_bind_group
+
+[[group(1), binding(0)]]
+var skybox_texture: texture_cube<f32>;
+[[group(1), binding(1)]]
+var skybox_sampler: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] ndc: vec2<f32>;
+};
+
+// Draws a single triangle covering the whole screen, on the far plane. With reverse-z the far
+// plane is at depth 0.0, so the skybox only passes the depth test where nothing else was drawn.
+[[stage(vertex)]]
+fn vertex([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * 2.0 - vec2<f32>(1.0, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // Undo the projection to get the view space direction through this fragment, then rotate it
+    // into world space with the world transform of the camera.
+    let view_direction = vec3<f32>(
+        in.ndc.x / view.projection[0][0],
+        in.ndc.y / view.projection[1][1],
+        -1.0,
+    );
+    let direction = (view.view * vec4<f32>(view_direction, 0.0)).xyz;
+    return textureSample(skybox_texture, skybox_sampler, direction);
+}
@@ -0,0 +1,355 @@
+use crate::{MeshPipeline, SetMeshViewBindGroup};
+use bevy_app::Plugin;
+use bevy_asset::{Assets, Handle, HandleUntyped};
+use bevy_core_pipeline::Opaque3d;
+use bevy_ecs::{
+    prelude::*,
+    system::{lifetimeless::*, SystemParamItem},
+};
+use bevy_reflect::TypeUuid;
+use bevy_render::{
+    camera::Camera,
+    render_asset::RenderAssets,
+    render_phase::{
+        AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase,
+        SetItemPipeline, TrackedRenderPass,
+    },
+    render_resource::*,
+    renderer::RenderDevice,
+    texture::{BevyDefault, Image},
+    view::{ExtractedView, Msaa},
+    RenderApp, RenderStage,
+};
+
+pub const SKYBOX_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 5832164090751243108);
+
+/// Draws the [`Skybox`] of each camera behind all other geometry.
+#[derive(Debug, Default)]
+pub struct SkyboxPlugin;
+
+impl Plugin for SkyboxPlugin {
+    fn build(&self, app: &mut bevy_app::App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            SKYBOX_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("render/skybox.wgsl")),
+        );
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .add_render_command::<Opaque3d, DrawSkybox>()
+                .init_resource::<SkyboxPipeline>()
+                .init_resource::<SpecializedPipelines<SkyboxPipeline>>()
+                .add_system_to_stage(RenderStage::Extract, extract_skyboxes)
+                .add_system_to_stage(RenderStage::Queue, queue_skyboxes);
+        }
+    }
+}
+
+/// A cubemap drawn around a camera, behind all other geometry.
+///
+/// The image must be a cubemap: a square 2D texture with 6 array layers, holding the +X, -X, +Y,
+/// -Y, +Z and -Z faces in that order. Cameras whose skybox image isn't a cubemap draw no skybox.
+#[derive(Component, Debug, Clone)]
+pub struct Skybox(pub Handle<Image>);
+
+/// Returns `true` if a texture described by `descriptor` can be viewed as a cubemap.
+pub fn is_cubemap(descriptor: &TextureDescriptor) -> bool {
+    descriptor.dimension == TextureDimension::D2
+        && descriptor.size.depth_or_array_layers == 6
+        && descriptor.size.width == descriptor.size.height
+}
+
+/// The depth state of the skybox pipeline.
+///
+/// The skybox is drawn on the far plane, which is at a depth of `0.0` with the reverse-z
+/// projections used by Bevy. Comparing with [`CompareFunction::GreaterEqual`] lets it pass only
+/// where the depth buffer still holds its cleared value, so it never covers opaque geometry,
+/// whichever order they are drawn in. The skybox doesn't write depth, as the far plane is already
+/// the cleared value.
+pub fn skybox_depth_stencil_state() -> DepthStencilState {
+    DepthStencilState {
+        format: TextureFormat::Depth32Float,
+        depth_write_enabled: false,
+        depth_compare: CompareFunction::GreaterEqual,
+        stencil: StencilState {
+            front: StencilFaceState::IGNORE,
+            back: StencilFaceState::IGNORE,
+            read_mask: 0,
+            write_mask: 0,
+        },
+        bias: DepthBiasState {
+            constant: 0,
+            slope_scale: 0.0,
+            clamp: 0.0,
+        },
+    }
+}
+
+fn extract_skyboxes(
+    mut commands: Commands,
+    images: Res<Assets<Image>>,
+    query: Query<(Entity, &Skybox), With<Camera>>,
+) {
+    for (entity, skybox) in query.iter() {
+        if matches!(images.get(&skybox.0), Some(image) if is_cubemap(&image.texture_descriptor)) {
+            commands.get_or_spawn(entity).insert(skybox.clone());
+        }
+    }
+}
+
+pub struct SkyboxPipeline {
+    view_layout: BindGroupLayout,
+    skybox_layout: BindGroupLayout,
+    shader: Handle<Shader>,
+}
+
+impl FromWorld for SkyboxPipeline {
+    fn from_world(render_world: &mut World) -> Self {
+        let render_device = render_world.get_resource::<RenderDevice>().unwrap();
+        let skybox_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("skybox_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::Cube,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let mesh_pipeline = render_world.get_resource::<MeshPipeline>().unwrap();
+        SkyboxPipeline {
+            view_layout: mesh_pipeline.view_layout.clone(),
+            skybox_layout,
+            shader: SKYBOX_SHADER_HANDLE.typed(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct SkyboxPipelineKey {
+    pub msaa_samples: u32,
+}
+
+impl SpecializedPipeline for SkyboxPipeline {
+    type Key = SkyboxPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            vertex: VertexState {
+                shader: self.shader.clone_weak(),
+                entry_point: "vertex".into(),
+                shader_defs: Vec::new(),
+                buffers: Vec::new(),
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone_weak(),
+                shader_defs: Vec::new(),
+                entry_point: "fragment".into(),
+                targets: vec![ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            layout: Some(vec![self.view_layout.clone(), self.skybox_layout.clone()]),
+            primitive: PrimitiveState {
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+            },
+            depth_stencil: Some(skybox_depth_stencil_state()),
+            multisample: MultisampleState {
+                count: key.msaa_samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            label: Some("skybox_pipeline".into()),
+        }
+    }
+}
+
+/// The bind group of the cubemap a view's [`Skybox`] samples.
+#[derive(Component)]
+pub struct SkyboxBindGroup {
+    pub value: BindGroup,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_skyboxes(
+    mut commands: Commands,
+    opaque_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    render_device: Res<RenderDevice>,
+    skybox_pipeline: Res<SkyboxPipeline>,
+    mut pipeline_cache: ResMut<RenderPipelineCache>,
+    mut specialized_pipelines: ResMut<SpecializedPipelines<SkyboxPipeline>>,
+    msaa: Res<Msaa>,
+    gpu_images: Res<RenderAssets<Image>>,
+    mut views: Query<(Entity, &Skybox, &mut RenderPhase<Opaque3d>), With<ExtractedView>>,
+) {
+    let draw_skybox = opaque_draw_functions.read().get_id::<DrawSkybox>().unwrap();
+    let pipeline = specialized_pipelines.specialize(
+        &mut pipeline_cache,
+        &skybox_pipeline,
+        SkyboxPipelineKey {
+            msaa_samples: msaa.samples,
+        },
+    );
+    for (entity, skybox, mut opaque_phase) in views.iter_mut() {
+        let gpu_image = match gpu_images.get(&skybox.0) {
+            Some(gpu_image) => gpu_image,
+            None => continue,
+        };
+        let cube_view = gpu_image.texture.create_view(&TextureViewDescriptor {
+            label: Some("skybox_texture_view"),
+            dimension: Some(TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("skybox_bind_group"),
+            layout: &skybox_pipeline.skybox_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&cube_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&gpu_image.sampler),
+                },
+            ],
+        });
+        commands
+            .entity(entity)
+            .insert(SkyboxBindGroup { value: bind_group });
+
+        // Sorted after all opaque geometry, so that early depth testing skips every fragment of
+        // the skybox that is covered.
+        opaque_phase.add(Opaque3d {
+            distance: f32::MAX,
+            pipeline,
+            entity,
+            draw_function: draw_skybox,
+        });
+    }
+}
+
+pub struct SetSkyboxBindGroup<const I: usize>;
+impl<const I: usize> EntityRenderCommand for SetSkyboxBindGroup<I> {
+    type Param = SQuery<Read<SkyboxBindGroup>>;
+    #[inline]
+    fn render<'w>(
+        view: Entity,
+        _item: Entity,
+        skybox_query: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        match skybox_query.get(view) {
+            Ok(skybox_bind_group) => {
+                pass.set_bind_group(I, &skybox_bind_group.value, &[]);
+                RenderCommandResult::Success
+            }
+            Err(_) => RenderCommandResult::Failure,
+        }
+    }
+}
+
+pub struct DrawSkyboxTriangle;
+impl EntityRenderCommand for DrawSkyboxTriangle {
+    type Param = ();
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        _item: Entity,
+        _param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        pass.draw(0..3, 0..1);
+        RenderCommandResult::Success
+    }
+}
+
+pub type DrawSkybox = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetSkyboxBindGroup<1>,
+    DrawSkyboxTriangle,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_math::Vec3;
+    use bevy_transform::components::GlobalTransform;
+
+    #[test]
+    fn skybox_stays_behind_opaque_geometry() {
+        let depth_stencil = skybox_depth_stencil_state();
+        assert!(!depth_stencil.depth_write_enabled);
+
+        // Reverse-z: the cleared depth and the skybox are both at the far plane, 0.0.
+        let passes = |stored: f32| match depth_stencil.depth_compare {
+            CompareFunction::GreaterEqual => 0.0 >= stored,
+            CompareFunction::Greater => 0.0 > stored,
+            CompareFunction::Always => true,
+            other => panic!("unexpected depth compare {:?}", other),
+        };
+        assert!(passes(0.0), "the skybox covers the cleared background");
+        assert!(!passes(0.25), "the skybox never covers opaque geometry");
+    }
+
+    #[test]
+    fn skybox_direction_follows_the_camera() {
+        let shader = include_str!("render/skybox.wgsl");
+        assert!(shader.contains("(view.view * vec4<f32>(view_direction, 0.0)).xyz"));
+
+        // The `view` matrix of the view uniform, as written by `prepare_view_uniforms`, of a
+        // camera turned to look along +X
+        let camera = GlobalTransform::identity().looking_at(Vec3::X, Vec3::Y);
+        let view = camera.compute_matrix();
+        // The view space direction through the center of the screen
+        let view_direction = Vec3::new(0.0, 0.0, -1.0);
+
+        let direction = view.transform_vector3(view_direction);
+        assert!(direction.abs_diff_eq(Vec3::X, 1e-5));
+        // The world to view matrix turns the other way.
+        let inverse = view.inverse().transform_vector3(view_direction);
+        assert!(inverse.abs_diff_eq(-Vec3::X, 1e-5));
+    }
+
+    #[test]
+    fn cubemap_requires_six_square_layers() {
+        let mut descriptor = TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: 64,
+                height: 64,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING,
+        };
+        assert!(is_cubemap(&descriptor));
+        descriptor.size.depth_or_array_layers = 1;
+        assert!(!is_cubemap(&descriptor));
+    }
+}
@@ -0,0 +1,101 @@
+use bevy_app::Plugin;
+use bevy_ecs::{prelude::*, reflect::ReflectComponent};
+use bevy_reflect::Reflect;
+use bevy_render::{render_resource::PrimitiveTopology, RenderApp, RenderStage};
+
+/// Draws meshes with a different primitive topology than their own, e.g. as points or lines, to
+/// debug their geometry.
+///
+/// Meshes are drawn through the variant of their material pipeline specialized for the
+/// [`TopologyOverrideConfig::topology`], so no separate pipeline has to be authored.
+#[derive(Debug, Default)]
+pub struct TopologyOverridePlugin;
+
+impl Plugin for TopologyOverridePlugin {
+    fn build(&self, app: &mut bevy_app::App) {
+        app.init_resource::<TopologyOverrideConfig>();
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .add_system_to_stage(RenderStage::Extract, extract_topology_overrides)
+                .add_system_to_stage(RenderStage::Extract, extract_topology_override_config);
+        }
+    }
+}
+
+fn extract_topology_override_config(
+    mut commands: Commands,
+    topology_override_config: Res<TopologyOverrideConfig>,
+) {
+    if topology_override_config.is_added() || topology_override_config.is_changed() {
+        commands.insert_resource(topology_override_config.into_inner().clone());
+    }
+}
+
+fn extract_topology_overrides(
+    mut commands: Commands,
+    query: Query<Entity, With<TopologyOverride>>,
+) {
+    for entity in query.iter() {
+        commands.get_or_spawn(entity).insert(TopologyOverride);
+    }
+}
+
+/// Marks an entity to be drawn with the [`TopologyOverrideConfig::topology`] if the
+/// [`TopologyOverridePlugin`] is enabled
+#[derive(Component, Debug, Clone, Default, Reflect)]
+#[reflect(Component)]
+pub struct TopologyOverride;
+
+#[derive(Debug, Clone, Default)]
+pub struct TopologyOverrideConfig {
+    /// The topology to draw meshes with, or `None` to draw them with their own
+    pub topology: Option<PrimitiveTopology>,
+    /// Whether to override the topology of all meshes. If `false`, only meshes with a
+    /// [`TopologyOverride`] component are affected.
+    pub global: bool,
+}
+
+impl TopologyOverrideConfig {
+    /// Returns the topology to draw a mesh of `mesh_topology` with, where `tagged` is whether the
+    /// mesh has a [`TopologyOverride`] component.
+    pub fn topology(&self, mesh_topology: PrimitiveTopology, tagged: bool) -> PrimitiveTopology {
+        match self.topology {
+            Some(topology) if self.global || tagged => topology,
+            _ => mesh_topology,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MeshPipelineKey;
+
+    #[test]
+    fn points_mode_selects_point_list_variant() {
+        let config = TopologyOverrideConfig {
+            topology: Some(PrimitiveTopology::PointList),
+            global: false,
+        };
+        let mesh_topology = PrimitiveTopology::TriangleList;
+
+        let key = MeshPipelineKey::from_primitive_topology(config.topology(mesh_topology, true));
+        assert_eq!(key.primitive_topology(), PrimitiveTopology::PointList);
+        assert_ne!(key, MeshPipelineKey::from_primitive_topology(mesh_topology));
+        assert_eq!(config.topology(mesh_topology, false), mesh_topology);
+
+        let global = TopologyOverrideConfig {
+            global: true,
+            ..config
+        };
+        assert_eq!(
+            global.topology(mesh_topology, false),
+            PrimitiveTopology::PointList
+        );
+        assert_eq!(
+            TopologyOverrideConfig::default().topology(mesh_topology, true),
+            mesh_topology
+        );
+    }
+}
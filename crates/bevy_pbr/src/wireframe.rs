@@ -119,8 +119,12 @@ fn queue_wireframes(
         let add_render_phase =
             |(entity, mesh_handle, mesh_uniform): (Entity, &Handle<Mesh>, &MeshUniform)| {
                 if let Some(mesh) = render_meshes.get(mesh_handle) {
-                    let key =
-                        key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+                    let key = key
+                        | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology)
+                        | MeshPipelineKey::from_index_format(
+                            mesh.primitive_topology,
+                            mesh.index_format(),
+                        );
                     transparent_phase.add(Opaque3d {
                         entity,
                         pipeline: specialized_pipelines.specialize(
@@ -0,0 +1,147 @@
+use crate::camera::{ActiveCameras, Camera, CameraPlugin};
+use bevy_ecs::{prelude::*, reflect::ReflectComponent};
+use bevy_math::{Mat3, Quat, Vec3};
+use bevy_reflect::{Reflect, ReflectDeserialize};
+use bevy_transform::components::GlobalTransform;
+use serde::{Deserialize, Serialize};
+
+/// Rotates an entity to face the 3d camera, e.g. for sprites, impostors or labels in 3d.
+///
+/// The entity is turned so that its local `+Z` axis, the normal of a
+/// [`Quad`](crate::mesh::shape::Quad), points at the camera, with its local `+Y` axis kept as
+/// close to the world up axis as possible. The rotation is applied to the [`GlobalTransform`]
+/// after transform propagation, so it replaces any rotation of the entity and its parents, and
+/// doesn't affect the children of the entity.
+#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+#[reflect(Component)]
+pub struct Billboard {
+    pub mode: BillboardMode,
+}
+
+/// How a [`Billboard`] rotates to face the camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+#[reflect_value(PartialEq, Serialize, Deserialize)]
+pub enum BillboardMode {
+    /// Faces the camera from any direction
+    Full,
+    /// Only rotates around the world `Y` axis, staying upright, e.g. for trees or characters
+    AxisY,
+}
+
+impl Default for BillboardMode {
+    fn default() -> Self {
+        BillboardMode::Full
+    }
+}
+
+impl BillboardMode {
+    /// Returns the rotation of a billboard at `position` facing a camera at `camera_position`, or
+    /// `None` if the facing direction is undefined, e.g. for [`BillboardMode::AxisY`] billboards
+    /// right below or above the camera.
+    pub fn rotation(self, position: Vec3, camera_position: Vec3) -> Option<Quat> {
+        let mut facing = camera_position - position;
+        if self == BillboardMode::AxisY {
+            facing.y = 0.0;
+        }
+        if facing.length_squared() <= f32::EPSILON {
+            return None;
+        }
+        let facing = facing.normalize();
+        let mut right = Vec3::Y.cross(facing);
+        if right.length_squared() <= f32::EPSILON {
+            // Looking straight up or down, so any rotation around the facing axis will do.
+            right = Vec3::X;
+        }
+        let right = right.normalize();
+        let up = facing.cross(right);
+        Some(Quat::from_mat3(&Mat3::from_cols(right, up, facing)))
+    }
+}
+
+/// Rotates the [`GlobalTransform`] of every [`Billboard`] to face the active 3d camera.
+pub fn billboard_system(
+    active_cameras: Res<ActiveCameras>,
+    cameras: Query<&GlobalTransform, With<Camera>>,
+    mut billboards: Query<(&Billboard, &mut GlobalTransform), Without<Camera>>,
+) {
+    let camera_position = match active_cameras
+        .get(CameraPlugin::CAMERA_3D)
+        .and_then(|camera| camera.entity)
+        .and_then(|entity| cameras.get(entity).ok())
+    {
+        Some(camera_transform) => camera_transform.translation,
+        None => return,
+    };
+    for (billboard, mut transform) in billboards.iter_mut() {
+        if let Some(rotation) = billboard
+            .mode
+            .rotation(transform.translation, camera_position)
+        {
+            transform.rotation = rotation;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::schedule::{Stage, SystemStage};
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn full_billboard_faces_camera() {
+        let mut world = World::default();
+        let camera_position = Vec3::new(3.0, 4.0, 5.0);
+        let camera = world
+            .spawn()
+            .insert_bundle((
+                Camera::default(),
+                GlobalTransform::from_translation(camera_position),
+            ))
+            .id();
+        let mut active_cameras = ActiveCameras::default();
+        active_cameras.add(CameraPlugin::CAMERA_3D);
+        active_cameras
+            .get_mut(CameraPlugin::CAMERA_3D)
+            .unwrap()
+            .entity = Some(camera);
+        world.insert_resource(active_cameras);
+
+        let position = Vec3::new(-1.0, 0.0, 2.0);
+        let full = world
+            .spawn()
+            .insert_bundle((
+                Billboard::default(),
+                GlobalTransform::from_translation(position),
+            ))
+            .id();
+        let axis_y = world
+            .spawn()
+            .insert_bundle((
+                Billboard {
+                    mode: BillboardMode::AxisY,
+                },
+                GlobalTransform::from_translation(position),
+            ))
+            .id();
+
+        SystemStage::single(billboard_system).run(&mut world);
+
+        let transform = world.get::<GlobalTransform>(full).unwrap();
+        assert_near(
+            transform.rotation * Vec3::Z,
+            (camera_position - position).normalize(),
+        );
+        assert_eq!(transform.translation, position);
+
+        let transform = world.get::<GlobalTransform>(axis_y).unwrap();
+        assert_near(transform.rotation * Vec3::Y, Vec3::Y);
+        assert_near(
+            transform.rotation * Vec3::Z,
+            Vec3::new(4.0, 0.0, 3.0).normalize(),
+        );
+    }
+}
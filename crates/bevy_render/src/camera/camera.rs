@@ -52,6 +52,19 @@ impl Camera {
     ) -> Option<Vec2> {
         let window = windows.get(self.window)?;
         let window_size = Vec2::new(window.width(), window.height());
+        self.world_to_viewport(camera_transform, window_size, world_position)
+    }
+
+    /// Given a position in world space, use the camera to compute the coordinates in a viewport of
+    /// `viewport_size`, with the origin in the bottom left corner.
+    ///
+    /// Returns `None` if the position is behind the camera or outside of its depth range.
+    pub fn world_to_viewport(
+        &self,
+        camera_transform: &GlobalTransform,
+        viewport_size: Vec2,
+        world_position: Vec3,
+    ) -> Option<Vec2> {
         // Build a transform to convert from world to NDC using camera data
         let world_to_ndc: Mat4 =
             self.projection_matrix * camera_transform.compute_matrix().inverse();
@@ -61,7 +74,7 @@ impl Camera {
             return None;
         }
         // Once in NDC space, we can discard the z element and rescale x/y to fit the screen
-        let screen_space_coords = (ndc_space_coords.truncate() + Vec2::ONE) / 2.0 * window_size;
+        let screen_space_coords = (ndc_space_coords.truncate() + Vec2::ONE) / 2.0 * viewport_size;
         if !screen_space_coords.is_nan() {
             Some(screen_space_coords)
         } else {
@@ -118,3 +131,40 @@ pub fn camera_system<T: CameraProjection + Component>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::PerspectiveProjection;
+
+    #[test]
+    fn world_to_viewport_projects_known_points() {
+        let projection = PerspectiveProjection {
+            aspect_ratio: 2.0,
+            ..Default::default()
+        };
+        let camera = Camera {
+            projection_matrix: projection.get_projection_matrix(),
+            ..Default::default()
+        };
+        let transform = GlobalTransform::from_xyz(0.0, 0.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y);
+        let viewport_size = Vec2::new(800.0, 400.0);
+
+        let center = camera
+            .world_to_viewport(&transform, viewport_size, Vec3::ZERO)
+            .unwrap();
+        assert!((center - Vec2::new(400.0, 200.0)).length() < 1e-3);
+
+        // A point on the top edge of the vertical field of view lands on the top row of pixels
+        let top = Vec3::new(0.0, 10.0 * (projection.fov / 2.0).tan(), 0.0);
+        let top = camera
+            .world_to_viewport(&transform, viewport_size, top)
+            .unwrap();
+        assert!((top - Vec2::new(400.0, 400.0)).length() < 1e-2);
+
+        assert_eq!(
+            camera.world_to_viewport(&transform, viewport_size, Vec3::new(0.0, 0.0, 20.0)),
+            None
+        );
+    }
+}
@@ -1,20 +1,22 @@
 mod active_cameras;
+mod billboard;
 mod bundle;
 #[allow(clippy::module_inception)]
 mod camera;
 mod projection;
 
 pub use active_cameras::*;
-use bevy_transform::components::GlobalTransform;
+use bevy_transform::{components::GlobalTransform, TransformSystem};
 use bevy_utils::HashMap;
 use bevy_window::{WindowId, Windows};
+pub use billboard::*;
 pub use bundle::*;
 pub use camera::*;
 pub use projection::*;
 
 use crate::{
     primitives::Aabb,
-    view::{ComputedVisibility, ExtractedView, Visibility, VisibleEntities},
+    view::{ComputedVisibility, ExtractedView, Visibility, VisibilitySystems, VisibleEntities},
     RenderApp, RenderStage,
 };
 use bevy_app::{App, CoreStage, Plugin};
@@ -33,7 +35,9 @@ impl Plugin for CameraPlugin {
         let mut active_cameras = ActiveCameras::default();
         active_cameras.add(Self::CAMERA_2D);
         active_cameras.add(Self::CAMERA_3D);
-        app.register_type::<Camera>()
+        app.register_type::<Billboard>()
+            .register_type::<BillboardMode>()
+            .register_type::<Camera>()
             .register_type::<Visibility>()
             .register_type::<ComputedVisibility>()
             .register_type::<OrthographicProjection>()
@@ -52,6 +56,12 @@ impl Plugin for CameraPlugin {
             .add_system_to_stage(
                 CoreStage::PostUpdate,
                 crate::camera::camera_system::<PerspectiveProjection>,
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                billboard_system
+                    .after(TransformSystem::TransformPropagate)
+                    .before(VisibilitySystems::CheckVisibility),
             );
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app
@@ -2,7 +2,6 @@ mod colorspace;
 
 pub use colorspace::*;
 
-use crate::color::{HslRepresentation, SrgbColorSpace};
 use bevy_math::{Vec3, Vec4};
 use bevy_reflect::{FromReflect, Reflect, ReflectDeserialize};
 use serde::{Deserialize, Serialize};
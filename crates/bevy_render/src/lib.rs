@@ -36,8 +36,11 @@ use crate::{
     mesh::MeshPlugin,
     primitives::{CubemapFrusta, Frustum},
     render_graph::RenderGraph,
-    render_resource::{RenderPipelineCache, Shader, ShaderLoader},
-    renderer::render_system,
+    render_resource::{
+        update_buffer_pool_system, BufferPool, RenderPipelineCache, ResourcePoolSettings, Shader,
+        ShaderLoader, StagingBuffer,
+    },
+    renderer::{render_system, update_gpu_timings_system, GpuTimings},
     texture::ImagePlugin,
     view::{ViewPlugin, WindowRenderPlugin},
 };
@@ -113,6 +116,11 @@ impl Plugin for RenderPlugin {
             .get_resource::<options::WgpuOptions>()
             .cloned()
             .unwrap_or_default();
+        let pool_settings = app
+            .world
+            .get_resource::<ResourcePoolSettings>()
+            .cloned()
+            .unwrap_or_default();
 
         app.add_asset::<Shader>()
             .init_asset_loader::<ShaderLoader>()
@@ -167,13 +175,22 @@ impl Plugin for RenderPlugin {
                         .with_system(RenderPipelineCache::process_pipeline_queue_system)
                         .with_system(render_system.exclusive_system().at_end()),
                 )
-                .add_stage(RenderStage::Cleanup, SystemStage::parallel())
+                .add_stage(
+                    RenderStage::Cleanup,
+                    SystemStage::parallel()
+                        .with_system(update_buffer_pool_system)
+                        .with_system(update_gpu_timings_system),
+                )
                 .insert_resource(instance)
                 .insert_resource(device)
                 .insert_resource(queue)
                 .insert_resource(options)
                 .insert_resource(render_pipeline_cache)
                 .insert_resource(asset_server)
+                .insert_resource(pool_settings)
+                .init_resource::<BufferPool>()
+                .init_resource::<StagingBuffer>()
+                .init_resource::<GpuTimings>()
                 .init_resource::<RenderGraph>();
 
             app.add_sub_app(RenderApp, render_app, move |app_world, render_app| {
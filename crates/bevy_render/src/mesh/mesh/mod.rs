@@ -2,16 +2,24 @@ mod conversions;
 
 use crate::{
     primitives::Aabb,
-    render_asset::{PrepareAssetError, RenderAsset},
-    render_resource::Buffer,
-    renderer::RenderDevice,
+    render_asset::{PrepareAssetError, RenderAsset, RenderAssets},
+    render_resource::{Buffer, StagingBuffer},
+    renderer::{RenderDevice, RenderQueue, RenderResourceError},
 };
+use bevy_asset::Handle;
 use bevy_core::cast_slice;
-use bevy_ecs::system::{lifetimeless::SRes, SystemParamItem};
+use bevy_ecs::system::{
+    lifetimeless::{SRes, SResMut},
+    SystemParamItem,
+};
 use bevy_math::*;
 use bevy_reflect::TypeUuid;
 use bevy_utils::EnumVariantMeta;
-use std::{borrow::Cow, collections::BTreeMap};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
 use wgpu::{
     util::BufferInitDescriptor, BufferUsages, IndexFormat, PrimitiveTopology, VertexFormat,
 };
@@ -30,6 +38,24 @@ pub struct Mesh {
     /// which allows easy stable VertexBuffers (i.e. same buffer order)
     attributes: BTreeMap<Cow<'static, str>, VertexAttributeValues>,
     indices: Option<Indices>,
+    /// Changes whenever the vertex attributes may have been modified.
+    vertex_data_id: MeshDataId,
+    /// Changes whenever the indices may have been modified.
+    index_data_id: MeshDataId,
+}
+
+/// Identifies a version of the vertex or index data of a [`Mesh`].
+///
+/// A new id is generated whenever the data may have changed, so two meshes (e.g. a mesh and its
+/// clone) with the same id are guaranteed to have the same data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshDataId(u64);
+
+impl MeshDataId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        MeshDataId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
 }
 
 /// Contains geometry in the form of a mesh.
@@ -76,6 +102,8 @@ impl Mesh {
             primitive_topology,
             attributes: Default::default(),
             indices: None,
+            vertex_data_id: MeshDataId::new(),
+            index_data_id: MeshDataId::new(),
         }
     }
 
@@ -93,6 +121,7 @@ impl Mesh {
     ) {
         let values: VertexAttributeValues = values.into();
         self.attributes.insert(name.into(), values);
+        self.vertex_data_id = MeshDataId::new();
     }
 
     /// Retrieves the data currently set to the vertex attribute with the specified `name`.
@@ -105,6 +134,7 @@ impl Mesh {
         &mut self,
         name: impl Into<Cow<'static, str>>,
     ) -> Option<&mut VertexAttributeValues> {
+        self.vertex_data_id = MeshDataId::new();
         self.attributes.get_mut(&name.into())
     }
 
@@ -113,6 +143,7 @@ impl Mesh {
     /// that use triangles.
     pub fn set_indices(&mut self, indices: Option<Indices>) {
         self.indices = indices;
+        self.index_data_id = MeshDataId::new();
     }
 
     /// Retrieves the vertex `indices` of the mesh.
@@ -122,9 +153,20 @@ impl Mesh {
 
     /// Retrieves the vertex `indices` of the mesh mutably.
     pub fn indices_mut(&mut self) -> Option<&mut Indices> {
+        self.index_data_id = MeshDataId::new();
         self.indices.as_mut()
     }
 
+    /// Returns the id of the current version of the vertex attribute data.
+    pub fn vertex_data_id(&self) -> MeshDataId {
+        self.vertex_data_id
+    }
+
+    /// Returns the id of the current version of the index data.
+    pub fn index_data_id(&self) -> MeshDataId {
+        self.index_data_id
+    }
+
     /// Computes and returns the index data of the mesh as bytes.
     /// This is used to transform the index data into a GPU friendly format.
     pub fn get_index_buffer_bytes(&self) -> Option<&[u8]> {
@@ -174,6 +216,35 @@ impl Mesh {
         vertex_count.unwrap_or(0)
     }
 
+    /// Returns the names of the attributes in `required` that this mesh doesn't provide, or
+    /// provides in a format the shader can't read them as.
+    ///
+    /// `required` is usually reflected from a vertex shader with
+    /// [`ShaderReflection::required_vertex_attributes`](crate::render_resource::ShaderReflection::required_vertex_attributes).
+    /// Attribute names are matched ignoring case, underscores and a leading `vertex`, so a shader
+    /// input named `normal` or `vertex_normal` matches [`Mesh::ATTRIBUTE_NORMAL`].
+    pub fn missing_vertex_attributes(&self, required: &[(String, VertexFormat)]) -> Vec<String> {
+        required
+            .iter()
+            .filter(|(name, format)| {
+                let name = normalize_attribute_name(name);
+                !self.attributes.iter().any(|(attribute_name, values)| {
+                    normalize_attribute_name(attribute_name) == name
+                        && shader_input_type(VertexFormat::from(values))
+                            == shader_input_type(*format)
+                })
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Returns `true` if this mesh provides all the vertex attributes in `required`.
+    ///
+    /// See [`Mesh::missing_vertex_attributes`] for how attributes are matched.
+    pub fn satisfies(&self, required: &[(String, VertexFormat)]) -> bool {
+        self.missing_vertex_attributes(required).is_empty()
+    }
+
     /// Computes and returns the vertex data of the mesh as bytes.
     /// Therefore the attributes are located in alphabetical order.
     /// This is used to transform the vertex data into a GPU friendly format.
@@ -230,6 +301,8 @@ impl Mesh {
             Some(indices) => indices,
             None => return,
         };
+        self.vertex_data_id = MeshDataId::new();
+        self.index_data_id = MeshDataId::new();
         for attributes in self.attributes.values_mut() {
             let indices = indices.iter();
             match attributes {
@@ -288,6 +361,46 @@ impl Mesh {
         self.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
     }
 
+    /// Calculates the [`Mesh::ATTRIBUTE_TANGENT`] of a mesh from its positions, normals and UVs,
+    /// as needed for normal mapping.
+    ///
+    /// The tangent of each triangle is accumulated on its vertices, so vertices shared between
+    /// triangles get the average direction, which is then orthonormalized against the vertex
+    /// normal. Following the glTF convention, the `w` component holds the handedness: the
+    /// bitangent is `cross(normal, tangent.xyz) * w`, pointing towards decreasing v (up in the
+    /// texture).
+    ///
+    /// # Panics
+    /// Panics if the mesh isn't a [`PrimitiveTopology::TriangleList`], or if
+    /// [`Mesh::ATTRIBUTE_POSITION`] and [`Mesh::ATTRIBUTE_NORMAL`] are missing or not of type
+    /// `float3`, or [`Mesh::ATTRIBUTE_UV_0`] is missing or not of type `float2`.
+    pub fn compute_tangents(&mut self) {
+        assert!(
+            matches!(self.primitive_topology, PrimitiveTopology::TriangleList),
+            "can only compute tangents for `TriangleList`s"
+        );
+
+        let positions = self
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .and_then(VertexAttributeValues::as_float3)
+            .expect("`Mesh::ATTRIBUTE_POSITION` vertex attributes should be of type `float3`");
+        let normals = self
+            .attribute(Mesh::ATTRIBUTE_NORMAL)
+            .and_then(VertexAttributeValues::as_float3)
+            .expect("`Mesh::ATTRIBUTE_NORMAL` vertex attributes should be of type `float3`");
+        let uvs = self
+            .attribute(Mesh::ATTRIBUTE_UV_0)
+            .and_then(VertexAttributeValues::as_float2)
+            .expect("`Mesh::ATTRIBUTE_UV_0` vertex attributes should be of type `float2`");
+
+        let indices: Vec<usize> = match &self.indices {
+            Some(indices) => indices.iter().collect(),
+            None => (0..positions.len()).collect(),
+        };
+        let tangents = compute_tangents(positions, normals, uvs, &indices);
+        self.set_attribute(Mesh::ATTRIBUTE_TANGENT, tangents);
+    }
+
     /// Compute the Axis-Aligned Bounding Box of the mesh vertices in model space
     pub fn compute_aabb(&self) -> Option<Aabb> {
         if let Some(VertexAttributeValues::Float32x3(values)) =
@@ -322,6 +435,65 @@ fn face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
     (b - a).cross(c - a).normalize().into()
 }
 
+fn compute_tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    uvs: &[[f32; 2]],
+    indices: &[usize],
+) -> Vec<[f32; 4]> {
+    let mut tangents = vec![Vec3::ZERO; positions.len()];
+    let mut bitangents = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [i0, i1, i2] = [triangle[0], triangle[1], triangle[2]];
+        let edge1 = Vec3::from(positions[i1]) - Vec3::from(positions[i0]);
+        let edge2 = Vec3::from(positions[i2]) - Vec3::from(positions[i0]);
+        let delta1 = Vec2::from(uvs[i1]) - Vec2::from(uvs[i0]);
+        let delta2 = Vec2::from(uvs[i2]) - Vec2::from(uvs[i0]);
+
+        let determinant = delta1.x * delta2.y - delta2.x * delta1.y;
+        if determinant.abs() <= f32::EPSILON {
+            // The UVs of this triangle are degenerate, so it has no defined tangent.
+            continue;
+        }
+        let tangent = (edge1 * delta2.y - edge2 * delta1.y) / determinant;
+        // Towards decreasing v, as the v axis of textures points down.
+        let bitangent = (edge1 * delta2.x - edge2 * delta1.x) / determinant;
+        for &i in triangle {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    tangents
+        .iter()
+        .zip(&bitangents)
+        .zip(normals)
+        .map(|((&tangent, &bitangent), &normal)| {
+            let normal = Vec3::from(normal);
+            // Gram-Schmidt orthonormalization against the normal
+            let mut tangent = tangent - normal * normal.dot(tangent);
+            if tangent.length_squared() <= f32::EPSILON {
+                tangent = any_orthogonal_vector(normal);
+            }
+            let tangent = tangent.normalize();
+            let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            tangent.extend(handedness).into()
+        })
+        .collect()
+}
+
+fn any_orthogonal_vector(v: Vec3) -> Vec3 {
+    if v.x.abs() > 0.9 {
+        v.cross(Vec3::Y)
+    } else {
+        v.cross(Vec3::X)
+    }
+}
+
 pub trait VertexFormatSize {
     fn get_size(self) -> u64;
 }
@@ -442,6 +614,14 @@ impl VertexAttributeValues {
         self.len() == 0
     }
 
+    /// Returns the values as float pairs if possible.
+    fn as_float2(&self) -> Option<&[[f32; 2]]> {
+        match self {
+            VertexAttributeValues::Float32x2(values) => Some(values),
+            _ => None,
+        }
+    }
+
     /// Returns the values as float triples if possible.
     fn as_float3(&self) -> Option<&[[f32; 3]]> {
         match self {
@@ -592,6 +772,9 @@ pub struct GpuMesh {
     pub buffer_info: GpuBufferInfo,
     pub has_tangents: bool,
     pub primitive_topology: PrimitiveTopology,
+    vertex_buffer_size: usize,
+    vertex_data_id: MeshDataId,
+    index_data_id: MeshDataId,
 }
 
 /// The index/vertex buffer info of a [`GpuMesh`].
@@ -611,7 +794,11 @@ pub enum GpuBufferInfo {
 impl RenderAsset for Mesh {
     type ExtractedAsset = Mesh;
     type PreparedAsset = GpuMesh;
-    type Param = SRes<RenderDevice>;
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<RenderQueue>,
+        SResMut<StagingBuffer>,
+    );
 
     /// Clones the mesh.
     fn extract_asset(&self) -> Self::ExtractedAsset {
@@ -621,35 +808,355 @@ impl RenderAsset for Mesh {
     /// Converts the extracted mesh a into [`GpuMesh`].
     fn prepare_asset(
         mesh: Self::ExtractedAsset,
-        render_device: &mut SystemParamItem<Self::Param>,
+        (render_device, render_queue, staging_buffer): &mut SystemParamItem<Self::Param>,
     ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
         let vertex_buffer_data = mesh.get_vertex_buffer_data();
-        let vertex_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-            usage: BufferUsages::VERTEX,
-            label: Some("Mesh Vertex Buffer"),
-            contents: &vertex_buffer_data,
-        });
-
+        let vertex_buffer = create_vertex_buffer(
+            render_device,
+            render_queue,
+            staging_buffer,
+            &vertex_buffer_data,
+        );
         let buffer_info = mesh.get_index_buffer_bytes().map_or(
             GpuBufferInfo::NonIndexed {
                 vertex_count: mesh.count_vertices() as u32,
             },
             |data| GpuBufferInfo::Indexed {
-                buffer: render_device.create_buffer_with_data(&BufferInitDescriptor {
-                    usage: BufferUsages::INDEX,
-                    contents: data,
-                    label: Some("Mesh Index Buffer"),
-                }),
+                buffer: create_index_buffer(render_device, render_queue, staging_buffer, data),
                 count: mesh.indices().unwrap().len() as u32,
                 index_format: mesh.indices().unwrap().into(),
             },
         );
 
-        Ok(GpuMesh {
+        Ok(GpuMesh::new(
+            &mesh,
+            vertex_buffer,
+            vertex_buffer_data.len(),
+            buffer_info,
+        ))
+    }
+
+    /// Only re-uploads the vertex and index data of `mesh` that changed since `previous` was
+    /// prepared. Vertex data of unchanged size is written into the existing vertex buffer.
+    fn prepare_modified_asset(
+        mesh: Self::ExtractedAsset,
+        previous: &Self::PreparedAsset,
+        (render_device, render_queue, staging_buffer): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let changes = previous.changes(&mesh);
+
+        let (vertex_buffer, vertex_buffer_size) = if changes.vertices {
+            let vertex_buffer_data = mesh.get_vertex_buffer_data();
+            let vertex_buffer = if previous.vertex_buffer_size == vertex_buffer_data.len() {
+                render_queue.write_buffer(&previous.vertex_buffer, 0, &vertex_buffer_data);
+                previous.vertex_buffer.clone()
+            } else {
+                create_vertex_buffer(
+                    render_device,
+                    render_queue,
+                    staging_buffer,
+                    &vertex_buffer_data,
+                )
+            };
+            (vertex_buffer, vertex_buffer_data.len())
+        } else {
+            (previous.vertex_buffer.clone(), previous.vertex_buffer_size)
+        };
+
+        let buffer_info = if changes.indices {
+            mesh.get_index_buffer_bytes().map_or(
+                GpuBufferInfo::NonIndexed {
+                    vertex_count: mesh.count_vertices() as u32,
+                },
+                |data| GpuBufferInfo::Indexed {
+                    buffer: create_index_buffer(render_device, render_queue, staging_buffer, data),
+                    count: mesh.indices().unwrap().len() as u32,
+                    index_format: mesh.indices().unwrap().into(),
+                },
+            )
+        } else {
+            match &previous.buffer_info {
+                GpuBufferInfo::NonIndexed { .. } => GpuBufferInfo::NonIndexed {
+                    vertex_count: mesh.count_vertices() as u32,
+                },
+                indexed => indexed.clone(),
+            }
+        };
+
+        Ok(GpuMesh::new(
+            &mesh,
+            vertex_buffer,
+            vertex_buffer_size,
+            buffer_info,
+        ))
+    }
+}
+
+fn create_vertex_buffer(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    staging_buffer: &mut StagingBuffer,
+    data: &[u8],
+) -> Buffer {
+    staging_buffer.create_buffer_with_data(
+        render_device,
+        render_queue,
+        &BufferInitDescriptor {
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            label: Some("Mesh Vertex Buffer"),
+            contents: data,
+        },
+    )
+}
+
+fn create_index_buffer(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    staging_buffer: &mut StagingBuffer,
+    data: &[u8],
+) -> Buffer {
+    staging_buffer.create_buffer_with_data(
+        render_device,
+        render_queue,
+        &BufferInitDescriptor {
+            usage: BufferUsages::INDEX,
+            contents: data,
+            label: Some("Mesh Index Buffer"),
+        },
+    )
+}
+
+/// Which buffers of a [`GpuMesh`] are out of date with respect to a [`Mesh`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpuMeshChanges {
+    pub vertices: bool,
+    pub indices: bool,
+}
+
+impl GpuMesh {
+    /// Looks up the [`GpuMesh`] prepared for `handle`.
+    ///
+    /// Returns [`RenderResourceError::BufferNotFound`] with the id of `handle` if the mesh hasn't
+    /// been prepared yet, or has been removed since.
+    pub fn get<'a>(
+        gpu_meshes: &'a RenderAssets<Mesh>,
+        handle: &Handle<Mesh>,
+    ) -> Result<&'a GpuMesh, RenderResourceError> {
+        gpu_meshes
+            .get(handle)
+            .ok_or(RenderResourceError::BufferNotFound(handle.id))
+    }
+
+    fn new(
+        mesh: &Mesh,
+        vertex_buffer: Buffer,
+        vertex_buffer_size: usize,
+        buffer_info: GpuBufferInfo,
+    ) -> Self {
+        GpuMesh {
             vertex_buffer,
             buffer_info,
+            vertex_buffer_size,
             has_tangents: mesh.attributes.contains_key(Mesh::ATTRIBUTE_TANGENT),
             primitive_topology: mesh.primitive_topology(),
-        })
+            vertex_data_id: mesh.vertex_data_id,
+            index_data_id: mesh.index_data_id,
+        }
+    }
+
+    /// Returns the format of the index buffer, or `None` if the mesh isn't indexed.
+    pub fn index_format(&self) -> Option<IndexFormat> {
+        match &self.buffer_info {
+            GpuBufferInfo::Indexed { index_format, .. } => Some(*index_format),
+            GpuBufferInfo::NonIndexed { .. } => None,
+        }
+    }
+
+    /// Returns which buffers need to be re-uploaded to match `mesh`.
+    pub fn changes(&self, mesh: &Mesh) -> GpuMeshChanges {
+        GpuMeshChanges {
+            vertices: self.vertex_data_id != mesh.vertex_data_id,
+            indices: self.index_data_id != mesh.index_data_id,
+        }
+    }
+}
+
+fn normalize_attribute_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|c| *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect();
+    match name.strip_prefix("vertex") {
+        Some(stripped) => stripped.to_string(),
+        None => name,
+    }
+}
+
+/// The kind of values a vertex shader reads from an attribute.
+#[derive(Debug, PartialEq, Eq)]
+enum ShaderInputKind {
+    Float,
+    Uint,
+    Sint,
+}
+
+/// Returns the kind and the number of components a vertex shader reads from an attribute of
+/// `format`. Normalized and half-precision formats are read as floats.
+fn shader_input_type(format: VertexFormat) -> (ShaderInputKind, u32) {
+    use ShaderInputKind::*;
+    match format {
+        VertexFormat::Uint8x2 | VertexFormat::Uint16x2 | VertexFormat::Uint32x2 => (Uint, 2),
+        VertexFormat::Uint8x4 | VertexFormat::Uint16x4 | VertexFormat::Uint32x4 => (Uint, 4),
+        VertexFormat::Uint32 => (Uint, 1),
+        VertexFormat::Uint32x3 => (Uint, 3),
+        VertexFormat::Sint8x2 | VertexFormat::Sint16x2 | VertexFormat::Sint32x2 => (Sint, 2),
+        VertexFormat::Sint8x4 | VertexFormat::Sint16x4 | VertexFormat::Sint32x4 => (Sint, 4),
+        VertexFormat::Sint32 => (Sint, 1),
+        VertexFormat::Sint32x3 => (Sint, 3),
+        VertexFormat::Unorm8x2
+        | VertexFormat::Snorm8x2
+        | VertexFormat::Unorm16x2
+        | VertexFormat::Snorm16x2
+        | VertexFormat::Float16x2
+        | VertexFormat::Float32x2 => (Float, 2),
+        VertexFormat::Unorm8x4
+        | VertexFormat::Snorm8x4
+        | VertexFormat::Unorm16x4
+        | VertexFormat::Snorm16x4
+        | VertexFormat::Float16x4
+        | VertexFormat::Float32x4 => (Float, 4),
+        VertexFormat::Float32 | VertexFormat::Float64 => (Float, 1),
+        VertexFormat::Float32x3 | VertexFormat::Float64x3 => (Float, 3),
+        VertexFormat::Float64x2 => (Float, 2),
+        VertexFormat::Float64x4 => (Float, 4),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_resource::ProcessedShader;
+    use bevy_asset::HandleId;
+
+    #[test]
+    fn stale_gpu_mesh_reports_its_handle() {
+        // No buffers are prepared for a mesh that was removed, so its handle is stale.
+        let gpu_meshes = RenderAssets::<Mesh>::default();
+        let handle = Handle::<Mesh>::weak(HandleId::random::<Mesh>());
+        assert_eq!(
+            GpuMesh::get(&gpu_meshes, &handle).unwrap_err(),
+            RenderResourceError::BufferNotFound(handle.id)
+        );
+    }
+
+    #[test]
+    fn mutating_vertices_only_invalidates_vertex_data() {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        );
+        mesh.set_indices(Some(Indices::U16(vec![0, 1, 2])));
+        let uploaded = mesh.clone();
+        assert_eq!(uploaded.vertex_data_id(), mesh.vertex_data_id());
+        assert_eq!(uploaded.index_data_id(), mesh.index_data_id());
+
+        if let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+        {
+            positions[0][2] = 1.0;
+        }
+        assert_ne!(uploaded.vertex_data_id(), mesh.vertex_data_id());
+        assert_eq!(uploaded.index_data_id(), mesh.index_data_id());
+
+        mesh.set_indices(Some(Indices::U16(vec![2, 1, 0])));
+        assert_ne!(uploaded.index_data_id(), mesh.index_data_id());
+    }
+
+    #[test]
+    fn uint32_indices_address_more_vertices_than_uint16() {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0.0f32; 3]; 70_000]);
+        mesh.set_indices(Some(Indices::U32(vec![0, 69_999, 65_536])));
+
+        let indices = mesh.indices().unwrap();
+        assert_eq!(IndexFormat::from(indices), IndexFormat::Uint32);
+        assert_eq!(indices.iter().max(), Some(69_999));
+        let bytes = mesh.get_index_buffer_bytes().unwrap();
+        assert_eq!(bytes.len(), 3 * 4);
+        assert_eq!(cast_slice::<u8, u32>(bytes), [0, 69_999, 65_536]);
+    }
+
+    #[test]
+    fn mesh_without_normals_does_not_satisfy_pipeline() {
+        const WGSL: &str = r"
+[[stage(vertex)]]
+fn vertex(
+    [[location(0)]] vertex_position: vec3<f32>,
+    [[location(1)]] vertex_normal: vec3<f32>,
+    [[location(2)]] vertex_uv: vec2<f32>,
+) -> [[builtin(position)]] vec4<f32> {
+    return vec4<f32>(vertex_position + vertex_normal, vertex_uv.x);
+}
+";
+        let required = ProcessedShader::Wgsl(WGSL.into())
+            .reflect()
+            .unwrap()
+            .required_vertex_attributes("vertex")
+            .unwrap();
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0.0, 0.0, 0.0]; 3]);
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; 3]);
+        assert!(!mesh.satisfies(&required));
+        assert_eq!(mesh.missing_vertex_attributes(&required), ["vertex_normal"]);
+
+        // An attribute of the wrong type doesn't count either.
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0]; 3]);
+        assert!(!mesh.satisfies(&required));
+
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 1.0, 0.0]; 3]);
+        assert!(mesh.satisfies(&required));
+    }
+
+    #[test]
+    fn tangents_of_uv_mapped_quad() {
+        // A quad facing +z, with u increasing along +x and v increasing along -y.
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [-1.0, -1.0, 0.0],
+                [1.0, -1.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [-1.0, 1.0, 0.0],
+            ],
+        );
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 4]);
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            vec![[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]],
+        );
+        mesh.set_indices(Some(Indices::U16(vec![0, 1, 2, 0, 2, 3])));
+
+        mesh.compute_tangents();
+        let tangents = match mesh.attribute(Mesh::ATTRIBUTE_TANGENT) {
+            Some(VertexAttributeValues::Float32x4(tangents)) => tangents.clone(),
+            _ => panic!("tangents should be of type `float4`"),
+        };
+        assert_eq!(tangents, vec![[1.0, 0.0, 0.0, 1.0]; 4]);
+
+        // Mirroring the texture horizontally flips the tangent and the handedness.
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            vec![[1.0, 1.0], [0.0, 1.0], [0.0, 0.0], [1.0, 0.0]],
+        );
+        mesh.compute_tangents();
+        assert!(matches!(
+            mesh.attribute(Mesh::ATTRIBUTE_TANGENT),
+            Some(VertexAttributeValues::Float32x4(tangents))
+                if tangents.iter().all(|t| *t == [-1.0, 0.0, 0.0, -1.0])
+        ));
     }
 }
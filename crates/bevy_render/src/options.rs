@@ -1,6 +1,9 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, sync::Arc};
 
-pub use wgpu::{Backends, Features as WgpuFeatures, Limits as WgpuLimits, PowerPreference};
+pub use wgpu::{
+    AdapterInfo, Backend, Backends, DeviceType, Features as WgpuFeatures, Limits as WgpuLimits,
+    PowerPreference,
+};
 
 #[derive(Clone)]
 pub enum WgpuOptionsPriority {
@@ -9,11 +12,41 @@ pub enum WgpuOptionsPriority {
     WebGL2,
 }
 
+/// Selects the GPU adapter the renderer is initialized with.
+#[derive(Clone)]
+pub enum AdapterSelection {
+    /// Let wgpu pick an adapter according to [`WgpuOptions::power_preference`].
+    Default,
+    /// Use the adapter at this index among all adapters available for [`WgpuOptions::backends`].
+    Index(usize),
+    /// Use the first available adapter this predicate returns `true` for, e.g. to force the
+    /// discrete GPU or a specific device on multi-GPU systems.
+    Predicate(Arc<dyn Fn(&AdapterInfo) -> bool + Send + Sync>),
+}
+
+impl AdapterSelection {
+    /// Returns the index of the selected adapter among `adapters`.
+    ///
+    /// Returns `None` for [`AdapterSelection::Default`] or if no adapter matches.
+    pub fn select(&self, adapters: &[AdapterInfo]) -> Option<usize> {
+        match self {
+            AdapterSelection::Default => None,
+            AdapterSelection::Index(index) if *index < adapters.len() => Some(*index),
+            AdapterSelection::Index(_) => None,
+            AdapterSelection::Predicate(predicate) => {
+                adapters.iter().position(|info| predicate(info))
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct WgpuOptions {
     pub device_label: Option<Cow<'static, str>>,
     pub backends: Option<Backends>,
     pub power_preference: PowerPreference,
+    /// Overrides the adapter chosen according to `power_preference`.
+    pub adapter_selection: AdapterSelection,
     pub priority: WgpuOptionsPriority,
     /// The enabled features. Setting features will require them to be enabled when initializing
     /// the renderer.
@@ -56,6 +89,7 @@ impl Default for WgpuOptions {
             device_label: Default::default(),
             backends,
             power_preference: PowerPreference::HighPerformance,
+            adapter_selection: AdapterSelection::Default,
             priority,
             features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
             disabled_features: None,
@@ -80,3 +114,45 @@ pub fn options_priority_from_env() -> Option<WgpuOptionsPriority> {
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adapter(name: &str, device_type: DeviceType, backend: Backend) -> AdapterInfo {
+        AdapterInfo {
+            name: name.to_string(),
+            vendor: 0,
+            device: 0,
+            device_type,
+            backend,
+        }
+    }
+
+    #[test]
+    fn select_adapter() {
+        let adapters = [
+            adapter("integrated", DeviceType::IntegratedGpu, Backend::Vulkan),
+            adapter("discrete", DeviceType::DiscreteGpu, Backend::Vulkan),
+            adapter("discrete", DeviceType::DiscreteGpu, Backend::Gl),
+        ];
+
+        let discrete = AdapterSelection::Predicate(Arc::new(|info: &AdapterInfo| {
+            info.device_type == DeviceType::DiscreteGpu
+        }));
+        assert_eq!(discrete.select(&adapters), Some(1));
+
+        let gl =
+            AdapterSelection::Predicate(Arc::new(|info: &AdapterInfo| info.backend == Backend::Gl));
+        assert_eq!(gl.select(&adapters), Some(2));
+
+        let cpu = AdapterSelection::Predicate(Arc::new(|info: &AdapterInfo| {
+            info.device_type == DeviceType::Cpu
+        }));
+        assert_eq!(cpu.select(&adapters), None);
+
+        assert_eq!(AdapterSelection::Index(0).select(&adapters), Some(0));
+        assert_eq!(AdapterSelection::Index(3).select(&adapters), None);
+        assert_eq!(AdapterSelection::Default.select(&adapters), None);
+    }
+}
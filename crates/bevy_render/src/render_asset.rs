@@ -37,6 +37,17 @@ pub trait RenderAsset: Asset {
         extracted_asset: Self::ExtractedAsset,
         param: &mut SystemParamItem<Self::Param>,
     ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>>;
+    /// Prepares the `extracted_asset` of a modified asset, whose `previous` GPU-representation
+    /// is still available. This allows reusing the unchanged parts of `previous`.
+    ///
+    /// Defaults to [`RenderAsset::prepare_asset`].
+    fn prepare_modified_asset(
+        extracted_asset: Self::ExtractedAsset,
+        _previous: &Self::PreparedAsset,
+        param: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        Self::prepare_asset(extracted_asset, param)
+    }
 }
 
 /// This plugin extracts the changed assets from the "app world" into the "render world"
@@ -154,9 +165,16 @@ impl<R: RenderAsset> RunSystem for PrepareAssetSystem<R> {
     fn run(
         (mut extracted_assets, mut render_assets, mut prepare_next_frame, mut param): SystemParamItem<Self::Param>,
     ) {
+        let removed_assets = std::mem::take(&mut extracted_assets.removed);
         let mut queued_assets = std::mem::take(&mut prepare_next_frame.assets);
+        // Assets that were removed while waiting to be prepared must not be prepared anymore.
+        queued_assets.retain(|(handle, _)| !removed_assets.contains(handle));
         for (handle, extracted_asset) in queued_assets.drain(..) {
-            match R::prepare_asset(extracted_asset, &mut param) {
+            let prepared_asset = match render_assets.get(&handle) {
+                Some(previous) => R::prepare_modified_asset(extracted_asset, previous, &mut param),
+                None => R::prepare_asset(extracted_asset, &mut param),
+            };
+            match prepared_asset {
                 Ok(prepared_asset) => {
                     render_assets.insert(handle, prepared_asset);
                 }
@@ -166,12 +184,16 @@ impl<R: RenderAsset> RunSystem for PrepareAssetSystem<R> {
             }
         }
 
-        for removed in std::mem::take(&mut extracted_assets.removed) {
+        for removed in removed_assets {
             render_assets.remove(&removed);
         }
 
         for (handle, extracted_asset) in std::mem::take(&mut extracted_assets.extracted) {
-            match R::prepare_asset(extracted_asset, &mut param) {
+            let prepared_asset = match render_assets.get(&handle) {
+                Some(previous) => R::prepare_modified_asset(extracted_asset, previous, &mut param),
+                None => R::prepare_asset(extracted_asset, &mut param),
+            };
+            match prepared_asset {
                 Ok(prepared_asset) => {
                     render_assets.insert(handle, prepared_asset);
                 }
@@ -182,3 +204,71 @@ impl<R: RenderAsset> RunSystem for PrepareAssetSystem<R> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_asset::HandleId;
+    use bevy_ecs::schedule::{Stage, SystemStage};
+    use bevy_reflect::TypeUuid;
+
+    #[derive(TypeUuid)]
+    #[uuid = "3b9b6b7e-5a2c-4d1f-9a0c-6f1e2d3c4b5a"]
+    struct TestAsset;
+
+    impl RenderAsset for TestAsset {
+        /// Whether the asset can be prepared yet
+        type ExtractedAsset = bool;
+        type PreparedAsset = ();
+        type Param = ();
+
+        fn extract_asset(&self) -> Self::ExtractedAsset {
+            true
+        }
+
+        fn prepare_asset(
+            ready: Self::ExtractedAsset,
+            _param: &mut SystemParamItem<Self::Param>,
+        ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+            if ready {
+                Ok(())
+            } else {
+                Err(PrepareAssetError::RetryNextUpdate(ready))
+            }
+        }
+    }
+
+    #[test]
+    fn removed_assets_are_dropped_even_if_not_prepared_yet() {
+        let mut world = World::default();
+        world.init_resource::<RenderAssets<TestAsset>>();
+        world.init_resource::<PrepareNextFrameAssets<TestAsset>>();
+        let prepared = Handle::<TestAsset>::weak(HandleId::random::<TestAsset>());
+        let pending = Handle::<TestAsset>::weak(HandleId::random::<TestAsset>());
+        let mut stage = SystemStage::single(PrepareAssetSystem::<TestAsset>::system(&mut world));
+
+        world.insert_resource(ExtractedAssets::<TestAsset> {
+            extracted: vec![(prepared.clone(), true), (pending.clone(), false)],
+            removed: Vec::new(),
+        });
+        stage.run(&mut world);
+        assert!(world
+            .get_resource::<RenderAssets<TestAsset>>()
+            .unwrap()
+            .contains_key(&prepared));
+        let next_frame = world.get_resource::<PrepareNextFrameAssets<TestAsset>>();
+        assert_eq!(next_frame.unwrap().assets.len(), 1);
+
+        world.insert_resource(ExtractedAssets::<TestAsset> {
+            extracted: Vec::new(),
+            removed: vec![prepared, pending],
+        });
+        stage.run(&mut world);
+        assert!(world
+            .get_resource::<RenderAssets<TestAsset>>()
+            .unwrap()
+            .is_empty());
+        let next_frame = world.get_resource::<PrepareNextFrameAssets<TestAsset>>();
+        assert!(next_frame.unwrap().assets.is_empty());
+    }
+}
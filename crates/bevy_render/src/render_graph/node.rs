@@ -3,7 +3,7 @@ use crate::{
         Edge, InputSlotError, OutputSlotError, RenderGraphContext, RenderGraphError,
         RunSubGraphError, SlotInfo, SlotInfos,
     },
-    renderer::RenderContext,
+    renderer::{RenderContext, RenderResourceError},
 };
 use bevy_ecs::world::World;
 use bevy_utils::Uuid;
@@ -78,6 +78,8 @@ pub enum NodeRunError {
     OutputSlotError(#[from] OutputSlotError),
     #[error("encountered an error when running a sub-graph")]
     RunSubGraphError(#[from] RunSubGraphError),
+    #[error("encountered an invalid render resource: {0}")]
+    RenderResourceError(#[from] RenderResourceError),
 }
 
 /// A collection of input and output [`Edges`](Edge) for a [`Node`].
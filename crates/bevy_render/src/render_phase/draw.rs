@@ -1,5 +1,5 @@
 use crate::{
-    render_phase::TrackedRenderPass,
+    render_phase::{PipelineRequirements, TrackedRenderPass},
     render_resource::{CachedPipelineId, RenderPipelineCache},
 };
 use bevy_app::App;
@@ -233,8 +233,14 @@ impl<P: CachedPipelinePhaseItem> RenderCommand<P> for SetItemPipeline {
         pipeline_cache: SystemParamItem<'w, '_, Self::Param>,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
-        if let Some(pipeline) = pipeline_cache.into_inner().get(item.cached_pipeline()) {
+        let pipeline_cache = pipeline_cache.into_inner();
+        if let Some(pipeline) = pipeline_cache.get(item.cached_pipeline()) {
             pass.set_render_pipeline(pipeline);
+            if pass.is_validation_enabled() {
+                pass.set_pipeline_requirements(PipelineRequirements::from_descriptor(
+                    pipeline_cache.get_descriptor(item.cached_pipeline()),
+                ));
+            }
             RenderCommandResult::Success
         } else {
             RenderCommandResult::Failure
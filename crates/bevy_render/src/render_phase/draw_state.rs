@@ -1,14 +1,144 @@
 use crate::{
     prelude::Color,
     render_resource::{
-        BindGroup, BindGroupId, BufferId, BufferSlice, RenderPipeline, RenderPipelineId,
-        ShaderStages,
+        BindGroup, BindGroupId, Buffer, BufferId, BufferSlice, RenderPipeline,
+        RenderPipelineDescriptor, RenderPipelineId, ShaderStages,
     },
 };
 use bevy_utils::tracing::trace;
 use std::ops::Range;
+use thiserror::Error;
 use wgpu::{IndexFormat, RenderPass};
 
+/// The state a pipeline needs to be bound before drawing with it, as checked by draw validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineRequirements {
+    /// The number of bind groups in the pipeline layout
+    pub bind_groups: usize,
+    /// The number of vertex buffers read by the vertex stage
+    pub vertex_buffers: usize,
+}
+
+impl PipelineRequirements {
+    pub fn from_descriptor(descriptor: &RenderPipelineDescriptor) -> Self {
+        Self {
+            bind_groups: descriptor.layout.as_ref().map_or(0, |layout| layout.len()),
+            vertex_buffers: descriptor.vertex.buffers.len(),
+        }
+    }
+}
+
+/// A draw call that is missing some of the state it needs, as reported by
+/// [`DrawState::validate_draw`].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawValidationError {
+    #[error("draw issued without a render pipeline set")]
+    MissingPipeline,
+    #[error(
+        "draw is missing bind group {index}: the pipeline {pipeline:?} uses {expected} bind groups"
+    )]
+    MissingBindGroup {
+        index: usize,
+        expected: usize,
+        pipeline: RenderPipelineId,
+    },
+    #[error(
+        "draw is missing vertex buffer {slot}: the pipeline {pipeline:?} uses {expected} vertex buffers"
+    )]
+    MissingVertexBuffer {
+        slot: usize,
+        expected: usize,
+        pipeline: RenderPipelineId,
+    },
+    #[error("indexed draw issued without an index buffer set")]
+    MissingIndexBuffer,
+}
+
+/// A binding of a bind group with a dynamic offset, reading a window of `binding_size` bytes
+/// from a buffer of `buffer_size` bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DynamicBinding {
+    /// The size of the bound buffer, in bytes
+    pub buffer_size: u64,
+    /// The size of the window of the buffer bound for a draw, in bytes
+    pub binding_size: u64,
+    /// The alignment dynamic offsets of this binding must have, e.g.
+    /// [`wgpu::Limits::min_uniform_buffer_offset_alignment`] for uniform buffers
+    pub alignment: u32,
+}
+
+impl DynamicBinding {
+    /// A dynamic uniform buffer binding, aligned as required by the device `limits`.
+    pub fn uniform(buffer_size: u64, binding_size: u64, limits: &wgpu::Limits) -> Self {
+        Self {
+            buffer_size,
+            binding_size,
+            alignment: limits.min_uniform_buffer_offset_alignment,
+        }
+    }
+
+    /// A dynamic storage buffer binding, aligned as required by the device `limits`.
+    pub fn storage(buffer_size: u64, binding_size: u64, limits: &wgpu::Limits) -> Self {
+        Self {
+            buffer_size,
+            binding_size,
+            alignment: limits.min_storage_buffer_offset_alignment,
+        }
+    }
+}
+
+/// A dynamic offset that would make wgpu fail, as reported by [`validate_dynamic_offsets`].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DynamicOffsetError {
+    #[error("expected {expected} dynamic offsets, one per dynamic binding, but got {actual}")]
+    CountMismatch { expected: usize, actual: usize },
+    #[error("dynamic offset {offset} of binding {index} is not a multiple of {alignment}")]
+    Misaligned {
+        index: usize,
+        offset: u32,
+        alignment: u32,
+    },
+    #[error("dynamic offset {offset} of binding {index} reads {binding_size} bytes past the end of its {buffer_size} byte buffer")]
+    OutOfRange {
+        index: usize,
+        offset: u32,
+        binding_size: u64,
+        buffer_size: u64,
+    },
+}
+
+/// Checks that `offsets` holds one offset per dynamic binding of a bind group, in binding order,
+/// and that each is aligned and keeps its binding within its buffer.
+pub fn validate_dynamic_offsets(
+    bindings: &[DynamicBinding],
+    offsets: &[u32],
+) -> Result<(), DynamicOffsetError> {
+    if bindings.len() != offsets.len() {
+        return Err(DynamicOffsetError::CountMismatch {
+            expected: bindings.len(),
+            actual: offsets.len(),
+        });
+    }
+    for (index, (binding, &offset)) in bindings.iter().zip(offsets).enumerate() {
+        if binding.alignment != 0 && offset % binding.alignment != 0 {
+            return Err(DynamicOffsetError::Misaligned {
+                index,
+                offset,
+                alignment: binding.alignment,
+            });
+        }
+        if offset as u64 + binding.binding_size > binding.buffer_size {
+            return Err(DynamicOffsetError::OutOfRange {
+                index,
+                offset,
+                binding_size: binding.binding_size,
+                buffer_size: binding.buffer_size,
+            });
+        }
+    }
+    Ok(())
+}
+
 /// Tracks the current [`TrackedRenderPass`] state to ensure draw calls are valid.
 #[derive(Debug, Default)]
 pub struct DrawState {
@@ -16,6 +146,7 @@ pub struct DrawState {
     bind_groups: Vec<(Option<BindGroupId>, Vec<u32>)>,
     vertex_buffers: Vec<Option<(BufferId, u64)>>,
     index_buffer: Option<(BufferId, u64, IndexFormat)>,
+    requirements: Option<PipelineRequirements>,
 }
 
 impl DrawState {
@@ -46,6 +177,37 @@ impl DrawState {
         }
     }
 
+    /// Records `bind_group` as bound to `index`, calling `set` only if it is not already bound
+    /// there with the same `dynamic_indices`. Returns whether `set` was called.
+    pub fn set_bind_group_if_changed(
+        &mut self,
+        index: usize,
+        bind_group: BindGroupId,
+        dynamic_indices: &[u32],
+        set: impl FnOnce(),
+    ) -> bool {
+        if self.is_bind_group_set(index, bind_group, dynamic_indices) {
+            return false;
+        }
+        set();
+        self.set_bind_group(index, bind_group, dynamic_indices);
+        true
+    }
+
+    /// Records the `bind_groups` as bound to the indices `0..bind_groups.len()`, calling `set` for
+    /// each index (in increasing order) whose bind group is not already bound.
+    ///
+    /// Bind groups shared between draws, like the view bind group, are thus only set once.
+    pub fn set_bind_groups(
+        &mut self,
+        bind_groups: &[(BindGroupId, &[u32])],
+        mut set: impl FnMut(usize),
+    ) {
+        for (index, (bind_group, dynamic_indices)) in bind_groups.iter().enumerate() {
+            self.set_bind_group_if_changed(index, *bind_group, dynamic_indices, || set(index));
+        }
+    }
+
     pub fn set_vertex_buffer(&mut self, index: usize, buffer: BufferId, offset: u64) {
         if index >= self.vertex_buffers.len() {
             self.vertex_buffers.resize(index + 1, None);
@@ -84,6 +246,63 @@ impl DrawState {
         // self.vertex_buffers.clear();
         // self.index_buffer = None;
         self.pipeline = Some(pipeline);
+        self.requirements = None;
+    }
+
+    /// Sets the requirements of the current pipeline, which [`DrawState::validate_draw`] checks.
+    pub fn set_pipeline_requirements(&mut self, requirements: PipelineRequirements) {
+        self.requirements = Some(requirements);
+    }
+
+    /// Checks that a draw with the current state has a pipeline, as well as all bind groups and
+    /// vertex buffers the pipeline requires, if they are known. `indexed` draws also need an index
+    /// buffer.
+    pub fn validate_draw(&self, indexed: bool) -> Result<(), DrawValidationError> {
+        let pipeline = self.pipeline.ok_or(DrawValidationError::MissingPipeline)?;
+        if let Some(requirements) = self.requirements {
+            let bind_group_set =
+                |index: usize| matches!(self.bind_groups.get(index), Some((Some(_), _)));
+            if let Some(index) = (0..requirements.bind_groups).find(|i| !bind_group_set(*i)) {
+                return Err(DrawValidationError::MissingBindGroup {
+                    index,
+                    expected: requirements.bind_groups,
+                    pipeline,
+                });
+            }
+            let vertex_buffer_set =
+                |slot: usize| matches!(self.vertex_buffers.get(slot), Some(Some(_)));
+            if let Some(slot) = (0..requirements.vertex_buffers).find(|i| !vertex_buffer_set(*i)) {
+                return Err(DrawValidationError::MissingVertexBuffer {
+                    slot,
+                    expected: requirements.vertex_buffers,
+                    pipeline,
+                });
+            }
+        }
+        if indexed && self.index_buffer.is_none() {
+            return Err(DrawValidationError::MissingIndexBuffer);
+        }
+        Ok(())
+    }
+}
+
+/// Labels the draw calls of a [`TrackedRenderPass`] with debug markers, if enabled.
+#[derive(Default)]
+struct DrawMarkers {
+    enabled: bool,
+    draws: u32,
+}
+
+impl DrawMarkers {
+    /// Returns the debug marker of the next draw call, recorded with `command`, or `None` if
+    /// markers are disabled.
+    fn next(&mut self, command: &str) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        let marker = format!("{} #{}", command, self.draws);
+        self.draws += 1;
+        Some(marker)
     }
 }
 
@@ -93,6 +312,8 @@ impl DrawState {
 pub struct TrackedRenderPass<'a> {
     pass: RenderPass<'a>,
     state: DrawState,
+    validate: bool,
+    markers: DrawMarkers,
 }
 
 impl<'a> TrackedRenderPass<'a> {
@@ -101,6 +322,50 @@ impl<'a> TrackedRenderPass<'a> {
         Self {
             state: DrawState::default(),
             pass,
+            validate: false,
+            markers: DrawMarkers::default(),
+        }
+    }
+
+    /// Enables checking every draw call for missing state with [`DrawState::validate_draw`]
+    /// before recording it, panicking with a description of what is missing.
+    ///
+    /// Validation only happens in debug builds.
+    pub fn enable_validation(&mut self) {
+        self.validate = cfg!(debug_assertions);
+    }
+
+    /// Returns `true` if draw calls are validated.
+    pub fn is_validation_enabled(&self) -> bool {
+        self.validate
+    }
+
+    /// Enables inserting a debug marker before every draw call, numbering the draws of this pass,
+    /// so that individual draws can be found in GPU captures.
+    pub fn enable_debug_markers(&mut self) {
+        self.markers.enabled = true;
+    }
+
+    /// Returns `true` if draw calls are labeled with debug markers.
+    pub fn are_debug_markers_enabled(&self) -> bool {
+        self.markers.enabled
+    }
+
+    /// Sets the requirements of the active [`RenderPipeline`], which draw calls are validated
+    /// against if validation is enabled.
+    pub fn set_pipeline_requirements(&mut self, requirements: PipelineRequirements) {
+        self.state.set_pipeline_requirements(requirements);
+    }
+
+    /// Validates and labels a draw call recorded with `command`, before it is recorded.
+    fn begin_draw(&mut self, command: &str, indexed: bool) {
+        if self.validate {
+            if let Err(err) = self.state.validate_draw(indexed) {
+                panic!("invalid draw call: {}", err);
+            }
+        }
+        if let Some(marker) = self.markers.next(command) {
+            self.insert_debug_marker(&marker);
         }
     }
 
@@ -124,29 +389,70 @@ impl<'a> TrackedRenderPass<'a> {
         bind_group: &'a BindGroup,
         dynamic_uniform_indices: &[u32],
     ) {
-        if self
-            .state
-            .is_bind_group_set(index as usize, bind_group.id(), dynamic_uniform_indices)
-        {
+        let pass = &mut self.pass;
+        let set = self.state.set_bind_group_if_changed(
+            index,
+            bind_group.id(),
+            dynamic_uniform_indices,
+            || {
+                trace!(
+                    "set bind_group {}: {:?} ({:?})",
+                    index,
+                    bind_group,
+                    dynamic_uniform_indices
+                );
+                pass.set_bind_group(index as u32, bind_group, dynamic_uniform_indices);
+            },
+        );
+        if !set {
             trace!(
                 "set bind_group {} (already set): {:?} ({:?})",
                 index,
                 bind_group,
                 dynamic_uniform_indices
             );
-            return;
-        } else {
+        }
+    }
+
+    /// Sets the active [`BindGroup`] for a given bind group index like
+    /// [`TrackedRenderPass::set_bind_group`], after checking its `dynamic_offsets` against its
+    /// dynamic `bindings`.
+    ///
+    /// Invalid offsets are reported before reaching wgpu, which would otherwise fail the whole
+    /// pass. The bind group isn't set in that case.
+    pub fn set_bind_group_with_dynamic_offsets(
+        &mut self,
+        index: usize,
+        bind_group: &'a BindGroup,
+        bindings: &[DynamicBinding],
+        dynamic_offsets: &[u32],
+    ) -> Result<(), DynamicOffsetError> {
+        validate_dynamic_offsets(bindings, dynamic_offsets)?;
+        self.set_bind_group(index, bind_group, dynamic_offsets);
+        Ok(())
+    }
+
+    /// Sets all bind groups of a draw at once, with `bind_groups[i]` bound to index `i`.
+    ///
+    /// Bind groups that are already bound at their index, e.g. a view bind group shared by all
+    /// draws of a pass, are skipped, while the others (e.g. per-material or per-object bind groups)
+    /// are set in increasing index order.
+    pub fn set_bind_groups(&mut self, bind_groups: &[(&'a BindGroup, &[u32])]) {
+        let ids = bind_groups
+            .iter()
+            .map(|(bind_group, dynamic_indices)| (bind_group.id(), *dynamic_indices))
+            .collect::<Vec<_>>();
+        let pass = &mut self.pass;
+        self.state.set_bind_groups(&ids, |index| {
+            let (bind_group, dynamic_indices) = bind_groups[index];
             trace!(
                 "set bind_group {}: {:?} ({:?})",
                 index,
                 bind_group,
-                dynamic_uniform_indices
+                dynamic_indices
             );
-        }
-        self.pass
-            .set_bind_group(index as u32, bind_group, dynamic_uniform_indices);
-        self.state
-            .set_bind_group(index as usize, bind_group.id(), dynamic_uniform_indices);
+            pass.set_bind_group(index as u32, bind_group, dynamic_indices);
+        });
     }
 
     /// Assign a vertex buffer to a slot.
@@ -216,6 +522,7 @@ impl<'a> TrackedRenderPass<'a> {
     /// The active vertex buffer(s) can be set with [`TrackedRenderPass::set_vertex_buffer`].
     pub fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
         trace!("draw: {:?} {:?}", vertices, instances);
+        self.begin_draw("draw", false);
         self.pass.draw(vertices, instances);
     }
 
@@ -230,9 +537,27 @@ impl<'a> TrackedRenderPass<'a> {
             base_vertex,
             instances
         );
+        self.begin_draw("draw_indexed", true);
         self.pass.draw_indexed(indices, base_vertex, instances);
     }
 
+    /// Draws indexed primitives using the active index buffer and the active vertex buffer(s),
+    /// with the arguments read from `indirect_buffer` at `indirect_offset`.
+    ///
+    /// The arguments are laid out as
+    /// [`DrawIndexedIndirectArgs`](crate::view::DrawIndexedIndirectArgs), and can thus be written by
+    /// the GPU, e.g. by the [`GpuCullingPipeline`](crate::view::GpuCullingPipeline).
+    pub fn draw_indexed_indirect(&mut self, indirect_buffer: &'a Buffer, indirect_offset: u64) {
+        trace!(
+            "draw indexed indirect: {:?} {}",
+            indirect_buffer.id(),
+            indirect_offset
+        );
+        self.begin_draw("draw_indexed_indirect", true);
+        self.pass
+            .draw_indexed_indirect(indirect_buffer, indirect_offset);
+    }
+
     pub fn set_stencil_reference(&mut self, reference: u32) {
         trace!("set stencil reference: {}", reference);
 
@@ -340,3 +665,157 @@ impl<'a> TrackedRenderPass<'a> {
         self.pass.set_blend_constant(wgpu::Color::from(color));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_markers_label_each_draw() {
+        let mut markers = DrawMarkers::default();
+        assert_eq!(markers.next("draw"), None);
+
+        markers.enabled = true;
+        let labels = ["draw", "draw_indexed", "draw_indexed_indirect"]
+            .iter()
+            .filter_map(|command| markers.next(command))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            ["draw #0", "draw_indexed #1", "draw_indexed_indirect #2"]
+        );
+    }
+
+    #[test]
+    fn bind_groups_are_set_in_order() {
+        let mut state = DrawState::default();
+        let view = BindGroupId::new();
+        let material = BindGroupId::new();
+        let objects = [BindGroupId::new(), BindGroupId::new()];
+
+        let mut set = Vec::new();
+        state.set_bind_groups(
+            &[(view, &[0]), (material, &[]), (objects[0], &[64])],
+            |index| set.push(index),
+        );
+        assert_eq!(set, vec![0, 1, 2]);
+
+        // Only the per-object bind group changes between these draws.
+        set.clear();
+        state.set_bind_groups(
+            &[(view, &[0]), (material, &[]), (objects[1], &[64])],
+            |index| set.push(index),
+        );
+        assert_eq!(set, vec![2]);
+        assert!(state.is_bind_group_set(0, view, &[0]));
+        assert!(state.is_bind_group_set(2, objects[1], &[64]));
+    }
+
+    #[test]
+    fn material_bind_group_is_bound_once_per_run() {
+        let mut state = DrawState::default();
+        let view = BindGroupId::new();
+        let materials = [BindGroupId::new(), BindGroupId::new()];
+
+        // Draws are issued like the `SetMeshViewBindGroup`, `SetMaterialBindGroup` and
+        // `SetMeshBindGroup` commands do, one bind group at a time, going through the same check
+        // as `TrackedRenderPass::set_bind_group`. The mesh bind group is shared
+        // by all meshes, each of which has its own dynamic offset.
+        let mesh = BindGroupId::new();
+        let draws = [(materials[0], 0), (materials[0], 256), (materials[0], 512)]
+            .into_iter()
+            .chain([(materials[1], 768), (materials[1], 1024)]);
+        let mut binds = [0; 3];
+        for (material, mesh_offset) in draws {
+            for (index, bind_group, dynamic_offsets) in [
+                (0, view, vec![0]),
+                (1, material, vec![]),
+                (2, mesh, vec![mesh_offset]),
+            ] {
+                state.set_bind_group_if_changed(index, bind_group, &dynamic_offsets, || {
+                    binds[index] += 1;
+                });
+            }
+        }
+        // The view is bound once, each material once for its run, and the mesh for every draw.
+        assert_eq!(binds, [1, 2, 5]);
+    }
+
+    #[test]
+    fn draw_missing_bind_group_is_invalid() {
+        let mut state = DrawState::default();
+        assert_eq!(
+            state.validate_draw(false),
+            Err(DrawValidationError::MissingPipeline)
+        );
+
+        let pipeline = RenderPipelineId::new();
+        state.set_pipeline(pipeline);
+        state.set_pipeline_requirements(PipelineRequirements {
+            bind_groups: 3,
+            vertex_buffers: 0,
+        });
+        state.set_bind_group(0, BindGroupId::new(), &[]);
+        state.set_bind_group(2, BindGroupId::new(), &[]);
+
+        let err = state.validate_draw(false).unwrap_err();
+        assert_eq!(
+            err,
+            DrawValidationError::MissingBindGroup {
+                index: 1,
+                expected: 3,
+                pipeline
+            }
+        );
+        assert!(err.to_string().starts_with("draw is missing bind group 1"));
+
+        state.set_bind_group(1, BindGroupId::new(), &[]);
+        assert_eq!(state.validate_draw(false), Ok(()));
+        assert_eq!(
+            state.validate_draw(true),
+            Err(DrawValidationError::MissingIndexBuffer)
+        );
+    }
+
+    #[test]
+    fn dynamic_offsets_are_validated() {
+        let limits = wgpu::Limits::default();
+        // A buffer holding 4 uniforms of 64 bytes, each padded to the 256 byte alignment
+        let bindings = [DynamicBinding::uniform(1024, 64, &limits)];
+
+        assert_eq!(validate_dynamic_offsets(&bindings, &[768]), Ok(()));
+        let mut state = DrawState::default();
+        state.set_pipeline(RenderPipelineId::new());
+        state.set_pipeline_requirements(PipelineRequirements {
+            bind_groups: 1,
+            vertex_buffers: 0,
+        });
+        state.set_bind_group(0, BindGroupId::new(), &[768]);
+        assert_eq!(state.validate_draw(false), Ok(()));
+
+        assert_eq!(
+            validate_dynamic_offsets(&bindings, &[1024]),
+            Err(DynamicOffsetError::OutOfRange {
+                index: 0,
+                offset: 1024,
+                binding_size: 64,
+                buffer_size: 1024,
+            })
+        );
+        assert_eq!(
+            validate_dynamic_offsets(&bindings, &[64]),
+            Err(DynamicOffsetError::Misaligned {
+                index: 0,
+                offset: 64,
+                alignment: 256,
+            })
+        );
+        assert_eq!(
+            validate_dynamic_offsets(&bindings, &[]),
+            Err(DynamicOffsetError::CountMismatch {
+                expected: 1,
+                actual: 0
+            })
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use super::TextureView;
 use bevy_reflect::Uuid;
 use std::{ops::Deref, sync::Arc};
 
@@ -5,6 +6,13 @@ use std::{ops::Deref, sync::Arc};
 #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
 pub struct BindGroupId(Uuid);
 
+#[cfg(test)]
+impl BindGroupId {
+    pub(crate) fn new() -> Self {
+        BindGroupId(Uuid::new_v4())
+    }
+}
+
 /// Bind groups are responsible for binding render resources (e.g. buffers, textures, samplers)
 /// to a [`TrackedRenderPass`](crate::render_phase::TrackedRenderPass).
 /// This makes them accessible in the pipeline (shaders) as uniforms.
@@ -42,3 +50,11 @@ impl Deref for BindGroup {
         &self.value
     }
 }
+
+/// Collects the wgpu views of `views`, so they can be bound to a single binding slot as a
+/// [`BindingResource::TextureViewArray`](wgpu::BindingResource::TextureViewArray).
+///
+/// See [`texture_array_layout_entry`](super::texture_array_layout_entry) for the matching layout entry.
+pub fn texture_view_array(views: &[TextureView]) -> Vec<&wgpu::TextureView> {
+    views.iter().map(|view| &**view).collect()
+}
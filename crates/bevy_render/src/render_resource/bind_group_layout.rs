@@ -1,5 +1,9 @@
 use bevy_reflect::Uuid;
-use std::{ops::Deref, sync::Arc};
+use std::{num::NonZeroU32, ops::Deref, sync::Arc};
+use wgpu::{
+    BindGroupLayoutEntry, BindingType, Features, ShaderStages, TextureSampleType,
+    TextureViewDimension,
+};
 
 #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
 pub struct BindGroupLayoutId(Uuid);
@@ -39,3 +43,77 @@ impl Deref for BindGroupLayout {
         &self.value
     }
 }
+
+/// Creates a [`BindGroupLayoutEntry`] that binds an array of `count` sampled textures to a single
+/// binding slot, as used for bindless-style rendering.
+///
+/// The matching bind group entry is a
+/// [`BindingResource::TextureViewArray`](wgpu::BindingResource::TextureViewArray), which can be built
+/// with [`texture_view_array`](super::texture_view_array).
+/// Layouts using this entry require [`Features::TEXTURE_BINDING_ARRAY`].
+pub fn texture_array_layout_entry(
+    binding: u32,
+    visibility: ShaderStages,
+    sample_type: TextureSampleType,
+    view_dimension: TextureViewDimension,
+    count: NonZeroU32,
+) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: BindingType::Texture {
+            sample_type,
+            view_dimension,
+            multisampled: false,
+        },
+        count: Some(count),
+    }
+}
+
+/// Returns the device [`Features`] required to create a bind group layout from `entries`.
+pub fn required_layout_features(entries: &[BindGroupLayoutEntry]) -> Features {
+    entries.iter().filter(|entry| entry.count.is_some()).fold(
+        Features::empty(),
+        |features, entry| {
+            features
+                | match entry.ty {
+                    BindingType::Texture { .. } | BindingType::StorageTexture { .. } => {
+                        Features::TEXTURE_BINDING_ARRAY
+                    }
+                    BindingType::Buffer { .. } => Features::BUFFER_BINDING_ARRAY,
+                    BindingType::Sampler(_) => Features::TEXTURE_BINDING_ARRAY,
+                }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_array_entry_requires_binding_array_feature() {
+        let entries = [
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            texture_array_layout_entry(
+                1,
+                ShaderStages::FRAGMENT,
+                TextureSampleType::Float { filterable: true },
+                TextureViewDimension::D2,
+                NonZeroU32::new(2).unwrap(),
+            ),
+        ];
+
+        assert_eq!(entries[1].count, NonZeroU32::new(2));
+        assert_eq!(
+            required_layout_features(&entries),
+            Features::TEXTURE_BINDING_ARRAY
+        );
+        assert_eq!(required_layout_features(&entries[..1]), Features::empty());
+    }
+}
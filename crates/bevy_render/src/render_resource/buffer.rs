@@ -1,3 +1,4 @@
+use crate::renderer::GpuAllocation;
 use bevy_utils::Uuid;
 use std::{
     ops::{Bound, Deref, RangeBounds},
@@ -11,6 +12,9 @@ pub struct BufferId(Uuid);
 pub struct Buffer {
     id: BufferId,
     value: Arc<wgpu::Buffer>,
+    usage: Option<wgpu::BufferUsages>,
+    size: Option<wgpu::BufferAddress>,
+    allocation: Option<Arc<GpuAllocation>>,
 }
 
 impl Buffer {
@@ -19,6 +23,38 @@ impl Buffer {
         self.id
     }
 
+    /// Returns the usages this buffer was created with, or `None` if the buffer was not created by a
+    /// [`RenderDevice`](crate::renderer::RenderDevice) and its usages are unknown.
+    #[inline]
+    pub fn usage(&self) -> Option<wgpu::BufferUsages> {
+        self.usage
+    }
+
+    /// Returns the size of this buffer in bytes, or `None` if the buffer was not created by a
+    /// [`RenderDevice`](crate::renderer::RenderDevice) and its size is unknown.
+    #[inline]
+    pub fn size(&self) -> Option<wgpu::BufferAddress> {
+        self.size
+    }
+
+    #[inline]
+    pub(crate) fn with_size(mut self, size: wgpu::BufferAddress) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_usage(mut self, usage: wgpu::BufferUsages) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_allocation(mut self, allocation: GpuAllocation) -> Self {
+        self.allocation = Some(Arc::new(allocation));
+        self
+    }
+
     pub fn slice(&self, bounds: impl RangeBounds<wgpu::BufferAddress>) -> BufferSlice {
         BufferSlice {
             id: self.id,
@@ -43,6 +79,9 @@ impl From<wgpu::Buffer> for Buffer {
         Buffer {
             id: BufferId(Uuid::new_v4()),
             value: Arc::new(value),
+            usage: None,
+            size: None,
+            allocation: None,
         }
     }
 }
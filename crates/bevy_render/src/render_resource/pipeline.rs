@@ -3,14 +3,22 @@ use bevy_asset::Handle;
 use bevy_reflect::Uuid;
 use std::{borrow::Cow, ops::Deref, sync::Arc};
 use wgpu::{
-    BufferAddress, ColorTargetState, DepthStencilState, MultisampleState, PrimitiveState,
-    VertexAttribute, VertexStepMode,
+    BlendComponent, BlendFactor, BlendOperation, BlendState, BufferAddress, ColorTargetState,
+    CompareFunction, DepthStencilState, MultisampleState, PrimitiveState, PrimitiveTopology,
+    TextureFormat, VertexAttribute, VertexStepMode,
 };
 
 /// A [`RenderPipeline`] identifier.
 #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
 pub struct RenderPipelineId(Uuid);
 
+#[cfg(test)]
+impl RenderPipelineId {
+    pub(crate) fn new() -> Self {
+        RenderPipelineId(Uuid::new_v4())
+    }
+}
+
 /// A [`RenderPipeline`] represents a graphics pipeline and its stages (shaders), bindings and vertex buffers.
 ///
 /// May be converted from and dereferences to a wgpu [`RenderPipeline`](wgpu::RenderPipeline).
@@ -105,6 +113,63 @@ pub struct RenderPipelineDescriptor {
     pub fragment: Option<FragmentState>,
 }
 
+impl RenderPipelineDescriptor {
+    /// Describes a pipeline drawing a [`PrimitiveTopology::LineList`], e.g. for debug lines.
+    ///
+    /// Lines have no faces, so culling is disabled. Depth testing is only enabled if
+    /// `depth_stencil` is given, which lets overlays draw on top of the scene. The vertex buffer
+    /// layouts are taken from `vertex`, and the bind group layouts are left to the caller.
+    pub fn line_list(
+        label: impl Into<Cow<'static, str>>,
+        vertex: VertexState,
+        fragment: Option<FragmentState>,
+        depth_stencil: Option<DepthStencilState>,
+    ) -> Self {
+        RenderPipelineDescriptor {
+            label: Some(label.into()),
+            layout: None,
+            vertex,
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::LineList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil,
+            multisample: MultisampleState::default(),
+            fragment,
+        }
+    }
+
+    /// Describes a pipeline that only writes depth into a `depth_format` attachment, e.g. for
+    /// shadow maps, so passes using it have no color attachments.
+    ///
+    /// Without a `fragment` stage the depth of the rasterized primitives is written as is. A
+    /// fragment stage, e.g. discarding alpha-masked fragments, must not have any color targets.
+    pub fn depth_only(
+        label: impl Into<Cow<'static, str>>,
+        vertex: VertexState,
+        fragment: Option<FragmentState>,
+        depth_format: TextureFormat,
+        depth_compare: CompareFunction,
+    ) -> Self {
+        RenderPipelineDescriptor {
+            label: Some(label.into()),
+            layout: None,
+            vertex,
+            primitive: PrimitiveState::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: true,
+                depth_compare,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: MultisampleState::default(),
+            fragment,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct VertexState {
     /// The compiled shader module for this stage.
@@ -117,6 +182,22 @@ pub struct VertexState {
     pub buffers: Vec<VertexBufferLayout>,
 }
 
+/// Describes a compute pipeline, which can be queued on the
+/// [`RenderPipelineCache`](super::RenderPipelineCache).
+#[derive(Clone, Debug)]
+pub struct ComputePipelineDescriptor {
+    /// Debug label of the pipeline. This will show up in graphics debuggers for easy identification.
+    pub label: Option<Cow<'static, str>>,
+    /// The layout of bind groups for this pipeline.
+    pub layout: Option<Vec<BindGroupLayout>>,
+    /// The compiled shader module for the compute stage.
+    pub shader: Handle<Shader>,
+    pub shader_defs: Vec<String>,
+    /// The name of the entry point in the compiled shader. There must be a
+    /// function with this name in the shader.
+    pub entry_point: Cow<'static, str>,
+}
+
 /// Describes how the vertex buffer is interpreted.
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub struct VertexBufferLayout {
@@ -140,3 +221,120 @@ pub struct FragmentState {
     /// The color state of the render targets.
     pub targets: Vec<ColorTargetState>,
 }
+
+/// How the output of a fragment shader is blended with the color target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Blends by the alpha of the output, whose color channels are independent of its alpha
+    StraightAlpha,
+    /// Blends by the alpha of the output, whose color channels are already multiplied by its alpha.
+    /// This avoids the dark fringes of filtered premultiplied textures drawn with straight alpha.
+    Premultiplied,
+    /// Adds the output, scaled by its alpha, to the target and keeps the alpha of the target
+    Additive,
+    /// Replaces the target with the output
+    Opaque,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::StraightAlpha
+    }
+}
+
+impl BlendMode {
+    /// The [`BlendState`] for the color and alpha channels of a [`ColorTargetState`].
+    pub fn blend_state(self) -> BlendState {
+        match self {
+            BlendMode::StraightAlpha => BlendState::ALPHA_BLENDING,
+            BlendMode::Premultiplied => BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: BlendFactor::Zero,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+            },
+            BlendMode::Opaque => BlendState::REPLACE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::{VertexFormat, VertexStepMode};
+
+    #[test]
+    fn blend_mode_factors() {
+        let factors = |mode: BlendMode| {
+            let state = mode.blend_state();
+            [
+                (state.color.src_factor, state.color.dst_factor),
+                (state.alpha.src_factor, state.alpha.dst_factor),
+            ]
+        };
+        use BlendFactor::*;
+        assert_eq!(
+            factors(BlendMode::default()),
+            [(SrcAlpha, OneMinusSrcAlpha), (One, OneMinusSrcAlpha)]
+        );
+        assert_eq!(
+            factors(BlendMode::Premultiplied),
+            [(One, OneMinusSrcAlpha), (One, OneMinusSrcAlpha)]
+        );
+        assert_eq!(factors(BlendMode::Additive), [(SrcAlpha, One), (Zero, One)]);
+        assert_eq!(factors(BlendMode::Opaque), [(One, Zero), (One, Zero)]);
+    }
+
+    #[test]
+    fn line_list_pipeline_does_not_cull() {
+        let vertex = VertexState {
+            shader: Handle::default(),
+            shader_defs: Vec::new(),
+            entry_point: "vertex".into(),
+            buffers: vec![VertexBufferLayout {
+                array_stride: VertexFormat::Float32x3.size(),
+                step_mode: VertexStepMode::Vertex,
+                attributes: vec![VertexAttribute {
+                    format: VertexFormat::Float32x3,
+                    offset: 0,
+                    shader_location: 0,
+                }],
+            }],
+        };
+        let descriptor = RenderPipelineDescriptor::line_list("gizmo_lines", vertex, None, None);
+        assert_eq!(descriptor.primitive.topology, PrimitiveTopology::LineList);
+        assert_eq!(descriptor.primitive.cull_mode, None);
+        assert!(descriptor.depth_stencil.is_none());
+        assert_eq!(descriptor.vertex.buffers[0].array_stride, 12);
+    }
+
+    #[test]
+    fn depth_only_pipeline_has_no_color_targets() {
+        let vertex = VertexState {
+            shader: Handle::default(),
+            shader_defs: Vec::new(),
+            entry_point: "vertex".into(),
+            buffers: Vec::new(),
+        };
+        let descriptor = RenderPipelineDescriptor::depth_only(
+            "shadow_pipeline",
+            vertex,
+            None,
+            TextureFormat::Depth32Float,
+            CompareFunction::GreaterEqual,
+        );
+        assert!(descriptor.fragment.is_none());
+        let depth_stencil = descriptor.depth_stencil.unwrap();
+        assert_eq!(depth_stencil.format, TextureFormat::Depth32Float);
+        assert!(depth_stencil.depth_write_enabled);
+        assert!(!depth_stencil.stencil.is_enabled());
+        assert_eq!(crate::renderer::validate_color_targets(&[], &[]), Ok(()));
+    }
+}
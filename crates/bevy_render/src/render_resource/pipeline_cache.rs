@@ -1,10 +1,11 @@
 use crate::{
     render_resource::{
-        AsModuleDescriptorError, BindGroupLayout, BindGroupLayoutId, ProcessShaderError,
+        AsModuleDescriptorError, BindGroupLayout, BindGroupLayoutId, ComputePipeline,
+        ComputePipelineDescriptor, ProcessShaderError, RawComputePipelineDescriptor,
         RawFragmentState, RawRenderPipelineDescriptor, RawVertexState, RenderPipeline,
         RenderPipelineDescriptor, Shader, ShaderImport, ShaderProcessor, ShaderReflectError,
     },
-    renderer::RenderDevice,
+    renderer::{RenderDevice, RenderResourceError},
     RenderWorld,
 };
 use bevy_app::EventReader;
@@ -13,14 +14,16 @@ use bevy_ecs::system::{Res, ResMut};
 use bevy_utils::{tracing::error, HashMap, HashSet};
 use std::{collections::hash_map::Entry, hash::Hash, ops::Deref, sync::Arc};
 use thiserror::Error;
-use wgpu::{PipelineLayoutDescriptor, ShaderModule, VertexBufferLayout};
+use wgpu::{
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BlendState, PipelineLayoutDescriptor,
+    ShaderModule, VertexBufferLayout,
+};
 
 use super::ProcessedShader;
 
 #[derive(Default)]
 pub struct ShaderData {
-    pipelines: HashSet<CachedPipelineId>,
-    processed_shaders: HashMap<Vec<String>, Arc<ShaderModule>>,
+    pipelines: HashSet<AnyPipelineId>,
     resolved_imports: HashMap<ShaderImport, Handle<Shader>>,
     dependents: HashSet<Handle<Shader>>,
 }
@@ -32,23 +35,115 @@ impl CachedPipelineId {
     pub const INVALID: Self = CachedPipelineId(usize::MAX);
 }
 
+#[cfg(test)]
+impl CachedPipelineId {
+    pub(crate) fn new(id: usize) -> Self {
+        CachedPipelineId(id)
+    }
+}
+
+/// The id of a compute pipeline queued on the [`RenderPipelineCache`].
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct CachedComputePipelineId(usize);
+
+impl CachedComputePipelineId {
+    pub const INVALID: Self = CachedComputePipelineId(usize::MAX);
+}
+
+/// The id of a render or compute pipeline using a shader, to queue it again once the shader changes.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+enum AnyPipelineId {
+    Render(CachedPipelineId),
+    Compute(CachedComputePipelineId),
+}
+
+impl From<CachedPipelineId> for AnyPipelineId {
+    fn from(id: CachedPipelineId) -> Self {
+        AnyPipelineId::Render(id)
+    }
+}
+
+impl From<CachedComputePipelineId> for AnyPipelineId {
+    fn from(id: CachedComputePipelineId) -> Self {
+        AnyPipelineId::Compute(id)
+    }
+}
+
+/// Caches the shader modules of each shader, separately for each stage's shader and set of shader
+/// defs.
+///
+/// A shader is only processed and reflected again once it, or one of its imports, changed. Hot
+/// reloading the fragment shader of a pipeline thus leaves its cached vertex module untouched.
 #[derive(Default)]
 struct ShaderCache {
+    cache: ShaderModuleCache<Arc<ShaderModule>>,
+}
+
+/// The bookkeeping of the [`ShaderCache`], generic over the cached module so that it can be
+/// tested without a [`RenderDevice`].
+struct ShaderModuleCache<M> {
     data: HashMap<Handle<Shader>, ShaderData>,
+    modules: HashMap<Handle<Shader>, HashMap<Vec<String>, M>>,
     shaders: HashMap<Handle<Shader>, Shader>,
     import_path_shaders: HashMap<ShaderImport, Handle<Shader>>,
     waiting_on_import: HashMap<ShaderImport, Vec<Handle<Shader>>>,
     processor: ShaderProcessor,
 }
 
+impl<M> Default for ShaderModuleCache<M> {
+    fn default() -> Self {
+        Self {
+            data: Default::default(),
+            modules: Default::default(),
+            shaders: Default::default(),
+            import_path_shaders: Default::default(),
+            waiting_on_import: Default::default(),
+            processor: Default::default(),
+        }
+    }
+}
+
 impl ShaderCache {
     fn get(
         &mut self,
         render_device: &RenderDevice,
-        pipeline: CachedPipelineId,
+        pipeline: impl Into<AnyPipelineId>,
         handle: &Handle<Shader>,
         shader_defs: &[String],
     ) -> Result<Arc<ShaderModule>, RenderPipelineError> {
+        self.cache
+            .get_or_create(pipeline, handle, shader_defs, |processed| {
+                let module_descriptor = match processed.get_module_descriptor() {
+                    Ok(module_descriptor) => module_descriptor,
+                    Err(err) => {
+                        return Err(RenderPipelineError::AsModuleDescriptorError(err, processed));
+                    }
+                };
+                Ok(Arc::new(
+                    render_device.create_shader_module(&module_descriptor),
+                ))
+            })
+    }
+
+    fn set_shader(&mut self, handle: &Handle<Shader>, shader: Shader) -> Vec<AnyPipelineId> {
+        self.cache.set_shader(handle, shader)
+    }
+
+    fn remove(&mut self, handle: &Handle<Shader>) -> Vec<AnyPipelineId> {
+        self.cache.remove(handle)
+    }
+}
+
+impl<M: Clone> ShaderModuleCache<M> {
+    /// Returns the module of `handle` with `shader_defs`, processing the shader and creating the
+    /// module with `create_module` if it isn't cached yet.
+    fn get_or_create(
+        &mut self,
+        pipeline: impl Into<AnyPipelineId>,
+        handle: &Handle<Shader>,
+        shader_defs: &[String],
+        create_module: impl FnOnce(ProcessedShader) -> Result<M, RenderPipelineError>,
+    ) -> Result<M, RenderPipelineError> {
         let shader = self
             .shaders
             .get(handle)
@@ -67,10 +162,11 @@ impl ShaderCache {
             return Err(RenderPipelineError::ShaderImportNotYetAvailable);
         }
 
-        data.pipelines.insert(pipeline);
+        data.pipelines.insert(pipeline.into());
 
         // PERF: this shader_defs clone isn't great. use raw_entry_mut when it stabilizes
-        let module = match data.processed_shaders.entry(shader_defs.to_vec()) {
+        let modules = self.modules.entry(handle.clone_weak()).or_default();
+        let module = match modules.entry(shader_defs.to_vec()) {
             Entry::Occupied(entry) => entry.into_mut(),
             Entry::Vacant(entry) => {
                 let processed = self.processor.process(
@@ -79,27 +175,21 @@ impl ShaderCache {
                     &self.shaders,
                     &self.import_path_shaders,
                 )?;
-                let module_descriptor = match processed.get_module_descriptor() {
-                    Ok(module_descriptor) => module_descriptor,
-                    Err(err) => {
-                        return Err(RenderPipelineError::AsModuleDescriptorError(err, processed));
-                    }
-                };
-                entry.insert(Arc::new(
-                    render_device.create_shader_module(&module_descriptor),
-                ))
+                entry.insert(create_module(processed)?)
             }
         };
 
         Ok(module.clone())
     }
 
-    fn clear(&mut self, handle: &Handle<Shader>) -> Vec<CachedPipelineId> {
+    fn clear(&mut self, handle: &Handle<Shader>) -> Vec<AnyPipelineId> {
         let mut shaders_to_clear = vec![handle.clone_weak()];
         let mut pipelines_to_queue = Vec::new();
         while let Some(handle) = shaders_to_clear.pop() {
+            if let Some(modules) = self.modules.get_mut(&handle) {
+                modules.clear();
+            }
             if let Some(data) = self.data.get_mut(&handle) {
-                data.processed_shaders.clear();
                 pipelines_to_queue.extend(data.pipelines.iter().cloned());
                 shaders_to_clear.extend(data.dependents.iter().map(|h| h.clone_weak()));
             }
@@ -108,7 +198,7 @@ impl ShaderCache {
         pipelines_to_queue
     }
 
-    fn set_shader(&mut self, handle: &Handle<Shader>, shader: Shader) -> Vec<CachedPipelineId> {
+    fn set_shader(&mut self, handle: &Handle<Shader>, shader: Shader) -> Vec<AnyPipelineId> {
         let pipelines_to_queue = self.clear(handle);
         if let Some(path) = shader.import_path() {
             self.import_path_shaders
@@ -145,7 +235,7 @@ impl ShaderCache {
         pipelines_to_queue
     }
 
-    fn remove(&mut self, handle: &Handle<Shader>) -> Vec<CachedPipelineId> {
+    fn remove(&mut self, handle: &Handle<Shader>) -> Vec<AnyPipelineId> {
         let pipelines_to_queue = self.clear(handle);
         if let Some(shader) = self.shaders.remove(handle) {
             if let Some(import_path) = shader.import_path() {
@@ -160,6 +250,7 @@ impl ShaderCache {
 #[derive(Default)]
 struct LayoutCache {
     layouts: HashMap<Vec<BindGroupLayoutId>, wgpu::PipelineLayout>,
+    bind_group_layouts: HashMap<Vec<BindGroupLayoutEntry>, BindGroupLayout>,
 }
 
 impl LayoutCache {
@@ -180,30 +271,156 @@ impl LayoutCache {
             })
         })
     }
+
+    fn get_bind_group_layouts(
+        &mut self,
+        render_device: &RenderDevice,
+        groups: &[&[BindGroupLayoutEntry]],
+    ) -> Vec<BindGroupLayout> {
+        get_or_create_all(&mut self.bind_group_layouts, groups, |entries| {
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries,
+            })
+        })
+    }
+}
+
+/// Returns the cached value for each of `groups`, calling `create` for the ones not in `cache` yet.
+fn get_or_create_all<L: Clone>(
+    cache: &mut HashMap<Vec<BindGroupLayoutEntry>, L>,
+    groups: &[&[BindGroupLayoutEntry]],
+    mut create: impl FnMut(&[BindGroupLayoutEntry]) -> L,
+) -> Vec<L> {
+    groups
+        .iter()
+        .map(|entries| {
+            cache
+                .entry(entries.to_vec())
+                .or_insert_with(|| create(entries))
+                .clone()
+        })
+        .collect()
+}
+
+/// Tracks the pipelines that only differ from another cached pipeline by their blend state.
+#[derive(Default)]
+struct BlendVariantCache {
+    variants: HashMap<(CachedPipelineId, Option<BlendState>), CachedPipelineId>,
+    bases: HashMap<CachedPipelineId, CachedPipelineId>,
+}
+
+impl BlendVariantCache {
+    /// Returns the pipeline `id` was derived from, or `id` itself if it isn't a variant.
+    fn base(&self, id: CachedPipelineId) -> CachedPipelineId {
+        self.bases.get(&id).copied().unwrap_or(id)
+    }
+
+    fn get(&self, base: CachedPipelineId, blend: Option<BlendState>) -> Option<CachedPipelineId> {
+        self.variants.get(&(base, blend)).copied()
+    }
+
+    fn insert(&mut self, base: CachedPipelineId, blend: Option<BlendState>, id: CachedPipelineId) {
+        self.variants.insert((base, blend), id);
+        self.bases.insert(id, base);
+    }
+}
+
+/// Returns a copy of `descriptor` with the blend state of all color targets set to `blend`.
+fn with_blend_state(
+    descriptor: &RenderPipelineDescriptor,
+    blend: Option<BlendState>,
+) -> RenderPipelineDescriptor {
+    let mut descriptor = descriptor.clone();
+    if let Some(fragment) = &mut descriptor.fragment {
+        for target in &mut fragment.targets {
+            target.blend = blend;
+        }
+    }
+    descriptor
+}
+
+/// Returns whether all color targets of `descriptor` use the `blend` state.
+fn has_blend_state(descriptor: &RenderPipelineDescriptor, blend: Option<BlendState>) -> bool {
+    match &descriptor.fragment {
+        Some(fragment) => fragment.targets.iter().all(|target| target.blend == blend),
+        None => true,
+    }
 }
 
 pub struct RenderPipelineCache {
     layout_cache: LayoutCache,
     shader_cache: ShaderCache,
     device: RenderDevice,
-    pipelines: Vec<CachedPipeline>,
+    render_pipelines: RenderPipelineQueue,
+    compute_pipelines: Vec<CachedPipeline<ComputePipelineDescriptor, ComputePipeline>>,
+    waiting_compute_pipelines: HashSet<CachedComputePipelineId>,
+}
+
+struct CachedPipeline<D = RenderPipelineDescriptor, P = RenderPipeline> {
+    descriptor: D,
+    state: CachedPipelineState<P>,
+}
+
+/// The queued render pipelines of the [`RenderPipelineCache`] and their blend variants, generic
+/// over the created pipeline so that it can be tested without a [`RenderDevice`].
+struct RenderPipelineQueue<P = RenderPipeline> {
+    pipelines: Vec<CachedPipeline<RenderPipelineDescriptor, P>>,
     waiting_pipelines: HashSet<CachedPipelineId>,
+    blend_variants: BlendVariantCache,
+}
+
+impl<P> Default for RenderPipelineQueue<P> {
+    fn default() -> Self {
+        Self {
+            pipelines: Default::default(),
+            waiting_pipelines: Default::default(),
+            blend_variants: Default::default(),
+        }
+    }
 }
 
-struct CachedPipeline {
-    descriptor: RenderPipelineDescriptor,
-    state: CachedPipelineState,
+impl<P> RenderPipelineQueue<P> {
+    fn queue(&mut self, descriptor: RenderPipelineDescriptor) -> CachedPipelineId {
+        let id = CachedPipelineId(self.pipelines.len());
+        self.pipelines.push(CachedPipeline {
+            descriptor,
+            state: CachedPipelineState::Queued,
+        });
+        self.waiting_pipelines.insert(id);
+        id
+    }
+
+    fn set_blend_state(
+        &mut self,
+        id: CachedPipelineId,
+        blend: Option<BlendState>,
+    ) -> CachedPipelineId {
+        let base = self.blend_variants.base(id);
+        let base_descriptor = &self.pipelines[base.0].descriptor;
+        if has_blend_state(base_descriptor, blend) {
+            return base;
+        }
+        if let Some(id) = self.blend_variants.get(base, blend) {
+            return id;
+        }
+        let id = self.queue(with_blend_state(base_descriptor, blend));
+        self.blend_variants.insert(base, blend, id);
+        id
+    }
 }
 
+/// The state of a pipeline queued on the [`RenderPipelineCache`], holding either a
+/// [`RenderPipeline`] or a [`ComputePipeline`].
 #[derive(Debug)]
-pub enum CachedPipelineState {
+pub enum CachedPipelineState<P = RenderPipeline> {
     Queued,
-    Ok(RenderPipeline),
+    Ok(P),
     Err(RenderPipelineError),
 }
 
-impl CachedPipelineState {
-    pub fn unwrap(&self) -> &RenderPipeline {
+impl<P> CachedPipelineState<P> {
+    pub fn unwrap(&self) -> &P {
         match self {
             CachedPipelineState::Ok(pipeline) => pipeline,
             CachedPipelineState::Queued => {
@@ -226,6 +443,8 @@ pub enum RenderPipelineError {
     AsModuleDescriptorError(AsModuleDescriptorError, ProcessedShader),
     #[error("Shader import not yet available.")]
     ShaderImportNotYetAvailable,
+    #[error(transparent)]
+    InvalidDescriptor(#[from] RenderResourceError),
 }
 
 impl RenderPipelineCache {
@@ -234,76 +453,147 @@ impl RenderPipelineCache {
             device,
             layout_cache: Default::default(),
             shader_cache: Default::default(),
-            waiting_pipelines: Default::default(),
-            pipelines: Default::default(),
+            render_pipelines: Default::default(),
+            waiting_compute_pipelines: Default::default(),
+            compute_pipelines: Default::default(),
         }
     }
 
     #[inline]
     pub fn get_state(&self, id: CachedPipelineId) -> &CachedPipelineState {
-        &self.pipelines[id.0].state
+        &self.render_pipelines.pipelines[id.0].state
     }
 
     #[inline]
     pub fn get(&self, id: CachedPipelineId) -> Option<&RenderPipeline> {
-        if let CachedPipelineState::Ok(pipeline) = &self.pipelines[id.0].state {
+        if let CachedPipelineState::Ok(pipeline) = &self.render_pipelines.pipelines[id.0].state {
             Some(pipeline)
         } else {
             None
         }
     }
 
+    /// Returns the descriptor the pipeline `id` was queued with.
+    #[inline]
+    pub fn get_descriptor(&self, id: CachedPipelineId) -> &RenderPipelineDescriptor {
+        &self.render_pipelines.pipelines[id.0].descriptor
+    }
+
     pub fn queue(&mut self, descriptor: RenderPipelineDescriptor) -> CachedPipelineId {
-        let id = CachedPipelineId(self.pipelines.len());
-        self.pipelines.push(CachedPipeline {
+        self.render_pipelines.queue(descriptor)
+    }
+
+    #[inline]
+    pub fn get_compute_state(
+        &self,
+        id: CachedComputePipelineId,
+    ) -> &CachedPipelineState<ComputePipeline> {
+        &self.compute_pipelines[id.0].state
+    }
+
+    #[inline]
+    pub fn get_compute(&self, id: CachedComputePipelineId) -> Option<&ComputePipeline> {
+        if let CachedPipelineState::Ok(pipeline) = &self.compute_pipelines[id.0].state {
+            Some(pipeline)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the descriptor the compute pipeline `id` was queued with.
+    #[inline]
+    pub fn get_compute_descriptor(
+        &self,
+        id: CachedComputePipelineId,
+    ) -> &ComputePipelineDescriptor {
+        &self.compute_pipelines[id.0].descriptor
+    }
+
+    /// Queues a compute pipeline, which is created once its shader is loaded.
+    ///
+    /// Its shader module is cached and hot reloaded like the modules of render pipelines, and its
+    /// pipeline layout is shared with any pipeline using the same bind group layouts.
+    pub fn queue_compute(
+        &mut self,
+        descriptor: ComputePipelineDescriptor,
+    ) -> CachedComputePipelineId {
+        let id = CachedComputePipelineId(self.compute_pipelines.len());
+        self.compute_pipelines.push(CachedPipeline {
             descriptor,
             state: CachedPipelineState::Queued,
         });
-        self.waiting_pipelines.insert(id);
+        self.waiting_compute_pipelines.insert(id);
         id
     }
 
+    /// Creates the layouts of all bind groups of a pipeline layout, with `groups` holding the entries
+    /// of each group in order, along with the pipeline layout combining them.
+    ///
+    /// This allows warming up the layouts a pipeline will use before it is queued. Layouts are
+    /// cached by their entries, so groups that were already created are returned as is instead
+    /// of being created again. Pass the returned layouts as the
+    /// [`RenderPipelineDescriptor::layout`] to reuse the warmed pipeline layout.
+    pub fn create_bind_group_layouts(
+        &mut self,
+        groups: &[&[BindGroupLayoutEntry]],
+    ) -> Vec<BindGroupLayout> {
+        let bind_group_layouts = self
+            .layout_cache
+            .get_bind_group_layouts(&self.device, groups);
+        self.layout_cache.get(&self.device, &bind_group_layouts);
+        bind_group_layouts
+    }
+
+    /// Returns a pipeline that is identical to the pipeline `id`, except that all of its color
+    /// targets use the `blend` state, queueing it if it doesn't exist yet.
+    ///
+    /// This allows switching e.g. a material between opaque and transparent cheaply: the variant
+    /// shares its shader modules and pipeline layout with the original pipeline, so only the new
+    /// pipeline object has to be created. Variants are cached, and switching back to the blend
+    /// state of the original pipeline returns the original pipeline.
+    pub fn set_blend_state(
+        &mut self,
+        id: CachedPipelineId,
+        blend: Option<BlendState>,
+    ) -> CachedPipelineId {
+        self.render_pipelines.set_blend_state(id, blend)
+    }
+
     fn set_shader(&mut self, handle: &Handle<Shader>, shader: &Shader) {
         let pipelines_to_queue = self.shader_cache.set_shader(handle, shader.clone());
-        for cached_pipeline in pipelines_to_queue {
-            self.pipelines[cached_pipeline.0].state = CachedPipelineState::Queued;
-            self.waiting_pipelines.insert(cached_pipeline);
-        }
+        self.requeue(pipelines_to_queue);
     }
 
     fn remove_shader(&mut self, shader: &Handle<Shader>) {
         let pipelines_to_queue = self.shader_cache.remove(shader);
-        for cached_pipeline in pipelines_to_queue {
-            self.pipelines[cached_pipeline.0].state = CachedPipelineState::Queued;
-            self.waiting_pipelines.insert(cached_pipeline);
+        self.requeue(pipelines_to_queue);
+    }
+
+    fn requeue(&mut self, pipelines: Vec<AnyPipelineId>) {
+        for cached_pipeline in pipelines {
+            match cached_pipeline {
+                AnyPipelineId::Render(id) => {
+                    self.render_pipelines.pipelines[id.0].state = CachedPipelineState::Queued;
+                    self.render_pipelines.waiting_pipelines.insert(id);
+                }
+                AnyPipelineId::Compute(id) => {
+                    self.compute_pipelines[id.0].state = CachedPipelineState::Queued;
+                    self.waiting_compute_pipelines.insert(id);
+                }
+            }
         }
     }
 
     pub fn process_queue(&mut self) {
-        let pipelines = std::mem::take(&mut self.waiting_pipelines);
+        let pipelines = std::mem::take(&mut self.render_pipelines.waiting_pipelines);
         for id in pipelines {
-            let state = &mut self.pipelines[id.0];
-            match &state.state {
-                CachedPipelineState::Ok(_) => continue,
-                CachedPipelineState::Queued => {}
-                CachedPipelineState::Err(err) => {
-                    match err {
-                        RenderPipelineError::ShaderNotLoaded(_)
-                        | RenderPipelineError::ShaderImportNotYetAvailable => { /* retry */ }
-                        // shader could not be processed ... retrying won't help
-                        RenderPipelineError::ProcessShaderError(err) => {
-                            error!("failed to process shader: {}", err);
-                            continue;
-                        }
-                        RenderPipelineError::AsModuleDescriptorError(err, source) => {
-                            log_shader_error(source, err);
-                            continue;
-                        }
-                    }
-                }
+            let state = &mut self.render_pipelines.pipelines[id.0];
+            if !needs_creation(&state.state) {
+                continue;
             }
 
             let descriptor = &state.descriptor;
+
             let vertex_module = match self.shader_cache.get(
                 &self.device,
                 id,
@@ -313,7 +603,7 @@ impl RenderPipelineCache {
                 Ok(module) => module,
                 Err(err) => {
                     state.state = CachedPipelineState::Err(err);
-                    self.waiting_pipelines.insert(id);
+                    self.render_pipelines.waiting_pipelines.insert(id);
                     continue;
                 }
             };
@@ -328,7 +618,7 @@ impl RenderPipelineCache {
                     Ok(module) => module,
                     Err(err) => {
                         state.state = CachedPipelineState::Err(err);
-                        self.waiting_pipelines.insert(id);
+                        self.render_pipelines.waiting_pipelines.insert(id);
                         continue;
                     }
                 };
@@ -379,7 +669,48 @@ impl RenderPipelineCache {
                     }),
             };
 
-            let pipeline = self.device.create_render_pipeline(&descriptor);
+            state.state = match self.device.try_create_render_pipeline(&descriptor) {
+                Ok(pipeline) => CachedPipelineState::Ok(pipeline),
+                Err(err) => CachedPipelineState::Err(err.into()),
+            };
+        }
+
+        let compute_pipelines = std::mem::take(&mut self.waiting_compute_pipelines);
+        for id in compute_pipelines {
+            let state = &mut self.compute_pipelines[id.0];
+            if !needs_creation(&state.state) {
+                continue;
+            }
+
+            let descriptor = &state.descriptor;
+            let module = match self.shader_cache.get(
+                &self.device,
+                id,
+                &descriptor.shader,
+                &descriptor.shader_defs,
+            ) {
+                Ok(module) => module,
+                Err(err) => {
+                    state.state = CachedPipelineState::Err(err);
+                    self.waiting_compute_pipelines.insert(id);
+                    continue;
+                }
+            };
+
+            let layout = if let Some(layout) = &descriptor.layout {
+                Some(self.layout_cache.get(&self.device, layout))
+            } else {
+                None
+            };
+
+            let pipeline = self
+                .device
+                .create_compute_pipeline(&RawComputePipelineDescriptor {
+                    label: descriptor.label.as_deref(),
+                    layout,
+                    module: &module,
+                    entry_point: descriptor.entry_point.deref(),
+                });
             state.state = CachedPipelineState::Ok(pipeline);
         }
     }
@@ -407,6 +738,32 @@ impl RenderPipelineCache {
     }
 }
 
+/// Returns whether a pipeline in `state` should be created, logging the error if it failed in a
+/// way that retrying won't fix.
+fn needs_creation<P>(state: &CachedPipelineState<P>) -> bool {
+    match state {
+        CachedPipelineState::Ok(_) => false,
+        CachedPipelineState::Queued => true,
+        CachedPipelineState::Err(err) => match err {
+            RenderPipelineError::ShaderNotLoaded(_)
+            | RenderPipelineError::ShaderImportNotYetAvailable => true,
+            // shader could not be processed ... retrying won't help
+            RenderPipelineError::ProcessShaderError(err) => {
+                error!("failed to process shader: {}", err);
+                false
+            }
+            RenderPipelineError::AsModuleDescriptorError(err, source) => {
+                log_shader_error(source, err);
+                false
+            }
+            RenderPipelineError::InvalidDescriptor(err) => {
+                error!("invalid pipeline descriptor: {}", err);
+                false
+            }
+        },
+    }
+}
+
 fn log_shader_error(source: &ProcessedShader, error: &AsModuleDescriptorError) {
     use codespan_reporting::{
         diagnostic::{Diagnostic, Label},
@@ -450,6 +807,9 @@ fn log_shader_error(source: &ProcessedShader, error: &AsModuleDescriptorError) {
             ShaderReflectError::SpirVParse(error) => {
                 error!("failed to process shader:\n{}", error);
             }
+            ShaderReflectError::Process(error) => {
+                error!("failed to process shader:\n{}", error);
+            }
             ShaderReflectError::Validation(error) => {
                 let (filename, source) = match source {
                     ProcessedShader::Wgsl(source) => ("wgsl", source.as_ref()),
@@ -519,3 +879,327 @@ impl<'a> Iterator for ErrorSources<'a> {
         current
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_resource::{FragmentState, VertexState};
+    use wgpu::{ColorTargetState, ColorWrites, MultisampleState, PrimitiveState, TextureFormat};
+
+    fn descriptor(shader: &Handle<Shader>) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: None,
+            layout: None,
+            vertex: VertexState {
+                shader: shader.clone_weak(),
+                shader_defs: vec!["VERTEX_TANGENTS".to_string()],
+                entry_point: "vertex".into(),
+                buffers: Vec::new(),
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                shader: shader.clone_weak(),
+                shader_defs: Vec::new(),
+                entry_point: "fragment".into(),
+                targets: vec![ColorTargetState {
+                    format: TextureFormat::Rgba8UnormSrgb,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+        }
+    }
+
+    #[test]
+    fn blend_variant_only_changes_the_blend_state() {
+        let shader = Handle::<Shader>::default();
+        let opaque = descriptor(&shader);
+        let transparent = with_blend_state(&opaque, Some(BlendState::ALPHA_BLENDING));
+
+        // The shader cache is keyed by shader handle and defs, so these being equal means the
+        // variant reuses the shader modules of the original pipeline.
+        assert_eq!(transparent.vertex.shader, opaque.vertex.shader);
+        assert_eq!(transparent.vertex.shader_defs, opaque.vertex.shader_defs);
+        let (opaque_fragment, transparent_fragment) = (
+            opaque.fragment.as_ref().unwrap(),
+            transparent.fragment.as_ref().unwrap(),
+        );
+        assert_eq!(transparent_fragment.shader, opaque_fragment.shader);
+        assert_eq!(
+            transparent_fragment.shader_defs,
+            opaque_fragment.shader_defs
+        );
+
+        assert!(has_blend_state(&opaque, Some(BlendState::REPLACE)));
+        assert!(has_blend_state(
+            &transparent,
+            Some(BlendState::ALPHA_BLENDING)
+        ));
+        assert_eq!(
+            transparent_fragment.targets[0].format,
+            opaque_fragment.targets[0].format
+        );
+    }
+
+    #[test]
+    fn blend_variants_are_cached() {
+        let mut variants = BlendVariantCache::default();
+        let base = CachedPipelineId(0);
+        let blend = Some(BlendState::ALPHA_BLENDING);
+        assert_eq!(variants.get(base, blend), None);
+
+        let variant = CachedPipelineId(1);
+        variants.insert(base, blend, variant);
+        assert_eq!(variants.get(base, blend), Some(variant));
+        assert_eq!(variants.get(base, None), None);
+        assert_eq!(variants.base(variant), base);
+        assert_eq!(variants.base(base), base);
+    }
+
+    #[test]
+    fn blend_variants_share_shader_modules() {
+        use bevy_asset::HandleId;
+
+        let shader = Handle::<Shader>::weak(HandleId::random::<Shader>());
+        let mut shader_cache = ShaderModuleCache::<usize>::default();
+        shader_cache.set_shader(&shader, Shader::from_wgsl("// shader"));
+        let mut render_pipelines = RenderPipelineQueue::<()>::default();
+
+        let opaque = render_pipelines.queue(descriptor(&shader));
+        let transparent =
+            render_pipelines.set_blend_state(opaque, Some(BlendState::ALPHA_BLENDING));
+        let premultiplied = render_pipelines
+            .set_blend_state(transparent, Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING));
+        assert_ne!(transparent, opaque);
+        assert_ne!(premultiplied, opaque);
+        assert_ne!(premultiplied, transparent);
+        assert_eq!(
+            render_pipelines.set_blend_state(premultiplied, Some(BlendState::ALPHA_BLENDING)),
+            transparent
+        );
+        assert_eq!(
+            render_pipelines.set_blend_state(transparent, Some(BlendState::REPLACE)),
+            opaque
+        );
+        assert_eq!(render_pipelines.waiting_pipelines.len(), 3);
+
+        // Fetch the modules of each queued pipeline like `process_queue` does
+        let mut created = 0;
+        let mut modules = Vec::new();
+        for id in [opaque, transparent, premultiplied] {
+            let descriptor = &render_pipelines.pipelines[id.0].descriptor;
+            let fragment = descriptor.fragment.as_ref().unwrap();
+            let mut get = |shader_defs: &[String]| {
+                shader_cache
+                    .get_or_create(id, &shader, shader_defs, |_| {
+                        created += 1;
+                        Ok(created)
+                    })
+                    .unwrap()
+            };
+            modules.push((
+                get(&descriptor.vertex.shader_defs),
+                get(&fragment.shader_defs),
+            ));
+        }
+        assert_eq!(created, 2);
+        assert_eq!(modules, [(1, 2); 3]);
+    }
+
+    #[test]
+    fn bind_group_layouts_are_created_once() {
+        use wgpu::{BindingType, BufferBindingType, SamplerBindingType, ShaderStages};
+
+        let uniform = BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let sampler = BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        };
+        let view_group = [uniform];
+        let material_group = [
+            uniform,
+            BindGroupLayoutEntry {
+                binding: 1,
+                ..sampler
+            },
+        ];
+        let mesh_group = [sampler];
+
+        let mut cache = HashMap::default();
+        let mut created = Vec::new();
+        let layouts = get_or_create_all(
+            &mut cache,
+            &[&view_group, &material_group, &mesh_group],
+            |entries| {
+                created.push(entries.to_vec());
+                created.len()
+            },
+        );
+        assert_eq!(layouts, vec![1, 2, 3]);
+        assert_eq!(
+            created,
+            vec![
+                view_group.to_vec(),
+                material_group.to_vec(),
+                mesh_group.to_vec()
+            ]
+        );
+
+        // Groups created before are reused, in the order they are requested.
+        let layouts = get_or_create_all(&mut cache, &[&mesh_group, &view_group], |_| {
+            panic!("layout should be cached")
+        });
+        assert_eq!(layouts, vec![3, 1]);
+    }
+
+    #[test]
+    fn hot_reloading_fragment_shader_only_reprocesses_fragment_stage() {
+        use bevy_asset::HandleId;
+
+        let vertex = Handle::<Shader>::weak(HandleId::random::<Shader>());
+        let fragment = Handle::<Shader>::weak(HandleId::random::<Shader>());
+        let mut cache = ShaderModuleCache::<usize>::default();
+        cache.set_shader(&vertex, Shader::from_wgsl("// vertex"));
+        cache.set_shader(&fragment, Shader::from_wgsl("// fragment"));
+
+        let pipeline = CachedPipelineId(0);
+        let mut processed = Vec::new();
+        let mut get = |cache: &mut ShaderModuleCache<usize>, handle: &Handle<Shader>| {
+            cache
+                .get_or_create(pipeline, handle, &[], |shader| {
+                    processed.push(shader.get_wgsl_source().unwrap().trim().to_string());
+                    Ok(processed.len())
+                })
+                .unwrap()
+        };
+
+        assert_eq!(get(&mut cache, &vertex), 1);
+        assert_eq!(get(&mut cache, &fragment), 2);
+
+        let pipelines_to_queue =
+            cache.set_shader(&fragment, Shader::from_wgsl("// fragment, edited"));
+        assert_eq!(pipelines_to_queue, vec![AnyPipelineId::Render(pipeline)]);
+        assert_eq!(get(&mut cache, &vertex), 1);
+        assert_eq!(get(&mut cache, &fragment), 3);
+
+        assert_eq!(
+            processed,
+            ["// vertex", "// fragment", "// fragment, edited"]
+        );
+    }
+
+    #[test]
+    fn editing_an_imported_shader_recompiles_its_importers() {
+        use bevy_asset::HandleId;
+
+        let library = Handle::<Shader>::weak(HandleId::random::<Shader>());
+        let importer = Handle::<Shader>::weak(HandleId::random::<Shader>());
+        let library_shader = |source: &str| {
+            let mut shader = Shader::from_wgsl(source.to_string());
+            shader.set_import_path("my::lib");
+            shader
+        };
+        let mut cache = ShaderModuleCache::<String>::default();
+        cache.set_shader(&importer, Shader::from_wgsl("#import my::lib\n// importer"));
+        cache.set_shader(&library, library_shader("// lib"));
+
+        let pipeline = CachedPipelineId(0);
+        let get = |cache: &mut ShaderModuleCache<String>| {
+            cache
+                .get_or_create(pipeline, &importer, &[], |shader| {
+                    Ok(shader.get_wgsl_source().unwrap().to_string())
+                })
+                .unwrap()
+        };
+        assert!(get(&mut cache).contains("// lib"));
+
+        let pipelines_to_queue = cache.set_shader(&library, library_shader("// lib, edited"));
+        assert_eq!(pipelines_to_queue, vec![AnyPipelineId::Render(pipeline)]);
+        assert!(get(&mut cache).contains("// lib, edited"));
+
+        // Without the library, the importer can't be compiled until it is added again
+        assert_eq!(
+            cache.remove(&library),
+            vec![AnyPipelineId::Render(pipeline)]
+        );
+        assert!(cache
+            .get_or_create(pipeline, &importer, &[], |_| Ok(String::new()))
+            .is_err());
+    }
+
+    #[test]
+    fn each_shader_def_set_gets_its_own_module() {
+        use bevy_asset::HandleId;
+
+        let handle = Handle::<Shader>::weak(HandleId::random::<Shader>());
+        let mut cache = ShaderModuleCache::<String>::default();
+        cache.set_shader(
+            &handle,
+            Shader::from_wgsl(
+                r"
+[[group(0), binding(0)]]
+var base_color_sampler: sampler;
+# ifdef TEXTURE
+[[group(0), binding(1)]]
+var base_color_texture: texture_2d<f32>;
+# endif
+",
+            ),
+        );
+
+        let mut get = |pipeline, shader_defs: &[String]| {
+            cache
+                .get_or_create(CachedPipelineId(pipeline), &handle, shader_defs, |shader| {
+                    Ok(shader.reflect().unwrap().to_debug_string())
+                })
+                .unwrap()
+        };
+
+        let untextured = get(0, &[]);
+        let textured = get(1, &["TEXTURE".to_string()]);
+        assert!(!untextured.contains("base_color_texture"));
+        assert!(textured.contains("binding 1 \"base_color_texture\": D2 texture"));
+        assert_eq!(get(2, &[]), untextured);
+    }
+
+    #[test]
+    fn shader_changes_requeue_compute_pipelines() {
+        use bevy_asset::HandleId;
+
+        let render = Handle::<Shader>::weak(HandleId::random::<Shader>());
+        let compute = Handle::<Shader>::weak(HandleId::random::<Shader>());
+        let mut cache = ShaderModuleCache::<()>::default();
+        cache.set_shader(&render, Shader::from_wgsl("// render"));
+        cache.set_shader(&compute, Shader::from_wgsl("// compute"));
+
+        // Render and compute pipelines are numbered separately, so their ids may be equal.
+        cache
+            .get_or_create(CachedPipelineId(0), &render, &[], |_| Ok(()))
+            .unwrap();
+        cache
+            .get_or_create(CachedComputePipelineId(0), &compute, &[], |_| Ok(()))
+            .unwrap();
+
+        assert_eq!(
+            cache.set_shader(&compute, Shader::from_wgsl("// compute, edited")),
+            vec![AnyPipelineId::Compute(CachedComputePipelineId(0))]
+        );
+        assert_eq!(
+            cache.remove(&render),
+            vec![AnyPipelineId::Render(CachedPipelineId(0))]
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::render_resource::{CachedPipelineId, RenderPipelineCache, RenderPipelineDescriptor};
 use bevy_utils::HashMap;
 use std::hash::Hash;
+use wgpu::PrimitiveTopology;
 
 pub struct SpecializedPipelines<S: SpecializedPipeline> {
     cache: HashMap<S::Key, CachedPipelineId>,
@@ -20,10 +21,21 @@ impl<S: SpecializedPipeline> SpecializedPipelines<S> {
         cache: &mut RenderPipelineCache,
         specialize_pipeline: &S,
         key: S::Key,
+    ) -> CachedPipelineId {
+        self.get_or_queue(specialize_pipeline, key, |descriptor| {
+            cache.queue(descriptor)
+        })
+    }
+
+    fn get_or_queue(
+        &mut self,
+        specialize_pipeline: &S,
+        key: S::Key,
+        queue: impl FnOnce(RenderPipelineDescriptor) -> CachedPipelineId,
     ) -> CachedPipelineId {
         *self.cache.entry(key.clone()).or_insert_with(|| {
             let descriptor = specialize_pipeline.specialize(key);
-            cache.queue(descriptor)
+            queue(descriptor)
         })
     }
 }
@@ -32,3 +44,106 @@ pub trait SpecializedPipeline {
     type Key: Clone + Hash + PartialEq + Eq;
     fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor;
 }
+
+/// The properties in which variants of a base [`RenderPipelineDescriptor`] differ.
+///
+/// A [`RenderPipelineDescriptor`] is a [`SpecializedPipeline`] with this key, so the same
+/// descriptor can be specialized with [`SpecializedPipelines<RenderPipelineDescriptor>`] into
+/// a pipeline for each permutation of e.g. a `NORMAL_MAP` shader def and MSAA sample count.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PipelineSpecialization {
+    /// Shader defs added to the shader defs of both the vertex and the fragment stage
+    pub shader_defs: Vec<String>,
+    pub primitive_topology: PrimitiveTopology,
+    pub sample_count: u32,
+}
+
+impl Default for PipelineSpecialization {
+    fn default() -> Self {
+        Self {
+            shader_defs: Vec::new(),
+            primitive_topology: PrimitiveTopology::TriangleList,
+            sample_count: 1,
+        }
+    }
+}
+
+impl SpecializedPipeline for RenderPipelineDescriptor {
+    type Key = PipelineSpecialization;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut descriptor = self.clone();
+        descriptor
+            .vertex
+            .shader_defs
+            .extend(key.shader_defs.iter().cloned());
+        if let Some(fragment) = &mut descriptor.fragment {
+            fragment.shader_defs.extend(key.shader_defs);
+        }
+        descriptor.primitive.topology = key.primitive_topology;
+        descriptor.multisample.count = key.sample_count;
+        descriptor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_resource::{FragmentState, VertexState};
+    use bevy_asset::Handle;
+    use wgpu::{MultisampleState, PrimitiveState};
+
+    #[test]
+    fn each_specialization_queues_its_own_pipeline() {
+        let base = RenderPipelineDescriptor {
+            label: None,
+            layout: None,
+            vertex: VertexState {
+                shader: Handle::default(),
+                shader_defs: vec!["VERTEX_UVS".to_string()],
+                entry_point: "vertex".into(),
+                buffers: Vec::new(),
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                shader: Handle::default(),
+                shader_defs: Vec::new(),
+                entry_point: "fragment".into(),
+                targets: Vec::new(),
+            }),
+        };
+        let plain = PipelineSpecialization::default();
+        let normal_mapped = PipelineSpecialization {
+            shader_defs: vec!["NORMAL_MAP".to_string()],
+            sample_count: 4,
+            ..Default::default()
+        };
+
+        let mut queued = Vec::new();
+        let mut pipelines = SpecializedPipelines::<RenderPipelineDescriptor>::default();
+        let mut specialize = |key: &PipelineSpecialization| {
+            pipelines.get_or_queue(&base, key.clone(), |descriptor| {
+                queued.push(descriptor);
+                CachedPipelineId::new(queued.len() - 1)
+            })
+        };
+
+        let plain_id = specialize(&plain);
+        let normal_mapped_id = specialize(&normal_mapped);
+        assert_ne!(plain_id, normal_mapped_id);
+        assert_eq!(specialize(&plain), plain_id);
+        assert_eq!(specialize(&normal_mapped), normal_mapped_id);
+
+        assert_eq!(queued.len(), 2);
+        assert_eq!(queued[0].vertex.shader_defs, ["VERTEX_UVS"]);
+        assert_eq!(queued[0].multisample.count, 1);
+        assert_eq!(queued[1].vertex.shader_defs, ["VERTEX_UVS", "NORMAL_MAP"]);
+        assert_eq!(
+            queued[1].fragment.as_ref().unwrap().shader_defs,
+            ["NORMAL_MAP"]
+        );
+        assert_eq!(queued[1].multisample.count, 4);
+    }
+}
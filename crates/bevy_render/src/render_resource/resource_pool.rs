@@ -0,0 +1,278 @@
+use crate::{render_resource::Buffer, renderer::RenderDevice};
+use bevy_ecs::{
+    prelude::ResMut,
+    world::{FromWorld, World},
+};
+use bevy_utils::HashMap;
+use std::hash::Hash;
+use wgpu::{BufferAddress, BufferUsages};
+
+/// A class of buffers that are pooled together by a [`BufferPool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BufferSizeClass {
+    /// The size of the buffers in this class, in bytes
+    pub size: BufferAddress,
+    /// The usages of the buffers in this class
+    pub usage: BufferUsages,
+}
+
+/// Configures the resources that are allocated up front, when the renderer is initialized.
+///
+/// Insert this resource before adding the [`RenderPlugin`](crate::RenderPlugin). Pre-allocating the
+/// resources that are commonly requested at runtime bounds the allocations (and the resulting GPU
+/// memory spikes and hitches) that happen during gameplay.
+#[derive(Debug, Clone, Default)]
+pub struct ResourcePoolSettings {
+    /// The number of buffers to pre-allocate for each size class of the [`BufferPool`]
+    pub buffers: Vec<(BufferSizeClass, usize)>,
+    /// The maximum number of buffers the [`BufferPool`] keeps for each size class on top of the
+    /// pre-allocated ones, or `None` to keep every buffer that was used recently
+    pub max_pooled_buffers: Option<usize>,
+    /// The number of textures to pre-allocate in the
+    /// [`TextureCache`](crate::texture::TextureCache) for each descriptor
+    pub textures: Vec<(wgpu::TextureDescriptor<'static>, usize)>,
+}
+
+struct PooledResource<R> {
+    resource: R,
+    taken: bool,
+    /// Pre-allocated resources are never freed.
+    pinned: bool,
+    frames_since_last_use: usize,
+}
+
+/// Recycles resources of the same key between frames.
+struct ResourcePool<K, R> {
+    resources: HashMap<K, Vec<PooledResource<R>>>,
+    /// The maximum number of resources that aren't pinned kept for each key
+    max_unpinned: usize,
+}
+
+impl<K, R> Default for ResourcePool<K, R> {
+    fn default() -> Self {
+        Self {
+            resources: Default::default(),
+            max_unpinned: usize::MAX,
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, R: Clone> ResourcePool<K, R> {
+    fn preallocate(&mut self, key: K, count: usize, mut create: impl FnMut(&K) -> R) {
+        let resources = self.resources.entry(key.clone()).or_default();
+        resources.extend((0..count).map(|_| PooledResource {
+            resource: create(&key),
+            taken: false,
+            pinned: true,
+            frames_since_last_use: 0,
+        }));
+    }
+
+    fn get(&mut self, key: K, create: impl FnOnce(&K) -> R) -> R {
+        let resources = self.resources.entry(key.clone()).or_default();
+        if let Some(pooled) = resources.iter_mut().find(|pooled| !pooled.taken) {
+            pooled.taken = true;
+            pooled.frames_since_last_use = 0;
+            return pooled.resource.clone();
+        }
+
+        let resource = create(&key);
+        resources.push(PooledResource {
+            resource: resource.clone(),
+            taken: true,
+            pinned: false,
+            frames_since_last_use: 0,
+        });
+        resource
+    }
+
+    fn update(&mut self) {
+        for resources in self.resources.values_mut() {
+            for pooled in resources.iter_mut() {
+                pooled.frames_since_last_use += 1;
+                pooled.taken = false;
+            }
+
+            let mut unpinned = 0;
+            resources.retain(|pooled| {
+                if pooled.pinned {
+                    return true;
+                }
+                unpinned += 1;
+                pooled.frames_since_last_use < 3 && unpinned <= self.max_unpinned
+            });
+        }
+    }
+}
+
+/// This resource hands out buffers that are only required for one frame, recycling them in later
+/// frames.
+///
+/// Requests are rounded up to the smallest configured [`BufferSizeClass`] that fits them, so that
+/// buffers pre-allocated through [`ResourcePoolSettings`] are reused instead of creating new ones.
+pub struct BufferPool {
+    size_classes: Vec<BufferSizeClass>,
+    pool: ResourcePool<BufferSizeClass, Buffer>,
+}
+
+impl BufferPool {
+    /// Creates a pool with the buffers of `settings` allocated up front.
+    pub fn new(render_device: &RenderDevice, settings: &ResourcePoolSettings) -> Self {
+        let mut pool = BufferPool::empty();
+        pool.pool.max_unpinned = settings.max_pooled_buffers.unwrap_or(usize::MAX);
+        for (size_class, count) in &settings.buffers {
+            pool.add_size_class(*size_class);
+            pool.pool.preallocate(*size_class, *count, |size_class| {
+                create_pooled_buffer(render_device, size_class)
+            });
+        }
+        pool
+    }
+
+    fn empty() -> Self {
+        Self {
+            size_classes: Vec::new(),
+            pool: Default::default(),
+        }
+    }
+
+    fn add_size_class(&mut self, size_class: BufferSizeClass) {
+        if !self.size_classes.contains(&size_class) {
+            self.size_classes.push(size_class);
+            self.size_classes.sort_by_key(|size_class| size_class.size);
+        }
+    }
+
+    /// Returns the size class a request for a buffer of `size` bytes with `usage` is served from.
+    pub fn size_class(&self, size: BufferAddress, usage: BufferUsages) -> BufferSizeClass {
+        self.size_classes
+            .iter()
+            .find(|size_class| size_class.size >= size && size_class.usage.contains(usage))
+            .copied()
+            .unwrap_or(BufferSizeClass { size, usage })
+    }
+
+    /// Retrieves a buffer of at least `size` bytes supporting `usage` that isn't used by anything
+    /// else this frame, creating a new one if none is free.
+    pub fn get(
+        &mut self,
+        render_device: &RenderDevice,
+        size: BufferAddress,
+        usage: BufferUsages,
+    ) -> Buffer {
+        let size_class = self.size_class(size, usage);
+        self.pool.get(size_class, |size_class| {
+            create_pooled_buffer(render_device, size_class)
+        })
+    }
+
+    /// Makes all buffers available again, and frees the ones that weren't used recently and
+    /// haven't been pre-allocated.
+    pub fn update(&mut self) {
+        self.pool.update();
+    }
+}
+
+impl FromWorld for BufferPool {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.get_resource::<RenderDevice>().unwrap();
+        let settings = world
+            .get_resource::<ResourcePoolSettings>()
+            .cloned()
+            .unwrap_or_default();
+        BufferPool::new(render_device, &settings)
+    }
+}
+
+fn create_pooled_buffer(render_device: &RenderDevice, size_class: &BufferSizeClass) -> Buffer {
+    render_device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("pooled_buffer"),
+        size: size_class.size,
+        usage: size_class.usage,
+        mapped_at_creation: false,
+    })
+}
+
+/// Updates the [`BufferPool`] to make its buffers available for the next frame.
+pub fn update_buffer_pool_system(mut buffer_pool: ResMut<BufferPool>) {
+    buffer_pool.update();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIFORM_256: BufferSizeClass = BufferSizeClass {
+        size: 256,
+        usage: BufferUsages::UNIFORM,
+    };
+
+    #[test]
+    fn pooled_size_reuses_preallocated_resource() {
+        let mut created = 0;
+        let mut pool = ResourcePool::<BufferSizeClass, usize>::default();
+        pool.preallocate(UNIFORM_256, 2, |_| {
+            created += 1;
+            created
+        });
+
+        let mut create =
+            |_: &BufferSizeClass| -> usize { panic!("should reuse a pooled resource") };
+        assert_eq!(pool.get(UNIFORM_256, &mut create), 1);
+        assert_eq!(pool.get(UNIFORM_256, &mut create), 2);
+        assert_eq!(pool.get(UNIFORM_256, |_| 3), 3);
+
+        // Pre-allocated resources survive any number of unused frames, others don't.
+        for _ in 0..4 {
+            pool.update();
+        }
+        assert_eq!(pool.resources[&UNIFORM_256].len(), 2);
+    }
+
+    #[test]
+    fn freed_resource_is_reused_by_a_later_frame() {
+        let mut created = 0;
+        let mut create = |_: &BufferSizeClass| {
+            created += 1;
+            created
+        };
+        let mut pool = ResourcePool::<BufferSizeClass, usize> {
+            max_unpinned: 2,
+            ..Default::default()
+        };
+        assert_eq!(pool.get(UNIFORM_256, &mut create), 1);
+        assert_eq!(pool.get(UNIFORM_256, &mut create), 2);
+        assert_eq!(pool.get(UNIFORM_256, &mut create), 3);
+
+        // Resources used in one frame are freed for the next, but only up to the maximum are kept.
+        pool.update();
+        assert_eq!(pool.resources[&UNIFORM_256].len(), 2);
+        assert_eq!(pool.get(UNIFORM_256, &mut create), 1);
+        assert_eq!(pool.get(UNIFORM_256, &mut create), 2);
+        assert_eq!(pool.get(UNIFORM_256, &mut create), 4);
+    }
+
+    #[test]
+    fn requests_are_rounded_up_to_a_size_class() {
+        let mut pool = BufferPool::empty();
+        pool.add_size_class(BufferSizeClass {
+            size: 1024,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        pool.add_size_class(UNIFORM_256);
+
+        assert_eq!(pool.size_class(100, BufferUsages::UNIFORM), UNIFORM_256);
+        assert_eq!(
+            pool.size_class(100, BufferUsages::COPY_DST).size,
+            1024,
+            "the smaller class lacks the requested usage"
+        );
+        assert_eq!(
+            pool.size_class(4096, BufferUsages::UNIFORM),
+            BufferSizeClass {
+                size: 4096,
+                usage: BufferUsages::UNIFORM
+            }
+        );
+    }
+}
@@ -6,10 +6,11 @@ use naga::{valid::ModuleInfo, Module};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use std::{
-    borrow::Cow, collections::HashSet, marker::Copy, ops::Deref, path::PathBuf, str::FromStr,
+    borrow::Cow, collections::HashSet, fmt::Write, marker::Copy, num::NonZeroU32, ops::Deref,
+    path::PathBuf, str::FromStr,
 };
 use thiserror::Error;
-use wgpu::{util::make_spirv, ShaderModuleDescriptor, ShaderSource};
+use wgpu::{util::make_spirv, ShaderModuleDescriptor, ShaderSource, ShaderStages, VertexFormat};
 
 #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
 pub struct ShaderId(Uuid);
@@ -31,6 +32,8 @@ pub enum ShaderReflectError {
     SpirVParse(#[from] naga::front::spv::Error),
     #[error(transparent)]
     Validation(#[from] naga::WithSpan<naga::valid::ValidationError>),
+    #[error(transparent)]
+    Process(#[from] ProcessShaderError),
 }
 /// A shader, as defined by its [`ShaderSource`] and [`ShaderStage`](naga::ShaderStage)
 /// This is an "unprocessed" shader. It can contain preprocessor directives.
@@ -61,6 +64,58 @@ impl Shader {
         }
     }
 
+    /// Splits a GLSL source holding both a vertex and a fragment stage into a [`Shader`] per
+    /// stage.
+    ///
+    /// Each stage starts at a `#pragma stage vertex` or `#pragma stage fragment` line and runs
+    /// until the next marker. Lines before the first marker, like the `#version` directive and
+    /// shared declarations, are part of both stages. Lines that aren't part of a stage are kept
+    /// as blank lines, so that line numbers in compilation errors match the combined source.
+    pub fn from_combined_glsl(source: &str) -> Result<CombinedGlslShaders, CombinedGlslError> {
+        let mut vertex = String::with_capacity(source.len());
+        let mut fragment = String::with_capacity(source.len());
+        let mut current_stage = None;
+        let mut has_vertex = false;
+        let mut has_fragment = false;
+        for line in source.lines() {
+            let (vertex_line, fragment_line) =
+                if let Some(stage) = line.trim().strip_prefix("#pragma stage") {
+                    let (stage, seen) = match stage.trim() {
+                        "vertex" => (naga::ShaderStage::Vertex, &mut has_vertex),
+                        "fragment" => (naga::ShaderStage::Fragment, &mut has_fragment),
+                        other => return Err(CombinedGlslError::UnknownStage(other.to_string())),
+                    };
+                    if *seen {
+                        return Err(CombinedGlslError::DuplicateStage(stage));
+                    }
+                    *seen = true;
+                    current_stage = Some(stage);
+                    ("", "")
+                } else {
+                    match current_stage {
+                        None => (line, line),
+                        Some(naga::ShaderStage::Vertex) => (line, ""),
+                        Some(_) => ("", line),
+                    }
+                };
+            vertex.push_str(vertex_line);
+            vertex.push('\n');
+            fragment.push_str(fragment_line);
+            fragment.push('\n');
+        }
+
+        if !has_vertex {
+            return Err(CombinedGlslError::MissingStage(naga::ShaderStage::Vertex));
+        }
+        if !has_fragment {
+            return Err(CombinedGlslError::MissingStage(naga::ShaderStage::Fragment));
+        }
+        Ok(CombinedGlslShaders {
+            vertex: Shader::from_glsl(vertex, naga::ShaderStage::Vertex),
+            fragment: Shader::from_glsl(fragment, naga::ShaderStage::Fragment),
+        })
+    }
+
     pub fn from_spirv(source: impl Into<Cow<'static, [u8]>>) -> Shader {
         Shader {
             imports: Vec::new(),
@@ -89,6 +144,23 @@ impl Shader {
     }
 }
 
+/// The stages of a combined GLSL source, as split by [`Shader::from_combined_glsl`].
+#[derive(Debug, Clone)]
+pub struct CombinedGlslShaders {
+    pub vertex: Shader,
+    pub fragment: Shader,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CombinedGlslError {
+    #[error("combined GLSL source has no `#pragma stage` marker for the {0:?} stage")]
+    MissingStage(naga::ShaderStage),
+    #[error("combined GLSL source has more than one `#pragma stage` marker for the {0:?} stage")]
+    DuplicateStage(naga::ShaderStage),
+    #[error("`#pragma stage {0}` is not a supported stage, expected `vertex` or `fragment`")]
+    UnknownStage(String),
+}
+
 #[derive(Debug, Clone)]
 pub enum Source {
     Wgsl(Cow<'static, str>),
@@ -210,6 +282,472 @@ impl ShaderReflection {
     pub fn get_wgsl(&self) -> Result<String, naga::back::wgsl::Error> {
         naga::back::wgsl::write_string(&self.module, &self.module_info, WriterFlags::EXPLICIT_TYPES)
     }
+
+    /// Returns the members of the uniform block bound at `group` and `binding`, in declaration order.
+    ///
+    /// Returns `None` if there is no uniform struct at that binding.
+    pub fn uniform_block_members(
+        &self,
+        group: u32,
+        binding: u32,
+    ) -> Option<Vec<UniformBlockMember>> {
+        let variable =
+            self.module
+                .global_variables
+                .iter()
+                .find_map(|(_, variable)| match &variable.binding {
+                    Some(resource_binding)
+                        if variable.class == naga::StorageClass::Uniform
+                            && resource_binding.group == group
+                            && resource_binding.binding == binding =>
+                    {
+                        Some(variable)
+                    }
+                    _ => None,
+                })?;
+        let members = match &self.module.types[variable.ty].inner {
+            naga::TypeInner::Struct { members, .. } => members,
+            _ => return None,
+        };
+
+        let mut layouter = naga::proc::Layouter::default();
+        layouter
+            .update(&self.module.types, &self.module.constants)
+            .ok()?;
+        Some(
+            members
+                .iter()
+                .map(|member| UniformBlockMember {
+                    name: member.name.clone(),
+                    offset: member.offset,
+                    size: layouter[member.ty].size,
+                    ty: member.ty,
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the vertex attributes read by the vertex shader `entry_point`, as pairs of
+    /// their names and the formats matching their shader types, ordered by location.
+    ///
+    /// Both attributes passed as separate arguments and as members of an input struct are
+    /// reflected. Returns `None` if there is no vertex entry point with that name.
+    pub fn required_vertex_attributes(
+        &self,
+        entry_point: &str,
+    ) -> Option<Vec<(String, VertexFormat)>> {
+        let entry_point =
+            self.module.entry_points.iter().find(|entry| {
+                entry.stage == naga::ShaderStage::Vertex && entry.name == entry_point
+            })?;
+
+        Some(
+            self.vertex_attributes(entry_point)
+                .into_iter()
+                .map(|(_, name, format)| (name, format))
+                .collect(),
+        )
+    }
+
+    /// Returns the stages whose entry points use the resource bound at `group` and `binding`,
+    /// either directly or through the functions they call.
+    ///
+    /// Use this as the `visibility` of the matching [`BindGroupLayoutEntry`](wgpu::BindGroupLayoutEntry),
+    /// so that e.g. a sampler only read by the fragment shader isn't visible to the vertex
+    /// shader. A binding that is declared but never used has no visibility. Returns `None` if
+    /// the shader doesn't declare the binding.
+    pub fn binding_visibility(&self, group: u32, binding: u32) -> Option<ShaderStages> {
+        let (handle, _) = self.module.global_variables.iter().find(|(_, variable)| {
+            matches!(&variable.binding, Some(resource_binding)
+                    if resource_binding.group == group && resource_binding.binding == binding)
+        })?;
+
+        let mut visibility = ShaderStages::NONE;
+        for (index, entry_point) in self.module.entry_points.iter().enumerate() {
+            if !self.module_info.get_entry_point(index)[handle].is_empty() {
+                visibility |= match entry_point.stage {
+                    naga::ShaderStage::Vertex => ShaderStages::VERTEX,
+                    naga::ShaderStage::Fragment => ShaderStages::FRAGMENT,
+                    naga::ShaderStage::Compute => ShaderStages::COMPUTE,
+                };
+            }
+        }
+        Some(visibility)
+    }
+
+    /// Returns the entries of a bind group layout matching the resources the shader binds in
+    /// `group`, ordered by binding.
+    ///
+    /// Each entry is only visible to the stages using it, see
+    /// [`ShaderReflection::binding_visibility`]. The shader doesn't tell whether a buffer has a
+    /// dynamic offset or whether a float texture is filterable, so buffers get no dynamic offset
+    /// and float textures and samplers are filterable. An array of textures or samplers bound to a
+    /// single binding gets its length as the `count` of its entry.
+    pub fn bind_group_layout_entries(&self, group: u32) -> Vec<wgpu::BindGroupLayoutEntry> {
+        let mut entries = self
+            .module
+            .global_variables
+            .iter()
+            .filter_map(|(_, variable)| {
+                let binding = variable.binding.as_ref()?;
+                if binding.group != group {
+                    return None;
+                }
+                Some(wgpu::BindGroupLayoutEntry {
+                    binding: binding.binding,
+                    visibility: self.binding_visibility(group, binding.binding)?,
+                    ty: binding_type(&self.module, variable)?,
+                    count: binding_array_length(&self.module, variable),
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| entry.binding);
+        entries
+    }
+
+    /// attributes of its vertex entry points as human-readable text, e.g.
+    ///
+    /// ```text
+    /// group 0:
+    ///   binding 0 "view": uniform buffer
+    ///   binding 1 "lights": read-only storage buffer
+    /// vertex entry point "vertex":
+    ///   location 0 "position": Float32x3
+    /// ```
+    ///
+    /// This is useful to log when a bind group or a vertex buffer doesn't match what the shader
+    /// expects. Whether a buffer binding has a dynamic offset is set by its bind group layout, not
+    /// by the shader, so it isn't listed.
+    pub fn to_debug_string(&self) -> String {
+        let mut bindings = self
+            .module
+            .global_variables
+            .iter()
+            .filter_map(|(_, variable)| {
+                let binding = variable.binding.as_ref()?;
+                Some((binding.group, binding.binding, variable))
+            })
+            .collect::<Vec<_>>();
+        bindings.sort_by_key(|(group, binding, _)| (*group, *binding));
+
+        let mut output = String::new();
+        let mut current_group = None;
+        for (group, binding, variable) in bindings {
+            if current_group != Some(group) {
+                current_group = Some(group);
+                let _ = writeln!(output, "group {}:", group);
+            }
+            let _ = writeln!(
+                output,
+                "  binding {} {:?}: {}",
+                binding,
+                variable.name.as_deref().unwrap_or_default(),
+                self.binding_type_name(variable)
+            );
+        }
+
+        for entry_point in &self.module.entry_points {
+            if entry_point.stage != naga::ShaderStage::Vertex {
+                continue;
+            }
+            let _ = writeln!(output, "vertex entry point {:?}:", entry_point.name);
+            for (location, name, format) in self.vertex_attributes(entry_point) {
+                let _ = writeln!(output, "  location {} {:?}: {:?}", location, name, format);
+            }
+        }
+        output
+    }
+
+    fn binding_type_name(&self, variable: &naga::GlobalVariable) -> String {
+        let ty = binding_element_type(&self.module, variable);
+        match variable.class {
+            _ if ty != variable.ty => {}
+            naga::StorageClass::Uniform => return "uniform buffer".to_string(),
+            naga::StorageClass::Storage { access } => {
+                return if access.contains(naga::StorageAccess::STORE) {
+                    "read-write storage buffer".to_string()
+                } else {
+                    "read-only storage buffer".to_string()
+                };
+            }
+            _ => {}
+        }
+        let name = match &self.module.types[ty].inner {
+            naga::TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            } => {
+                let kind = match class {
+                    naga::ImageClass::Sampled { multi: true, .. } => "multisampled texture",
+                    naga::ImageClass::Sampled { .. } => "texture",
+                    naga::ImageClass::Depth { .. } => "depth texture",
+                    naga::ImageClass::Storage { .. } => "storage texture",
+                };
+                let array = if *arrayed { " array" } else { "" };
+                format!("{:?}{} {}", dim, array, kind)
+            }
+            naga::TypeInner::Sampler { comparison: true } => "comparison sampler".to_string(),
+            naga::TypeInner::Sampler { comparison: false } => "sampler".to_string(),
+            other => format!("{:?}", other),
+        };
+        match binding_array_length(&self.module, variable) {
+            Some(length) => format!("array of {} {}", length, name),
+            None => name,
+        }
+    }
+
+    /// Returns the location, name and format of the vertex attributes of `entry_point`, ordered by
+    /// location.
+    fn vertex_attributes(
+        &self,
+        entry_point: &naga::EntryPoint,
+    ) -> Vec<(u32, String, VertexFormat)> {
+        let mut attributes = Vec::new();
+        for argument in &entry_point.function.arguments {
+            match &self.module.types[argument.ty].inner {
+                naga::TypeInner::Struct { members, .. } => {
+                    for member in members {
+                        self.push_vertex_attribute(
+                            &mut attributes,
+                            &member.name,
+                            &member.binding,
+                            member.ty,
+                        );
+                    }
+                }
+                _ => self.push_vertex_attribute(
+                    &mut attributes,
+                    &argument.name,
+                    &argument.binding,
+                    argument.ty,
+                ),
+            }
+        }
+        attributes.sort_by_key(|(location, _, _)| *location);
+        attributes
+    }
+
+    fn push_vertex_attribute(
+        &self,
+        attributes: &mut Vec<(u32, String, VertexFormat)>,
+        name: &Option<String>,
+        binding: &Option<naga::Binding>,
+        ty: naga::Handle<naga::Type>,
+    ) {
+        if let Some(naga::Binding::Location { location, .. }) = binding {
+            if let Some(format) = vertex_format(&self.module.types[ty].inner) {
+                let name = name.clone().unwrap_or_else(|| location.to_string());
+                attributes.push((*location, name, format));
+            }
+        }
+    }
+}
+
+/// Returns the 32 bit [`VertexFormat`] of a vertex shader input of type `ty`.
+fn vertex_format(ty: &naga::TypeInner) -> Option<VertexFormat> {
+    let (kind, size) = match *ty {
+        naga::TypeInner::Scalar { kind, width: 4 } => (kind, 1),
+        naga::TypeInner::Vector {
+            size,
+            kind,
+            width: 4,
+        } => (kind, size as u8),
+        _ => return None,
+    };
+    Some(match (kind, size) {
+        (naga::ScalarKind::Float, 1) => VertexFormat::Float32,
+        (naga::ScalarKind::Float, 2) => VertexFormat::Float32x2,
+        (naga::ScalarKind::Float, 3) => VertexFormat::Float32x3,
+        (naga::ScalarKind::Float, 4) => VertexFormat::Float32x4,
+        (naga::ScalarKind::Uint, 1) => VertexFormat::Uint32,
+        (naga::ScalarKind::Uint, 2) => VertexFormat::Uint32x2,
+        (naga::ScalarKind::Uint, 3) => VertexFormat::Uint32x3,
+        (naga::ScalarKind::Uint, 4) => VertexFormat::Uint32x4,
+        (naga::ScalarKind::Sint, 1) => VertexFormat::Sint32,
+        (naga::ScalarKind::Sint, 2) => VertexFormat::Sint32x2,
+        (naga::ScalarKind::Sint, 3) => VertexFormat::Sint32x3,
+        (naga::ScalarKind::Sint, 4) => VertexFormat::Sint32x4,
+        _ => return None,
+    })
+}
+
+/// Returns the [`BindingType`](wgpu::BindingType) of the resource `variable`, which is the type of
+/// each element for a binding array.
+fn binding_type(module: &Module, variable: &naga::GlobalVariable) -> Option<wgpu::BindingType> {
+    let ty = binding_element_type(module, variable);
+    match variable.class {
+        // The GLSL frontend puts arrays of textures in the uniform storage class
+        _ if ty != variable.ty => {}
+        naga::StorageClass::Uniform => {
+            return Some(wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            })
+        }
+        naga::StorageClass::Storage { access } => {
+            return Some(wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage {
+                    read_only: !access.contains(naga::StorageAccess::STORE),
+                },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            })
+        }
+        _ => {}
+    }
+    match &module.types[ty].inner {
+        naga::TypeInner::Image {
+            dim,
+            arrayed,
+            class,
+        } => {
+            let view_dimension = match (dim, arrayed) {
+                (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+                (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+                (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+                (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+                (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+                (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+            };
+            Some(match class {
+                naga::ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+                    sample_type: match kind {
+                        naga::ScalarKind::Float => {
+                            wgpu::TextureSampleType::Float { filterable: true }
+                        }
+                        naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                        naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                        naga::ScalarKind::Bool => return None,
+                    },
+                    view_dimension,
+                    multisampled: *multi,
+                },
+                naga::ImageClass::Depth { multi } => wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension,
+                    multisampled: *multi,
+                },
+                naga::ImageClass::Storage { format, access } => wgpu::BindingType::StorageTexture {
+                    access: if !access.contains(naga::StorageAccess::STORE) {
+                        wgpu::StorageTextureAccess::ReadOnly
+                    } else if !access.contains(naga::StorageAccess::LOAD) {
+                        wgpu::StorageTextureAccess::WriteOnly
+                    } else {
+                        wgpu::StorageTextureAccess::ReadWrite
+                    },
+                    format: storage_texture_format(*format),
+                    view_dimension,
+                },
+            })
+        }
+        naga::TypeInner::Sampler { comparison: true } => Some(wgpu::BindingType::Sampler(
+            wgpu::SamplerBindingType::Comparison,
+        )),
+        naga::TypeInner::Sampler { comparison: false } => Some(wgpu::BindingType::Sampler(
+            wgpu::SamplerBindingType::Filtering,
+        )),
+        _ => None,
+    }
+}
+
+/// Returns the number of elements of `variable` if it is an array of textures or samplers bound to
+/// a single binding, as used for bindless-style rendering.
+///
+/// This is the `count` of its [`BindGroupLayoutEntry`](wgpu::BindGroupLayoutEntry), see
+/// [`texture_array_layout_entry`](super::texture_array_layout_entry).
+fn binding_array_length(module: &Module, variable: &naga::GlobalVariable) -> Option<NonZeroU32> {
+    match module.types[variable.ty].inner {
+        naga::TypeInner::Array {
+            size: naga::ArraySize::Constant(length),
+            ..
+        } if binding_element_type(module, variable) != variable.ty => {
+            match module.constants[length].inner {
+                naga::ConstantInner::Scalar {
+                    value: naga::ScalarValue::Uint(length),
+                    ..
+                } => u32::try_from(length).ok().and_then(NonZeroU32::new),
+                naga::ConstantInner::Scalar {
+                    value: naga::ScalarValue::Sint(length),
+                    ..
+                } => u32::try_from(length).ok().and_then(NonZeroU32::new),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Returns the type of the elements of `variable` if it is an array of textures or samplers, or its
+/// own type otherwise.
+fn binding_element_type(
+    module: &Module,
+    variable: &naga::GlobalVariable,
+) -> naga::Handle<naga::Type> {
+    match module.types[variable.ty].inner {
+        naga::TypeInner::Array { base, .. }
+            if matches!(
+                module.types[base].inner,
+                naga::TypeInner::Image { .. } | naga::TypeInner::Sampler { .. }
+            ) =>
+        {
+            base
+        }
+        _ => variable.ty,
+    }
+}
+
+/// Returns the texture format of a storage texture of the shader format `format`.
+fn storage_texture_format(format: naga::StorageFormat) -> wgpu::TextureFormat {
+    use naga::StorageFormat as S;
+    use wgpu::TextureFormat as T;
+    match format {
+        S::R8Unorm => T::R8Unorm,
+        S::R8Snorm => T::R8Snorm,
+        S::R8Uint => T::R8Uint,
+        S::R8Sint => T::R8Sint,
+        S::R16Uint => T::R16Uint,
+        S::R16Sint => T::R16Sint,
+        S::R16Float => T::R16Float,
+        S::Rg8Unorm => T::Rg8Unorm,
+        S::Rg8Snorm => T::Rg8Snorm,
+        S::Rg8Uint => T::Rg8Uint,
+        S::Rg8Sint => T::Rg8Sint,
+        S::R32Uint => T::R32Uint,
+        S::R32Sint => T::R32Sint,
+        S::R32Float => T::R32Float,
+        S::Rg16Uint => T::Rg16Uint,
+        S::Rg16Sint => T::Rg16Sint,
+        S::Rg16Float => T::Rg16Float,
+        S::Rgba8Unorm => T::Rgba8Unorm,
+        S::Rgba8Snorm => T::Rgba8Snorm,
+        S::Rgba8Uint => T::Rgba8Uint,
+        S::Rgba8Sint => T::Rgba8Sint,
+        S::Rgb10a2Unorm => T::Rgb10a2Unorm,
+        S::Rg11b10Float => T::Rg11b10Float,
+        S::Rg32Uint => T::Rg32Uint,
+        S::Rg32Sint => T::Rg32Sint,
+        S::Rg32Float => T::Rg32Float,
+        S::Rgba16Uint => T::Rgba16Uint,
+        S::Rgba16Sint => T::Rgba16Sint,
+        S::Rgba16Float => T::Rgba16Float,
+        S::Rgba32Uint => T::Rgba32Uint,
+        S::Rgba32Sint => T::Rgba32Sint,
+        S::Rgba32Float => T::Rgba32Float,
+    }
+}
+
+/// A member of a uniform block, as reflected by [`ShaderReflection::uniform_block_members`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniformBlockMember {
+    pub name: Option<String>,
+    /// The byte offset of this member from the start of the block.
+    pub offset: u32,
+    /// The size of this member in bytes.
+    pub size: u32,
+    /// The type of this member in [`ShaderReflection::module`].
+    pub ty: naga::Handle<naga::Type>,
 }
 
 #[derive(Default)]
@@ -345,6 +883,23 @@ impl Default for ShaderProcessor {
 }
 
 impl ShaderProcessor {
+    /// Processes `shader` with `shader_defs`, like [`ShaderProcessor::process`], and reflects the
+    /// result.
+    ///
+    /// Shader defs can add or remove bindings and vertex attributes, so each set of defs a shader
+    /// is specialized with has its own reflection, just like it gets its own shader module in the
+    /// [`RenderPipelineCache`](super::RenderPipelineCache).
+    pub fn reflect(
+        &self,
+        shader: &Shader,
+        shader_defs: &[String],
+        shaders: &HashMap<Handle<Shader>, Shader>,
+        import_handles: &HashMap<ShaderImport, Handle<Shader>>,
+    ) -> Result<ShaderReflection, ShaderReflectError> {
+        self.process(shader, shader_defs, shaders, import_handles)?
+            .reflect()
+    }
+
     pub fn process(
         &self,
         shader: &Shader,
@@ -480,8 +1035,12 @@ mod tests {
     use bevy_reflect::TypeUuid;
     use bevy_utils::HashMap;
     use naga::ShaderStage;
+    use wgpu::ShaderStages;
 
-    use crate::render_resource::{ProcessShaderError, Shader, ShaderImport, ShaderProcessor};
+    use crate::render_resource::{
+        CombinedGlslError, ProcessShaderError, ProcessedShader, Shader, ShaderImport,
+        ShaderProcessor,
+    };
     #[rustfmt::skip]
 const WGSL: &str = r"
 struct View {
@@ -613,6 +1172,326 @@ fn vertex(
 }
 ";
 
+    #[test]
+    fn reflect_uniform_block_member_offsets() {
+        const WGSL: &str = r"
+struct Material {
+    color: vec3<f32>;
+    roughness: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: Material;
+
+[[stage(fragment)]]
+fn fragment() -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(material.color, material.roughness);
+}
+";
+        let reflection = ProcessedShader::Wgsl(WGSL.into()).reflect().unwrap();
+        let members = reflection.uniform_block_members(1, 0).unwrap();
+        let layout = members
+            .iter()
+            .map(|member| (member.name.as_deref().unwrap(), member.offset, member.size))
+            .collect::<Vec<_>>();
+        assert_eq!(layout, vec![("color", 0, 12), ("roughness", 12, 4)]);
+        assert_eq!(
+            reflection.module.types[members[0].ty].inner,
+            naga::TypeInner::Vector {
+                size: naga::VectorSize::Tri,
+                kind: naga::ScalarKind::Float,
+                width: 4,
+            }
+        );
+        assert!(reflection.uniform_block_members(0, 0).is_none());
+    }
+
+    #[test]
+    fn reflect_required_vertex_attributes() {
+        const WGSL: &str = r"
+struct Vertex {
+    [[location(1)]] normal: vec3<f32>;
+    [[location(0)]] position: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(
+    vertex: Vertex,
+    [[location(2)]] uv: vec2<f32>,
+    [[builtin(instance_index)]] instance_index: u32,
+) -> [[builtin(position)]] vec4<f32> {
+    return vec4<f32>(vertex.position + vertex.normal, uv.x);
+}
+";
+        let reflection = ProcessedShader::Wgsl(WGSL.into()).reflect().unwrap();
+        assert_eq!(
+            reflection.required_vertex_attributes("vertex").unwrap(),
+            vec![
+                ("position".to_string(), wgpu::VertexFormat::Float32x3),
+                ("normal".to_string(), wgpu::VertexFormat::Float32x3),
+                ("uv".to_string(), wgpu::VertexFormat::Float32x2),
+            ]
+        );
+        assert!(reflection.required_vertex_attributes("fragment").is_none());
+    }
+
+    #[test]
+    fn binding_visibility_follows_entry_point_usage() {
+        const WGSL: &str = r"
+struct View {
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+[[group(1), binding(0)]]
+var base_color_texture: texture_2d<f32>;
+[[group(1), binding(1)]]
+var base_color_sampler: sampler;
+[[group(1), binding(2)]]
+var unused_sampler: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex([[location(0)]] position: vec3<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = view.view_proj * vec4<f32>(position, 1.0);
+    out.uv = position.xy;
+    return out;
+}
+
+fn base_color(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(base_color_texture, base_color_sampler, uv);
+}
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return base_color(in.uv) * view.view_proj[0];
+}
+";
+        let reflection = ProcessedShader::Wgsl(WGSL.into()).reflect().unwrap();
+        assert_eq!(
+            reflection.binding_visibility(0, 0),
+            Some(ShaderStages::VERTEX | ShaderStages::FRAGMENT)
+        );
+        assert_eq!(
+            reflection.binding_visibility(1, 1),
+            Some(ShaderStages::FRAGMENT)
+        );
+        assert_eq!(
+            reflection.binding_visibility(1, 0),
+            Some(ShaderStages::FRAGMENT)
+        );
+        assert_eq!(
+            reflection.binding_visibility(1, 2),
+            Some(ShaderStages::NONE)
+        );
+        assert_eq!(reflection.binding_visibility(2, 0), None);
+    }
+
+    #[test]
+    fn reflected_layout_entries_are_only_visible_where_used() {
+        const WGSL: &str = r"
+struct Offset {
+    value: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> offset: Offset;
+[[group(0), binding(1)]]
+var color_sampler: sampler;
+[[group(0), binding(2)]]
+var color_texture: texture_2d<f32>;
+
+[[stage(vertex)]]
+fn vertex([[location(0)]] position: vec4<f32>) -> [[builtin(position)]] vec4<f32> {
+    return position + offset.value;
+}
+
+[[stage(fragment)]]
+fn fragment([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    return textureSample(color_texture, color_sampler, position.xy);
+}
+";
+        let reflection = ProcessedShader::Wgsl(WGSL.into()).reflect().unwrap();
+        let entries = reflection.bind_group_layout_entries(0);
+        assert_eq!(
+            entries,
+            vec![
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ]
+        );
+        assert!(reflection.bind_group_layout_entries(1).is_empty());
+    }
+
+    #[test]
+    fn texture_binding_arrays_are_reflected_with_their_length() {
+        use super::{binding_array_length, binding_type};
+        use crate::render_resource::{required_layout_features, texture_array_layout_entry};
+        use std::num::NonZeroU32;
+
+        const GLSL: &str = r"
+#version 450
+layout(location = 0) out vec4 o_Target;
+layout(set = 0, binding = 0) uniform texture2D textures[2];
+layout(set = 0, binding = 1) uniform sampler texture_sampler;
+void main() {
+    o_Target = texture(sampler2D(textures[1], texture_sampler), vec2(0.0));
+}
+";
+        // naga doesn't validate binding arrays yet, so this reflects the parsed module directly
+        let module = naga::front::glsl::Parser::default()
+            .parse(
+                &naga::front::glsl::Options::from(ShaderStage::Fragment),
+                GLSL,
+            )
+            .unwrap();
+        let entries = module
+            .global_variables
+            .iter()
+            .filter_map(|(_, variable)| {
+                Some(wgpu::BindGroupLayoutEntry {
+                    binding: variable.binding.as_ref()?.binding,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: binding_type(&module, variable)?,
+                    count: binding_array_length(&module, variable),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            entries[0],
+            texture_array_layout_entry(
+                0,
+                ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::TextureViewDimension::D2,
+                NonZeroU32::new(2).unwrap(),
+            )
+        );
+        assert_eq!(entries[1].count, None);
+        assert_eq!(
+            required_layout_features(&entries),
+            wgpu::Features::TEXTURE_BINDING_ARRAY
+        );
+    }
+
+    #[test]
+    fn reflected_layout_debug_string() {
+        const WGSL: &str = r"
+struct View {
+    view_proj: mat4x4<f32>;
+};
+
+struct Lights {
+    count: u32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+[[group(0), binding(1)]]
+var<storage> lights: Lights;
+[[group(1), binding(1)]]
+var base_color_sampler: sampler;
+[[group(1), binding(0)]]
+var base_color_texture: texture_2d<f32>;
+
+[[stage(vertex)]]
+fn vertex(
+    [[location(1)]] uv: vec2<f32>,
+    [[location(0)]] position: vec3<f32>,
+) -> [[builtin(position)]] vec4<f32> {
+    let color = textureSampleLevel(base_color_texture, base_color_sampler, uv, 0.0);
+    return view.view_proj * vec4<f32>(position, f32(lights.count)) * color;
+}
+";
+        let reflection = ProcessedShader::Wgsl(WGSL.into()).reflect().unwrap();
+        assert_eq!(
+            reflection.to_debug_string(),
+            r#"group 0:
+  binding 0 "view": uniform buffer
+  binding 1 "lights": read-only storage buffer
+group 1:
+  binding 0 "base_color_texture": D2 texture
+  binding 1 "base_color_sampler": sampler
+vertex entry point "vertex":
+  location 0 "position": Float32x3
+  location 1 "uv": Float32x2
+"#
+        );
+    }
+
+    #[test]
+    fn reflect_with_shader_defs() {
+        const WGSL: &str = r"
+[[group(0), binding(0)]]
+var base_color_sampler: sampler;
+# ifdef TEXTURE
+[[group(0), binding(1)]]
+var base_color_texture: texture_2d<f32>;
+# endif
+
+[[stage(fragment)]]
+fn fragment() -> [[location(0)]] vec4<f32> {
+# ifdef TEXTURE
+    return textureSample(base_color_texture, base_color_sampler, vec2<f32>(0.5, 0.5));
+# else
+    return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+# endif
+}
+";
+        let processor = ShaderProcessor::default();
+        let reflect = |shader_defs: &[String]| {
+            processor
+                .reflect(
+                    &Shader::from_wgsl(WGSL),
+                    shader_defs,
+                    &HashMap::default(),
+                    &HashMap::default(),
+                )
+                .unwrap()
+                .to_debug_string()
+        };
+
+        assert_eq!(
+            reflect(&[]),
+            "group 0:\n  binding 0 \"base_color_sampler\": sampler\n"
+        );
+        assert_eq!(
+            reflect(&["TEXTURE".to_string()]),
+            "group 0:\n  binding 0 \"base_color_sampler\": sampler\n  binding 1 \"base_color_texture\": D2 texture\n"
+        );
+    }
+
     #[test]
     fn process_shader_def_defined() {
         #[rustfmt::skip]
@@ -1205,4 +2084,55 @@ fn in_main() { }
             .unwrap();
         assert_eq!(result.get_wgsl_source().unwrap(), EXPECTED);
     }
+
+    #[test]
+    fn combined_glsl_splits_into_stages() {
+        const COMBINED: &str = r"#version 450
+layout(set = 0, binding = 0) uniform View {
+    mat4 ViewProj;
+};
+#pragma stage vertex
+layout(location = 0) in vec3 Vertex_Position;
+void main() {
+    gl_Position = ViewProj * vec4(Vertex_Position, 1.0);
+}
+#pragma stage fragment
+layout(location = 0) out vec4 o_Target;
+void main() {
+    o_Target = vec4(1.0);
+}
+";
+        let shaders = Shader::from_combined_glsl(COMBINED).unwrap();
+        let processor = ShaderProcessor::default();
+        for (shader, stage) in [
+            (&shaders.vertex, ShaderStage::Vertex),
+            (&shaders.fragment, ShaderStage::Fragment),
+        ] {
+            let processed = processor
+                .process(shader, &[], &HashMap::default(), &HashMap::default())
+                .unwrap();
+            let source = processed.get_glsl_source().unwrap();
+            assert_eq!(source.lines().count(), COMBINED.lines().count());
+            assert!(source.contains("ViewProj"));
+
+            let spirv = processed.reflect().unwrap().get_spirv().unwrap();
+            let bytes = spirv
+                .iter()
+                .flat_map(|word| word.to_le_bytes())
+                .collect::<Vec<_>>();
+            let module = naga::front::spv::parse_u8_slice(&bytes, &Default::default()).unwrap();
+            assert_eq!(module.entry_points.len(), 1);
+            assert_eq!(module.entry_points[0].stage, stage);
+        }
+
+        assert_eq!(
+            Shader::from_combined_glsl(&COMBINED.replace("#pragma stage fragment", ""))
+                .unwrap_err(),
+            CombinedGlslError::MissingStage(ShaderStage::Fragment)
+        );
+        assert_eq!(
+            Shader::from_combined_glsl(&COMBINED.replace("fragment", "vertex")).unwrap_err(),
+            CombinedGlslError::DuplicateStage(ShaderStage::Vertex)
+        );
+    }
 }
@@ -0,0 +1,410 @@
+use crate::{
+    render_resource::{Buffer, Texture},
+    renderer::{RenderContext, RenderDevice, RenderQueue, RenderResourceError},
+};
+use std::{num::NonZeroU32, ops::Range};
+use wgpu::{BufferAddress, BufferUsages, CommandEncoder};
+
+/// A single GPU buffer that many uploads are suballocated from.
+///
+/// Instead of creating a temporary buffer per upload, data is [`push`](StagingBuffer::push)ed into
+/// a CPU-side scratch buffer, written to the GPU with [`write_buffer`](StagingBuffer::write_buffer)
+/// and then copied to its destinations with [`RenderContext::copy_buffer_to_buffer`] or
+/// [`RenderContext::copy_buffer_to_texture`]. Each upload gets its own range of the buffer until
+/// the next [`clear`](StagingBuffer::clear), so any number of them can be staged before the
+/// copies are submitted. The GPU buffer is reused across frames and only recreated when the
+/// pushed data outgrows it.
+///
+/// The render world holds a `StagingBuffer` resource, which
+/// [`create_buffer_with_data`](StagingBuffer::create_buffer_with_data) and
+/// [`write_texture`](StagingBuffer::write_texture) record their copies for. These are submitted
+/// before the render graph runs.
+#[derive(Default)]
+pub struct StagingBuffer {
+    data: Vec<u8>,
+    /// The number of bytes of `data` already written to `buffer`
+    written: usize,
+    buffer: Option<Buffer>,
+    capacity: usize,
+    /// Buffers replaced by a larger one, which copies may still read from until the next clear
+    retired: Vec<Buffer>,
+    encoder: Option<CommandEncoder>,
+}
+
+/// A write of pushed data to the GPU buffer, as planned by [`StagingBuffer::prepare_write`].
+#[derive(Debug, PartialEq, Eq)]
+struct PendingWrite {
+    /// Whether the GPU buffer has to be recreated with the new capacity first
+    recreate: bool,
+    /// The range of the pushed data to write, at the same offset of the GPU buffer
+    range: Range<usize>,
+}
+
+impl StagingBuffer {
+    #[inline]
+    pub fn buffer(&self) -> Option<&Buffer> {
+        self.buffer.as_ref()
+    }
+
+    /// The size in bytes of the GPU buffer.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of bytes pushed since the last [`clear`](StagingBuffer::clear).
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Appends `bytes` at the next offset that is a multiple of `alignment` and returns that offset.
+    ///
+    /// Copies require offsets aligned to [`wgpu::COPY_BUFFER_ALIGNMENT`], and copies into textures
+    /// additionally to the size of a texel block. `alignment` must be a power of two.
+    pub fn push(&mut self, bytes: &[u8], alignment: usize) -> BufferAddress {
+        debug_assert!(alignment.is_power_of_two());
+        let alignment = alignment.max(wgpu::COPY_BUFFER_ALIGNMENT as usize);
+        let offset = (self.data.len() + alignment - 1) & !(alignment - 1);
+        self.data.resize(offset, 0);
+        self.data.extend_from_slice(bytes);
+        offset as BufferAddress
+    }
+
+    /// Appends the `rows` of an image, each `bytes_per_row` long, padding each row to
+    /// [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`] as required by copies into textures. Returns the
+    /// layout of the pushed data for [`StagingBuffer::copy_to_texture`].
+    ///
+    /// `rows_per_image` is the height of each layer of an image with several array layers.
+    pub fn push_image(
+        &mut self,
+        data: &[u8],
+        bytes_per_row: usize,
+        rows_per_image: Option<NonZeroU32>,
+    ) -> wgpu::ImageDataLayout {
+        let padded_bytes_per_row = RenderDevice::align_copy_bytes_per_row(bytes_per_row);
+        let offset = self.push(&[], wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize);
+        for row in data.chunks(bytes_per_row) {
+            self.data.extend_from_slice(row);
+            self.data
+                .resize(self.data.len() + padded_bytes_per_row - row.len(), 0);
+        }
+        wgpu::ImageDataLayout {
+            offset,
+            bytes_per_row: NonZeroU32::new(padded_bytes_per_row as u32),
+            rows_per_image,
+        }
+    }
+
+    /// Makes sure the GPU buffer can hold at least `capacity` bytes, returning `true` if it was
+    /// (re)created.
+    ///
+    /// The buffer at least doubles in size when it grows, so that a steady stream of uploads
+    /// settles on a single buffer.
+    pub fn reserve(&mut self, capacity: usize, device: &RenderDevice) -> bool {
+        if capacity <= self.capacity {
+            return false;
+        }
+        self.capacity = grown_capacity(self.capacity, capacity);
+        // The new buffer holds none of the data written so far
+        self.written = 0;
+        self.create_buffer(device);
+        true
+    }
+
+    fn create_buffer(&mut self, device: &RenderDevice) {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("staging_buffer"),
+            size: self.capacity as BufferAddress,
+            usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        self.retired.extend(self.buffer.replace(buffer));
+    }
+
+    /// Pads the pushed data to [`wgpu::COPY_BUFFER_ALIGNMENT`] and returns the part of it that
+    /// isn't in the GPU buffer yet, growing the buffer if needed.
+    fn prepare_write(&mut self) -> Option<PendingWrite> {
+        // Buffer writes need to be a multiple of `COPY_BUFFER_ALIGNMENT` in size
+        let aligned_len = (self.data.len() + wgpu::COPY_BUFFER_ALIGNMENT as usize - 1)
+            & !(wgpu::COPY_BUFFER_ALIGNMENT as usize - 1);
+        self.data.resize(aligned_len, 0);
+
+        let recreate = self.data.len() > self.capacity;
+        if recreate {
+            self.capacity = grown_capacity(self.capacity, self.data.len());
+            // The new buffer holds none of the data written so far
+            self.written = 0;
+        }
+        if self.written == self.data.len() {
+            return None;
+        }
+        let range = self.written..self.data.len();
+        self.written = self.data.len();
+        Some(PendingWrite { recreate, range })
+    }
+
+    /// Uploads the data pushed since the last write to the GPU buffer, growing it first if
+    /// necessary.
+    ///
+    /// Data is written at the offset it was pushed at, so it doesn't overwrite earlier uploads
+    /// whose copies haven't been submitted yet.
+    pub fn write_buffer(&mut self, device: &RenderDevice, queue: &RenderQueue) {
+        if let Some(write) = self.prepare_write() {
+            if write.recreate {
+                self.create_buffer(device);
+            }
+            if let Some(buffer) = &self.buffer {
+                queue.write_buffer(
+                    buffer,
+                    write.range.start as BufferAddress,
+                    &self.data[write.range],
+                );
+            }
+        }
+    }
+
+    fn encoder(&mut self, device: &RenderDevice) -> &mut CommandEncoder {
+        self.encoder.get_or_insert_with(|| {
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("staging_buffer_encoder"),
+            })
+        })
+    }
+
+    /// Creates a [`Buffer`] initialized with the data of `desc` like
+    /// [`RenderDevice::create_buffer_with_data`], but uploads the data through this staging
+    /// buffer rather than a temporary one.
+    ///
+    /// The buffer additionally gets [`BufferUsages::COPY_DST`]. Its contents are copied into it
+    /// by the next [`submit`](StagingBuffer::submit).
+    pub fn create_buffer_with_data(
+        &mut self,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        desc: &wgpu::util::BufferInitDescriptor,
+    ) -> Buffer {
+        // Copies need to be a multiple of `COPY_BUFFER_ALIGNMENT` in size
+        let size = (desc.contents.len() as BufferAddress + wgpu::COPY_BUFFER_ALIGNMENT - 1)
+            & !(wgpu::COPY_BUFFER_ALIGNMENT - 1);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: desc.label,
+            size,
+            usage: desc.usage | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        if size == 0 {
+            return buffer;
+        }
+        let offset = self.push(desc.contents, wgpu::COPY_BUFFER_ALIGNMENT as usize);
+        self.write_buffer(device, queue);
+        if let Some(staging_buffer) = self.buffer.clone() {
+            self.encoder(device)
+                .copy_buffer_to_buffer(&staging_buffer, offset, &buffer, 0, size);
+        }
+        buffer
+    }
+
+    /// Writes `data` laid out as described by `data_layout` into `texture` like
+    /// [`wgpu::Queue::write_texture`], but uploads it through this staging buffer rather than a
+    /// temporary one.
+    ///
+    /// The texture needs [`wgpu::TextureUsages::COPY_DST`]. The data is copied into it by the
+    /// next [`submit`](StagingBuffer::submit).
+    pub fn write_texture(
+        &mut self,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        texture: wgpu::ImageCopyTexture,
+        data: &[u8],
+        data_layout: wgpu::ImageDataLayout,
+        size: wgpu::Extent3d,
+    ) {
+        let data = &data[data_layout.offset as usize..];
+        let bytes_per_row = data_layout
+            .bytes_per_row
+            .map_or(data.len(), |bytes_per_row| bytes_per_row.get() as usize);
+        if bytes_per_row == 0 {
+            return;
+        }
+        let layout = self.push_image(data, bytes_per_row, data_layout.rows_per_image);
+        self.write_buffer(device, queue);
+        if let Some(staging_buffer) = self.buffer.clone() {
+            self.encoder(device).copy_buffer_to_texture(
+                wgpu::ImageCopyBuffer {
+                    buffer: &staging_buffer,
+                    layout,
+                },
+                texture,
+                size,
+            );
+        }
+    }
+
+    /// Submits the copies recorded by [`StagingBuffer::create_buffer_with_data`] and
+    /// [`StagingBuffer::write_texture`], then [`clear`](StagingBuffer::clear)s the buffer for the
+    /// next uploads.
+    pub fn submit(&mut self, queue: &RenderQueue) {
+        if let Some(encoder) = self.encoder.take() {
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+        self.clear();
+    }
+
+    /// Records a copy of `size` bytes at `offset` of this buffer into `destination`.
+    pub fn copy_to_buffer(
+        &self,
+        render_context: &mut RenderContext,
+        offset: BufferAddress,
+        destination: &Buffer,
+        destination_offset: BufferAddress,
+        size: BufferAddress,
+    ) -> Result<(), RenderResourceError> {
+        if let Some(buffer) = &self.buffer {
+            render_context.copy_buffer_to_buffer(
+                buffer,
+                offset,
+                destination,
+                destination_offset,
+                size,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Records a copy of the image pushed with `layout` by [`StagingBuffer::push_image`] into
+    /// `destination`.
+    pub fn copy_to_texture(
+        &self,
+        render_context: &mut RenderContext,
+        layout: wgpu::ImageDataLayout,
+        destination: wgpu::ImageCopyTextureBase<&Texture>,
+        copy_size: wgpu::Extent3d,
+    ) -> Result<(), RenderResourceError> {
+        if let Some(buffer) = &self.buffer {
+            render_context.copy_buffer_to_texture(buffer, layout, destination, copy_size)?;
+        }
+        Ok(())
+    }
+
+    /// Discards all pushed data. The GPU buffer is kept for reuse.
+    ///
+    /// This must only be called once the copies of the pushed data have been submitted, as the
+    /// next uploads reuse its range of the buffer.
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.written = 0;
+        self.retired.clear();
+    }
+}
+
+fn grown_capacity(current: usize, required: usize) -> usize {
+    required.max(current * 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pushes_are_aligned_and_packed() {
+        let mut staging_buffer = StagingBuffer::default();
+        assert_eq!(staging_buffer.push(&[1; 3], 4), 0);
+        assert_eq!(staging_buffer.push(&[2; 8], 4), 4);
+        assert_eq!(staging_buffer.push(&[3; 4], 16), 16);
+        assert_eq!(staging_buffer.len(), 20);
+
+        staging_buffer.clear();
+        assert!(staging_buffer.is_empty());
+        assert_eq!(staging_buffer.push(&[4; 4], 4), 0);
+    }
+
+    #[test]
+    fn image_rows_are_padded_to_the_copy_alignment() {
+        let (width, height) = (100, 100);
+        let bytes_per_row = width * 4;
+        let image = (0..height)
+            .flat_map(|row| vec![row as u8; bytes_per_row])
+            .collect::<Vec<_>>();
+
+        let mut staging_buffer = StagingBuffer::default();
+        staging_buffer.push(&[0; 4], 4);
+        let layout = staging_buffer.push_image(&image, bytes_per_row, None);
+        assert_eq!(layout.offset, 256);
+        let padded_bytes_per_row = layout.bytes_per_row.unwrap().get() as usize;
+        assert_eq!(padded_bytes_per_row, 512);
+        assert_eq!(staging_buffer.len(), 256 + height * 512);
+
+        for (row, padded_row) in staging_buffer.data[256..]
+            .chunks(padded_bytes_per_row)
+            .enumerate()
+        {
+            assert_eq!(
+                &padded_row[..bytes_per_row],
+                &image[row * bytes_per_row..][..bytes_per_row]
+            );
+            assert!(padded_row[bytes_per_row..].iter().all(|&byte| byte == 0));
+        }
+    }
+
+    #[test]
+    fn uploads_staged_before_a_submit_get_their_own_range() {
+        let mut staging_buffer = StagingBuffer::default();
+
+        // The first frame creates the buffer, and grows it for its second upload. The larger
+        // buffer gets all the data, as earlier copies may not have been recorded yet.
+        assert_eq!(staging_buffer.push(&[1; 8], 4), 0);
+        assert_eq!(
+            staging_buffer.prepare_write(),
+            Some(PendingWrite {
+                recreate: true,
+                range: 0..8
+            })
+        );
+        assert_eq!(staging_buffer.push(&[2; 8], 4), 8);
+        assert_eq!(
+            staging_buffer.prepare_write(),
+            Some(PendingWrite {
+                recreate: true,
+                range: 0..16
+            })
+        );
+        assert_eq!(staging_buffer.prepare_write(), None);
+        staging_buffer.clear();
+
+        // Two uploads of the next frame are written at different offsets of the same buffer.
+        assert_eq!(staging_buffer.push(&[3; 3], 4), 0);
+        assert_eq!(
+            staging_buffer.prepare_write(),
+            Some(PendingWrite {
+                recreate: false,
+                range: 0..4
+            })
+        );
+        assert_eq!(staging_buffer.push(&[4; 8], 4), 4);
+        assert_eq!(
+            staging_buffer.prepare_write(),
+            Some(PendingWrite {
+                recreate: false,
+                range: 4..12
+            })
+        );
+        assert_eq!(
+            &staging_buffer.data[..],
+            &[3, 3, 3, 0, 4, 4, 4, 4, 4, 4, 4, 4]
+        );
+        assert_eq!(staging_buffer.capacity(), 16);
+    }
+
+    #[test]
+    fn buffer_grows_geometrically() {
+        assert_eq!(grown_capacity(0, 100), 100);
+        assert_eq!(grown_capacity(100, 101), 200);
+        assert_eq!(grown_capacity(100, 1000), 1000);
+    }
+}
@@ -1,5 +1,6 @@
+use crate::renderer::GpuAllocation;
 use bevy_utils::Uuid;
-use std::{ops::Deref, sync::Arc};
+use std::{num::NonZeroU32, ops::Deref, sync::Arc};
 
 /// A [`Texture`] identifier.
 #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
@@ -13,6 +14,8 @@ pub struct TextureId(Uuid);
 pub struct Texture {
     id: TextureId,
     value: Arc<wgpu::Texture>,
+    usage: Option<wgpu::TextureUsages>,
+    allocation: Option<Arc<GpuAllocation>>,
 }
 
 impl Texture {
@@ -22,10 +25,46 @@ impl Texture {
         self.id
     }
 
+    /// Returns the usages this texture was created with, or `None` if the texture was not created
+    /// by a [`RenderDevice`](crate::renderer::RenderDevice) and its usages are unknown.
+    #[inline]
+    pub fn usage(&self) -> Option<wgpu::TextureUsages> {
+        self.usage
+    }
+
+    #[inline]
+    pub(crate) fn with_usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_allocation(mut self, allocation: GpuAllocation) -> Self {
+        self.allocation = Some(Arc::new(allocation));
+        self
+    }
+
     /// Creates a view of this texture.
     pub fn create_view(&self, desc: &wgpu::TextureViewDescriptor) -> TextureView {
         TextureView::from(self.value.create_view(desc))
     }
+
+    /// Creates a view of the single `array_layer` and `mip_level` of this texture, to be used as a
+    /// color or depth stencil attachment of a render pass.
+    ///
+    /// See [`layer_attachment_view_descriptor`].
+    pub fn create_layer_attachment_view(
+        &self,
+        label: Option<&str>,
+        array_layer: u32,
+        mip_level: u32,
+    ) -> TextureView {
+        self.create_view(&layer_attachment_view_descriptor(
+            label,
+            array_layer,
+            mip_level,
+        ))
+    }
 }
 
 impl From<wgpu::Texture> for Texture {
@@ -33,6 +72,8 @@ impl From<wgpu::Texture> for Texture {
         Texture {
             id: TextureId(Uuid::new_v4()),
             value: Arc::new(value),
+            usage: None,
+            allocation: None,
         }
     }
 }
@@ -46,6 +87,28 @@ impl Deref for Texture {
     }
 }
 
+/// Describes a 2D view of the single `array_layer` and `mip_level` of a texture.
+///
+/// A render pass attachment must be a view of a single layer and mip level, so rendering into one
+/// layer of a texture array or one face of a cubemap, like the cascades and faces of shadow maps,
+/// needs such a view. The pass then only writes to that layer.
+pub fn layer_attachment_view_descriptor(
+    label: Option<&str>,
+    array_layer: u32,
+    mip_level: u32,
+) -> wgpu::TextureViewDescriptor<'_> {
+    wgpu::TextureViewDescriptor {
+        label,
+        format: None,
+        dimension: Some(wgpu::TextureViewDimension::D2),
+        aspect: wgpu::TextureAspect::All,
+        base_mip_level: mip_level,
+        mip_level_count: NonZeroU32::new(1),
+        base_array_layer: array_layer,
+        array_layer_count: NonZeroU32::new(1),
+    }
+}
+
 /// A [`TextureView`] identifier.
 #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
 pub struct TextureViewId(Uuid);
@@ -167,3 +230,20 @@ impl Deref for Sampler {
         &self.value
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layer_attachment_view_covers_only_its_layer() {
+        let desc = layer_attachment_view_descriptor(Some("cascade_2"), 2, 1);
+        assert_eq!(desc.dimension, Some(wgpu::TextureViewDimension::D2));
+
+        let layers = desc.base_array_layer
+            ..desc.base_array_layer + desc.array_layer_count.map_or(u32::MAX, NonZeroU32::get);
+        assert_eq!(layers, 2..3);
+        let mips = desc.base_mip_level..desc.base_mip_level + desc.mip_level_count.unwrap().get();
+        assert_eq!(mips, 1..2);
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Counts the bytes of the buffers and textures created by a
+/// [`RenderDevice`](super::RenderDevice) that are still alive.
+///
+/// The count is an estimate: it ignores the padding and alignment the driver adds to resources.
+#[derive(Debug, Default)]
+pub struct GpuMemoryTracker {
+    bytes: AtomicUsize,
+}
+
+impl GpuMemoryTracker {
+    /// The estimated number of bytes used by the live resources
+    pub fn estimated_gpu_memory(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Records the allocation of `size` bytes, which is released when the returned
+    /// [`GpuAllocation`] is dropped.
+    pub fn allocate(self: &Arc<Self>, size: u64) -> GpuAllocation {
+        let size = size as usize;
+        self.bytes.fetch_add(size, Ordering::Relaxed);
+        GpuAllocation {
+            tracker: self.clone(),
+            size,
+        }
+    }
+}
+
+/// The memory of a resource counted by a [`GpuMemoryTracker`], released when this is dropped.
+#[derive(Debug)]
+pub struct GpuAllocation {
+    tracker: Arc<GpuMemoryTracker>,
+    size: usize,
+}
+
+impl Drop for GpuAllocation {
+    fn drop(&mut self) {
+        self.tracker.bytes.fetch_sub(self.size, Ordering::Relaxed);
+    }
+}
+
+/// Estimates the number of bytes of a texture described by `desc`, summed over all of its mip
+/// levels, array layers and samples.
+pub fn texture_size_bytes(desc: &wgpu::TextureDescriptor) -> u64 {
+    let info = desc.format.describe();
+    let (block_width, block_height) = info.block_dimensions;
+    let blocks = |texels: u32, block: u8| -> u64 {
+        let block = block as u32;
+        match texels % block {
+            0 => (texels / block) as u64,
+            _ => (texels / block + 1) as u64,
+        }
+    };
+    (0..desc.mip_level_count)
+        .filter_map(|level| desc.mip_level_size(level))
+        .map(|size| {
+            blocks(size.width, block_width)
+                * blocks(size.height, block_height)
+                * size.depth_or_array_layers as u64
+                * info.block_size as u64
+        })
+        .sum::<u64>()
+        * desc.sample_count as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocations_are_counted_until_dropped() {
+        let tracker = Arc::new(GpuMemoryTracker::default());
+        let buffer = tracker.allocate(256);
+        assert_eq!(tracker.estimated_gpu_memory(), 256);
+
+        // 64x64 RGBA8 with a full mip chain: 4 * (4096 + 1024 + 256 + 64 + 16 + 4 + 1) bytes.
+        let texture_size = texture_size_bytes(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 64,
+                height: 64,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 7,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        assert_eq!(texture_size, 21844);
+        let texture = tracker.allocate(texture_size);
+        assert_eq!(tracker.estimated_gpu_memory(), 256 + 21844);
+
+        drop(buffer);
+        assert_eq!(tracker.estimated_gpu_memory(), 21844);
+        drop(texture);
+        assert_eq!(tracker.estimated_gpu_memory(), 0);
+    }
+
+    #[test]
+    fn texture_size_counts_layers_samples_and_blocks() {
+        let desc = |format, depth_or_array_layers, sample_count| wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 10,
+                height: 10,
+                depth_or_array_layers,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+        };
+        assert_eq!(
+            texture_size_bytes(&desc(wgpu::TextureFormat::Depth32Float, 6, 1)),
+            10 * 10 * 4 * 6
+        );
+        assert_eq!(
+            texture_size_bytes(&desc(wgpu::TextureFormat::Rgba16Float, 1, 4)),
+            10 * 10 * 8 * 4
+        );
+        // 10x10 texels take 3x3 blocks of 4x4 texels, of 8 bytes each.
+        assert_eq!(
+            texture_size_bytes(&desc(wgpu::TextureFormat::Bc1RgbaUnorm, 1, 1)),
+            3 * 3 * 8
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{
+    render_resource::Buffer,
+    renderer::{RenderDevice, RenderQueue},
+};
+use bevy_ecs::prelude::*;
+use futures_lite::future;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use wgpu::{BufferAsyncError, BufferDescriptor, BufferUsages, MapMode, QuerySet};
+
+/// The time the GPU spent on one labeled span of a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuTime {
+    pub label: String,
+    pub duration: Duration,
+}
+
+/// Collects the results of asynchronous readbacks without ever blocking on them.
+///
+/// Readbacks are pushed in submission order and checked with [`DeferredReadback::poll`], usually
+/// once per frame. A readback that hasn't completed yet holds back the ones pushed after it, so
+/// [`DeferredReadback::latest`] never goes back to an older result.
+pub struct DeferredReadback<T, F> {
+    pending: VecDeque<F>,
+    latest: Option<T>,
+}
+
+impl<T, F> Default for DeferredReadback<T, F> {
+    fn default() -> Self {
+        Self {
+            pending: VecDeque::new(),
+            latest: None,
+        }
+    }
+}
+
+impl<T, F: Future<Output = Option<T>> + Unpin> DeferredReadback<T, F> {
+    /// Queues a readback. It resolves to `None` if it failed, in which case it is dropped.
+    pub fn push(&mut self, readback: F) {
+        self.pending.push_back(readback);
+    }
+
+    /// Takes the results of all readbacks that completed, in order, without waiting for the
+    /// others.
+    pub fn poll(&mut self) {
+        while let Some(readback) = self.pending.front_mut() {
+            match future::block_on(future::poll_once(readback)) {
+                Some(result) => {
+                    if let Some(result) = result {
+                        self.latest = Some(result);
+                    }
+                    self.pending.pop_front();
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// The result of the most recently completed readback
+    pub fn latest(&self) -> Option<&T> {
+        self.latest.as_ref()
+    }
+
+    /// The number of readbacks that haven't completed yet
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Converts resolved timestamp queries, a begin and an end timestamp per label, to [`GpuTime`]s.
+///
+/// `period` is the number of nanoseconds per timestamp tick, as returned by
+/// [`wgpu::Queue::get_timestamp_period`].
+pub fn decode_timestamps(timestamps: &[u64], labels: &[String], period: f32) -> Vec<GpuTime> {
+    labels
+        .iter()
+        .zip(timestamps.chunks_exact(2))
+        .map(|(label, span)| GpuTime {
+            label: label.clone(),
+            duration: Duration::from_nanos(
+                (span[1].saturating_sub(span[0]) as f64 * period as f64) as u64,
+            ),
+        })
+        .collect()
+}
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), BufferAsyncError>> + Send + Sync>>;
+
+/// The readback of the timestamps of one frame.
+pub struct TimestampReadback {
+    map: MapFuture,
+    buffer: Buffer,
+    labels: Vec<String>,
+    period: f32,
+}
+
+impl Future for TimestampReadback {
+    type Output = Option<Vec<GpuTime>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.map.as_mut().poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(_)) => Poll::Ready(None),
+            Poll::Ready(Ok(())) => {
+                let timestamps = self
+                    .buffer
+                    .slice(..)
+                    .get_mapped_range()
+                    .chunks_exact(8)
+                    .map(|bytes| {
+                        let mut timestamp = [0; 8];
+                        timestamp.copy_from_slice(bytes);
+                        u64::from_le_bytes(timestamp)
+                    })
+                    .collect::<Vec<_>>();
+                self.buffer.unmap();
+                Poll::Ready(Some(decode_timestamps(
+                    &timestamps,
+                    &self.labels,
+                    self.period,
+                )))
+            }
+        }
+    }
+}
+
+/// The GPU times of recent frames, read back without stalling the renderer.
+///
+/// Timestamp queries written during a frame are handed to [`GpuTimings::read_back`] once the
+/// frame's commands have been submitted. Their results are mapped asynchronously and picked up by
+/// [`update_gpu_timings_system`] as soon as the GPU is done, usually a frame later.
+#[derive(Default)]
+pub struct GpuTimings {
+    readback: DeferredReadback<Vec<GpuTime>, TimestampReadback>,
+}
+
+impl GpuTimings {
+    /// The GPU times of the most recent frame whose timestamps have been read back
+    pub fn last_frame_gpu_times(&self) -> Option<&[GpuTime]> {
+        self.readback.latest().map(Vec::as_slice)
+    }
+
+    /// Resolves `query_set`, holding a begin and an end timestamp for each of `labels` in order,
+    /// and starts reading it back.
+    pub fn read_back(
+        &mut self,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        query_set: &QuerySet,
+        labels: Vec<String>,
+    ) {
+        let query_count = labels.len() as u32 * 2;
+        if query_count == 0 {
+            return;
+        }
+        let size = query_count as u64 * std::mem::size_of::<u64>() as u64;
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("gpu_timings_readback_buffer"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = render_device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("gpu_timings_encoder"),
+        });
+        encoder.resolve_query_set(query_set, 0..query_count, &buffer, 0);
+        render_queue.submit(vec![encoder.finish()]);
+
+        let map = Box::pin(buffer.slice(..).map_async(MapMode::Read));
+        self.readback.push(TimestampReadback {
+            map,
+            buffer,
+            labels,
+            period: render_queue.get_timestamp_period(),
+        });
+    }
+}
+
+/// Picks up the timestamp readbacks of [`GpuTimings`] that completed, without waiting for the
+/// GPU.
+pub fn update_gpu_timings_system(
+    render_device: Res<RenderDevice>,
+    mut gpu_timings: ResMut<GpuTimings>,
+) {
+    render_device.poll(wgpu::Maintain::Poll);
+    gpu_timings.readback.poll();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    /// Completes once the fake GPU has finished `frame`.
+    struct FakeReadback {
+        gpu_frame: Rc<Cell<u32>>,
+        frame: u32,
+    }
+
+    impl Future for FakeReadback {
+        type Output = Option<Vec<GpuTime>>;
+
+        fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+            if self.gpu_frame.get() < self.frame {
+                return Poll::Pending;
+            }
+            let timestamps = [100, 100 + 1000 * self.frame as u64];
+            Poll::Ready(Some(decode_timestamps(
+                &timestamps,
+                &["main_pass".to_string()],
+                1.0,
+            )))
+        }
+    }
+
+    #[test]
+    fn previous_frame_times_available_after_two_frames() {
+        let gpu_frame = Rc::new(Cell::new(0));
+        let mut readback = DeferredReadback::default();
+
+        // Frame 1: the GPU hasn't finished the frame when it is polled.
+        readback.push(FakeReadback {
+            gpu_frame: gpu_frame.clone(),
+            frame: 1,
+        });
+        readback.poll();
+        assert!(readback.latest().is_none());
+
+        // Frame 2: the GPU finished frame 1, but not yet frame 2.
+        gpu_frame.set(1);
+        readback.push(FakeReadback {
+            gpu_frame: gpu_frame.clone(),
+            frame: 2,
+        });
+        readback.poll();
+        assert_eq!(
+            readback.latest().unwrap(),
+            &vec![GpuTime {
+                label: "main_pass".to_string(),
+                duration: Duration::from_nanos(1000),
+            }]
+        );
+        assert_eq!(readback.pending_len(), 1);
+    }
+}
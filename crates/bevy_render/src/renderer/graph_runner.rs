@@ -18,6 +18,35 @@ use crate::{
 
 pub(crate) struct RenderGraphRunner;
 
+/// Something GPU commands can be recorded into, grouped under debug labels.
+trait DebugGroups {
+    fn push_debug_group(&mut self, label: &str);
+    fn pop_debug_group(&mut self);
+}
+
+impl DebugGroups for RenderContext {
+    fn push_debug_group(&mut self, label: &str) {
+        self.command_encoder.push_debug_group(label);
+    }
+
+    fn pop_debug_group(&mut self) {
+        self.command_encoder.pop_debug_group();
+    }
+}
+
+/// Runs `run` inside a debug group named `label`, so the commands it records show up grouped
+/// in GPU debuggers and profilers like RenderDoc or Xcode.
+fn run_in_debug_group<E: DebugGroups, T>(
+    encoder: &mut E,
+    label: &str,
+    run: impl FnOnce(&mut E) -> T,
+) -> T {
+    encoder.push_debug_group(label);
+    let result = run(encoder);
+    encoder.pop_debug_group();
+    result
+}
+
 #[derive(Error, Debug)]
 pub enum RenderGraphRunnerError {
     #[error(transparent)]
@@ -175,7 +204,10 @@ impl RenderGraphRunner {
                 #[cfg(feature = "trace")]
                 let guard = span.enter();
 
-                node_state.node.run(&mut context, render_context, world)?;
+                let label = node_state.name.as_deref().unwrap_or(node_state.type_name);
+                run_in_debug_group(render_context, label, |render_context| {
+                    node_state.node.run(&mut context, render_context, world)
+                })?;
 
                 #[cfg(feature = "trace")]
                 drop(guard);
@@ -217,3 +249,35 @@ impl RenderGraphRunner {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{run_in_debug_group, DebugGroups};
+
+    #[derive(Default)]
+    struct RecordingEncoder {
+        commands: Vec<String>,
+    }
+
+    impl DebugGroups for RecordingEncoder {
+        fn push_debug_group(&mut self, label: &str) {
+            self.commands.push(format!("push {}", label));
+        }
+
+        fn pop_debug_group(&mut self) {
+            self.commands.push("pop".to_string());
+        }
+    }
+
+    #[test]
+    fn node_run_is_wrapped_in_debug_group() {
+        let mut encoder = RecordingEncoder::default();
+        let result: Result<(), ()> = run_in_debug_group(&mut encoder, "main_pass", |encoder| {
+            encoder.commands.push("draw".to_string());
+            Err(())
+        });
+
+        assert!(result.is_err());
+        assert_eq!(encoder.commands, vec!["push main_pass", "draw", "pop"]);
+    }
+}
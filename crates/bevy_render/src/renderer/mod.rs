@@ -1,13 +1,18 @@
+mod gpu_memory;
+mod gpu_timings;
 mod graph_runner;
 mod render_device;
 
-use bevy_utils::tracing::{info, info_span};
+use bevy_utils::tracing::{info, info_span, warn};
+pub use gpu_memory::*;
+pub use gpu_timings::*;
 pub use graph_runner::*;
 pub use render_device::*;
 
 use crate::{
-    options::{WgpuOptions, WgpuOptionsPriority},
+    options::{AdapterSelection, WgpuOptions, WgpuOptionsPriority},
     render_graph::RenderGraph,
+    render_resource::{Buffer, StagingBuffer, Texture},
     view::{ExtractedWindows, ViewTarget},
 };
 use bevy_ecs::prelude::*;
@@ -19,13 +24,17 @@ pub fn render_system(world: &mut World) {
     world.resource_scope(|world, mut graph: Mut<RenderGraph>| {
         graph.update(world);
     });
+    let render_queue = world.get_resource::<RenderQueue>().unwrap().clone();
+    // Uploads staged while preparing assets need to be copied before they are drawn
+    if let Some(mut staging_buffer) = world.get_resource_mut::<StagingBuffer>() {
+        staging_buffer.submit(&render_queue);
+    }
     let graph = world.get_resource::<RenderGraph>().unwrap();
     let render_device = world.get_resource::<RenderDevice>().unwrap();
-    let render_queue = world.get_resource::<RenderQueue>().unwrap();
     RenderGraphRunner::run(
         graph,
         render_device.clone(), // TODO: is this clone really necessary?
-        render_queue,
+        &render_queue,
         world,
     )
     .unwrap();
@@ -61,6 +70,50 @@ pub type RenderQueue = Arc<Queue>;
 /// aswell as to create [`WindowSurfaces`](crate::view::window::WindowSurfaces).
 pub type RenderInstance = Instance;
 
+/// Enumerates the available adapters and picks one according to [`WgpuOptions::adapter_selection`].
+#[cfg(not(target_arch = "wasm32"))]
+fn select_adapter(
+    instance: &Instance,
+    options: &WgpuOptions,
+    request_adapter_options: &RequestAdapterOptions<'_>,
+) -> Option<wgpu::Adapter> {
+    if matches!(options.adapter_selection, AdapterSelection::Default) {
+        return None;
+    }
+
+    let mut adapters = instance
+        .enumerate_adapters(options.backends.unwrap_or(wgpu::Backends::PRIMARY))
+        .filter(|adapter| match request_adapter_options.compatible_surface {
+            Some(surface) => adapter.is_surface_supported(surface),
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    let adapter_infos = adapters
+        .iter()
+        .map(wgpu::Adapter::get_info)
+        .collect::<Vec<_>>();
+    for (index, adapter_info) in adapter_infos.iter().enumerate() {
+        info!("Available adapter {}: {:?}", index, adapter_info);
+    }
+
+    match options.adapter_selection.select(&adapter_infos) {
+        Some(index) => Some(adapters.swap_remove(index)),
+        None => {
+            warn!("No available adapter matches the adapter selection, falling back to the default adapter");
+            None
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn select_adapter(
+    _instance: &Instance,
+    _options: &WgpuOptions,
+    _request_adapter_options: &RequestAdapterOptions<'_>,
+) -> Option<wgpu::Adapter> {
+    None
+}
+
 /// Initializes the renderer by retrieving and preparing the GPU instance, device and queue
 /// for the specified backend.
 pub async fn initialize_renderer(
@@ -68,10 +121,13 @@ pub async fn initialize_renderer(
     options: &mut WgpuOptions,
     request_adapter_options: &RequestAdapterOptions<'_>,
 ) -> (RenderDevice, RenderQueue) {
-    let adapter = instance
-        .request_adapter(request_adapter_options)
-        .await
-        .expect("Unable to find a GPU! Make sure you have installed required drivers!");
+    let adapter = match select_adapter(instance, options, request_adapter_options) {
+        Some(adapter) => adapter,
+        None => instance
+            .request_adapter(request_adapter_options)
+            .await
+            .expect("Unable to find a GPU! Make sure you have installed required drivers!"),
+    };
 
     let adapter_info = adapter.get_info();
     info!("{:?}", adapter_info);
@@ -226,3 +282,146 @@ pub struct RenderContext {
     pub render_device: RenderDevice,
     pub command_encoder: CommandEncoder,
 }
+
+impl RenderContext {
+    /// Records a copy of `size` bytes from `source` to `destination`.
+    ///
+    /// Returns an error instead of recording the copy if `source` is missing
+    /// [`COPY_SRC`](wgpu::BufferUsages::COPY_SRC) or `destination` is missing
+    /// [`COPY_DST`](wgpu::BufferUsages::COPY_DST).
+    pub fn copy_buffer_to_buffer(
+        &mut self,
+        source: &Buffer,
+        source_offset: wgpu::BufferAddress,
+        destination: &Buffer,
+        destination_offset: wgpu::BufferAddress,
+        size: wgpu::BufferAddress,
+    ) -> Result<(), RenderResourceError> {
+        validate_buffer_usage(source.usage(), "source", wgpu::BufferUsages::COPY_SRC)?;
+        validate_buffer_usage(
+            destination.usage(),
+            "destination",
+            wgpu::BufferUsages::COPY_DST,
+        )?;
+        self.command_encoder.copy_buffer_to_buffer(
+            source,
+            source_offset,
+            destination,
+            destination_offset,
+            size,
+        );
+        Ok(())
+    }
+
+    /// Records a copy from the buffer in `source` to the texture in `destination`.
+    ///
+    /// Returns an error instead of recording the copy if the source buffer is missing
+    /// [`COPY_SRC`](wgpu::BufferUsages::COPY_SRC), if the destination texture is missing
+    /// [`COPY_DST`](wgpu::TextureUsages::COPY_DST), or if the rows of `layout` aren't aligned to
+    /// [`COPY_BYTES_PER_ROW_ALIGNMENT`](wgpu::COPY_BYTES_PER_ROW_ALIGNMENT), see
+    /// [`StagingBuffer::push_image`](crate::render_resource::StagingBuffer::push_image).
+    pub fn copy_buffer_to_texture(
+        &mut self,
+        source: &Buffer,
+        layout: wgpu::ImageDataLayout,
+        destination: wgpu::ImageCopyTextureBase<&Texture>,
+        copy_size: wgpu::Extent3d,
+    ) -> Result<(), RenderResourceError> {
+        validate_buffer_usage(source.usage(), "source", wgpu::BufferUsages::COPY_SRC)?;
+        validate_texture_copy_usage(destination.texture.usage(), wgpu::TextureUsages::COPY_DST)?;
+        if let Some(bytes_per_row) = layout.bytes_per_row {
+            if bytes_per_row.get() % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT != 0 {
+                return Err(RenderResourceError::UnalignedBytesPerRow {
+                    bytes_per_row: bytes_per_row.get(),
+                });
+            }
+        }
+        self.command_encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: source,
+                layout,
+            },
+            wgpu::ImageCopyTexture {
+                texture: destination.texture,
+                mip_level: destination.mip_level,
+                origin: destination.origin,
+                aspect: destination.aspect,
+            },
+            copy_size,
+        );
+        Ok(())
+    }
+
+    /// Records a copy from mip level `source_mip` of `texture`, which is described by `desc`, to
+    /// its mip level `destination_mip`, e.g. to seed a level before generating it in a pass.
+    ///
+    /// The copied region starts at the origin of both levels and has the size of the smaller one,
+    /// see [`mip_copy_size`]. All array layers are copied. Returns an error instead of recording
+    /// the copy if the levels are invalid, or if the texture is missing
+    /// [`COPY_SRC`](wgpu::TextureUsages::COPY_SRC) or [`COPY_DST`](wgpu::TextureUsages::COPY_DST).
+    pub fn copy_texture_mip(
+        &mut self,
+        texture: &Texture,
+        desc: &wgpu::TextureDescriptor,
+        source_mip: u32,
+        destination_mip: u32,
+    ) -> Result<(), RenderResourceError> {
+        let copy_size = mip_copy_size(desc, source_mip, destination_mip)?;
+        self.command_encoder.copy_texture_to_texture(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: source_mip,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: destination_mip,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            copy_size,
+        );
+        Ok(())
+    }
+
+    /// Replaces `buffer` with a new buffer of `size` bytes and the same usages, recording a copy
+    /// of as much of its contents as fits, see [`resized_buffer_copy_size`].
+    ///
+    /// The new buffer has a new [`BufferId`](crate::render_resource::BufferId), so bind groups
+    /// using the old buffer have to be created again. The old buffer is freed once it is no
+    /// longer used. Returns an error if `buffer` wasn't created by a [`RenderDevice`], or is
+    /// missing [`COPY_SRC`](wgpu::BufferUsages::COPY_SRC) or
+    /// [`COPY_DST`](wgpu::BufferUsages::COPY_DST).
+    pub fn resize_buffer(
+        &mut self,
+        buffer: &mut Buffer,
+        size: wgpu::BufferAddress,
+    ) -> Result<(), RenderResourceError> {
+        let (usage, old_size) = match (buffer.usage(), buffer.size()) {
+            (Some(usage), Some(old_size)) => (usage, old_size),
+            _ => return Err(RenderResourceError::UntrackedBuffer),
+        };
+        validate_buffer_usage(
+            Some(usage),
+            "resized",
+            wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        )?;
+
+        let resized = self
+            .render_device
+            .try_create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size,
+                usage,
+                mapped_at_creation: false,
+            })?;
+        let copy_size = resized_buffer_copy_size(old_size, size);
+        if copy_size > 0 {
+            self.command_encoder
+                .copy_buffer_to_buffer(buffer, 0, &resized, 0, copy_size);
+        }
+        *buffer = resized;
+        Ok(())
+    }
+}
@@ -1,24 +1,170 @@
 use crate::render_resource::{
-    BindGroup, BindGroupLayout, Buffer, ComputePipeline, RawRenderPipelineDescriptor,
-    RenderPipeline, Sampler, Texture,
+    required_layout_features, BindGroup, BindGroupLayout, Buffer, ComputePipeline,
+    RawRenderPipelineDescriptor, RenderPipeline, Sampler, Texture,
 };
+use crate::renderer::{texture_size_bytes, GpuMemoryTracker};
+use bevy_asset::HandleId;
+use bevy_utils::tracing::warn;
 use futures_lite::future;
 use std::sync::Arc;
+use thiserror::Error;
 use wgpu::util::DeviceExt;
 
+/// An error returned by the fallible resource creation methods of [`RenderDevice`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RenderResourceError {
+    /// A bind group layout uses bindings, e.g. binding arrays, that need features the device
+    /// wasn't created with.
+    #[error("Bind group layout {label:?} requires the unsupported device features {missing:?}")]
+    MissingFeatures {
+        label: Option<String>,
+        missing: wgpu::Features,
+    },
+    /// A bind group binds an empty array of resources, which wgpu rejects.
+    #[error("Binding {binding} of bind group {label:?} is an empty binding array")]
+    EmptyBindingArray { label: Option<String>, binding: u32 },
+    /// The next texture of a surface couldn't be acquired, even after reconfiguring it.
+    #[error("Failed to acquire the next swap chain texture: {0}")]
+    SurfaceTexture(wgpu::SurfaceError),
+    /// A buffer is used for something, e.g. as the destination of a copy, that it wasn't
+    /// created for.
+    #[error("The {role} buffer is missing the buffer usages {missing:?}")]
+    MissingBufferUsage {
+        role: &'static str,
+        missing: wgpu::BufferUsages,
+    },
+    /// A texture is created with usages its format doesn't support on this device.
+    #[error("Texture {label:?} has the format {format:?}, which does not support the usages {unsupported:?}")]
+    UnsupportedTextureUsage {
+        label: Option<String>,
+        format: wgpu::TextureFormat,
+        unsupported: wgpu::TextureUsages,
+    },
+    /// A pipeline is used with a different number of color attachments than it has targets.
+    #[error(
+        "The pipeline has {pipeline} color targets, but {attachments} color attachments were given"
+    )]
+    ColorTargetCountMismatch { pipeline: usize, attachments: usize },
+    /// A pipeline has more color targets than [`MAX_COLOR_ATTACHMENTS`].
+    #[error(
+        "The pipeline has {count} color targets, but at most {MAX_COLOR_ATTACHMENTS} are supported"
+    )]
+    TooManyColorTargets { count: usize },
+    /// A pipeline is used with a color attachment of another format than its target.
+    #[error("Color target {index} of the pipeline has the format {pipeline:?}, but the attachment has the format {attachment:?}")]
+    ColorTargetFormatMismatch {
+        index: usize,
+        pipeline: wgpu::TextureFormat,
+        attachment: wgpu::TextureFormat,
+    },
+    /// A pipeline blends into a color target whose format can't be blended.
+    #[error("Color target {index} enables blending, but its format {format:?} is not blendable")]
+    UnblendableColorTarget {
+        index: usize,
+        format: wgpu::TextureFormat,
+    },
+    /// A copy between a buffer and a texture has rows that aren't aligned as wgpu requires.
+    #[error("A copy between a buffer and a texture has {bytes_per_row} bytes per row, which is not a multiple of {}", wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)]
+    UnalignedBytesPerRow { bytes_per_row: u32 },
+    /// A mip level past the last one of a texture is copied.
+    #[error("Mip level {level} is out of range for a texture with {mip_level_count} mip levels")]
+    InvalidMipLevel { level: u32, mip_level_count: u32 },
+    /// A mip level of a texture is copied onto itself.
+    #[error("Can't copy mip level {level} of a texture onto itself")]
+    SameMipLevel { level: u32 },
+    /// A texture is copied from or into without the usages this needs.
+    #[error("The texture of a copy is missing the texture usages {missing:?}")]
+    MissingTextureUsage { missing: wgpu::TextureUsages },
+    /// A buffer of zero bytes is created.
+    #[error("Buffer {label:?} has a size of zero bytes")]
+    ZeroSizedBuffer { label: Option<String> },
+    /// A texture with a zero width, height or depth is created.
+    #[error("Texture {label:?} has a zero dimension in its size {size:?}")]
+    ZeroSizedTexture {
+        label: Option<String>,
+        size: wgpu::Extent3d,
+    },
+    /// The attachments of a render pass don't all have the same sample count.
+    #[error("The {attachment} has a sample count of {samples}, but the {first} has a sample count of {expected}")]
+    AttachmentSampleCountMismatch {
+        attachment: PassAttachment,
+        samples: u32,
+        first: PassAttachment,
+        expected: u32,
+    },
+    /// A pipeline doesn't have the sample count of the attachments of the pass it draws in.
+    #[error("The pipeline has a sample count of {pipeline}, but the attachments of the pass have a sample count of {attachments}")]
+    PipelineSampleCountMismatch { pipeline: u32, attachments: u32 },
+    /// A buffer couldn't be mapped to be read on the host.
+    #[error("Failed to map buffer to host")]
+    BufferMap(wgpu::BufferAsyncError),
+    /// An image is used before its texture was prepared, e.g. while it is still loading.
+    #[error("No texture was prepared for the image {0:?}")]
+    TextureNotFound(HandleId),
+    /// A mesh is used before its buffers were prepared, or after it was removed.
+    #[error("No buffers were prepared for the mesh {0:?}")]
+    BufferNotFound(HandleId),
+    /// A buffer whose usages and size are needed wasn't created by a [`RenderDevice`].
+    #[error(
+        "The usages and size of the buffer are unknown, as it wasn't created by a RenderDevice"
+    )]
+    UntrackedBuffer,
+    /// wgpu rejected a render pipeline, e.g. because its shaders don't match its layout or
+    /// vertex buffers.
+    #[error("Render pipeline {label:?} is invalid: {message}")]
+    InvalidRenderPipeline {
+        label: Option<String>,
+        message: String,
+    },
+}
+
+/// The maximum number of color attachments of a render pass, and so of color targets of a render
+/// pipeline, supported by wgpu on all backends.
+pub const MAX_COLOR_ATTACHMENTS: usize = 4;
+
+/// An attachment of a render pass, as reported by [`validate_sample_counts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassAttachment {
+    /// The color attachment at this index
+    Color(usize),
+    DepthStencil,
+}
+
+impl std::fmt::Display for PassAttachment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PassAttachment::Color(index) => write!(f, "color attachment {}", index),
+            PassAttachment::DepthStencil => write!(f, "depth stencil attachment"),
+        }
+    }
+}
+
 /// This GPU device is responsible for the creation of most rendering and compute resources.
 #[derive(Clone)]
 pub struct RenderDevice {
     device: Arc<wgpu::Device>,
+    memory: Arc<GpuMemoryTracker>,
 }
 
 impl From<Arc<wgpu::Device>> for RenderDevice {
     fn from(device: Arc<wgpu::Device>) -> Self {
-        Self { device }
+        Self {
+            device,
+            memory: Default::default(),
+        }
     }
 }
 
 impl RenderDevice {
+    /// Estimates the number of bytes of GPU memory used by the buffers and textures created by
+    /// this device that are still alive.
+    ///
+    /// Buffers count their size and textures the size of all their mip levels, array layers and
+    /// samples, see [`texture_size_bytes`]. Padding and alignment added by the driver are ignored.
+    pub fn estimated_gpu_memory(&self) -> usize {
+        self.memory.estimated_gpu_memory()
+    }
+
     /// List all [`Features`](wgpu::Features) that may be used with this device.
     ///
     /// Functions may panic if you use unsupported features.
@@ -68,19 +214,50 @@ impl RenderDevice {
     }
 
     /// Creates a new [`BindGroup`](wgpu::BindGroup).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`RenderDevice::try_create_bind_group`] would return an error.
     #[inline]
     pub fn create_bind_group(&self, desc: &wgpu::BindGroupDescriptor) -> BindGroup {
-        let wgpu_bind_group = self.device.create_bind_group(desc);
-        BindGroup::from(wgpu_bind_group)
+        self.try_create_bind_group(desc)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new [`BindGroup`](wgpu::BindGroup), returning an error instead of panicking
+    /// if `desc` is known to be invalid.
+    pub fn try_create_bind_group(
+        &self,
+        desc: &wgpu::BindGroupDescriptor,
+    ) -> Result<BindGroup, RenderResourceError> {
+        validate_bind_group(desc)?;
+        Ok(BindGroup::from(self.device.create_bind_group(desc)))
     }
 
     /// Creates a [`BindGroupLayout`](wgpu::BindGroupLayout).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`RenderDevice::try_create_bind_group_layout`] would return an error.
     #[inline]
     pub fn create_bind_group_layout(
         &self,
         desc: &wgpu::BindGroupLayoutDescriptor,
     ) -> BindGroupLayout {
-        BindGroupLayout::from(self.device.create_bind_group_layout(desc))
+        self.try_create_bind_group_layout(desc)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a [`BindGroupLayout`](wgpu::BindGroupLayout), returning an error if the layout
+    /// requires features this device does not support (e.g. binding arrays).
+    pub fn try_create_bind_group_layout(
+        &self,
+        desc: &wgpu::BindGroupLayoutDescriptor,
+    ) -> Result<BindGroupLayout, RenderResourceError> {
+        validate_bind_group_layout(self.features(), desc)?;
+        Ok(BindGroupLayout::from(
+            self.device.create_bind_group_layout(desc),
+        ))
     }
 
     /// Creates a [`PipelineLayout`](wgpu::PipelineLayout).
@@ -93,10 +270,35 @@ impl RenderDevice {
     }
 
     /// Creates a [`RenderPipeline`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`RenderDevice::try_create_render_pipeline`] would return an error.
     #[inline]
     pub fn create_render_pipeline(&self, desc: &RawRenderPipelineDescriptor) -> RenderPipeline {
+        self.try_create_render_pipeline(desc)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a [`RenderPipeline`], returning an error instead of panicking if the pipeline is
+    /// invalid, e.g. it blends into a format that can't be blended or its shaders don't match its
+    /// layout.
+    pub fn try_create_render_pipeline(
+        &self,
+        desc: &RawRenderPipelineDescriptor,
+    ) -> Result<RenderPipeline, RenderResourceError> {
+        if let Some(fragment) = &desc.fragment {
+            validate_pipeline_color_targets(fragment.targets)?;
+        }
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
         let wgpu_render_pipeline = self.device.create_render_pipeline(desc);
-        RenderPipeline::from(wgpu_render_pipeline)
+        match future::block_on(self.device.pop_error_scope()) {
+            None => Ok(RenderPipeline::from(wgpu_render_pipeline)),
+            Some(err) => Err(RenderResourceError::InvalidRenderPipeline {
+                label: desc.label.map(str::to_string),
+                message: err.to_string(),
+            }),
+        }
     }
 
     /// Creates a [`ComputePipeline`].
@@ -110,23 +312,115 @@ impl RenderDevice {
     }
 
     /// Creates a [`Buffer`].
+    ///
+    /// A zero-sized buffer, which usually comes from an empty mesh or an asset that isn't loaded,
+    /// is replaced by a buffer of [`COPY_BUFFER_ALIGNMENT`](wgpu::COPY_BUFFER_ALIGNMENT) bytes,
+    /// logging a warning. Use [`RenderDevice::try_create_buffer`] to handle it instead.
     pub fn create_buffer(&self, desc: &wgpu::BufferDescriptor) -> Buffer {
+        self.try_create_buffer(desc).unwrap_or_else(|err| {
+            warn!("{}, creating a minimal buffer instead", err);
+            self.create_buffer_unchecked(&wgpu::BufferDescriptor {
+                size: wgpu::COPY_BUFFER_ALIGNMENT,
+                ..*desc
+            })
+        })
+    }
+
+    /// Creates a [`Buffer`], returning an error if it has a size of zero bytes.
+    pub fn try_create_buffer(
+        &self,
+        desc: &wgpu::BufferDescriptor,
+    ) -> Result<Buffer, RenderResourceError> {
+        validate_buffer_size(desc)?;
+        Ok(self.create_buffer_unchecked(desc))
+    }
+
+    fn create_buffer_unchecked(&self, desc: &wgpu::BufferDescriptor) -> Buffer {
         let wgpu_buffer = self.device.create_buffer(desc);
         Buffer::from(wgpu_buffer)
+            .with_usage(desc.usage)
+            .with_size(desc.size)
+            .with_allocation(self.memory.allocate(desc.size))
     }
 
     /// Creates a [`Buffer`] and initializes it with the specified data.
+    ///
+    /// The data is copied into the buffer, so if it is only collected into a temporary `Vec` for
+    /// this, [`RenderDevice::create_buffer_mapped`] avoids holding it twice.
     pub fn create_buffer_with_data(&self, desc: &wgpu::util::BufferInitDescriptor) -> Buffer {
         let wgpu_buffer = self.device.create_buffer_init(desc);
         Buffer::from(wgpu_buffer)
+            .with_usage(desc.usage)
+            .with_size(desc.contents.len() as u64)
+            .with_allocation(self.memory.allocate(desc.contents.len() as u64))
+    }
+
+    /// Creates a [`Buffer`] of `size` bytes and initializes it by calling `write` with its
+    /// contents, which start out zeroed.
+    ///
+    /// The buffer is mapped at creation, so `write` fills it in place. This is preferable to
+    /// [`RenderDevice::create_buffer_with_data`] for large buffers whose data is generated rather
+    /// than already held in memory, e.g. vertex data, as no temporary copy of it is needed. The
+    /// resulting buffer holds the same bytes.
+    pub fn create_buffer_mapped(
+        &self,
+        label: Option<&str>,
+        size: usize,
+        usage: wgpu::BufferUsages,
+        write: impl FnOnce(&mut [u8]),
+    ) -> Buffer {
+        let buffer = self.create_buffer_unchecked(&wgpu::BufferDescriptor {
+            label,
+            size: mapped_buffer_size(size as wgpu::BufferAddress),
+            usage,
+            // Like `create_buffer_with_data`, an empty buffer is created without mapping it
+            mapped_at_creation: size > 0,
+        });
+        if size == 0 {
+            write_mapped(&mut [], 0, write);
+            return buffer;
+        }
+        write_mapped(&mut buffer.slice(..).get_mapped_range_mut(), size, write);
+        buffer.unmap();
+        buffer
     }
 
     /// Creates a new [`Texture`].
     ///
     /// `desc` specifies the general format of the texture.
+    ///
+    /// A texture with a zero dimension is replaced by a texture of at least one texel in every
+    /// dimension, logging a warning.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`RenderDevice::try_create_texture`] would return any other error.
     pub fn create_texture(&self, desc: &wgpu::TextureDescriptor) -> Texture {
-        let wgpu_texture = self.device.create_texture(desc);
-        Texture::from(wgpu_texture)
+        match self.try_create_texture(desc) {
+            Ok(texture) => texture,
+            Err(err @ RenderResourceError::ZeroSizedTexture { .. }) => {
+                warn!("{}, creating a minimal texture instead", err);
+                self.create_texture(&wgpu::TextureDescriptor {
+                    size: minimal_texture_size(desc.size),
+                    ..*desc
+                })
+            }
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Creates a new [`Texture`], returning an error if it has a zero dimension, or if its format
+    /// doesn't support the requested usages, e.g. a
+    /// [`RENDER_ATTACHMENT`](wgpu::TextureUsages::RENDER_ATTACHMENT) of a compressed format.
+    pub fn try_create_texture(
+        &self,
+        desc: &wgpu::TextureDescriptor,
+    ) -> Result<Texture, RenderResourceError> {
+        validate_texture_size(desc)?;
+        validate_texture_usage(self.features(), desc)?;
+        Ok(Texture::from(self.device.create_texture(desc))
+            .with_usage(desc.usage)
+            .with_allocation(self.memory.allocate(texture_size_bytes(desc))))
     }
 
     /// Creates a new [`Sampler`].
@@ -147,6 +441,22 @@ impl RenderDevice {
         surface.configure(&self.device, config);
     }
 
+    /// Acquires the next [`SurfaceTexture`](wgpu::SurfaceTexture) of `surface`.
+    ///
+    /// If the surface is outdated (e.g. after a resize) or was lost (e.g. after a GPU reset), it is
+    /// reconfigured with `config` and the acquisition is retried once.
+    pub fn get_current_surface_texture(
+        &self,
+        surface: &wgpu::Surface,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Result<wgpu::SurfaceTexture, RenderResourceError> {
+        acquire_surface_texture(
+            || surface.get_current_texture(),
+            || self.configure_surface(surface, config),
+        )
+        .map_err(RenderResourceError::SurfaceTexture)
+    }
+
     /// Returns the wgpu [`Device`](wgpu::Device).
     pub fn wgpu_device(&self) -> &wgpu::Device {
         &self.device
@@ -161,9 +471,746 @@ impl RenderDevice {
         );
     }
 
+    /// Maps `range` of `buffer` for reading, waits for the GPU to finish any work using it and
+    /// calls `callback` with its contents, before unmapping the buffer again.
+    ///
+    /// This blocks until the buffer is mapped. `buffer` must have been created with
+    /// [`BufferUsages::MAP_READ`](wgpu::BufferUsages::MAP_READ), which is usually combined with
+    /// `COPY_DST` to read back a buffer or texture copied into it.
+    pub fn read_buffer(
+        &self,
+        buffer: &Buffer,
+        range: std::ops::Range<u64>,
+        callback: impl FnOnce(&[u8]),
+    ) -> Result<(), RenderResourceError> {
+        validate_buffer_usage(buffer.usage(), "readback", wgpu::BufferUsages::MAP_READ)?;
+        let slice = buffer.slice(range);
+        let data = slice.map_async(wgpu::MapMode::Read);
+        self.poll(wgpu::Maintain::Wait);
+        future::block_on(data).map_err(RenderResourceError::BufferMap)?;
+        callback(&slice.get_mapped_range());
+        buffer.unmap();
+        Ok(())
+    }
+
     pub fn align_copy_bytes_per_row(row_bytes: usize) -> usize {
         let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
         let padded_bytes_per_row_padding = (align - row_bytes % align) % align;
         row_bytes + padded_bytes_per_row_padding
     }
 }
+
+fn validate_bind_group_layout(
+    available_features: wgpu::Features,
+    desc: &wgpu::BindGroupLayoutDescriptor,
+) -> Result<(), RenderResourceError> {
+    let missing = required_layout_features(desc.entries) - available_features;
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(RenderResourceError::MissingFeatures {
+            label: desc.label.map(ToString::to_string),
+            missing,
+        })
+    }
+}
+
+fn validate_bind_group(desc: &wgpu::BindGroupDescriptor) -> Result<(), RenderResourceError> {
+    for entry in desc.entries {
+        let is_empty_array = match entry.resource {
+            wgpu::BindingResource::BufferArray(buffers) => buffers.is_empty(),
+            wgpu::BindingResource::SamplerArray(samplers) => samplers.is_empty(),
+            wgpu::BindingResource::TextureViewArray(views) => views.is_empty(),
+            _ => false,
+        };
+        if is_empty_array {
+            return Err(RenderResourceError::EmptyBindingArray {
+                label: desc.label.map(ToString::to_string),
+                binding: entry.binding,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a buffer created with `usage` supports the `required` usages.
+/// Buffers with unknown usages are assumed to be valid.
+pub(crate) fn validate_buffer_usage(
+    usage: Option<wgpu::BufferUsages>,
+    role: &'static str,
+    required: wgpu::BufferUsages,
+) -> Result<(), RenderResourceError> {
+    match usage {
+        Some(usage) if !usage.contains(required) => Err(RenderResourceError::MissingBufferUsage {
+            role,
+            missing: required - usage,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a texture created with `usage` supports the `required` usages of a copy.
+/// Textures with unknown usages are assumed to be valid.
+pub(crate) fn validate_texture_copy_usage(
+    usage: Option<wgpu::TextureUsages>,
+    required: wgpu::TextureUsages,
+) -> Result<(), RenderResourceError> {
+    match usage {
+        Some(usage) if !usage.contains(required) => Err(RenderResourceError::MissingTextureUsage {
+            missing: required - usage,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn validate_buffer_size(desc: &wgpu::BufferDescriptor) -> Result<(), RenderResourceError> {
+    if desc.size == 0 {
+        Err(RenderResourceError::ZeroSizedBuffer {
+            label: desc.label.map(ToString::to_string),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_texture_size(desc: &wgpu::TextureDescriptor) -> Result<(), RenderResourceError> {
+    let size = desc.size;
+    if size.width == 0 || size.height == 0 || size.depth_or_array_layers == 0 {
+        Err(RenderResourceError::ZeroSizedTexture {
+            label: desc.label.map(ToString::to_string),
+            size,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+fn minimal_texture_size(size: wgpu::Extent3d) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width: size.width.max(1),
+        height: size.height.max(1),
+        depth_or_array_layers: size.depth_or_array_layers.max(1),
+    }
+}
+
+/// The usages `format` may support beyond its guaranteed ones on some adapters, if
+/// [`TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`](wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+/// is enabled. Compressed formats can't be rendered to or used as storage textures on any adapter.
+fn adapter_specific_texture_usages(format: wgpu::TextureFormat) -> wgpu::TextureUsages {
+    if format.describe().block_dimensions == (1, 1) {
+        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::STORAGE_BINDING
+    } else {
+        wgpu::TextureUsages::empty()
+    }
+}
+
+fn validate_texture_usage(
+    features: wgpu::Features,
+    desc: &wgpu::TextureDescriptor,
+) -> Result<(), RenderResourceError> {
+    let mut allowed = desc
+        .format
+        .describe()
+        .guaranteed_format_features
+        .allowed_usages;
+    if features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+        allowed |= adapter_specific_texture_usages(desc.format);
+    }
+    if allowed.contains(desc.usage) {
+        Ok(())
+    } else {
+        Err(RenderResourceError::UnsupportedTextureUsage {
+            label: desc.label.map(ToString::to_string),
+            format: desc.format,
+            unsupported: desc.usage - allowed,
+        })
+    }
+}
+
+/// Checks that a render pipeline with `count` color targets, e.g. writing a G-buffer for deferred
+/// shading, doesn't exceed [`MAX_COLOR_ATTACHMENTS`].
+pub fn validate_color_target_count(count: usize) -> Result<(), RenderResourceError> {
+    if count > MAX_COLOR_ATTACHMENTS {
+        Err(RenderResourceError::TooManyColorTargets { count })
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks the color `targets` of a render pipeline on their own: there are at most
+/// [`MAX_COLOR_ATTACHMENTS`] of them, and blending is only enabled on formats that support
+/// filtering, which excludes [`Rgba32Float`](wgpu::TextureFormat::Rgba32Float).
+pub fn validate_pipeline_color_targets(
+    targets: &[wgpu::ColorTargetState],
+) -> Result<(), RenderResourceError> {
+    validate_color_target_count(targets.len())?;
+    for (index, target) in targets.iter().enumerate() {
+        if target.blend.is_some()
+            && !target
+                .format
+                .describe()
+                .guaranteed_format_features
+                .filterable
+        {
+            return Err(RenderResourceError::UnblendableColorTarget {
+                index,
+                format: target.format,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the color `targets` of a render pipeline can draw into color attachments of the
+/// given formats, one attachment per target.
+///
+/// Each target must have exactly the format of its attachment, so for example a pipeline meant
+/// for an HDR [`Rgba16Float`](wgpu::TextureFormat::Rgba16Float) target can't draw into the
+/// swap chain texture. The targets must also pass [`validate_pipeline_color_targets`].
+pub fn validate_color_targets(
+    targets: &[wgpu::ColorTargetState],
+    attachment_formats: &[wgpu::TextureFormat],
+) -> Result<(), RenderResourceError> {
+    validate_pipeline_color_targets(targets)?;
+    if targets.len() != attachment_formats.len() {
+        return Err(RenderResourceError::ColorTargetCountMismatch {
+            pipeline: targets.len(),
+            attachments: attachment_formats.len(),
+        });
+    }
+    for (index, (target, attachment)) in targets.iter().zip(attachment_formats).enumerate() {
+        if target.format != *attachment {
+            return Err(RenderResourceError::ColorTargetFormatMismatch {
+                index,
+                pipeline: target.format,
+                attachment: *attachment,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that all attachments of a render pass, its color attachments and its optional depth
+/// stencil attachment, have the same sample count, and that it is the `sample_count` of the
+/// pipelines drawing in the pass.
+///
+/// Mixing a multisampled color attachment with a single-sample depth attachment, or the other way
+/// around, is a common mistake when toggling MSAA, which wgpu otherwise rejects when the pass is
+/// recorded.
+pub fn validate_sample_counts(
+    color_attachments: &[u32],
+    depth_stencil_attachment: Option<u32>,
+    pipeline_sample_count: u32,
+) -> Result<(), RenderResourceError> {
+    let mut attachments = color_attachments
+        .iter()
+        .enumerate()
+        .map(|(index, samples)| (PassAttachment::Color(index), *samples))
+        .chain(depth_stencil_attachment.map(|samples| (PassAttachment::DepthStencil, samples)));
+    let (first, expected) = match attachments.next() {
+        Some(first) => first,
+        None => return Ok(()),
+    };
+    for (attachment, samples) in attachments {
+        if samples != expected {
+            return Err(RenderResourceError::AttachmentSampleCountMismatch {
+                attachment,
+                samples,
+                first,
+                expected,
+            });
+        }
+    }
+    if pipeline_sample_count != expected {
+        return Err(RenderResourceError::PipelineSampleCountMismatch {
+            pipeline: pipeline_sample_count,
+            attachments: expected,
+        });
+    }
+    Ok(())
+}
+
+/// Calls `acquire`, and if the surface turns out to be outdated or lost, `reconfigure` before
+/// calling `acquire` once more.
+fn acquire_surface_texture<T>(
+    mut acquire: impl FnMut() -> Result<T, wgpu::SurfaceError>,
+    reconfigure: impl FnOnce(),
+) -> Result<T, wgpu::SurfaceError> {
+    match acquire() {
+        Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+            reconfigure();
+            acquire()
+        }
+        result => result,
+    }
+}
+
+/// Returns the size of a buffer that is mapped at creation to hold `size` bytes, which is `size`
+/// rounded up to [`COPY_BUFFER_ALIGNMENT`](wgpu::COPY_BUFFER_ALIGNMENT).
+fn mapped_buffer_size(size: wgpu::BufferAddress) -> wgpu::BufferAddress {
+    let align_mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
+    (size + align_mask) & !align_mask
+}
+
+/// Fills the `mapped` range of a buffer created by [`RenderDevice::create_buffer_mapped`], calling
+/// `write` with its first `size` bytes and zeroing the padding after them, which matches the
+/// contents of a buffer created by [`RenderDevice::create_buffer_with_data`].
+fn write_mapped(mapped: &mut [u8], size: usize, write: impl FnOnce(&mut [u8])) {
+    let (contents, padding) = mapped.split_at_mut(size);
+    write(contents);
+    padding.fill(0);
+}
+
+/// Returns the number of bytes kept when resizing a buffer of `old_size` bytes to `new_size`
+/// bytes, which is the size of the smaller buffer rounded down to
+/// [`COPY_BUFFER_ALIGNMENT`](wgpu::COPY_BUFFER_ALIGNMENT).
+pub fn resized_buffer_copy_size(
+    old_size: wgpu::BufferAddress,
+    new_size: wgpu::BufferAddress,
+) -> wgpu::BufferAddress {
+    let size = old_size.min(new_size);
+    size - size % wgpu::COPY_BUFFER_ALIGNMENT
+}
+
+/// Returns the size of a copy from mip level `source_mip` to mip level `destination_mip` of a
+/// texture described by `desc`, which is the size of the smaller of both levels.
+///
+/// Fails if either level doesn't exist, if both are the same level, or if the texture can't be
+/// both copied from and to.
+pub fn mip_copy_size(
+    desc: &wgpu::TextureDescriptor,
+    source_mip: u32,
+    destination_mip: u32,
+) -> Result<wgpu::Extent3d, RenderResourceError> {
+    for level in [source_mip, destination_mip] {
+        if level >= desc.mip_level_count {
+            return Err(RenderResourceError::InvalidMipLevel {
+                level,
+                mip_level_count: desc.mip_level_count,
+            });
+        }
+    }
+    if source_mip == destination_mip {
+        return Err(RenderResourceError::SameMipLevel { level: source_mip });
+    }
+    validate_texture_copy_usage(
+        Some(desc.usage),
+        wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+    )?;
+    // Each level halves the size of the previous one, so the deeper level bounds the copy.
+    Ok(desc
+        .mip_level_size(source_mip.max(destination_mip))
+        .unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_resource::texture_array_layout_entry;
+    use std::num::NonZeroU32;
+
+    #[test]
+    fn outdated_surface_is_reconfigured_once() {
+        let mut results = vec![Ok(()), Err(wgpu::SurfaceError::Outdated)];
+        let mut reconfigured = false;
+        let acquired = acquire_surface_texture(|| results.pop().unwrap(), || reconfigured = true);
+        assert!(acquired.is_ok());
+        assert!(reconfigured);
+
+        // A surface that stays lost is reported instead of retried forever.
+        let mut attempts = 0;
+        let acquired = acquire_surface_texture(
+            || {
+                attempts += 1;
+                Err::<(), _>(wgpu::SurfaceError::Lost)
+            },
+            || {},
+        );
+        assert!(matches!(acquired, Err(wgpu::SurfaceError::Lost)));
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn binding_array_layout_without_feature_is_an_error() {
+        let entries = [texture_array_layout_entry(
+            0,
+            wgpu::ShaderStages::FRAGMENT,
+            wgpu::TextureSampleType::Float { filterable: true },
+            wgpu::TextureViewDimension::D2,
+            NonZeroU32::new(2).unwrap(),
+        )];
+        let desc = wgpu::BindGroupLayoutDescriptor {
+            label: Some("textures"),
+            entries: &entries,
+        };
+
+        assert_eq!(
+            validate_bind_group_layout(wgpu::Features::empty(), &desc),
+            Err(RenderResourceError::MissingFeatures {
+                label: Some("textures".to_string()),
+                missing: wgpu::Features::TEXTURE_BINDING_ARRAY,
+            })
+        );
+        assert_eq!(
+            validate_bind_group_layout(wgpu::Features::TEXTURE_BINDING_ARRAY, &desc),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn copy_from_buffer_without_copy_src_is_rejected() {
+        let usage = Some(wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM);
+        let err = validate_buffer_usage(usage, "source", wgpu::BufferUsages::COPY_SRC).unwrap_err();
+        assert_eq!(
+            err,
+            RenderResourceError::MissingBufferUsage {
+                role: "source",
+                missing: wgpu::BufferUsages::COPY_SRC,
+            }
+        );
+        let message = err.to_string();
+        assert!(message.contains("source") && message.contains("COPY_SRC"));
+
+        assert!(validate_buffer_usage(usage, "destination", wgpu::BufferUsages::COPY_DST).is_ok());
+        assert!(validate_buffer_usage(None, "source", wgpu::BufferUsages::COPY_SRC).is_ok());
+    }
+
+    #[test]
+    fn copy_into_texture_without_copy_dst_is_rejected() {
+        let usage = Some(wgpu::TextureUsages::TEXTURE_BINDING);
+        let err = validate_texture_copy_usage(usage, wgpu::TextureUsages::COPY_DST).unwrap_err();
+        assert_eq!(
+            err,
+            RenderResourceError::MissingTextureUsage {
+                missing: wgpu::TextureUsages::COPY_DST,
+            }
+        );
+        assert!(err.to_string().contains("COPY_DST"));
+
+        let usage = Some(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST);
+        assert!(validate_texture_copy_usage(usage, wgpu::TextureUsages::COPY_DST).is_ok());
+        assert!(validate_texture_copy_usage(None, wgpu::TextureUsages::COPY_DST).is_ok());
+    }
+
+    #[test]
+    fn readback_from_buffer_without_map_read_is_rejected() {
+        let usage = Some(wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::STORAGE);
+        let err =
+            validate_buffer_usage(usage, "readback", wgpu::BufferUsages::MAP_READ).unwrap_err();
+        assert_eq!(
+            err,
+            RenderResourceError::MissingBufferUsage {
+                role: "readback",
+                missing: wgpu::BufferUsages::MAP_READ,
+            }
+        );
+        assert!(err.to_string().contains("MAP_READ"));
+
+        let readback = Some(wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ);
+        assert!(validate_buffer_usage(readback, "readback", wgpu::BufferUsages::MAP_READ).is_ok());
+    }
+
+    #[test]
+    fn mapped_buffer_holds_the_same_bytes_as_initialized_buffer() {
+        for len in 0..10 {
+            let contents = (1..=len as u8).collect::<Vec<_>>();
+
+            // `create_buffer_init` uploads the contents followed by zeroed padding up to a
+            // multiple of `COPY_BUFFER_ALIGNMENT`, and doesn't map empty buffers
+            let mut initialized = contents.clone();
+            if len > 0 {
+                let align_mask = wgpu::COPY_BUFFER_ALIGNMENT as usize - 1;
+                let padded_len =
+                    ((len + align_mask) & !align_mask).max(wgpu::COPY_BUFFER_ALIGNMENT as usize);
+                initialized.resize(padded_len, 0);
+            }
+
+            let mut mapped = vec![u8::MAX; mapped_buffer_size(len as u64) as usize];
+            write_mapped(&mut mapped, len, |data| data.copy_from_slice(&contents));
+            assert_eq!(mapped, initialized);
+        }
+    }
+
+    #[test]
+    fn resizing_keeps_the_aligned_prefix() {
+        assert_eq!(resized_buffer_copy_size(64, 256), 64);
+        assert_eq!(resized_buffer_copy_size(256, 64), 64);
+        assert_eq!(resized_buffer_copy_size(10, 256), 8);
+        assert_eq!(resized_buffer_copy_size(0, 256), 0);
+    }
+
+    fn color_target(format: wgpu::TextureFormat) -> wgpu::ColorTargetState {
+        wgpu::ColorTargetState {
+            format,
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            write_mask: wgpu::ColorWrites::ALL,
+        }
+    }
+
+    #[test]
+    fn hdr_pipeline_matches_hdr_target() {
+        let hdr = wgpu::TextureFormat::Rgba16Float;
+        assert_eq!(validate_color_targets(&[color_target(hdr)], &[hdr]), Ok(()));
+
+        let ldr = wgpu::TextureFormat::Bgra8UnormSrgb;
+        assert_eq!(
+            validate_color_targets(&[color_target(ldr)], &[hdr]),
+            Err(RenderResourceError::ColorTargetFormatMismatch {
+                index: 0,
+                pipeline: ldr,
+                attachment: hdr,
+            })
+        );
+        assert!(validate_color_targets(&[color_target(hdr)], &[]).is_err());
+    }
+
+    #[test]
+    fn g_buffer_pipeline_draws_into_one_attachment_per_target() {
+        let formats = [
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            wgpu::TextureFormat::Rgba16Float,
+            wgpu::TextureFormat::Rgba16Float,
+        ];
+        let targets = formats.map(|format| wgpu::ColorTargetState {
+            blend: None,
+            ..color_target(format)
+        });
+        assert_eq!(validate_color_targets(&targets, &formats), Ok(()));
+        assert_eq!(
+            validate_color_targets(&targets, &formats[..1]),
+            Err(RenderResourceError::ColorTargetCountMismatch {
+                pipeline: 3,
+                attachments: 1,
+            })
+        );
+
+        let too_many = vec![color_target(formats[0]); MAX_COLOR_ATTACHMENTS + 1];
+        assert_eq!(
+            validate_color_targets(&too_many, &[formats[0]; MAX_COLOR_ATTACHMENTS + 1]),
+            Err(RenderResourceError::TooManyColorTargets { count: 5 })
+        );
+    }
+
+    #[test]
+    fn mixed_attachment_sample_counts_are_rejected() {
+        assert_eq!(validate_sample_counts(&[4], Some(4), 4), Ok(()));
+        assert_eq!(validate_sample_counts(&[], None, 4), Ok(()));
+
+        let err = validate_sample_counts(&[4], Some(1), 4).unwrap_err();
+        assert_eq!(
+            err,
+            RenderResourceError::AttachmentSampleCountMismatch {
+                attachment: PassAttachment::DepthStencil,
+                samples: 1,
+                first: PassAttachment::Color(0),
+                expected: 4,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "The depth stencil attachment has a sample count of 1, but the color attachment 0 has a sample count of 4"
+        );
+        assert!(validate_sample_counts(&[1, 4], None, 1).is_err());
+
+        assert_eq!(
+            validate_sample_counts(&[1], Some(1), 4),
+            Err(RenderResourceError::PipelineSampleCountMismatch {
+                pipeline: 4,
+                attachments: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn rgba32_float_target_is_not_blendable() {
+        let format = wgpu::TextureFormat::Rgba32Float;
+        let mut target = color_target(format);
+        assert_eq!(
+            validate_color_targets(&[target.clone()], &[format]),
+            Err(RenderResourceError::UnblendableColorTarget { index: 0, format })
+        );
+        target.blend = None;
+        assert_eq!(validate_color_targets(&[target], &[format]), Ok(()));
+    }
+
+    #[test]
+    fn pipeline_color_targets_are_validated_without_attachments() {
+        let hdr = wgpu::TextureFormat::Rgba16Float;
+        let unblendable = wgpu::TextureFormat::Rgba32Float;
+        assert_eq!(validate_pipeline_color_targets(&[]), Ok(()));
+        assert_eq!(
+            validate_pipeline_color_targets(&[color_target(hdr), color_target(hdr)]),
+            Ok(())
+        );
+        assert_eq!(
+            validate_pipeline_color_targets(&[color_target(hdr), color_target(unblendable)]),
+            Err(RenderResourceError::UnblendableColorTarget {
+                index: 1,
+                format: unblendable,
+            })
+        );
+        assert_eq!(
+            validate_pipeline_color_targets(&vec![color_target(hdr); MAX_COLOR_ATTACHMENTS + 1]),
+            Err(RenderResourceError::TooManyColorTargets {
+                count: MAX_COLOR_ATTACHMENTS + 1,
+            })
+        );
+    }
+
+    #[test]
+    fn float_formats_can_be_render_attachments() {
+        // The features enabled by the default `WgpuOptions`
+        let features = wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+        for format in [
+            wgpu::TextureFormat::Rgba16Float,
+            wgpu::TextureFormat::Rgba32Float,
+        ] {
+            let desc = wgpu::TextureDescriptor {
+                label: Some("hdr"),
+                size: wgpu::Extent3d::default(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+            };
+            assert_eq!(validate_texture_usage(features, &desc), Ok(()));
+        }
+
+        let desc = wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d::default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Bc1RgbaUnorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        };
+        assert!(validate_texture_usage(features, &desc).is_err());
+    }
+
+    #[test]
+    fn adapter_specific_features_only_relax_uncompressed_formats() {
+        let adapter_specific = wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+        let mut desc = wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d::default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::STORAGE_BINDING,
+        };
+        // Whether an sRGB storage texture works depends on the adapter.
+        assert!(validate_texture_usage(wgpu::Features::empty(), &desc).is_err());
+        assert_eq!(validate_texture_usage(adapter_specific, &desc), Ok(()));
+
+        // No adapter can render into a compressed texture.
+        desc.format = wgpu::TextureFormat::Bc1RgbaUnorm;
+        desc.usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        assert!(validate_texture_usage(adapter_specific, &desc).is_err());
+    }
+
+    #[test]
+    fn mip_copy_uses_size_of_smaller_level() {
+        let desc = wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 256,
+                height: 128,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 4,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+        };
+        let half = wgpu::Extent3d {
+            width: 128,
+            height: 64,
+            depth_or_array_layers: 1,
+        };
+        assert_eq!(mip_copy_size(&desc, 0, 1), Ok(half));
+        assert_eq!(mip_copy_size(&desc, 1, 0), Ok(half));
+        assert_eq!(
+            mip_copy_size(&desc, 1, 3),
+            Ok(wgpu::Extent3d {
+                width: 32,
+                height: 16,
+                depth_or_array_layers: 1,
+            })
+        );
+
+        assert_eq!(
+            mip_copy_size(&desc, 0, 4),
+            Err(RenderResourceError::InvalidMipLevel {
+                level: 4,
+                mip_level_count: 4
+            })
+        );
+        assert_eq!(
+            mip_copy_size(&desc, 2, 2),
+            Err(RenderResourceError::SameMipLevel { level: 2 })
+        );
+        let desc = wgpu::TextureDescriptor {
+            usage: wgpu::TextureUsages::COPY_DST,
+            ..desc
+        };
+        assert_eq!(
+            mip_copy_size(&desc, 0, 1),
+            Err(RenderResourceError::MissingTextureUsage {
+                missing: wgpu::TextureUsages::COPY_SRC
+            })
+        );
+    }
+
+    #[test]
+    fn zero_sized_resources_are_rejected() {
+        let buffer = wgpu::BufferDescriptor {
+            label: Some("empty_mesh"),
+            size: 0,
+            usage: wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        };
+        assert_eq!(
+            validate_buffer_size(&buffer),
+            Err(RenderResourceError::ZeroSizedBuffer {
+                label: Some("empty_mesh".to_string())
+            })
+        );
+        assert_eq!(
+            validate_buffer_size(&wgpu::BufferDescriptor { size: 4, ..buffer }),
+            Ok(())
+        );
+
+        let size = wgpu::Extent3d {
+            width: 64,
+            height: 0,
+            depth_or_array_layers: 1,
+        };
+        let texture = wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+        };
+        assert_eq!(
+            validate_texture_size(&texture),
+            Err(RenderResourceError::ZeroSizedTexture { label: None, size })
+        );
+        let minimal = wgpu::TextureDescriptor {
+            size: minimal_texture_size(size),
+            ..texture
+        };
+        assert_eq!(minimal.size.width, 64);
+        assert_eq!(minimal.size.height, 1);
+        assert_eq!(validate_texture_size(&minimal), Ok(()));
+    }
+}
@@ -1,18 +1,22 @@
 use super::image_texture_conversion::image_to_texture;
 use crate::{
-    render_asset::{PrepareAssetError, RenderAsset},
-    render_resource::{Sampler, Texture, TextureView},
-    renderer::{RenderDevice, RenderQueue},
+    color::SrgbColorSpace,
+    render_asset::{PrepareAssetError, RenderAsset, RenderAssets},
+    render_resource::{Sampler, StagingBuffer, Texture, TextureView},
+    renderer::{RenderDevice, RenderQueue, RenderResourceError},
     texture::BevyDefault,
 };
-use bevy_asset::HandleUntyped;
-use bevy_ecs::system::{lifetimeless::SRes, SystemParamItem};
+use bevy_asset::{Handle, HandleUntyped};
+use bevy_ecs::system::{
+    lifetimeless::{SRes, SResMut},
+    SystemParamItem,
+};
 use bevy_math::{Size, Vec2};
 use bevy_reflect::TypeUuid;
 use thiserror::Error;
 use wgpu::{
-    Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d, TextureDimension, TextureFormat,
-    TextureViewDescriptor,
+    AddressMode, Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d, TextureDimension,
+    TextureFormat, TextureViewDescriptor,
 };
 
 pub const TEXTURE_ASSET_INDEX: u64 = 0;
@@ -29,6 +33,68 @@ pub struct Image {
     pub sampler_descriptor: wgpu::SamplerDescriptor<'static>,
 }
 
+/// The [`AddressMode`]s of a sampler along each texture axis.
+///
+/// The address mode decides how texture coordinates outside of `0.0..=1.0` are sampled: clamped
+/// to the edge of the texture, repeated, or repeated while mirroring every other copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageAddressModes {
+    /// How to address the texture along the horizontal (U) axis
+    pub address_mode_u: AddressMode,
+    /// How to address the texture along the vertical (V) axis
+    pub address_mode_v: AddressMode,
+    /// How to address the texture along the depth (W) axis
+    pub address_mode_w: AddressMode,
+}
+
+impl ImageAddressModes {
+    /// Uses `mode` along all axes.
+    pub const fn all(mode: AddressMode) -> Self {
+        Self {
+            address_mode_u: mode,
+            address_mode_v: mode,
+            address_mode_w: mode,
+        }
+    }
+
+    /// Clamps coordinates to the edge of the texture along all axes. This is the default.
+    pub const fn clamp_all() -> Self {
+        Self::all(AddressMode::ClampToEdge)
+    }
+
+    /// Repeats the texture along all axes.
+    pub const fn repeat_all() -> Self {
+        Self::all(AddressMode::Repeat)
+    }
+
+    /// Repeats the texture along all axes, mirroring every other repetition.
+    pub const fn mirror_repeat_all() -> Self {
+        Self::all(AddressMode::MirrorRepeat)
+    }
+
+    /// Reads the address modes of `descriptor`.
+    pub fn from_sampler_descriptor(descriptor: &wgpu::SamplerDescriptor) -> Self {
+        Self {
+            address_mode_u: descriptor.address_mode_u,
+            address_mode_v: descriptor.address_mode_v,
+            address_mode_w: descriptor.address_mode_w,
+        }
+    }
+
+    /// Writes these address modes to `descriptor`, leaving its other fields untouched.
+    pub fn apply_to(self, descriptor: &mut wgpu::SamplerDescriptor) {
+        descriptor.address_mode_u = self.address_mode_u;
+        descriptor.address_mode_v = self.address_mode_v;
+        descriptor.address_mode_w = self.address_mode_w;
+    }
+}
+
+impl Default for ImageAddressModes {
+    fn default() -> Self {
+        Self::clamp_all()
+    }
+}
+
 impl Default for Image {
     fn default() -> Self {
         let format = wgpu::TextureFormat::bevy_default();
@@ -113,11 +179,89 @@ impl Image {
         value
     }
 
+    /// Returns the address modes of the sampler of this image.
+    pub fn address_modes(&self) -> ImageAddressModes {
+        ImageAddressModes::from_sampler_descriptor(&self.sampler_descriptor)
+    }
+
+    /// Sets the address modes of the sampler of this image.
+    pub fn set_address_modes(&mut self, address_modes: ImageAddressModes) {
+        address_modes.apply_to(&mut self.sampler_descriptor);
+    }
+
     /// Returns the aspect ratio (height/width) of a 2D image.
     pub fn aspect_2d(&self) -> f32 {
         self.texture_descriptor.size.height as f32 / self.texture_descriptor.size.width as f32
     }
 
+    /// Returns the layout of [`Image::data`], as it is uploaded to the GPU.
+    ///
+    /// The pixels are stored row by row, and the layers of an array texture, or the slices of a
+    /// 3D texture, one after the other. `rows_per_image` is the height of the texture, so that
+    /// every layer of the texture is read from its own part of the data.
+    pub fn data_layout(&self) -> ImageDataLayout {
+        data_layout(
+            self.texture_descriptor.size,
+            &self.texture_descriptor.format,
+        )
+    }
+
+    /// Returns the data of the mip level `level`, or `None` if [`Image::data`] doesn't hold it.
+    ///
+    /// The mip levels are stored one after the other, starting with the full size image.
+    pub fn mip_level_data(&self, level: u32) -> Option<&[u8]> {
+        let pixel_size = self.texture_descriptor.format.pixel_size();
+        let mut offset = 0;
+        for current in 0..=level {
+            let len = self.texture_descriptor.mip_level_size(current)?.volume() * pixel_size;
+            if current == level {
+                return self.data.get(offset..offset + len);
+            }
+            offset += len;
+        }
+        None
+    }
+
+    /// Generates the mip levels of the image, up to `mip_level_count` levels including the full
+    /// size image, by repeatedly averaging blocks of 2x2 pixels.
+    ///
+    /// Each level is half the size of the previous one, rounded down, down to a single pixel, so
+    /// `mip_level_count` is clamped to the length of the full mip chain. sRGB images are
+    /// averaged in linear space. Sampling a texture with mip levels avoids the aliasing of
+    /// textures seen from far away; images are uploaded without mip levels unless this is called.
+    ///
+    /// Only 2D images with a single layer, of the [`TextureFormat::Rgba8Unorm`],
+    /// [`TextureFormat::Rgba8UnormSrgb`], [`TextureFormat::Bgra8Unorm`] and
+    /// [`TextureFormat::Bgra8UnormSrgb`] formats are supported.
+    pub fn generate_mipmaps(&mut self, mip_level_count: u32) -> Result<(), TextureError> {
+        let format = self.texture_descriptor.format;
+        let srgb = match format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Bgra8Unorm => false,
+            TextureFormat::Rgba8UnormSrgb | TextureFormat::Bgra8UnormSrgb => true,
+            _ => return Err(TextureError::UnsupportedMipmapFormat(format)),
+        };
+        let size = self.texture_descriptor.size;
+        if self.texture_descriptor.dimension != TextureDimension::D2
+            || size.depth_or_array_layers != 1
+        {
+            return Err(TextureError::UnsupportedMipmapDimension);
+        }
+
+        let full_mip_chain = 32 - size.width.max(size.height).leading_zeros();
+        let mip_level_count = mip_level_count.clamp(1, full_mip_chain);
+        self.data.truncate(size.volume() * format.pixel_size());
+        let (mut width, mut height) = (size.width, size.height);
+        let mut level = self.data.clone();
+        for _ in 1..mip_level_count {
+            level = downsample_rgba8(&level, width, height, srgb);
+            width = (width / 2).max(1);
+            height = (height / 2).max(1);
+            self.data.extend_from_slice(&level);
+        }
+        self.texture_descriptor.mip_level_count = mip_level_count;
+        Ok(())
+    }
+
     /// Returns the size of a 2D image.
     pub fn size(&self) -> Vec2 {
         Vec2::new(
@@ -225,6 +369,55 @@ impl Image {
     }
 }
 
+/// Returns the layout of the data of an image of `size` and `format`.
+fn data_layout(size: Extent3d, format: &TextureFormat) -> ImageDataLayout {
+    ImageDataLayout {
+        offset: 0,
+        bytes_per_row: Some(
+            std::num::NonZeroU32::new(size.width * format.pixel_size() as u32).unwrap(),
+        ),
+        rows_per_image: if size.depth_or_array_layers > 1 {
+            std::num::NonZeroU32::new(size.height)
+        } else {
+            None
+        },
+    }
+}
+
+/// Halves the size of an image of `width` by `height` pixels of 4 bytes, rounding down, by
+/// averaging each block of 2x2 pixels.
+fn downsample_rgba8(source: &[u8], width: u32, height: u32, srgb: bool) -> Vec<u8> {
+    // The alpha channel is always linear.
+    let is_srgb = |channel: u32| srgb && channel < 3;
+    let (destination_width, destination_height) = ((width / 2).max(1), (height / 2).max(1));
+    let mut destination = Vec::with_capacity((destination_width * destination_height * 4) as usize);
+    for y in 0..destination_height {
+        for x in 0..destination_width {
+            for channel in 0..4 {
+                let mut sum = 0.0;
+                for (source_x, source_y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let source_x = (2 * x + source_x).min(width - 1);
+                    let source_y = (2 * y + source_y).min(height - 1);
+                    let value = source[((source_y * width + source_x) * 4 + channel) as usize]
+                        as f32
+                        / 255.0;
+                    sum += if is_srgb(channel) {
+                        value.nonlinear_to_linear_srgb()
+                    } else {
+                        value
+                    };
+                }
+                let mut average = sum / 4.0;
+                if is_srgb(channel) {
+                    average = average.linear_to_nonlinear_srgb();
+                }
+                destination.push((average * 255.0).round().clamp(0.0, 255.0) as u8);
+            }
+        }
+    }
+    destination
+}
+
 /// An error that occurs when loading a texture
 #[derive(Error, Debug)]
 pub enum TextureError {
@@ -234,6 +427,10 @@ pub enum TextureError {
     InvalidImageExtension(String),
     #[error("failed to load an image: {0}")]
     ImageError(#[from] image::ImageError),
+    #[error("can't generate the mipmaps of an image of format {0:?}")]
+    UnsupportedMipmapFormat(TextureFormat),
+    #[error("mipmaps can only be generated for 2D images with a single layer")]
+    UnsupportedMipmapDimension,
 }
 
 /// The type of a raw image buffer.
@@ -391,10 +588,29 @@ pub struct GpuImage {
     pub size: Size,
 }
 
+impl GpuImage {
+    /// Looks up the [`GpuImage`] prepared for `handle`.
+    ///
+    /// Returns [`RenderResourceError::TextureNotFound`] with the id of `handle` if the image hasn't
+    /// been prepared yet, or has been removed since.
+    pub fn get<'a>(
+        gpu_images: &'a RenderAssets<Image>,
+        handle: &Handle<Image>,
+    ) -> Result<&'a GpuImage, RenderResourceError> {
+        gpu_images
+            .get(handle)
+            .ok_or(RenderResourceError::TextureNotFound(handle.id))
+    }
+}
+
 impl RenderAsset for Image {
     type ExtractedAsset = Image;
     type PreparedAsset = GpuImage;
-    type Param = (SRes<RenderDevice>, SRes<RenderQueue>);
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<RenderQueue>,
+        SResMut<StagingBuffer>,
+    );
 
     /// Clones the Image.
     fn extract_asset(&self) -> Self::ExtractedAsset {
@@ -404,36 +620,33 @@ impl RenderAsset for Image {
     /// Converts the extracted image into a [`GpuImage`].
     fn prepare_asset(
         image: Self::ExtractedAsset,
-        (render_device, render_queue): &mut SystemParamItem<Self::Param>,
+        (render_device, render_queue, staging_buffer): &mut SystemParamItem<Self::Param>,
     ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
         let texture = render_device.create_texture(&image.texture_descriptor);
         let sampler = render_device.create_sampler(&image.sampler_descriptor);
 
-        let format_size = image.texture_descriptor.format.pixel_size();
-        render_queue.write_texture(
-            ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &image.data,
-            ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(
-                    std::num::NonZeroU32::new(
-                        image.texture_descriptor.size.width * format_size as u32,
-                    )
-                    .unwrap(),
-                ),
-                rows_per_image: if image.texture_descriptor.size.depth_or_array_layers > 1 {
-                    std::num::NonZeroU32::new(image.texture_descriptor.size.height)
-                } else {
-                    None
+        for mip_level in 0..image.texture_descriptor.mip_level_count {
+            let (data, size) = match (
+                image.mip_level_data(mip_level),
+                image.texture_descriptor.mip_level_size(mip_level),
+            ) {
+                (Some(data), Some(size)) => (data, size),
+                _ => break,
+            };
+            staging_buffer.write_texture(
+                render_device,
+                render_queue,
+                ImageCopyTexture {
+                    texture: &texture,
+                    mip_level,
+                    origin: Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
                 },
-            },
-            image.texture_descriptor.size,
-        );
+                data,
+                data_layout(size, &image.texture_descriptor.format),
+                size,
+            );
+        }
 
         let texture_view = texture.create_view(&TextureViewDescriptor::default());
         let size = Size::new(
@@ -453,6 +666,17 @@ impl RenderAsset for Image {
 mod test {
 
     use super::*;
+    use bevy_asset::HandleId;
+
+    #[test]
+    fn missing_gpu_image_reports_its_handle() {
+        let gpu_images = RenderAssets::<Image>::default();
+        let handle = Handle::<Image>::weak(HandleId::random::<Image>());
+        assert_eq!(
+            GpuImage::get(&gpu_images, &handle).unwrap_err(),
+            RenderResourceError::TextureNotFound(handle.id)
+        );
+    }
 
     #[test]
     fn image_size() {
@@ -477,4 +701,129 @@ mod test {
         let image = Image::default();
         assert_eq!(Vec2::new(1.0, 1.0), image.size());
     }
+
+    #[test]
+    fn address_modes_round_trip() {
+        let mut image = Image::default();
+        assert_eq!(image.address_modes(), ImageAddressModes::clamp_all());
+
+        let address_modes = ImageAddressModes {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::MirrorRepeat,
+        };
+        image.set_address_modes(address_modes);
+        assert_eq!(image.address_modes(), address_modes);
+        assert_eq!(
+            image.sampler_descriptor,
+            wgpu::SamplerDescriptor {
+                address_mode_u: AddressMode::Repeat,
+                address_mode_v: AddressMode::ClampToEdge,
+                address_mode_w: AddressMode::MirrorRepeat,
+                ..Default::default()
+            }
+        );
+
+        image.set_address_modes(ImageAddressModes::repeat_all());
+        assert_eq!(image.sampler_descriptor.address_mode_v, AddressMode::Repeat);
+    }
+
+    #[test]
+    fn rows_are_as_long_as_the_pixels_of_the_format() {
+        let size = Extent3d {
+            width: 3,
+            height: 2,
+            depth_or_array_layers: 1,
+        };
+        for (format, pixel_size) in [
+            (TextureFormat::R8Unorm, 1),
+            (TextureFormat::Rg8Unorm, 2),
+            (TextureFormat::Rgba8UnormSrgb, 4),
+            (TextureFormat::Rgba32Float, 16),
+        ] {
+            assert_eq!(format.pixel_size(), pixel_size);
+            let data = vec![0; 3 * 2 * pixel_size];
+            let image = Image::new(size, TextureDimension::D2, data, format);
+            let layout = image.data_layout();
+            assert_eq!(layout.bytes_per_row.unwrap().get(), 3 * pixel_size as u32);
+            assert_eq!(layout.rows_per_image, None);
+        }
+    }
+
+    #[test]
+    fn array_layers_are_read_from_their_own_data() {
+        let size = Extent3d {
+            width: 4,
+            height: 2,
+            depth_or_array_layers: 2,
+        };
+        let layer_len = 4 * 2 * 4;
+        let mut data = vec![1; layer_len];
+        data.extend(vec![2; layer_len]);
+
+        for dimension in [TextureDimension::D2, TextureDimension::D3] {
+            let image = Image::new(size, dimension, data.clone(), TextureFormat::Rgba8Unorm);
+            let layout = image.data_layout();
+            let layer_stride = layout.bytes_per_row.unwrap().get() as usize
+                * layout.rows_per_image.unwrap().get() as usize;
+            assert_eq!(layer_stride, layer_len);
+
+            let layers = image.data[layout.offset as usize..]
+                .chunks(layer_stride)
+                .collect::<Vec<_>>();
+            assert_eq!(layers, [&data[..layer_len], &data[layer_len..]]);
+        }
+    }
+
+    #[test]
+    fn generate_mipmaps_of_4x4_image() {
+        let size = Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 1,
+        };
+        // The left half is black, the right half is white.
+        let data = (0..16)
+            .flat_map(|pixel| {
+                let value = if pixel % 4 < 2 { 0 } else { 255 };
+                [value, value, value, 255]
+            })
+            .collect();
+        let mut image = Image::new(size, TextureDimension::D2, data, TextureFormat::Rgba8Unorm);
+        image.generate_mipmaps(3).unwrap();
+
+        assert_eq!(image.texture_descriptor.mip_level_count, 3);
+        assert_eq!(image.data.len(), 4 * (16 + 4 + 1));
+        assert_eq!(
+            image.mip_level_data(1).unwrap(),
+            [0, 0, 0, 255, 255, 255, 255, 255, 0, 0, 0, 255, 255, 255, 255, 255]
+        );
+        assert_eq!(image.mip_level_data(2).unwrap(), [128, 128, 128, 255]);
+        assert!(image.mip_level_data(3).is_none());
+
+        // The mip chain of a 4x4 image has 3 levels, and sRGB is averaged in linear space.
+        let mut srgb = Image::new_fill(
+            size,
+            TextureDimension::D2,
+            &[0, 0, 0, 255, 255, 255, 255, 255],
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        srgb.generate_mipmaps(8).unwrap();
+        assert_eq!(srgb.texture_descriptor.mip_level_count, 3);
+        assert_eq!(srgb.mip_level_data(2).unwrap(), [188, 188, 188, 255]);
+
+        let mut float = Image::new_fill(
+            size,
+            TextureDimension::D2,
+            &[0; 16],
+            TextureFormat::Rgba32Float,
+        );
+        assert!(matches!(
+            float.generate_mipmaps(3),
+            Err(TextureError::UnsupportedMipmapFormat(
+                TextureFormat::Rgba32Float
+            ))
+        ));
+        assert_eq!(float.texture_descriptor.mip_level_count, 1);
+    }
 }
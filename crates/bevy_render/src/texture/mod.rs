@@ -14,7 +14,10 @@ pub use hdr_texture_loader::*;
 pub use image_texture_loader::*;
 pub use texture_cache::*;
 
-use crate::{render_asset::RenderAssetPlugin, RenderApp, RenderStage};
+use crate::{
+    render_asset::RenderAssetPlugin, render_resource::ResourcePoolSettings, renderer::RenderDevice,
+    RenderApp, RenderStage,
+};
 use bevy_app::{App, Plugin};
 use bevy_asset::{AddAsset, Assets};
 
@@ -48,8 +51,18 @@ impl Plugin for ImagePlugin {
             .set_untracked(DEFAULT_IMAGE_HANDLE, Image::default());
 
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            let mut texture_cache = TextureCache::default();
+            let world = &render_app.world;
+            if let (Some(render_device), Some(settings)) = (
+                world.get_resource::<RenderDevice>(),
+                world.get_resource::<ResourcePoolSettings>(),
+            ) {
+                for (descriptor, count) in &settings.textures {
+                    texture_cache.preallocate(render_device, descriptor.clone(), *count);
+                }
+            }
             render_app
-                .init_resource::<TextureCache>()
+                .insert_resource(texture_cache)
                 .add_system_to_stage(RenderStage::Cleanup, update_texture_cache_system);
         }
     }
@@ -12,6 +12,8 @@ struct CachedTextureMeta {
     texture: Texture,
     default_view: TextureView,
     taken: bool,
+    /// Pre-allocated textures are never freed.
+    pinned: bool,
     frames_since_last_use: usize,
 }
 
@@ -58,6 +60,7 @@ impl TextureCache {
                     default_view: default_view.clone(),
                     frames_since_last_use: 0,
                     taken: true,
+                    pinned: false,
                 });
                 CachedTexture {
                     texture,
@@ -71,6 +74,7 @@ impl TextureCache {
                     texture: texture.clone(),
                     default_view: default_view.clone(),
                     taken: true,
+                    pinned: false,
                     frames_since_last_use: 0,
                 }]);
                 CachedTexture {
@@ -81,7 +85,29 @@ impl TextureCache {
         }
     }
 
-    /// Updates the cache and only retains recently used textures.
+    /// Allocates `count` textures matching the `descriptor` up front. They are handed out by
+    /// [`TextureCache::get`] like any other cached texture, but are never freed.
+    pub fn preallocate(
+        &mut self,
+        render_device: &RenderDevice,
+        descriptor: TextureDescriptor<'static>,
+        count: usize,
+    ) {
+        let textures = self.textures.entry(descriptor.clone()).or_default();
+        textures.extend((0..count).map(|_| {
+            let texture = render_device.create_texture(&descriptor);
+            let default_view = texture.create_view(&TextureViewDescriptor::default());
+            CachedTextureMeta {
+                texture,
+                default_view,
+                taken: false,
+                pinned: true,
+                frames_since_last_use: 0,
+            }
+        }));
+    }
+
+    /// Updates the cache and only retains recently used or pre-allocated textures.
     pub fn update(&mut self) {
         for textures in self.textures.values_mut() {
             for texture in textures.iter_mut() {
@@ -89,7 +115,7 @@ impl TextureCache {
                 texture.taken = false;
             }
 
-            textures.retain(|texture| texture.frames_since_last_use < 3);
+            textures.retain(|texture| texture.pinned || texture.frames_since_last_use < 3);
         }
     }
 }
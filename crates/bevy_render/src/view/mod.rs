@@ -83,6 +83,14 @@ pub struct ExtractedView {
     pub far: f32,
 }
 
+impl ExtractedView {
+    /// Returns the matrix transforming world space positions into this view's clip space.
+    #[inline]
+    pub fn view_proj(&self) -> Mat4 {
+        self.projection * self.transform.compute_matrix().inverse()
+    }
+}
+
 #[derive(Clone, AsStd140)]
 pub struct ViewUniform {
     view_proj: Mat4,
@@ -110,6 +118,8 @@ pub struct ViewUniformOffset {
 pub struct ViewTarget {
     pub view: TextureView,
     pub sampled_target: Option<TextureView>,
+    /// The sample count of the attachment returned by [`ViewTarget::get_color_attachment`]
+    pub sample_count: u32,
 }
 
 impl ViewTarget {
@@ -128,12 +138,26 @@ impl ViewTarget {
             ops,
         }
     }
+
+    /// Returns a color attachment drawing straight into the [`ViewTarget::view`], without
+    /// multisampling, e.g. on top of what the multisampled passes resolved into it.
+    pub fn get_unsampled_color_attachment(
+        &self,
+        ops: Operations<Color>,
+    ) -> RenderPassColorAttachment {
+        RenderPassColorAttachment {
+            view: &self.view,
+            resolve_target: None,
+            ops,
+        }
+    }
 }
 
 #[derive(Component)]
 pub struct ViewDepthTexture {
     pub texture: Texture,
     pub view: TextureView,
+    pub sample_count: u32,
 }
 
 fn prepare_view_uniforms(
@@ -150,7 +174,7 @@ fn prepare_view_uniforms(
         let inverse_view = view.inverse();
         let view_uniforms = ViewUniformOffset {
             offset: view_uniforms.uniforms.push(ViewUniform {
-                view_proj: projection * inverse_view,
+                view_proj: camera.view_proj(),
                 view,
                 inverse_view,
                 projection,
@@ -219,7 +243,44 @@ fn prepare_view_targets(
 
         commands.entity(entity).insert(ViewTarget {
             view: swap_chain_texture.clone(),
+            sample_count: if sampled_target.is_some() {
+                msaa.samples
+            } else {
+                1
+            },
             sampled_target,
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ExtractedView;
+    use crate::camera::{CameraProjection, PerspectiveProjection};
+    use bevy_math::Vec3;
+    use bevy_transform::components::GlobalTransform;
+
+    #[test]
+    fn view_proj_of_known_camera_pose() {
+        let projection = PerspectiveProjection::default();
+        let view = ExtractedView {
+            projection: projection.get_projection_matrix(),
+            transform: GlobalTransform::from_xyz(0.0, 0.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
+            width: 100,
+            height: 100,
+            near: projection.near,
+            far: projection.far,
+        };
+        let view_proj = view.view_proj();
+
+        // The look-at target ends up in the center of the screen, with reversed depth `near / distance`.
+        let center = view_proj.project_point3(Vec3::ZERO);
+        assert!(center.truncate().abs().max_element() < 1e-6);
+        assert!((center.z - 0.01).abs() < 1e-6);
+
+        let focal_length = 1.0 / (projection.fov / 2.0).tan();
+        let right = view_proj.project_point3(Vec3::X);
+        assert!((right.x - focal_length / 10.0).abs() < 1e-6);
+        assert!(right.y.abs() < 1e-6);
+    }
+}
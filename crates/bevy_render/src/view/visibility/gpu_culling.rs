@@ -0,0 +1,342 @@
+use crate::{
+    primitives::{Aabb, Frustum},
+    render_resource::{
+        std140::{AsStd140, Std140},
+        std430::{AsStd430, Std430},
+        BindGroupLayout, Buffer, ComputePipeline,
+    },
+    renderer::{RenderDevice, RenderQueue},
+    RenderApp,
+};
+use bevy_app::{App, Plugin};
+use bevy_ecs::world::{FromWorld, World};
+use bevy_math::{Mat4, Vec3, Vec4};
+use wgpu::{
+    util::BufferInitDescriptor, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferBinding, BufferBindingType,
+    BufferSize, BufferUsages, CommandEncoder, ComputePassDescriptor, ComputePipelineDescriptor,
+    PipelineLayoutDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+};
+
+/// The compute shader culling instances against a frustum.
+pub const GPU_CULLING_SHADER: &str = include_str!("gpu_culling.wgsl");
+
+/// Sets up the [`GpuCullingPipeline`], to frustum cull large numbers of instances on the GPU.
+#[derive(Default)]
+pub struct GpuCullingPlugin;
+
+impl Plugin for GpuCullingPlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<GpuCullingPipeline>();
+        }
+    }
+}
+
+/// The world space bounds of an instance culled by the [`GpuCullingPipeline`].
+#[derive(Debug, Clone, Copy, PartialEq, AsStd430)]
+pub struct GpuInstanceBounds {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+}
+
+impl GpuInstanceBounds {
+    /// The world space axis-aligned box enclosing `aabb` transformed by `model_to_world`.
+    pub fn from_aabb(aabb: &Aabb, model_to_world: &Mat4) -> Self {
+        let abs_axes = [
+            model_to_world.x_axis.truncate().abs(),
+            model_to_world.y_axis.truncate().abs(),
+            model_to_world.z_axis.truncate().abs(),
+        ];
+        Self {
+            center: model_to_world.transform_point3(aabb.center),
+            half_extents: abs_axes[0] * aabb.half_extents.x
+                + abs_axes[1] * aabb.half_extents.y
+                + abs_axes[2] * aabb.half_extents.z,
+        }
+    }
+}
+
+#[derive(AsStd140)]
+struct GpuFrustum {
+    planes: [Vec4; 6],
+}
+
+/// The arguments of an indexed indirect draw, as read by
+/// [`TrackedRenderPass::draw_indexed_indirect`](crate::render_phase::TrackedRenderPass::draw_indexed_indirect).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsStd430)]
+pub struct DrawIndexedIndirectArgs {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32,
+}
+
+/// Frustum culls instances on the GPU.
+///
+/// A dispatch tests the [`GpuInstanceBounds`] of each instance against the frustum, writes the
+/// indices of the visible instances to the front of a storage buffer of `u32`s, and counts them in
+/// the `instance_count` of [`DrawIndexedIndirectArgs`]. Drawing with these arguments draws one
+/// instance per visible instance, whose vertex shader looks up the index of the instance to draw
+/// from the visible instances with its `instance_index`. The order of the visible instances is
+/// unspecified.
+pub struct GpuCullingPipeline {
+    pub layout: BindGroupLayout,
+    pub pipeline: ComputePipeline,
+}
+
+impl GpuCullingPipeline {
+    /// The number of instances culled by each workgroup
+    pub const WORKGROUP_SIZE: u32 = 64;
+
+    /// Records the culling of the first `instance_count` [`GpuInstanceBounds`] of `instances`
+    /// against `frustum`.
+    ///
+    /// `visible_instances` must hold at least `instance_count` `u32`s. The arguments of an indexed
+    /// draw of `index_count` indices are written to `indirect`, with an `instance_count` of zero
+    /// that the dispatch then increments, so they must be submitted before `encoder`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cull(
+        &self,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        encoder: &mut CommandEncoder,
+        frustum: &Frustum,
+        instances: &Buffer,
+        instance_count: u32,
+        visible_instances: &Buffer,
+        indirect: &Buffer,
+        index_count: u32,
+    ) {
+        if instance_count == 0 {
+            return;
+        }
+        let args = DrawIndexedIndirectArgs {
+            index_count,
+            instance_count: 0,
+            first_index: 0,
+            base_vertex: 0,
+            first_instance: 0,
+        };
+        render_queue.write_buffer(indirect, 0, args.as_std430().as_bytes());
+
+        let frustum = GpuFrustum {
+            planes: frustum.planes.map(|plane| plane.normal_d),
+        };
+        let frustum_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("gpu_culling_frustum_buffer"),
+            contents: frustum.as_std140().as_bytes(),
+            usage: BufferUsages::UNIFORM,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("gpu_culling_bind_group"),
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: frustum_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Buffer(BufferBinding {
+                        buffer: instances,
+                        offset: 0,
+                        size: BufferSize::new(
+                            instance_count as u64 * GpuInstanceBounds::std430_size_static() as u64,
+                        ),
+                    }),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: visible_instances.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: indirect.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("gpu_culling_pass"),
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch(workgroup_count(instance_count), 1, 1);
+    }
+}
+
+impl FromWorld for GpuCullingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.get_resource::<RenderDevice>().unwrap();
+        let storage = |read_only, min_binding_size| BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: BufferSize::new(min_binding_size),
+        };
+        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("gpu_culling_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(GpuFrustum::std140_size_static() as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: storage(true, GpuInstanceBounds::std430_size_static() as u64),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: storage(false, std::mem::size_of::<u32>() as u64),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: storage(false, DrawIndexedIndirectArgs::std430_size_static() as u64),
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = render_device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("gpu_culling_shader"),
+            source: ShaderSource::Wgsl(GPU_CULLING_SHADER.into()),
+        });
+        let pipeline_layout = render_device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("gpu_culling_pipeline_layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = render_device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("gpu_culling_pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cull",
+        });
+
+        GpuCullingPipeline { layout, pipeline }
+    }
+}
+
+/// The number of workgroups needed to cull `instance_count` instances.
+pub fn workgroup_count(instance_count: u32) -> u32 {
+    let size = GpuCullingPipeline::WORKGROUP_SIZE;
+    match instance_count % size {
+        0 => instance_count / size,
+        _ => instance_count / size + 1,
+    }
+}
+
+/// Culls `instances` against `frustum` on the CPU the way the [`GpuCullingPipeline`] does,
+/// returning the indices of the visible ones in order.
+pub fn cull_instances(frustum: &Frustum, instances: &[GpuInstanceBounds]) -> Vec<u32> {
+    instances
+        .iter()
+        .enumerate()
+        .filter(|(_, bounds)| {
+            let aabb = Aabb {
+                center: bounds.center,
+                half_extents: bounds.half_extents,
+            };
+            frustum.intersects_obb(&aabb, &Mat4::IDENTITY)
+        })
+        .map(|(index, _)| index as u32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitives::Plane, render_resource::ProcessedShader};
+
+    /// The box `-10 <= x, y, z <= 10`.
+    fn box_frustum() -> Frustum {
+        let plane = |normal: Vec3| Plane {
+            normal_d: normal.extend(10.0),
+        };
+        Frustum {
+            planes: [
+                plane(Vec3::X),
+                plane(-Vec3::X),
+                plane(Vec3::Y),
+                plane(-Vec3::Y),
+                plane(Vec3::Z),
+                plane(-Vec3::Z),
+            ],
+        }
+    }
+
+    #[test]
+    fn culling_counts_visible_instances() {
+        let cube = Aabb {
+            center: Vec3::ZERO,
+            half_extents: Vec3::ONE,
+        };
+        let instances = [
+            // Inside, straddling a plane, outside, and outside on the diagonal
+            Mat4::IDENTITY,
+            Mat4::from_translation(Vec3::new(10.5, 0.0, 0.0)),
+            Mat4::from_translation(Vec3::new(0.0, -12.0, 0.0)),
+            Mat4::from_translation(Vec3::new(0.0, 0.0, 20.0)),
+            // Rotating by 45° grows the bounds enough to reach into the frustum.
+            Mat4::from_translation(Vec3::new(0.0, 11.2, 0.0))
+                * Mat4::from_rotation_z(std::f32::consts::FRAC_PI_4),
+            Mat4::from_translation(Vec3::new(0.0, 11.2, 0.0)),
+        ]
+        .iter()
+        .map(|transform| GpuInstanceBounds::from_aabb(&cube, transform))
+        .collect::<Vec<_>>();
+
+        assert_eq!(cull_instances(&box_frustum(), &instances), vec![0, 1, 4]);
+        assert_eq!(workgroup_count(1), 1);
+        assert_eq!(workgroup_count(64), 1);
+        assert_eq!(workgroup_count(65), 2);
+    }
+
+    #[test]
+    fn culling_shader_matches_buffer_layouts() {
+        let reflection = ProcessedShader::Wgsl(GPU_CULLING_SHADER.into())
+            .reflect()
+            .unwrap();
+        let entry_point = &reflection.module.entry_points[0];
+        assert_eq!(entry_point.name, "cull");
+        assert_eq!(
+            entry_point.workgroup_size,
+            [GpuCullingPipeline::WORKGROUP_SIZE, 1, 1]
+        );
+
+        let mut layouter = naga::proc::Layouter::default();
+        layouter
+            .update(&reflection.module.types, &reflection.module.constants)
+            .unwrap();
+        let size_of = |name: &str| {
+            let (handle, _) = reflection
+                .module
+                .types
+                .iter()
+                .find(|(_, ty)| ty.name.as_deref() == Some(name))
+                .unwrap();
+            layouter[handle].size as usize
+        };
+        assert_eq!(
+            size_of("InstanceBounds"),
+            GpuInstanceBounds::std430_size_static()
+        );
+        assert_eq!(
+            size_of("DrawIndexedIndirect"),
+            DrawIndexedIndirectArgs::std430_size_static()
+        );
+        assert_eq!(size_of("Frustum"), GpuFrustum::std140_size_static());
+    }
+}
@@ -0,0 +1,59 @@
+// Frustum culls instances on the GPU, compacting the indices of the visible ones and counting
+// them in the arguments of an indirect draw.
+
+struct Frustum {
+    // xyz: the normal pointing into the frustum, w: the distance along it
+    planes: array<vec4<f32>, 6>;
+};
+
+struct InstanceBounds {
+    // World space axis-aligned bounding box
+    center: vec3<f32>;
+    half_extents: vec3<f32>;
+};
+
+struct Instances {
+    bounds: array<InstanceBounds>;
+};
+
+struct VisibleInstances {
+    indices: array<u32>;
+};
+
+// The arguments of `draw_indexed_indirect`
+struct DrawIndexedIndirect {
+    index_count: u32;
+    instance_count: atomic<u32>;
+    first_index: u32;
+    base_vertex: i32;
+    first_instance: u32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> frustum: Frustum;
+[[group(0), binding(1)]]
+var<storage, read> instances: Instances;
+[[group(0), binding(2)]]
+var<storage, read_write> visible_instances: VisibleInstances;
+[[group(0), binding(3)]]
+var<storage, read_write> indirect: DrawIndexedIndirect;
+
+[[stage(compute), workgroup_size(64)]]
+fn cull([[builtin(global_invocation_id)]] invocation_id: vec3<u32>) {
+    let index = invocation_id.x;
+    if (index >= arrayLength(&instances.bounds)) {
+        return;
+    }
+
+    let bounds = instances.bounds[index];
+    for (var i: i32 = 0; i < 6; i = i + 1) {
+        let plane = frustum.planes[i];
+        let relative_radius = dot(abs(plane.xyz), bounds.half_extents);
+        if (dot(plane.xyz, bounds.center) + plane.w + relative_radius <= 0.0) {
+            return;
+        }
+    }
+
+    let slot = atomicAdd(&indirect.instance_count, 1u);
+    visible_instances.indices[slot] = index;
+}
@@ -1,5 +1,7 @@
+mod gpu_culling;
 mod render_layers;
 
+pub use gpu_culling::*;
 pub use render_layers::*;
 
 use bevy_app::{CoreStage, Plugin};
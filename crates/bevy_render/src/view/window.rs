@@ -6,8 +6,11 @@ use crate::{
 };
 use bevy_app::{App, Plugin};
 use bevy_ecs::prelude::*;
-use bevy_utils::{tracing::debug, HashMap, HashSet};
-use bevy_window::{PresentMode, RawWindowHandleWrapper, WindowId, Windows};
+use bevy_utils::{
+    tracing::{debug, warn},
+    HashMap, HashSet,
+};
+use bevy_window::{PresentMode, RawWindowHandleWrapper, Window, WindowId, Windows};
 use std::ops::{Deref, DerefMut};
 use wgpu::TextureFormat;
 
@@ -46,6 +49,98 @@ pub struct ExtractedWindow {
     pub present_mode: PresentMode,
     pub swap_chain_texture: Option<TextureView>,
     pub size_changed: bool,
+    /// The ratio of physical pixels to logical pixels of the window
+    pub scale_factor: f64,
+    /// Whether the window moved to a monitor with a different scale factor since the last frame
+    pub scale_factor_changed: bool,
+    /// Whether the present mode of the window was changed since the last frame
+    pub present_mode_changed: bool,
+}
+
+impl ExtractedWindow {
+    pub fn new(window: &Window) -> Self {
+        let (physical_width, physical_height) = physical_size(window);
+        ExtractedWindow {
+            id: window.id(),
+            handle: window.raw_window_handle(),
+            physical_width,
+            physical_height,
+            present_mode: window.present_mode(),
+            swap_chain_texture: None,
+            size_changed: false,
+            scale_factor: window.scale_factor(),
+            scale_factor_changed: false,
+            present_mode_changed: false,
+        }
+    }
+
+    /// Updates the extracted window to the current physical size, scale factor and present mode
+    /// of `window`, recording what changed since the last frame.
+    pub fn update(&mut self, window: &Window) {
+        let (new_width, new_height) = physical_size(window);
+        let scale_factor = window.scale_factor();
+        self.size_changed = new_width != self.physical_width || new_height != self.physical_height;
+        self.scale_factor_changed = scale_factor != self.scale_factor;
+        self.present_mode_changed = window.present_mode() != self.present_mode;
+
+        if self.size_changed {
+            debug!(
+                "Window size changed from {}x{} to {}x{}",
+                self.physical_width, self.physical_height, new_width, new_height
+            );
+            self.physical_width = new_width;
+            self.physical_height = new_height;
+        }
+        if self.scale_factor_changed {
+            debug!(
+                "Window scale factor changed from {} to {}",
+                self.scale_factor, scale_factor
+            );
+            self.scale_factor = scale_factor;
+        }
+        if self.present_mode_changed {
+            debug!(
+                "Window present mode changed from {:?} to {:?}",
+                self.present_mode,
+                window.present_mode()
+            );
+            self.present_mode = window.present_mode();
+        }
+    }
+
+    /// Returns `true` if the swap chain has to be recreated to match the window.
+    ///
+    /// Besides resizes, this includes scale factor changes, after which the swap chain is
+    /// recreated at the new physical size even if the platform reports it a frame late, and
+    /// present mode changes.
+    pub fn needs_swap_chain_recreation(&self) -> bool {
+        self.size_changed || self.scale_factor_changed || self.present_mode_changed
+    }
+
+    /// Returns the configuration of the swap chain of the window.
+    ///
+    /// If the surface doesn't support the [`PresentMode`] of the window, wgpu falls back to
+    /// [`PresentMode::Fifo`], which is supported everywhere, when configuring it.
+    pub fn swap_chain_descriptor(&self) -> wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
+            format: TextureFormat::bevy_default(),
+            width: self.physical_width,
+            height: self.physical_height,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            present_mode: match self.present_mode {
+                PresentMode::Fifo => wgpu::PresentMode::Fifo,
+                PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+                PresentMode::Immediate => wgpu::PresentMode::Immediate,
+            },
+        }
+    }
+}
+
+fn physical_size(window: &Window) -> (u32, u32) {
+    (
+        window.physical_width().max(1),
+        window.physical_height().max(1),
+    )
 }
 
 #[derive(Default)]
@@ -70,40 +165,13 @@ impl DerefMut for ExtractedWindows {
 fn extract_windows(mut render_world: ResMut<RenderWorld>, windows: Res<Windows>) {
     let mut extracted_windows = render_world.get_resource_mut::<ExtractedWindows>().unwrap();
     for window in windows.iter() {
-        let (new_width, new_height) = (
-            window.physical_width().max(1),
-            window.physical_height().max(1),
-        );
-
-        let mut extracted_window =
-            extracted_windows
-                .entry(window.id())
-                .or_insert(ExtractedWindow {
-                    id: window.id(),
-                    handle: window.raw_window_handle(),
-                    physical_width: new_width,
-                    physical_height: new_height,
-                    present_mode: window.present_mode(),
-                    swap_chain_texture: None,
-                    size_changed: false,
-                });
+        let extracted_window = extracted_windows
+            .entry(window.id())
+            .or_insert_with(|| ExtractedWindow::new(window));
 
         // NOTE: Drop the swap chain frame here
         extracted_window.swap_chain_texture = None;
-        extracted_window.size_changed = new_width != extracted_window.physical_width
-            || new_height != extracted_window.physical_height;
-
-        if extracted_window.size_changed {
-            debug!(
-                "Window size changed from {}x{} to {}x{}",
-                extracted_window.physical_width,
-                extracted_window.physical_height,
-                new_width,
-                new_height
-            );
-            extracted_window.physical_width = new_width;
-            extracted_window.physical_height = new_height;
-        }
+        extracted_window.update(window);
     }
 }
 
@@ -133,34 +201,91 @@ pub fn prepare_windows(
                 render_instance.create_surface(&window.handle.get_handle())
             });
 
-        let swap_chain_descriptor = wgpu::SurfaceConfiguration {
-            format: TextureFormat::bevy_default(),
-            width: window.physical_width,
-            height: window.physical_height,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            present_mode: match window.present_mode {
-                PresentMode::Fifo => wgpu::PresentMode::Fifo,
-                PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
-                PresentMode::Immediate => wgpu::PresentMode::Immediate,
-            },
-        };
+        let swap_chain_descriptor = window.swap_chain_descriptor();
 
         // Do the initial surface configuration if it hasn't been configured yet
-        if window_surfaces.configured_windows.insert(window.id) || window.size_changed {
+        if window_surfaces.configured_windows.insert(window.id)
+            || window.needs_swap_chain_recreation()
+        {
             render_device.configure_surface(surface, &swap_chain_descriptor);
         }
 
-        let frame = match surface.get_current_texture() {
-            Ok(swap_chain_frame) => swap_chain_frame,
-            Err(wgpu::SurfaceError::Outdated) => {
-                render_device.configure_surface(surface, &swap_chain_descriptor);
-                surface
-                    .get_current_texture()
-                    .expect("Error reconfiguring surface")
-            }
-            err => err.expect("Failed to acquire next swap chain texture!"),
-        };
-
-        window.swap_chain_texture = Some(TextureView::from(frame));
+        // Without a swap chain texture, nothing is drawn to the window this frame.
+        match render_device.get_current_surface_texture(surface, &swap_chain_descriptor) {
+            Ok(frame) => window.swap_chain_texture = Some(TextureView::from(frame)),
+            Err(err) => warn!("Skipping a frame of window {}: {}", window.id, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_window::WindowDescriptor;
+    use raw_window_handle::{RawWindowHandle, WebHandle};
+
+    #[test]
+    fn scale_factor_change_recreates_swap_chain() {
+        let mut window = Window::new(
+            WindowId::primary(),
+            &WindowDescriptor::default(),
+            800,
+            600,
+            1.0,
+            None,
+            RawWindowHandle::Web(WebHandle::empty()),
+        );
+        let mut extracted_window = ExtractedWindow::new(&window);
+        extracted_window.update(&window);
+        assert!(!extracted_window.needs_swap_chain_recreation());
+
+        // Moving the window to a monitor with twice the pixel density
+        window.update_scale_factor_from_backend(2.0);
+        window.update_actual_size_from_backend(1600, 1200);
+        extracted_window.update(&window);
+        assert!(extracted_window.needs_swap_chain_recreation());
+        assert!(extracted_window.scale_factor_changed);
+        assert_eq!(
+            (
+                extracted_window.physical_width,
+                extracted_window.physical_height
+            ),
+            (1600, 1200)
+        );
+
+        extracted_window.update(&window);
+        assert!(!extracted_window.needs_swap_chain_recreation());
+    }
+
+    #[test]
+    fn present_mode_flows_into_swap_chain_descriptor() {
+        let mut window = Window::new(
+            WindowId::primary(),
+            &WindowDescriptor {
+                present_mode: PresentMode::Immediate,
+                ..Default::default()
+            },
+            800,
+            600,
+            1.0,
+            None,
+            RawWindowHandle::Web(WebHandle::empty()),
+        );
+        let mut extracted_window = ExtractedWindow::new(&window);
+        assert_eq!(
+            extracted_window.swap_chain_descriptor().present_mode,
+            wgpu::PresentMode::Immediate
+        );
+
+        window.set_present_mode(PresentMode::Mailbox);
+        extracted_window.update(&window);
+        assert!(extracted_window.needs_swap_chain_recreation());
+        assert_eq!(
+            extracted_window.swap_chain_descriptor().present_mode,
+            wgpu::PresentMode::Mailbox
+        );
+
+        extracted_window.update(&window);
+        assert!(!extracted_window.needs_swap_chain_recreation());
     }
 }
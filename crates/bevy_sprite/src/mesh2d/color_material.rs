@@ -1,6 +1,9 @@
 use bevy_app::{App, Plugin};
 use bevy_asset::{AssetServer, Assets, Handle, HandleUntyped};
-use bevy_ecs::system::{lifetimeless::SRes, SystemParamItem};
+use bevy_ecs::{
+    prelude::*,
+    system::{lifetimeless::SRes, SystemParamItem},
+};
 use bevy_math::Vec4;
 use bevy_reflect::TypeUuid;
 use bevy_render::{
@@ -13,9 +16,13 @@ use bevy_render::{
     },
     renderer::RenderDevice,
     texture::Image,
+    RenderApp, RenderStage,
 };
 
-use crate::{Material2d, Material2dPipeline, Material2dPlugin, MaterialMesh2dBundle};
+use crate::{
+    HasMaterial2d, Material2dPipeline, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle,
+    SpecializedMaterial2d,
+};
 
 pub const COLOR_MATERIAL_SHADER_HANDLE: HandleUntyped =
     HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 3253086872234592509);
@@ -31,7 +38,8 @@ impl Plugin for ColorMaterialPlugin {
             Shader::from_wgsl(include_str!("color_material.wgsl")),
         );
 
-        app.add_plugin(Material2dPlugin::<ColorMaterial>::default());
+        app.add_plugin(Material2dPlugin::<ColorMaterial>::default())
+            .init_resource::<DefaultColorMaterial>();
 
         app.world
             .get_resource_mut::<Assets<ColorMaterial>>()
@@ -43,10 +51,51 @@ impl Plugin for ColorMaterialPlugin {
                     ..Default::default()
                 },
             );
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .add_system_to_stage(RenderStage::Extract, extract_default_color_material)
+                .add_system_to_stage(RenderStage::Prepare, apply_default_color_material);
+        }
+    }
+}
+
+/// The material 2d meshes without a material of any [`Material2d`] type are drawn with.
+///
+/// Defaults to the flat magenta [`Handle::<ColorMaterial>::default()`], so that meshes missing
+/// their material stand out instead of silently not being drawn. Set it to `None` to skip them.
+#[derive(Debug, Clone)]
+pub struct DefaultColorMaterial(pub Option<Handle<ColorMaterial>>);
+
+impl Default for DefaultColorMaterial {
+    fn default() -> Self {
+        Self(Some(Handle::default()))
+    }
+}
+
+fn extract_default_color_material(
+    mut commands: Commands,
+    default_color_material: Res<DefaultColorMaterial>,
+) {
+    if default_color_material.is_changed() {
+        commands.insert_resource(default_color_material.clone());
+    }
+}
+
+/// Gives the [`DefaultColorMaterial`] to the extracted 2d meshes without a material.
+pub fn apply_default_color_material(
+    mut commands: Commands,
+    default_color_material: Res<DefaultColorMaterial>,
+    meshes: Query<Entity, (With<Mesh2dHandle>, Without<HasMaterial2d>)>,
+) {
+    if let Some(material) = &default_color_material.0 {
+        for entity in meshes.iter() {
+            commands.entity(entity).insert(material.clone_weak());
+        }
     }
 }
 
-/// A [2d material](Material2d) that renders [2d meshes](crate::Mesh2dHandle) with a texture tinted by a uniform color
+/// A [2d material](SpecializedMaterial2d) that renders [2d meshes](crate::Mesh2dHandle) with a texture tinted by a uniform color
 #[derive(Debug, Clone, TypeUuid)]
 #[uuid = "e228a544-e3ca-4e1e-bb9d-4d8bc1ad8c19"]
 pub struct ColorMaterial {
@@ -81,21 +130,10 @@ impl From<Handle<Image>> for ColorMaterial {
     }
 }
 
-// NOTE: These must match the bit flags in bevy_sprite/src/mesh2d/color_material.wgsl!
-bitflags::bitflags! {
-    #[repr(transparent)]
-    pub struct ColorMaterialFlags: u32 {
-        const TEXTURE           = (1 << 0);
-        const NONE              = 0;
-        const UNINITIALIZED     = 0xFFFF;
-    }
-}
-
 /// The GPU representation of the uniform data of a [`ColorMaterial`].
 #[derive(Clone, Default, AsStd140)]
 pub struct ColorMaterialUniformData {
     pub color: Vec4,
-    pub flags: u32,
 }
 
 /// The GPU representation of a [`ColorMaterial`].
@@ -106,7 +144,6 @@ pub struct GpuColorMaterial {
     /// The bind group specifying how the [`ColorMaterialUniformData`] and
     /// the texture of the material are bound.
     pub bind_group: BindGroup,
-    pub flags: ColorMaterialFlags,
     pub texture: Option<Handle<Image>>,
 }
 
@@ -136,14 +173,8 @@ impl RenderAsset for ColorMaterial {
             return Err(PrepareAssetError::RetryNextUpdate(material));
         };
 
-        let mut flags = ColorMaterialFlags::NONE;
-        if material.texture.is_some() {
-            flags |= ColorMaterialFlags::TEXTURE;
-        }
-
         let value = ColorMaterialUniformData {
             color: material.color.as_linear_rgba_f32().into(),
-            flags: flags.bits(),
         };
         let value_std140 = value.as_std140();
 
@@ -174,13 +205,38 @@ impl RenderAsset for ColorMaterial {
         Ok(GpuColorMaterial {
             buffer,
             bind_group,
-            flags,
             texture: material.texture,
         })
     }
 }
 
-impl Material2d for ColorMaterial {
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ColorMaterialKey {
+    texture: bool,
+}
+
+impl SpecializedMaterial2d for ColorMaterial {
+    type Key = ColorMaterialKey;
+
+    fn key(render_asset: &<Self as RenderAsset>::PreparedAsset) -> Self::Key {
+        ColorMaterialKey {
+            texture: render_asset.texture.is_some(),
+        }
+    }
+
+    /// Only samples the texture of materials that have one, which are otherwise drawn in their
+    /// color alone.
+    fn specialize(key: Self::Key, descriptor: &mut RenderPipelineDescriptor) {
+        if key.texture {
+            descriptor
+                .fragment
+                .as_mut()
+                .unwrap()
+                .shader_defs
+                .push(String::from("COLOR_MATERIAL_TEXTURE"));
+        }
+    }
+
     fn fragment_shader(_asset_server: &AssetServer) -> Option<Handle<Shader>> {
         Some(COLOR_MATERIAL_SHADER_HANDLE.typed())
     }
@@ -233,3 +289,67 @@ impl Material2d for ColorMaterial {
 
 /// A component bundle for entities with a [`Mesh2dHandle`](crate::Mesh2dHandle) and a [`ColorMaterial`].
 pub type ColorMesh2dBundle = MaterialMesh2dBundle<ColorMaterial>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::schedule::{Stage, SystemStage};
+
+    #[test]
+    fn textured_material_samples_its_texture() {
+        let descriptor = || RenderPipelineDescriptor {
+            label: None,
+            layout: None,
+            vertex: VertexState {
+                shader: Handle::default(),
+                shader_defs: Vec::new(),
+                entry_point: "vertex".into(),
+                buffers: Vec::new(),
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                shader: COLOR_MATERIAL_SHADER_HANDLE.typed(),
+                shader_defs: Vec::new(),
+                entry_point: "fragment".into(),
+                targets: Vec::new(),
+            }),
+        };
+
+        let mut textured = descriptor();
+        ColorMaterial::specialize(ColorMaterialKey { texture: true }, &mut textured);
+        assert_eq!(
+            textured.fragment.unwrap().shader_defs,
+            ["COLOR_MATERIAL_TEXTURE"]
+        );
+
+        let mut untextured = descriptor();
+        ColorMaterial::specialize(ColorMaterialKey { texture: false }, &mut untextured);
+        assert!(untextured.fragment.unwrap().shader_defs.is_empty());
+    }
+
+    #[test]
+    fn mesh_without_material_uses_default_material() {
+        let mut world = World::default();
+        world.insert_resource(DefaultColorMaterial::default());
+        let missing = world.spawn().insert(Mesh2dHandle::default()).id();
+        let custom = world
+            .spawn()
+            .insert_bundle((Mesh2dHandle::default(), HasMaterial2d))
+            .id();
+
+        let mut stage = SystemStage::single(apply_default_color_material);
+        stage.run(&mut world);
+
+        let material = world.get::<Handle<ColorMaterial>>(missing).unwrap();
+        assert_eq!(material.id, Handle::<ColorMaterial>::default().id);
+        assert_eq!(ColorMaterial::default().color, Color::rgb(1.0, 0.0, 1.0));
+        assert!(world.get::<Handle<ColorMaterial>>(custom).is_none());
+
+        world.insert_resource(DefaultColorMaterial(None));
+        world.entity_mut(missing).remove::<Handle<ColorMaterial>>();
+        stage.run(&mut world);
+        assert!(world.get::<Handle<ColorMaterial>>(missing).is_none());
+    }
+}
@@ -3,10 +3,7 @@
 
 struct ColorMaterial {
     color: vec4<f32>;
-    // 'flags' is a bit field indicating various options. u32 is 32 bits so we have up to 32 options.
-    flags: u32;
 };
-let COLOR_MATERIAL_FLAGS_TEXTURE_BIT: u32 = 1u;
 
 [[group(0), binding(0)]]
 var<uniform> view: View;
@@ -34,8 +31,8 @@ struct FragmentInput {
 [[stage(fragment)]]
 fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
     var output_color: vec4<f32> = material.color;
-    if ((material.flags & COLOR_MATERIAL_FLAGS_TEXTURE_BIT) != 0u) {
-        output_color = output_color * textureSample(texture, texture_sampler, in.uv);
-    }
+#ifdef COLOR_MATERIAL_TEXTURE
+    output_color = output_color * textureSample(texture, texture_sampler, in.uv);
+#endif
     return output_color;
 }
\ No newline at end of file
@@ -4,10 +4,10 @@ use bevy_core::FloatOrd;
 use bevy_core_pipeline::Transparent2d;
 use bevy_ecs::{
     entity::Entity,
-    prelude::{Bundle, World},
+    prelude::{Bundle, Component, With, World},
     system::{
         lifetimeless::{Read, SQuery, SRes},
-        Query, Res, ResMut, SystemParamItem,
+        Commands, Query, Res, ResMut, SystemParamItem,
     },
     world::FromWorld,
 };
@@ -173,11 +173,28 @@ impl<M: SpecializedMaterial2d> Plugin for Material2dPlugin<M> {
                 .add_render_command::<Transparent2d, DrawMaterial2d<M>>()
                 .init_resource::<Material2dPipeline<M>>()
                 .init_resource::<SpecializedPipelines<Material2dPipeline<M>>>()
+                .add_system_to_stage(RenderStage::Extract, extract_has_material2d::<M>)
                 .add_system_to_stage(RenderStage::Queue, queue_material2d_meshes::<M>);
         }
     }
 }
 
+/// Marks the 2d meshes in the render world that have a material of any [`SpecializedMaterial2d`]
+/// type.
+///
+/// Meshes without it are drawn with the [`DefaultColorMaterial`](crate::DefaultColorMaterial).
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct HasMaterial2d;
+
+fn extract_has_material2d<M: SpecializedMaterial2d>(
+    mut commands: Commands,
+    query: Query<Entity, (With<Mesh2dHandle>, With<Handle<M>>)>,
+) {
+    for entity in query.iter() {
+        commands.get_or_spawn(entity).insert(HasMaterial2d);
+    }
+}
+
 pub struct Material2dPipeline<M: SpecializedMaterial2d> {
     pub mesh2d_pipeline: Mesh2dPipeline,
     pub material2d_layout: BindGroupLayout,
@@ -290,6 +307,10 @@ pub fn queue_material2d_meshes<M: SpecializedMaterial2d>(
                         }
                         mesh2d_key |=
                             Mesh2dPipelineKey::from_primitive_topology(mesh.primitive_topology);
+                        mesh2d_key |= Mesh2dPipelineKey::from_index_format(
+                            mesh.primitive_topology,
+                            mesh.index_format(),
+                        );
                     }
 
                     let specialized_key = M::key(material2d);
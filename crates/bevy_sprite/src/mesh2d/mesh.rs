@@ -7,7 +7,7 @@ use bevy_ecs::{
 use bevy_math::{Mat4, Size};
 use bevy_reflect::TypeUuid;
 use bevy_render::{
-    mesh::{GpuBufferInfo, Mesh},
+    mesh::{GpuBufferInfo, GpuMesh, Mesh},
     render_asset::RenderAssets,
     render_component::{ComponentUniforms, DynamicUniformIndex, UniformComponentPlugin},
     render_phase::{EntityRenderCommand, RenderCommandResult, TrackedRenderPass},
@@ -235,6 +235,7 @@ bitflags::bitflags! {
     pub struct Mesh2dPipelineKey: u32 {
         const NONE                        = 0;
         const VERTEX_TANGENTS             = (1 << 0);
+        const INDEX_FORMAT_UINT32         = (1 << 1);
         const MSAA_RESERVED_BITS          = Mesh2dPipelineKey::MSAA_MASK_BITS << Mesh2dPipelineKey::MSAA_SHIFT_BITS;
         const PRIMITIVE_TOPOLOGY_RESERVED_BITS = Mesh2dPipelineKey::PRIMITIVE_TOPOLOGY_MASK_BITS << Mesh2dPipelineKey::PRIMITIVE_TOPOLOGY_SHIFT_BITS;
     }
@@ -274,6 +275,36 @@ impl Mesh2dPipelineKey {
             _ => PrimitiveTopology::default(),
         }
     }
+
+    /// Selects the index format the pipeline is specialized for. Only strip topologies depend on
+    /// it, as their primitive restart value is the maximum index of the format.
+    pub fn from_index_format(
+        primitive_topology: PrimitiveTopology,
+        index_format: Option<IndexFormat>,
+    ) -> Self {
+        match (primitive_topology, index_format) {
+            (
+                PrimitiveTopology::LineStrip | PrimitiveTopology::TriangleStrip,
+                Some(IndexFormat::Uint32),
+            ) => Mesh2dPipelineKey::INDEX_FORMAT_UINT32,
+            _ => Mesh2dPipelineKey::NONE,
+        }
+    }
+
+    /// Returns the `strip_index_format` of the pipeline's [`PrimitiveState`], which must be set
+    /// for strip topologies and unset for all others.
+    pub fn strip_index_format(&self) -> Option<IndexFormat> {
+        match self.primitive_topology() {
+            PrimitiveTopology::LineStrip | PrimitiveTopology::TriangleStrip => {
+                if self.contains(Mesh2dPipelineKey::INDEX_FORMAT_UINT32) {
+                    Some(IndexFormat::Uint32)
+                } else {
+                    Some(IndexFormat::Uint16)
+                }
+            }
+            _ => None,
+        }
+    }
 }
 
 impl SpecializedPipeline for Mesh2dPipeline {
@@ -373,7 +404,7 @@ impl SpecializedPipeline for Mesh2dPipeline {
                 polygon_mode: PolygonMode::Fill,
                 conservative: false,
                 topology: key.primitive_topology(),
-                strip_index_format: None,
+                strip_index_format: key.strip_index_format(),
             },
             depth_stencil: None,
             multisample: MultisampleState {
@@ -491,24 +522,65 @@ impl EntityRenderCommand for DrawMesh2d {
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
         let mesh_handle = &mesh2d_query.get(item).unwrap().0;
-        if let Some(gpu_mesh) = meshes.into_inner().get(mesh_handle) {
-            pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
-            match &gpu_mesh.buffer_info {
-                GpuBufferInfo::Indexed {
-                    buffer,
-                    index_format,
-                    count,
-                } => {
-                    pass.set_index_buffer(buffer.slice(..), 0, *index_format);
-                    pass.draw_indexed(0..*count, 0, 0..1);
-                }
-                GpuBufferInfo::NonIndexed { vertex_count } => {
-                    pass.draw(0..*vertex_count, 0..1);
-                }
+        let gpu_mesh = match GpuMesh::get(meshes.into_inner(), mesh_handle) {
+            Ok(gpu_mesh) => gpu_mesh,
+            Err(_) => return RenderCommandResult::Failure,
+        };
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..1);
+            }
+            GpuBufferInfo::NonIndexed { vertex_count } => {
+                pass.draw(0..*vertex_count, 0..1);
             }
-            RenderCommandResult::Success
-        } else {
-            RenderCommandResult::Failure
         }
+        RenderCommandResult::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mesh2dPipelineKey;
+    use bevy_render::render_resource::{IndexFormat, PrimitiveTopology};
+
+    #[test]
+    fn mesh2d_key_index_format() {
+        let strip_key = |index_format| {
+            Mesh2dPipelineKey::from_primitive_topology(PrimitiveTopology::TriangleStrip)
+                | Mesh2dPipelineKey::from_index_format(
+                    PrimitiveTopology::TriangleStrip,
+                    index_format,
+                )
+        };
+        assert_eq!(
+            strip_key(Some(IndexFormat::Uint32)).strip_index_format(),
+            Some(IndexFormat::Uint32)
+        );
+        assert_eq!(
+            strip_key(Some(IndexFormat::Uint16)).strip_index_format(),
+            Some(IndexFormat::Uint16)
+        );
+
+        let list_key = |index_format| {
+            Mesh2dPipelineKey::from_primitive_topology(PrimitiveTopology::TriangleList)
+                | Mesh2dPipelineKey::from_index_format(
+                    PrimitiveTopology::TriangleList,
+                    index_format,
+                )
+        };
+        assert_eq!(
+            list_key(Some(IndexFormat::Uint32)).strip_index_format(),
+            None
+        );
+        assert_eq!(
+            list_key(Some(IndexFormat::Uint32)),
+            list_key(Some(IndexFormat::Uint16))
+        );
     }
 }
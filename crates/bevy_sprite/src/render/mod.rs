@@ -1,8 +1,8 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, hash::Hash};
 
 use crate::{
     texture_atlas::{TextureAtlas, TextureAtlasSprite},
-    Rect, Sprite, SPRITE_SHADER_HANDLE,
+    Pivot, Rect, Sprite, SPRITE_SHADER_HANDLE,
 };
 use bevy_asset::{AssetEvent, Assets, Handle, HandleId};
 use bevy_core::FloatOrd;
@@ -191,6 +191,8 @@ pub struct ExtractedSprite {
     pub image_handle_id: HandleId,
     pub flip_x: bool,
     pub flip_y: bool,
+    /// The point the sprite rotates and scales around, see [`Pivot`]
+    pub pivot: Pivot,
 }
 
 #[derive(Default)]
@@ -203,6 +205,19 @@ pub struct SpriteAssetEvents {
     pub images: Vec<AssetEvent<Image>>,
 }
 
+impl SpriteAssetEvents {
+    /// Returns the images whose bind groups are stale, because the [`GpuImage`] they were
+    /// created from was replaced or removed this frame.
+    ///
+    /// [`GpuImage`]: bevy_render::texture::GpuImage
+    pub fn invalidated_images(&self) -> impl Iterator<Item = &Handle<Image>> {
+        self.images.iter().filter_map(|event| match event {
+            AssetEvent::Created { .. } => None,
+            AssetEvent::Modified { handle } | AssetEvent::Removed { handle } => Some(handle),
+        })
+    }
+}
+
 pub fn extract_sprite_events(
     mut render_world: ResMut<RenderWorld>,
     mut image_events: EventReader<AssetEvent<Image>>,
@@ -232,17 +247,24 @@ pub fn extract_sprite_events(
 pub fn extract_sprites(
     mut render_world: ResMut<RenderWorld>,
     texture_atlases: Res<Assets<TextureAtlas>>,
-    sprite_query: Query<(&Visibility, &Sprite, &GlobalTransform, &Handle<Image>)>,
+    sprite_query: Query<(
+        &Visibility,
+        &Sprite,
+        &GlobalTransform,
+        &Handle<Image>,
+        Option<&Pivot>,
+    )>,
     atlas_query: Query<(
         &Visibility,
         &TextureAtlasSprite,
         &GlobalTransform,
         &Handle<TextureAtlas>,
+        Option<&Pivot>,
     )>,
 ) {
     let mut extracted_sprites = render_world.get_resource_mut::<ExtractedSprites>().unwrap();
     extracted_sprites.sprites.clear();
-    for (visibility, sprite, transform, handle) in sprite_query.iter() {
+    for (visibility, sprite, transform, handle, pivot) in sprite_query.iter() {
         if !visibility.is_visible {
             continue;
         }
@@ -256,10 +278,11 @@ pub fn extract_sprites(
             custom_size: sprite.custom_size,
             flip_x: sprite.flip_x,
             flip_y: sprite.flip_y,
+            pivot: pivot.copied().unwrap_or_default(),
             image_handle_id: handle.id,
         });
     }
-    for (visibility, atlas_sprite, transform, texture_atlas_handle) in atlas_query.iter() {
+    for (visibility, atlas_sprite, transform, texture_atlas_handle, pivot) in atlas_query.iter() {
         if !visibility.is_visible {
             continue;
         }
@@ -274,6 +297,7 @@ pub fn extract_sprites(
                 custom_size: atlas_sprite.custom_size,
                 flip_x: atlas_sprite.flip_x,
                 flip_y: atlas_sprite.flip_y,
+                pivot: pivot.copied().unwrap_or_default(),
                 image_handle_id: texture_atlas.texture.id,
             });
         }
@@ -327,15 +351,173 @@ const QUAD_UVS: [Vec2; 4] = [
     const_vec2!([0., 0.]),
 ];
 
+/// Returns the UVs of the [`QUAD_VERTEX_POSITIONS`], mirrored as requested.
+fn quad_uvs(flip_x: bool, flip_y: bool) -> [Vec2; 4] {
+    let mut uvs = QUAD_UVS;
+    if flip_x {
+        uvs = [uvs[1], uvs[0], uvs[3], uvs[2]];
+    }
+    if flip_y {
+        uvs = [uvs[3], uvs[2], uvs[1], uvs[0]];
+    }
+    uvs
+}
+
 #[derive(Component, Eq, PartialEq, Copy, Clone)]
 pub struct SpriteBatch {
     image_handle_id: HandleId,
     colored: bool,
 }
 
-#[derive(Default)]
+/// The default maximum number of bind groups kept by [`ImageBindGroups`].
+pub const DEFAULT_MAX_IMAGE_BIND_GROUPS: usize = 1024;
+
+struct CachedValue<V> {
+    value: V,
+    last_used_frame: u64,
+}
+
+/// A cache freeing its least recently used values at the end of each frame once it holds more
+/// than `max_len` of them. Values used in the current frame are never freed.
+struct LruCache<K, V> {
+    values: HashMap<K, CachedValue<V>>,
+    max_len: usize,
+    frame: u64,
+}
+
+impl<K: Clone + Eq + Hash, V> LruCache<K, V> {
+    fn new(max_len: usize) -> Self {
+        Self {
+            values: Default::default(),
+            max_len,
+            frame: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        self.values.get(key).map(|cached| &cached.value)
+    }
+
+    fn get_or_insert_with(&mut self, key: K, create: impl FnOnce() -> V) -> &V {
+        let frame = self.frame;
+        let cached = self.values.entry(key).or_insert_with(|| CachedValue {
+            value: create(),
+            last_used_frame: frame,
+        });
+        cached.last_used_frame = frame;
+        &cached.value
+    }
+
+    fn end_frame(&mut self) {
+        let frame = self.frame;
+        self.frame += 1;
+
+        let excess = self.values.len().saturating_sub(self.max_len);
+        if excess == 0 {
+            return;
+        }
+        let mut unused = self
+            .values
+            .iter()
+            .filter(|(_, cached)| cached.last_used_frame != frame)
+            .map(|(key, cached)| (cached.last_used_frame, key.clone()))
+            .collect::<Vec<_>>();
+        unused.sort_unstable_by_key(|(last_used_frame, _)| *last_used_frame);
+        for (_, key) in unused.into_iter().take(excess) {
+            self.values.remove(&key);
+        }
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.values.remove(key).map(|cached| cached.value)
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+/// The bind groups of the images drawn by sprites, recycled between frames.
+///
+/// Once more than [`ImageBindGroups::max_len`] bind groups are cached, the least recently used
+/// ones are freed at the end of each frame. Bind groups used in the current frame are never
+/// freed, even if that leaves more than `max_len` of them.
+///
+/// Insert this resource in the render world before adding the [`SpritePlugin`](crate::SpritePlugin)
+/// to configure the limit.
 pub struct ImageBindGroups {
-    values: HashMap<Handle<Image>, BindGroup>,
+    cache: LruCache<Handle<Image>, BindGroup>,
+}
+
+impl Default for ImageBindGroups {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_IMAGE_BIND_GROUPS)
+    }
+}
+
+impl ImageBindGroups {
+    /// Creates an empty cache keeping at most `max_len` bind groups between frames.
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            cache: LruCache::new(max_len),
+        }
+    }
+
+    /// The maximum number of bind groups kept between frames
+    pub fn max_len(&self) -> usize {
+        self.cache.max_len
+    }
+
+    /// The number of cached bind groups
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.len() == 0
+    }
+
+    /// Returns the bind group of `image`, if it is cached.
+    pub fn get(&self, image: &Handle<Image>) -> Option<&BindGroup> {
+        self.cache.get(image)
+    }
+
+    /// Returns the bind group of `image`, creating it if it isn't cached, and marks it as used in
+    /// the current frame.
+    pub fn get_or_insert_with(
+        &mut self,
+        image: Handle<Image>,
+        create: impl FnOnce() -> BindGroup,
+    ) -> &BindGroup {
+        self.cache.get_or_insert_with(image, create)
+    }
+
+    /// Frees the least recently used bind groups in excess of [`ImageBindGroups::max_len`],
+    /// keeping the ones used in the current frame, and starts a new frame.
+    pub fn end_frame(&mut self) {
+        self.cache.end_frame();
+    }
+
+    /// Drops the bind group of `image`, so that a new one is created the next time it is drawn.
+    pub fn remove(&mut self, image: &Handle<Image>) -> Option<BindGroup> {
+        self.cache.remove(image)
+    }
+
+    /// Drops the bind groups of the images invalidated by `events`.
+    pub fn invalidate(&mut self, events: &SpriteAssetEvents) {
+        for image in events.invalidated_images() {
+            self.cache.remove(image);
+        }
+    }
+
+    /// Drops all bind groups, so that they are all recreated the next time they are drawn.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -357,13 +539,7 @@ pub fn queue_sprites(
     events: Res<SpriteAssetEvents>,
 ) {
     // If an image has changed, the GpuImage has (probably) changed
-    for event in &events.images {
-        match event {
-            AssetEvent::Created { .. } => None,
-            AssetEvent::Modified { handle } => image_bind_groups.values.remove(handle),
-            AssetEvent::Removed { handle } => image_bind_groups.values.remove(handle),
-        };
-    }
+    image_bind_groups.invalidate(&events);
 
     if let Some(view_binding) = view_uniforms.uniforms.binding() {
         let sprite_meta = &mut sprite_meta;
@@ -440,10 +616,9 @@ pub fn queue_sprites(
                         current_image_size = Vec2::new(gpu_image.size.width, gpu_image.size.height);
                         current_batch_entity = commands.spawn_bundle((current_batch,)).id();
 
-                        image_bind_groups
-                            .values
-                            .entry(Handle::weak(current_batch.image_handle_id))
-                            .or_insert_with(|| {
+                        image_bind_groups.get_or_insert_with(
+                            Handle::weak(current_batch.image_handle_id),
+                            || {
                                 render_device.create_bind_group(&BindGroupDescriptor {
                                     entries: &[
                                         BindGroupEntry {
@@ -460,7 +635,8 @@ pub fn queue_sprites(
                                     label: Some("sprite_material_bind_group"),
                                     layout: &sprite_pipeline.material_layout,
                                 })
-                            });
+                            },
+                        );
                     } else {
                         // Skip this item if the texture is not ready
                         continue;
@@ -469,13 +645,7 @@ pub fn queue_sprites(
 
                 // Calculate vertex data for this item
 
-                let mut uvs = QUAD_UVS;
-                if extracted_sprite.flip_x {
-                    uvs = [uvs[1], uvs[0], uvs[3], uvs[2]];
-                }
-                if extracted_sprite.flip_y {
-                    uvs = [uvs[3], uvs[2], uvs[1], uvs[0]];
-                }
+                let mut uvs = quad_uvs(extracted_sprite.flip_x, extracted_sprite.flip_y);
 
                 // By default, the size of the quad is the size of the texture
                 let mut quad_size = current_image_size;
@@ -494,13 +664,12 @@ pub fn queue_sprites(
                     quad_size = custom_size;
                 }
 
-                // Apply size and global transform
-                let positions = QUAD_VERTEX_POSITIONS.map(|quad_pos| {
-                    extracted_sprite
-                        .transform
-                        .mul_vec3((quad_pos * quad_size).extend(0.))
-                        .into()
-                });
+                // Apply size and global transform, around the pivot of the sprite
+                let transform = extracted_sprite
+                    .pivot
+                    .transform_quad(&extracted_sprite.transform, quad_size);
+                let positions = QUAD_VERTEX_POSITIONS
+                    .map(|quad_pos| transform.mul_vec3((quad_pos * quad_size).extend(0.)).into());
 
                 // These items will be sorted by depth with other phase items
                 let sort_key = FloatOrd(extracted_sprite.transform.translation.z);
@@ -559,6 +728,8 @@ pub fn queue_sprites(
             .colored_vertices
             .write_buffer(&render_device, &render_queue);
     }
+
+    image_bind_groups.end_frame();
 }
 
 pub type DrawSprite = (
@@ -603,7 +774,6 @@ impl<const I: usize> EntityRenderCommand for SetSpriteTextureBindGroup<I> {
         pass.set_bind_group(
             I,
             image_bind_groups
-                .values
                 .get(&Handle::weak(sprite_batch.image_handle_id))
                 .unwrap(),
             &[],
@@ -633,3 +803,84 @@ impl<P: BatchedPhaseItem> RenderCommand<P> for DrawSpriteBatch {
         RenderCommandResult::Success
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flip_x_mirrors_uvs_horizontally() {
+        let uvs = quad_uvs(false, false);
+        let flipped = quad_uvs(true, false);
+        for (uv, flipped) in uvs.iter().zip(flipped.iter()) {
+            assert_eq!(*flipped, Vec2::new(1.0 - uv.x, uv.y));
+        }
+    }
+
+    #[test]
+    fn flip_y_mirrors_uvs_vertically() {
+        let uvs = quad_uvs(false, false);
+        let flipped = quad_uvs(false, true);
+        for (uv, flipped) in uvs.iter().zip(flipped.iter()) {
+            assert_eq!(*flipped, Vec2::new(uv.x, 1.0 - uv.y));
+        }
+        let flipped_both = quad_uvs(true, true);
+        for (uv, flipped) in uvs.iter().zip(flipped_both.iter()) {
+            assert_eq!(*flipped, Vec2::ONE - *uv);
+        }
+    }
+
+    #[test]
+    fn modified_and_removed_images_are_invalidated() {
+        let created = Handle::<Image>::weak(HandleId::random::<Image>());
+        let modified = Handle::<Image>::weak(HandleId::random::<Image>());
+        let removed = Handle::<Image>::weak(HandleId::random::<Image>());
+        let events = SpriteAssetEvents {
+            images: vec![
+                AssetEvent::Created {
+                    handle: created.clone_weak(),
+                },
+                AssetEvent::Modified {
+                    handle: modified.clone_weak(),
+                },
+                AssetEvent::Removed {
+                    handle: removed.clone_weak(),
+                },
+            ],
+        };
+
+        // A bind group is only kept for the image whose GpuImage is unchanged, all others are
+        // recreated from the new GpuImage the next time they are drawn.
+        assert_eq!(
+            events.invalidated_images().collect::<Vec<_>>(),
+            vec![&modified, &removed]
+        );
+    }
+
+    #[test]
+    fn least_recently_used_bind_groups_are_evicted() {
+        let images = (0..3)
+            .map(|_| Handle::<Image>::weak(HandleId::random::<Image>()))
+            .collect::<Vec<_>>();
+        let mut bind_groups = LruCache::<Handle<Image>, usize>::new(2);
+
+        for (index, image) in images.iter().enumerate() {
+            bind_groups.get_or_insert_with(image.clone_weak(), || index);
+            bind_groups.end_frame();
+        }
+        assert_eq!(bind_groups.len(), 2);
+        assert!(bind_groups.get(&images[0]).is_none());
+
+        // Bind groups used in the current frame are kept, even above the limit.
+        bind_groups.get_or_insert_with(images[0].clone_weak(), || 0);
+        bind_groups.get_or_insert_with(images[1].clone_weak(), || 1);
+        bind_groups.get_or_insert_with(images[2].clone_weak(), || 2);
+        bind_groups.end_frame();
+        assert_eq!(bind_groups.len(), 3);
+
+        bind_groups.get_or_insert_with(images[2].clone_weak(), || 2);
+        bind_groups.end_frame();
+        assert_eq!(bind_groups.len(), 2);
+        assert_eq!(bind_groups.get(&images[2]), Some(&2));
+    }
+}
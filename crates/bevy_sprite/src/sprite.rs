@@ -1,7 +1,8 @@
-use bevy_ecs::component::Component;
-use bevy_math::Vec2;
+use bevy_ecs::{component::Component, reflect::ReflectComponent};
+use bevy_math::{const_vec2, Vec2};
 use bevy_reflect::Reflect;
 use bevy_render::color::Color;
+use bevy_transform::components::GlobalTransform;
 
 #[derive(Component, Debug, Default, Clone, Reflect)]
 #[repr(C)]
@@ -16,3 +17,63 @@ pub struct Sprite {
     /// of the sprite's image
     pub custom_size: Option<Vec2>,
 }
+
+/// The point a sprite or a UI node rotates and scales around, instead of its center.
+///
+/// The pivot is normalized over the quad: `(0.0, 0.0)` is its bottom left corner and `(1.0, 1.0)`
+/// its top right corner. Without rotation or scale, the quad is drawn at the same place whatever
+/// its pivot, e.g. a gauge needle with a pivot at the bottom center spins around that point.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Component, PartialEq)]
+pub struct Pivot(pub Vec2);
+
+impl Pivot {
+    pub const CENTER: Pivot = Pivot(const_vec2!([0.5, 0.5]));
+    pub const BOTTOM_LEFT: Pivot = Pivot(const_vec2!([0.0, 0.0]));
+
+    /// Returns the transform placing a quad of `size`, centered on its origin, like `transform`
+    /// but rotated and scaled around this pivot.
+    pub fn transform_quad(&self, transform: &GlobalTransform, size: Vec2) -> GlobalTransform {
+        let pivot = ((self.0 - Vec2::splat(0.5)) * size).extend(0.0);
+        let mut pivoted = *transform;
+        pivoted.translation += pivot - transform.rotation * (transform.scale * pivot);
+        pivoted
+    }
+}
+
+impl Default for Pivot {
+    fn default() -> Self {
+        Self::CENTER
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_math::{Quat, Vec3};
+
+    #[test]
+    fn rotating_around_corner_pivot_keeps_corner_fixed() {
+        let size = Vec2::new(40.0, 20.0);
+        let bottom_left = Vec3::new(-20.0, -10.0, 0.0);
+        let top_right = Vec3::new(20.0, 10.0, 0.0);
+        let unrotated = GlobalTransform::from_xyz(100.0, 50.0, 0.0);
+        let rotated = unrotated.with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2));
+
+        let pivoted = Pivot::BOTTOM_LEFT.transform_quad(&rotated, size);
+        assert!(pivoted
+            .mul_vec3(bottom_left)
+            .abs_diff_eq(Vec3::new(80.0, 40.0, 0.0), 1e-4));
+        // The opposite corner swings around it, from (120, 60) to (60, 80).
+        assert!(pivoted
+            .mul_vec3(top_right)
+            .abs_diff_eq(Vec3::new(60.0, 80.0, 0.0), 1e-4));
+
+        // Without rotation or scale, the pivot doesn't move the quad.
+        assert_eq!(
+            Pivot::BOTTOM_LEFT.transform_quad(&unrotated, size),
+            unrotated
+        );
+        assert_eq!(Pivot::CENTER.transform_quad(&rotated, size), rotated);
+    }
+}
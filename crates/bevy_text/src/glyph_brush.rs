@@ -179,3 +179,37 @@ impl GlyphPlacementAdjuster {
         Vec2::new(self.0, 0.) + v
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monospace_glyphs_advance_evenly() {
+        let font =
+            FontArc::try_from_slice(include_bytes!("../../../assets/fonts/FiraMono-Medium.ttf"))
+                .unwrap();
+        let advance = font.as_scaled(20.0).h_advance(font.glyph_id('m'));
+        let mut brush = GlyphBrush::default();
+        let font_id = brush.add_font(Handle::default(), font);
+
+        let glyphs = brush
+            .compute_glyphs(
+                &[SectionText {
+                    text: "mil W",
+                    scale: 20.0.into(),
+                    font_id,
+                }],
+                Size::new(f32::MAX, f32::MAX),
+                TextAlignment::default(),
+            )
+            .unwrap();
+
+        // The space is laid out as a glyph too.
+        assert_eq!(glyphs.len(), 5);
+        for (index, glyph) in glyphs.iter().enumerate() {
+            assert_eq!(glyph.byte_index, index);
+            assert!((glyph.glyph.position.x - index as f32 * advance).abs() < 1e-3);
+        }
+    }
+}
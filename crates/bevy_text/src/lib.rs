@@ -7,6 +7,7 @@ mod glyph_brush;
 mod pipeline;
 mod text;
 mod text2d;
+mod wrap;
 
 pub use error::*;
 pub use font::*;
@@ -17,6 +18,7 @@ pub use glyph_brush::*;
 pub use pipeline::*;
 pub use text::*;
 pub use text2d::*;
+pub use wrap::*;
 
 pub mod prelude {
     #[doc(hidden)]
@@ -1,17 +1,17 @@
 use std::hash::Hash;
 
-use ab_glyph::{PxScale, ScaleFont};
+use ab_glyph::{FontArc, PxScaleFont, ScaleFont};
 use bevy_asset::{Assets, Handle, HandleId};
 use bevy_math::Size;
 use bevy_render::texture::Image;
 use bevy_sprite::TextureAtlas;
 use bevy_utils::HashMap;
 
-use glyph_brush_layout::{FontId, SectionText};
+use glyph_brush_layout::{FontId, SectionGlyph, SectionText};
 
 use crate::{
-    error::TextError, glyph_brush::GlyphBrush, scale_value, Font, FontAtlasSet, PositionedGlyph,
-    TextAlignment, TextSection,
+    error::TextError, glyph_brush::GlyphBrush, scale_value, wrap_sections, Font, FontAtlasSet,
+    PositionedGlyph, TextAlignment, TextSection, WrappedSection,
 };
 
 pub struct TextPipeline<ID> {
@@ -30,6 +30,53 @@ impl<ID> Default for TextPipeline<ID> {
     }
 }
 
+/// Lays out `sections`, given as their text, font and scaled font, breaking words wider than the
+/// `bounds` between their characters, and returns the glyphs along with the wrapped sections they
+/// were laid out from.
+///
+/// Line breaks are inserted into the wrapped sections, but the byte indices of the glyphs refer to
+/// the original text of the sections.
+fn compute_wrapped_glyphs(
+    brush: &GlyphBrush,
+    sections: &[(&str, FontId, &PxScaleFont<&FontArc>)],
+    bounds: Size,
+    text_alignment: TextAlignment,
+) -> Result<(Vec<SectionGlyph>, Vec<WrappedSection>), TextError> {
+    let mut previous_glyph = None;
+    let wrapped = wrap_sections(
+        sections.iter().map(|(text, _, _)| *text),
+        bounds.width,
+        |section_index, character| {
+            let scaled_font = sections[section_index].2;
+            let glyph_id = scaled_font.glyph_id(character);
+            let kerning = match previous_glyph {
+                Some((previous_section, previous_id)) if previous_section == section_index => {
+                    scaled_font.kern(previous_id, glyph_id)
+                }
+                _ => 0.0,
+            };
+            previous_glyph = Some((section_index, glyph_id));
+            scaled_font.h_advance(glyph_id) + kerning
+        },
+    );
+
+    let section_texts = wrapped
+        .iter()
+        .zip(sections)
+        .map(|(wrapped, (_, font_id, scaled_font))| SectionText {
+            font_id: *font_id,
+            scale: scaled_font.scale(),
+            text: &wrapped.text,
+        })
+        .collect::<Vec<_>>();
+    let mut section_glyphs = brush.compute_glyphs(&section_texts, bounds, text_alignment)?;
+    for section_glyph in &mut section_glyphs {
+        section_glyph.byte_index =
+            wrapped[section_glyph.section_index].original_byte_index(section_glyph.byte_index);
+    }
+    Ok((section_glyphs, wrapped))
+}
+
 pub struct TextLayoutInfo {
     pub glyphs: Vec<PositionedGlyph>,
     pub size: Size,
@@ -62,30 +109,33 @@ impl<ID: Hash + Eq> TextPipeline<ID> {
         textures: &mut Assets<Image>,
     ) -> Result<(), TextError> {
         let mut scaled_fonts = Vec::new();
-        let sections = sections
+        let mut font_ids = Vec::new();
+        for section in sections {
+            let font = fonts
+                .get(section.style.font.id)
+                .ok_or(TextError::NoSuchFont)?;
+            font_ids.push(self.get_or_insert_font_id(&section.style.font, font));
+            let font_size = scale_value(section.style.font_size, scale_factor);
+            scaled_fonts.push(ab_glyph::Font::as_scaled(&font.font, font_size));
+        }
+
+        let section_fonts = sections
             .iter()
-            .map(|section| {
-                let font = fonts
-                    .get(section.style.font.id)
-                    .ok_or(TextError::NoSuchFont)?;
-                let font_id = self.get_or_insert_font_id(&section.style.font, font);
-                let font_size = scale_value(section.style.font_size, scale_factor);
-
-                scaled_fonts.push(ab_glyph::Font::as_scaled(&font.font, font_size));
-
-                let section = SectionText {
-                    font_id,
-                    scale: PxScale::from(font_size),
-                    text: &section.value,
-                };
-
-                Ok(section)
+            .zip(font_ids)
+            .zip(&scaled_fonts)
+            .map(|((section, font_id), scaled_font)| (section.value.as_str(), font_id, scaled_font))
+            .collect::<Vec<_>>();
+        let (section_glyphs, wrapped) =
+            compute_wrapped_glyphs(&self.brush, &section_fonts, bounds, text_alignment)?;
+        let sections = wrapped
+            .iter()
+            .zip(&section_fonts)
+            .map(|(wrapped, (_, font_id, scaled_font))| SectionText {
+                font_id: *font_id,
+                scale: scaled_font.scale(),
+                text: &wrapped.text,
             })
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let section_glyphs = self
-            .brush
-            .compute_glyphs(&sections, bounds, text_alignment)?;
+            .collect::<Vec<_>>();
 
         if section_glyphs.is_empty() {
             self.glyph_map.insert(
@@ -128,3 +178,44 @@ impl<ID: Hash + Eq> TextPipeline<ID> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ab_glyph::Font as _;
+    use bevy_asset::Handle;
+
+    #[test]
+    fn glyphs_after_a_word_break_keep_their_byte_index() {
+        let font =
+            FontArc::try_from_slice(include_bytes!("../../../assets/fonts/FiraMono-Medium.ttf"))
+                .unwrap();
+        let scaled_font = font.as_scaled(20.0);
+        let advance = scaled_font.h_advance(font.glyph_id('a'));
+        let mut brush = GlyphBrush::default();
+        let font_id = brush.add_font(Handle::default(), font.clone());
+
+        let (glyphs, wrapped) = compute_wrapped_glyphs(
+            &brush,
+            &[("abcdefghij", font_id, &scaled_font)],
+            Size::new(5.5 * advance, f32::MAX),
+            TextAlignment::default(),
+        )
+        .unwrap();
+        assert_eq!(wrapped[0].text, "abcde\nfghij");
+
+        let glyph_at = |byte_index| {
+            glyphs
+                .iter()
+                .find(|glyph| glyph.byte_index == byte_index)
+                .unwrap()
+                .glyph
+                .position
+        };
+        let (e, f) = (glyph_at(4), glyph_at(5));
+        assert_eq!(glyph_at(0).y, e.y);
+        assert!(f.y > e.y);
+        assert!((f.x - glyph_at(0).x).abs() < 1e-3);
+        assert_eq!(glyph_at(9).y, f.y);
+    }
+}
@@ -7,7 +7,7 @@ use bevy_ecs::{
 };
 use bevy_math::{Size, Vec3};
 use bevy_render::{texture::Image, view::Visibility, RenderWorld};
-use bevy_sprite::{ExtractedSprite, ExtractedSprites, TextureAtlas};
+use bevy_sprite::{ExtractedSprite, ExtractedSprites, Pivot, TextureAtlas};
 use bevy_transform::prelude::{GlobalTransform, Transform};
 use bevy_window::Windows;
 
@@ -103,6 +103,7 @@ pub fn extract_text2d_sprite(
                     image_handle_id: handle.id,
                     flip_x: false,
                     flip_y: false,
+                    pivot: Pivot::CENTER,
                 });
             }
         }
@@ -0,0 +1,189 @@
+/// Where [`wrap_breaks`] ends a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineWrap {
+    /// The line ends at the whitespace character at this index, which is replaced by the break.
+    Whitespace(usize),
+    /// The line ends before the character at this index, in the middle of a word that is wider
+    /// than a whole line.
+    Word(usize),
+}
+
+/// Computes where text made of `chars`, each with its horizontal advance, has to be broken so that
+/// no line is wider than `max_width`.
+///
+/// Lines are broken greedily at whitespace. A word that doesn't fit on a line of its own is broken
+/// between its characters instead, so that it never overflows the bounds. Existing `'\n'`s start
+/// a new line.
+pub fn wrap_breaks(chars: &[(char, f32)], max_width: f32) -> Vec<LineWrap> {
+    let mut breaks = Vec::new();
+    let mut line_width = 0.0;
+    let mut index = 0;
+    while index < chars.len() {
+        let (character, advance) = chars[index];
+        if character == '\n' {
+            line_width = 0.0;
+            index += 1;
+            continue;
+        }
+        if character.is_whitespace() {
+            line_width += advance;
+            index += 1;
+            continue;
+        }
+
+        let word_end = chars[index..]
+            .iter()
+            .position(|(character, _)| character.is_whitespace())
+            .map_or(chars.len(), |length| index + length);
+        let word_width: f32 = chars[index..word_end]
+            .iter()
+            .map(|(_, advance)| advance)
+            .sum();
+        if line_width > 0.0 && line_width + word_width > max_width {
+            // Words are delimited by whitespace, and the line isn't empty, so the word follows
+            // some whitespace.
+            breaks.push(LineWrap::Whitespace(index - 1));
+            line_width = 0.0;
+        }
+        for (offset, (_, advance)) in chars[index..word_end].iter().enumerate() {
+            if line_width > 0.0 && line_width + advance > max_width {
+                breaks.push(LineWrap::Word(index + offset));
+                line_width = 0.0;
+            }
+            line_width += advance;
+        }
+        index = word_end;
+    }
+    breaks
+}
+
+/// The text of a section with the line breaks of [`wrap_sections`] inserted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedSection {
+    pub text: String,
+    /// Pairs of byte indices into the wrapped and the original text, in increasing order, from
+    /// which on both texts are equal until the next pair.
+    edits: Vec<(usize, usize)>,
+}
+
+impl WrappedSection {
+    /// Maps a byte index into the wrapped text, e.g. of a laid out glyph, to the byte index into
+    /// the original text. An inserted line break maps to the character following it.
+    pub fn original_byte_index(&self, byte_index: usize) -> usize {
+        let edit = self
+            .edits
+            .iter()
+            .rev()
+            .find(|(wrapped, _)| *wrapped <= byte_index);
+        match edit {
+            Some((wrapped, original)) => original + (byte_index - wrapped),
+            None => byte_index,
+        }
+    }
+}
+
+/// Inserts the line breaks needed to fit a text into `max_width` into its `sections`.
+///
+/// `advance` returns the horizontal advance of a character of the section at the given index. The
+/// characters are measured in order, so it can account for kerning with the previous one.
+pub fn wrap_sections<'a>(
+    sections: impl IntoIterator<Item = &'a str>,
+    max_width: f32,
+    mut advance: impl FnMut(usize, char) -> f32,
+) -> Vec<WrappedSection> {
+    let sections = sections.into_iter().collect::<Vec<_>>();
+    let chars = sections
+        .iter()
+        .enumerate()
+        .flat_map(|(section_index, section)| {
+            section
+                .chars()
+                .map(move |character| (section_index, character))
+        })
+        .map(|(section_index, character)| (character, advance(section_index, character)))
+        .collect::<Vec<_>>();
+    let mut breaks = wrap_breaks(&chars, max_width).into_iter().peekable();
+
+    let mut index = 0;
+    sections
+        .iter()
+        .map(|section| {
+            let mut wrapped = WrappedSection {
+                text: String::with_capacity(section.len()),
+                edits: Vec::new(),
+            };
+            for (byte_index, character) in section.char_indices() {
+                match breaks.peek() {
+                    Some(LineWrap::Whitespace(at)) if *at == index => {
+                        breaks.next();
+                        wrapped.text.push('\n');
+                        wrapped
+                            .edits
+                            .push((wrapped.text.len(), byte_index + character.len_utf8()));
+                    }
+                    Some(LineWrap::Word(at)) if *at == index => {
+                        breaks.next();
+                        wrapped.edits.push((wrapped.text.len(), byte_index));
+                        wrapped.text.push('\n');
+                        wrapped.edits.push((wrapped.text.len(), byte_index));
+                        wrapped.text.push(character);
+                    }
+                    _ => wrapped.text.push(character),
+                }
+                index += 1;
+            }
+            wrapped
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_to_fixed_width() {
+        let texts = |sections: Vec<WrappedSection>| {
+            sections
+                .into_iter()
+                .map(|section| section.text)
+                .collect::<Vec<_>>()
+        };
+        let wrap = |text: &str, max_width: f32| texts(wrap_sections([text], max_width, |_, _| 1.0));
+
+        assert_eq!(
+            wrap("the quick brown fox jumps", 10.0),
+            ["the quick\nbrown fox\njumps"]
+        );
+        // Words wider than a line break at the character level.
+        assert_eq!(wrap("a abcdefghijkl b", 5.0), ["a\nabcde\nfghij\nkl b"]);
+        assert_eq!(wrap("first\nsecond line", 6.0), ["first\nsecond\nline"]);
+        assert_eq!(wrap("fits on one line", f32::MAX), ["fits on one line"]);
+
+        // Breaks are counted across sections.
+        assert_eq!(
+            texts(wrap_sections(["the quick ", "brown fox"], 10.0, |_, _| 1.0)),
+            ["the quick\n", "brown fox"]
+        );
+        assert_eq!(
+            texts(wrap_sections(["abc", "defg"], 5.0, |_, _| 1.0)),
+            ["abc", "de\nfg"]
+        );
+    }
+
+    #[test]
+    fn wrapped_byte_indices_map_to_the_original_text() {
+        // A word break inserts a line break, a whitespace break replaces the whitespace
+        let wrapped = &wrap_sections(["abcdefg h\u{3000}ij"], 5.0, |_, _| 1.0)[0];
+        assert_eq!(wrapped.text, "abcde\nfg h\nij");
+        let original = "abcdefg h\u{3000}ij";
+        for (byte_index, character) in wrapped.text.char_indices() {
+            let original_index = wrapped.original_byte_index(byte_index);
+            if character != '\n' {
+                assert_eq!(original[original_index..].chars().next(), Some(character));
+            }
+        }
+        assert_eq!(wrapped.original_byte_index(6), 5);
+        assert_eq!(wrapped.original_byte_index(11), original.find('i').unwrap());
+    }
+}
@@ -28,6 +28,16 @@ impl Children {
         Self(SmallVec::from_slice(entity))
     }
 
+    /// Appends `child`, unless it already is a child of this entity
+    ///
+    /// The [`Parent`](crate::components::Parent) of `child` is pointed at this entity by
+    /// [`children_update_system`](crate::hierarchy::children_update_system).
+    pub fn push(&mut self, child: Entity) {
+        if !self.0.contains(&child) {
+            self.0.push(child);
+        }
+    }
+
     /// Swaps the child at `a_index` with the child at `b_index`
     pub fn swap(&mut self, a_index: usize, b_index: usize) {
         self.0.swap(a_index, b_index);
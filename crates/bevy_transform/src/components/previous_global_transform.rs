@@ -0,0 +1,87 @@
+use super::GlobalTransform;
+use bevy_ecs::{component::Component, reflect::ReflectComponent, system::Query};
+use bevy_reflect::Reflect;
+
+/// The [`GlobalTransform`] of an entity at the previous step of a fixed timestep simulation.
+///
+/// When the simulation runs at a fixed rate that differs from the frame rate, drawing entities at
+/// their latest [`GlobalTransform`] makes them stutter. Entities with a [`PreviousGlobalTransform`]
+/// are instead drawn in between their previous and current global transforms, blended by
+/// [`TransformInterpolation::alpha`].
+///
+/// It is recorded by [`update_previous_global_transforms`], which should run at the start of each
+/// fixed step.
+#[derive(Component, Debug, PartialEq, Clone, Copy, Default, Reflect)]
+#[reflect(Component, PartialEq)]
+pub struct PreviousGlobalTransform(pub GlobalTransform);
+
+impl PreviousGlobalTransform {
+    /// Blends this transform towards `current` by `alpha`, where `0.0` is this transform and
+    /// `1.0` is `current`.
+    ///
+    /// The translation and scale are interpolated linearly, and the rotation spherically.
+    pub fn interpolate(&self, current: &GlobalTransform, alpha: f32) -> GlobalTransform {
+        GlobalTransform {
+            translation: self.0.translation.lerp(current.translation, alpha),
+            rotation: self.0.rotation.slerp(current.rotation, alpha),
+            scale: self.0.scale.lerp(current.scale, alpha),
+        }
+    }
+}
+
+/// How far the rendered frame is between the previous and the current step of a fixed timestep
+/// simulation, used to draw entities with a [`PreviousGlobalTransform`].
+///
+/// `alpha` is usually set each frame to the overstep percentage of the fixed timestep. It defaults
+/// to `1.0`, drawing entities at their current [`GlobalTransform`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransformInterpolation {
+    /// The fraction of a fixed step elapsed since the current step, from `0.0` to `1.0`
+    pub alpha: f32,
+}
+
+impl Default for TransformInterpolation {
+    fn default() -> Self {
+        Self { alpha: 1.0 }
+    }
+}
+
+/// Records the [`GlobalTransform`] of every entity with a [`PreviousGlobalTransform`] into it.
+///
+/// Add this system at the start of the fixed timestep stage, before the simulation moves anything.
+pub fn update_previous_global_transforms(
+    mut query: Query<(&GlobalTransform, &mut PreviousGlobalTransform)>,
+) {
+    for (global_transform, mut previous) in query.iter_mut() {
+        previous.0 = *global_transform;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_math::{Quat, Vec3};
+
+    #[test]
+    fn interpolate_halfway() {
+        let previous = PreviousGlobalTransform(GlobalTransform::identity());
+        let current = GlobalTransform {
+            translation: Vec3::new(2.0, 4.0, -6.0),
+            rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            scale: Vec3::splat(3.0),
+        };
+
+        let halfway = previous.interpolate(&current, 0.5);
+        assert_eq!(halfway.translation, Vec3::new(1.0, 2.0, -3.0));
+        assert!(halfway
+            .rotation
+            .abs_diff_eq(Quat::from_rotation_y(std::f32::consts::FRAC_PI_4), 1e-6));
+        assert_eq!(halfway.scale, Vec3::splat(2.0));
+
+        assert_eq!(previous.interpolate(&current, 0.0), previous.0);
+        assert_eq!(
+            previous.interpolate(&current, 1.0).translation,
+            current.translation
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use crate::components::{Children, Parent};
+use bevy_ecs::{entity::Entity, world::World};
+use bevy_utils::HashSet;
+use std::collections::VecDeque;
+
+/// Returns `true` if `ancestor` is found by repeatedly following `parent_of` from `entity`.
+///
+/// An entity isn't its own descendant. The walk stops when it reaches an entity without a parent,
+/// or when it revisits an entity because the hierarchy contains a cycle.
+///
+/// In a system, pass a closure looking up the [`Parent`] through a query:
+/// `is_descendant_of_with(a, b, |entity| parents.get(entity).ok().map(|parent| parent.0))`.
+pub fn is_descendant_of_with(
+    entity: Entity,
+    ancestor: Entity,
+    mut parent_of: impl FnMut(Entity) -> Option<Entity>,
+) -> bool {
+    let mut visited = HashSet::default();
+    let mut current = entity;
+    while let Some(parent) = parent_of(current) {
+        if parent == ancestor {
+            return true;
+        }
+        if !visited.insert(parent) {
+            return false;
+        }
+        current = parent;
+    }
+    false
+}
+
+/// Returns `true` if `entity` is within the subtree of `ancestor`, following [`Parent`]s.
+pub fn is_descendant_of(world: &World, entity: Entity, ancestor: Entity) -> bool {
+    is_descendant_of_with(entity, ancestor, |entity| {
+        world.get::<Parent>(entity).map(|parent| parent.0)
+    })
+}
+
+/// Returns `true` if `descendant` is within the subtree of `entity`, following [`Parent`]s.
+pub fn is_ancestor_of(world: &World, entity: Entity, descendant: Entity) -> bool {
+    is_descendant_of(world, descendant, entity)
+}
+
+/// Returns `true` if the [`Parent`] of `entity` points at an entity that no longer exists, e.g.
+/// because the parent was despawned without its children.
+pub fn has_stale_parent(world: &World, entity: Entity) -> bool {
+    match world.get::<Parent>(entity) {
+        Some(parent) => world.get_entity(parent.0).is_none(),
+        None => false,
+    }
+}
+
+/// An iterator over the descendants of an entity, returned by [`iter_descendants_with`] and
+/// [`iter_descendants_bfs_with`].
+pub struct DescendantIter<F> {
+    children_of: F,
+    worklist: VecDeque<Entity>,
+    depth_first: bool,
+}
+
+impl<F> DescendantIter<F> {
+    fn new<'a>(entity: Entity, mut children_of: F, depth_first: bool) -> Self
+    where
+        F: FnMut(Entity) -> Option<&'a [Entity]>,
+    {
+        let worklist = children_of(entity)
+            .unwrap_or_default()
+            .iter()
+            .copied()
+            .collect();
+        Self {
+            children_of,
+            worklist,
+            depth_first,
+        }
+    }
+}
+
+impl<'a, F> Iterator for DescendantIter<F>
+where
+    F: FnMut(Entity) -> Option<&'a [Entity]>,
+{
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        let entity = self.worklist.pop_front()?;
+        if let Some(children) = (self.children_of)(entity) {
+            if self.depth_first {
+                for child in children.iter().rev() {
+                    self.worklist.push_front(*child);
+                }
+            } else {
+                self.worklist.extend(children);
+            }
+        }
+        Some(entity)
+    }
+}
+
+/// Iterates over the descendants of `entity` depth-first, visiting the children of each entity in
+/// order before moving on to its next sibling. `entity` itself isn't included.
+///
+/// Entities for which `children_of` returns `None` are leaves. The hierarchy must not contain
+/// cycles, see [`validate_children`](super::validate_children). In a system, pass a closure
+/// looking up the [`Children`] through a query:
+/// `iter_descendants_with(entity, |entity| children.get(entity).ok().map(|children| &**children))`.
+pub fn iter_descendants_with<'a, F>(entity: Entity, children_of: F) -> DescendantIter<F>
+where
+    F: FnMut(Entity) -> Option<&'a [Entity]>,
+{
+    DescendantIter::new(entity, children_of, true)
+}
+
+/// Iterates over the descendants of `entity` breadth-first, visiting all children before any
+/// grandchild. `entity` itself isn't included.
+///
+/// See [`iter_descendants_with`] for the requirements on `children_of`.
+pub fn iter_descendants_bfs_with<'a, F>(entity: Entity, children_of: F) -> DescendantIter<F>
+where
+    F: FnMut(Entity) -> Option<&'a [Entity]>,
+{
+    DescendantIter::new(entity, children_of, false)
+}
+
+/// Iterates over the descendants of `entity` depth-first, following [`Children`].
+///
+/// Children that were despawned without being removed from their parent are skipped.
+pub fn iter_descendants(world: &World, entity: Entity) -> impl Iterator<Item = Entity> + '_ {
+    iter_descendants_with(entity, move |entity| children_in(world, entity))
+        .filter(move |entity| world.get_entity(*entity).is_some())
+}
+
+/// Iterates over the descendants of `entity` breadth-first, following [`Children`].
+///
+/// Children that were despawned without being removed from their parent are skipped.
+pub fn iter_descendants_bfs(world: &World, entity: Entity) -> impl Iterator<Item = Entity> + '_ {
+    iter_descendants_bfs_with(entity, move |entity| children_in(world, entity))
+        .filter(move |entity| world.get_entity(*entity).is_some())
+}
+
+fn children_in(world: &World, entity: Entity) -> Option<&[Entity]> {
+    world
+        .get::<Children>(entity)
+        .map(|children| &children.0[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hierarchy::BuildWorldChildren;
+
+    #[test]
+    fn ancestry_in_three_level_tree() {
+        let mut world = World::default();
+        let mut child = None;
+        let mut grandchild = None;
+        let root = world
+            .spawn()
+            .with_children(|parent| {
+                child = Some(
+                    parent
+                        .spawn()
+                        .with_children(|parent| {
+                            grandchild = Some(parent.spawn().id());
+                        })
+                        .id(),
+                );
+            })
+            .id();
+        let (child, grandchild) = (child.unwrap(), grandchild.unwrap());
+        let unrelated = world.spawn().id();
+
+        assert!(is_descendant_of(&world, child, root));
+        assert!(is_descendant_of(&world, grandchild, root));
+        assert!(is_descendant_of(&world, grandchild, child));
+        assert!(is_ancestor_of(&world, root, grandchild));
+        assert!(is_ancestor_of(&world, child, grandchild));
+
+        assert!(!is_descendant_of(&world, root, child));
+        assert!(!is_ancestor_of(&world, grandchild, root));
+        assert!(!is_descendant_of(&world, root, root));
+        assert!(!is_descendant_of(&world, grandchild, unrelated));
+        assert!(!is_ancestor_of(&world, unrelated, child));
+    }
+
+    #[test]
+    fn malformed_cycle_terminates() {
+        let mut world = World::default();
+        let a = world.spawn().id();
+        let b = world.spawn().insert(Parent(a)).id();
+        world.entity_mut(a).insert(Parent(b));
+        let unrelated = world.spawn().id();
+
+        assert!(is_descendant_of(&world, a, b));
+        assert!(!is_descendant_of(&world, a, unrelated));
+    }
+
+    #[test]
+    fn descendants_in_depth_first_and_breadth_first_order() {
+        let mut world = World::default();
+        let [a, a1, a2, b, b1] = [(); 5].map(|_| world.spawn().id());
+        world.entity_mut(a).push_children(&[a1, a2]);
+        world.entity_mut(b).push_children(&[b1]);
+        let root = world.spawn().push_children(&[a, b]).id();
+
+        let depth_first: Vec<_> = iter_descendants(&world, root).collect();
+        assert_eq!(depth_first, [a, a1, a2, b, b1]);
+        let breadth_first: Vec<_> = iter_descendants_bfs(&world, root).collect();
+        assert_eq!(breadth_first, [a, b, a1, a2, b1]);
+        assert_eq!(iter_descendants(&world, b1).count(), 0);
+
+        // Despawning without `despawn_recursive` leaves `a1` in the `Children` of `a`.
+        world.despawn(a1);
+        let depth_first: Vec<_> = iter_descendants(&world, root).collect();
+        assert_eq!(depth_first, [a, a2, b, b1]);
+    }
+}
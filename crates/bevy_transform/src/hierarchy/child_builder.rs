@@ -129,6 +129,34 @@ impl Command for RemoveChildren {
     }
 }
 
+/// Command that swaps the positions of two children of an entity
+#[derive(Debug)]
+pub struct SwapChildren {
+    parent: Entity,
+    a: Entity,
+    b: Entity,
+}
+
+impl Command for SwapChildren {
+    fn write(self, world: &mut World) {
+        let (a, b) = match world.get::<Children>(self.parent) {
+            Some(children) => (
+                children.iter().position(|child| *child == self.a),
+                children.iter().position(|child| *child == self.b),
+            ),
+            None => return,
+        };
+        // Only touch `Children` if both are children, so that a no-op doesn't trigger change
+        // detection.
+        if let (Some(a), Some(b)) = (a, b) {
+            if a != b {
+                let mut children = world.get_mut::<Children>(self.parent).unwrap();
+                children.0.swap(a, b);
+            }
+        }
+    }
+}
+
 /// Struct for building children onto an entity
 pub struct ChildBuilder<'w, 's, 'a> {
     commands: &'a mut Commands<'w, 's>,
@@ -174,6 +202,8 @@ pub trait BuildChildren {
     fn remove_children(&mut self, children: &[Entity]) -> &mut Self;
     /// Adds a single child
     fn add_child(&mut self, child: Entity) -> &mut Self;
+    /// Swaps the positions of two children, doing nothing if either isn't a child
+    fn swap_children(&mut self, a: Entity, b: Entity) -> &mut Self;
 }
 
 impl<'w, 's, 'a> BuildChildren for EntityCommands<'w, 's, 'a> {
@@ -228,6 +258,12 @@ impl<'w, 's, 'a> BuildChildren for EntityCommands<'w, 's, 'a> {
         self.commands().add(AddChild { child, parent });
         self
     }
+
+    fn swap_children(&mut self, a: Entity, b: Entity) -> &mut Self {
+        let parent = self.id();
+        self.commands().add(SwapChildren { parent, a, b });
+        self
+    }
 }
 
 /// Struct for adding children to an entity directly through the [`World`] for use in exclusive systems
@@ -465,6 +501,7 @@ mod tests {
     use bevy_ecs::{
         component::Component,
         entity::Entity,
+        query::Changed,
         system::{CommandQueue, Commands},
         world::World,
     };
@@ -589,6 +626,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn swap_children_command() {
+        let mut world = World::default();
+        let entities = world
+            .spawn_batch(vec![(C(1),), (C(2),), (C(3),), (C(4),), (C(5),)])
+            .collect::<Vec<Entity>>();
+        let parent = entities[0];
+        world.entity_mut(parent).push_children(&entities[1..4]);
+        let mut changed_children = world.query_filtered::<Entity, Changed<Children>>();
+
+        let mut queue = CommandQueue::default();
+        world.clear_trackers();
+        {
+            let mut commands = Commands::new(&mut queue, &world);
+            commands
+                .entity(parent)
+                .swap_children(entities[1], entities[3]);
+        }
+        queue.apply(&mut world);
+
+        let expected_children: SmallVec<[Entity; 8]> =
+            smallvec![entities[3], entities[2], entities[1]];
+        assert_eq!(
+            world.get::<Children>(parent).unwrap().0.clone(),
+            expected_children
+        );
+        assert_eq!(
+            changed_children.iter(&world).collect::<Vec<_>>(),
+            vec![parent]
+        );
+
+        // Swapping with an entity that isn't a child does nothing.
+        world.clear_trackers();
+        {
+            let mut commands = Commands::new(&mut queue, &world);
+            commands
+                .entity(parent)
+                .swap_children(entities[1], entities[4]);
+        }
+        queue.apply(&mut world);
+
+        assert_eq!(
+            world.get::<Children>(parent).unwrap().0.clone(),
+            expected_children
+        );
+        assert_eq!(changed_children.iter(&world).count(), 0);
+    }
+
     #[test]
     fn push_and_insert_and_remove_children_world() {
         let mut world = World::default();
@@ -4,7 +4,7 @@ use bevy_ecs::{
     system::{Command, EntityCommands},
     world::{EntityMut, World},
 };
-use bevy_utils::tracing::debug;
+use bevy_utils::{tracing::debug, HashSet};
 
 /// Despawns the given entity and all its children recursively
 #[derive(Debug)]
@@ -32,15 +32,30 @@ pub fn despawn_with_children_recursive(world: &mut World, entity: Entity) {
 }
 
 // Should only be called by `despawn_with_children_recursive`!
+//
+// Walks the hierarchy depth-first with an explicit stack, so that deep hierarchies can't overflow
+// the call stack, and despawns every descendant before its parent. Entities are visited at most
+// once, in case the `Children` contain a cycle.
 fn despawn_with_children_recursive_inner(world: &mut World, entity: Entity) {
-    if let Some(mut children) = world.get_mut::<Children>(entity) {
-        for e in std::mem::take(&mut children.0) {
-            despawn_with_children_recursive_inner(world, e);
+    let mut visited = HashSet::default();
+    let mut stack = vec![(entity, false)];
+    while let Some((entity, children_despawned)) = stack.pop() {
+        if children_despawned {
+            if !world.despawn(entity) {
+                debug!("Failed to despawn entity {:?}", entity);
+            }
+            continue;
+        }
+        if !visited.insert(entity) {
+            debug!("Entity {:?} is its own descendant", entity);
+            continue;
         }
-    }
 
-    if !world.despawn(entity) {
-        debug!("Failed to despawn entity {:?}", entity);
+        stack.push((entity, true));
+        if let Some(mut children) = world.get_mut::<Children>(entity) {
+            let children = std::mem::take(&mut children.0);
+            stack.extend(children.into_iter().rev().map(|child| (child, false)));
+        }
     }
 }
 
@@ -115,7 +130,7 @@ mod tests {
         world::World,
     };
 
-    use super::DespawnRecursiveExt;
+    use super::{despawn_with_children_recursive, DespawnRecursiveExt};
     use crate::{components::Children, hierarchy::BuildChildren};
 
     #[derive(Component, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Debug)]
@@ -200,4 +215,28 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn despawn_recursive_survives_cycles_and_despawned_children() {
+        let mut world = World::default();
+        let a = world.spawn().id();
+        let b = world.spawn().insert(Children::with(&[a])).id();
+        world.entity_mut(a).insert(Children::with(&[b]));
+
+        despawn_with_children_recursive(&mut world, a);
+        assert!(world.get_entity(a).is_none());
+        assert!(world.get_entity(b).is_none());
+
+        let despawned = world.spawn().id();
+        let child = world.spawn().id();
+        let root = world
+            .spawn()
+            .insert(Children::with(&[despawned, child]))
+            .id();
+        world.despawn(despawned);
+
+        despawn_with_children_recursive(&mut world, root);
+        assert!(world.get_entity(root).is_none());
+        assert!(world.get_entity(child).is_none());
+    }
 }
@@ -1,8 +1,9 @@
 use crate::components::*;
 use bevy_ecs::{
+    change_detection::DetectChanges,
     entity::Entity,
-    prelude::Changed,
-    query::Without,
+    prelude::{Added, Changed},
+    query::{QueryEntityError, Without},
     system::{Commands, Query},
 };
 use bevy_utils::HashMap;
@@ -70,16 +71,82 @@ pub fn parent_update_system(
         commands.entity(*e).insert(Children::with(v));
     });
 }
+
+/// Points the [`Parent`] of every entity listed in a changed [`Children`] component back at the
+/// entity owning the [`Children`]
+///
+/// A child that is still listed in the [`Children`] of its previous parent is re-parented, and
+/// removed from the previous parent by [`parent_update_system`], which must run after this
+/// system. A [`Parent`] that was changed since this system last ran takes precedence over the
+/// [`Children`] of the previous parent. Despawned children are skipped.
+pub fn children_update_system(
+    mut commands: Commands,
+    children_query: Query<(Entity, &Children), Changed<Children>>,
+    mut parent_query: Query<&mut Parent>,
+) {
+    for (entity, children) in children_query.iter() {
+        for child in children.iter() {
+            match parent_query.get_mut(*child) {
+                Ok(mut parent) => {
+                    if parent.0 != entity && !parent.is_changed() {
+                        parent.0 = entity;
+                    }
+                }
+                Err(QueryEntityError::QueryDoesNotMatch) => {
+                    commands.entity(*child).insert(Parent(entity));
+                }
+                Err(QueryEntityError::NoSuchEntity) => {}
+            }
+        }
+    }
+}
+
+/// Inserts an identity [`Transform`] and [`GlobalTransform`] on every entity that joined a
+/// hierarchy (it got a [`Parent`], or it or one of its children got [`Children`]) but is missing
+/// either of them.
+///
+/// Add it as an exclusive system, so that the transforms are available to
+/// [`transform_propagate_system`](crate::transform_propagate_system::transform_propagate_system)
+/// within the same stage.
+pub fn insert_missing_transforms_system(
+    mut commands: Commands,
+    added_parent_query: Query<Entity, Added<Parent>>,
+    changed_children_query: Query<(Entity, &Children), Changed<Children>>,
+    transform_query: Query<(Option<&Transform>, Option<&GlobalTransform>)>,
+) {
+    let hierarchy_changes = changed_children_query
+        .iter()
+        .flat_map(|(entity, children)| std::iter::once(entity).chain(children.iter().copied()))
+        .chain(added_parent_query.iter());
+    for entity in hierarchy_changes {
+        if let Ok((transform, global_transform)) = transform_query.get(entity) {
+            if transform.is_none() {
+                commands.entity(entity).insert(Transform::identity());
+            }
+            if global_transform.is_none() {
+                commands.entity(entity).insert(GlobalTransform::identity());
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use bevy_ecs::{
-        schedule::{Schedule, Stage, SystemStage},
-        system::CommandQueue,
+        schedule::{
+            ExclusiveSystemDescriptorCoercion, ParallelSystemDescriptorCoercion, Schedule, Stage,
+            SystemStage,
+        },
+        system::{CommandQueue, IntoExclusiveSystem},
         world::World,
     };
 
     use super::*;
-    use crate::{hierarchy::BuildChildren, transform_propagate_system::transform_propagate_system};
+    use crate::{
+        hierarchy::{has_stale_parent, BuildChildren, BuildWorldChildren},
+        transform_propagate_system::transform_propagate_system,
+        TransformBundle, TransformSystem,
+    };
 
     #[test]
     fn correct_children() {
@@ -167,4 +234,79 @@ mod test {
             vec![children[1]]
         );
     }
+
+    #[test]
+    fn child_without_transform_gets_identity() {
+        let mut world = World::default();
+
+        let mut update_stage = SystemStage::parallel();
+        update_stage.add_system(
+            insert_missing_transforms_system
+                .exclusive_system()
+                .at_start(),
+        );
+        update_stage.add_system(parent_update_system);
+        update_stage.add_system(transform_propagate_system);
+
+        let mut schedule = Schedule::default();
+        schedule.add_stage("update", update_stage);
+
+        let child = world.spawn().id();
+        let parent = world
+            .spawn()
+            .insert_bundle(TransformBundle::from(Transform::from_xyz(1.0, 0.0, 0.0)))
+            .push_children(&[child])
+            .id();
+        schedule.run(&mut world);
+
+        assert_eq!(
+            *world.get::<Transform>(child).unwrap(),
+            Transform::identity()
+        );
+        assert_eq!(
+            *world.get::<GlobalTransform>(child).unwrap(),
+            GlobalTransform::from_xyz(1.0, 0.0, 0.0)
+        );
+
+        // Children added to an existing hierarchy in a later frame get transforms too
+        let late_child = world.spawn().id();
+        world.entity_mut(parent).push_children(&[late_child]);
+        schedule.run(&mut world);
+        assert_eq!(
+            *world.get::<GlobalTransform>(late_child).unwrap(),
+            GlobalTransform::from_xyz(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn pushed_children_point_back_at_their_parent() {
+        let mut world = World::default();
+
+        let mut update_stage = SystemStage::parallel();
+        update_stage.add_system(children_update_system.before(TransformSystem::ParentUpdate));
+        update_stage.add_system(parent_update_system.label(TransformSystem::ParentUpdate));
+
+        let mut schedule = Schedule::default();
+        schedule.add_stage("update", update_stage);
+
+        let child = world.spawn().id();
+        let first = world.spawn().insert(Children::default()).id();
+        let second = world.spawn().insert(Children::default()).id();
+
+        world.get_mut::<Children>(first).unwrap().push(child);
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(world.get::<Parent>(child), Some(&Parent(first)));
+
+        // Re-parenting removes the child from its previous parent.
+        world.get_mut::<Children>(second).unwrap().push(child);
+        schedule.run(&mut world);
+        assert_eq!(world.get::<Parent>(child), Some(&Parent(second)));
+        assert!(world.get::<Children>(first).unwrap().is_empty());
+        assert_eq!(&**world.get::<Children>(second).unwrap(), &[child]);
+
+        assert!(!has_stale_parent(&world, child));
+        world.despawn(second);
+        assert!(has_stale_parent(&world, child));
+    }
 }
@@ -1,8 +1,14 @@
+mod ancestry;
 mod child_builder;
 #[allow(clippy::module_inception)]
 mod hierarchy;
 mod hierarchy_maintenance_system;
+mod static_hierarchy;
+mod validation;
 
+pub use ancestry::*;
 pub use child_builder::*;
 pub use hierarchy::*;
 pub use hierarchy_maintenance_system::*;
+pub use static_hierarchy::*;
+pub use validation::*;
@@ -0,0 +1,178 @@
+use crate::components::{Children, GlobalTransform, Parent, Transform};
+use bevy_ecs::{
+    change_detection::DetectChanges,
+    component::Component,
+    entity::Entity,
+    reflect::ReflectComponent,
+    system::{Command, EntityCommands},
+    world::World,
+};
+use bevy_reflect::Reflect;
+
+/// Marks the root of a subtree whose [`GlobalTransform`]s have been baked by [`bake_static`].
+///
+/// [`transform_propagate_system`](crate::transform_propagate_system::transform_propagate_system)
+/// skips this entity and all of its descendants, so changes to their [`Transform`]s (or to the
+/// [`Transform`]s of their ancestors) are ignored until the subtree is unfrozen with
+/// [`unfreeze_static`].
+#[derive(Component, Debug, Default, Copy, Clone, Reflect)]
+#[reflect(Component)]
+pub struct StaticHierarchy;
+
+/// Computes the [`GlobalTransform`]s of `root` and all its descendants, and marks `root` with
+/// [`StaticHierarchy`] so that transform propagation skips the subtree.
+///
+/// The subtree is placed relative to the current [`GlobalTransform`] of the parent of `root`, if
+/// it has one.
+pub fn bake_static(world: &mut World, root: Entity) {
+    let parent_global = world
+        .get::<Parent>(root)
+        .and_then(|parent| world.get::<GlobalTransform>(parent.0))
+        .copied()
+        .unwrap_or_else(GlobalTransform::identity);
+    bake_recursive(world, &parent_global, root);
+    world.entity_mut(root).insert(StaticHierarchy);
+}
+
+fn bake_recursive(world: &mut World, parent: &GlobalTransform, entity: Entity) {
+    let transform = match world.get::<Transform>(entity) {
+        Some(transform) => *transform,
+        None => return,
+    };
+    let global_transform = parent.mul_transform(transform);
+    world.entity_mut(entity).insert(global_transform);
+
+    let children = match world.get::<Children>(entity) {
+        Some(children) => children.0.clone(),
+        None => return,
+    };
+    for child in children {
+        bake_recursive(world, &global_transform, child);
+    }
+}
+
+/// Removes [`StaticHierarchy`] from `root`, so that transform propagation updates the subtree
+/// again, starting with the next run.
+pub fn unfreeze_static(world: &mut World, root: Entity) {
+    let mut root = world.entity_mut(root);
+    if root.remove::<StaticHierarchy>().is_some() {
+        // Propagation only recomputes changed subtrees, so flag the root to catch up on any
+        // changes made while the subtree was frozen.
+        if let Some(mut transform) = root.get_mut::<Transform>() {
+            transform.set_changed();
+        }
+    }
+}
+
+/// Command that bakes a subtree with [`bake_static`]
+#[derive(Debug)]
+pub struct BakeStatic {
+    root: Entity,
+}
+
+/// Command that unfreezes a subtree with [`unfreeze_static`]
+#[derive(Debug)]
+pub struct UnfreezeStatic {
+    root: Entity,
+}
+
+impl Command for BakeStatic {
+    fn write(self, world: &mut World) {
+        bake_static(world, self.root);
+    }
+}
+
+impl Command for UnfreezeStatic {
+    fn write(self, world: &mut World) {
+        unfreeze_static(world, self.root);
+    }
+}
+
+/// Trait that holds functions for freezing and unfreezing the transforms of a subtree
+pub trait StaticHierarchyExt {
+    /// Bakes the [`GlobalTransform`]s of the entity and its descendants and stops propagating
+    /// transforms to them.
+    fn bake_static(&mut self) -> &mut Self;
+
+    /// Resumes propagating transforms to the entity and its descendants.
+    fn unfreeze_static(&mut self) -> &mut Self;
+}
+
+impl<'w, 's, 'a> StaticHierarchyExt for EntityCommands<'w, 's, 'a> {
+    fn bake_static(&mut self) -> &mut Self {
+        let root = self.id();
+        self.commands().add(BakeStatic { root });
+        self
+    }
+
+    fn unfreeze_static(&mut self) -> &mut Self {
+        let root = self.id();
+        self.commands().add(UnfreezeStatic { root });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hierarchy::{parent_update_system, BuildWorldChildren},
+        transform_propagate_system::transform_propagate_system,
+        TransformBundle,
+    };
+    use bevy_ecs::schedule::{Schedule, Stage, SystemStage};
+
+    fn spawn_tree(world: &mut World) -> (Entity, Entity) {
+        let mut child = None;
+        let root = world
+            .spawn()
+            .insert_bundle(TransformBundle::from(Transform::from_xyz(1.0, 0.0, 0.0)))
+            .with_children(|parent| {
+                child = Some(
+                    parent
+                        .spawn_bundle(TransformBundle::from(Transform::from_xyz(0.0, 2.0, 0.0)))
+                        .id(),
+                );
+            })
+            .id();
+        (root, child.unwrap())
+    }
+
+    #[test]
+    fn baked_subtree_is_not_recomputed() {
+        let mut world = World::default();
+
+        let mut update_stage = SystemStage::parallel();
+        update_stage.add_system(parent_update_system);
+        update_stage.add_system(transform_propagate_system);
+        let mut schedule = Schedule::default();
+        schedule.add_stage("update", update_stage);
+
+        let (baked_root, baked_child) = spawn_tree(&mut world);
+        let (live_root, live_child) = spawn_tree(&mut world);
+        schedule.run(&mut world);
+
+        bake_static(&mut world, baked_root);
+        let baked = GlobalTransform::from_xyz(1.0, 2.0, 0.0);
+        assert_eq!(*world.get::<GlobalTransform>(baked_child).unwrap(), baked);
+
+        for root in [baked_root, live_root] {
+            world.get_mut::<Transform>(root).unwrap().translation.x = 5.0;
+        }
+        schedule.run(&mut world);
+
+        assert_eq!(*world.get::<GlobalTransform>(baked_child).unwrap(), baked);
+        assert_eq!(
+            *world.get::<GlobalTransform>(live_child).unwrap(),
+            GlobalTransform::from_xyz(5.0, 2.0, 0.0)
+        );
+
+        unfreeze_static(&mut world, baked_root);
+        schedule.run(&mut world);
+
+        assert_eq!(
+            *world.get::<GlobalTransform>(baked_child).unwrap(),
+            GlobalTransform::from_xyz(5.0, 2.0, 0.0)
+        );
+    }
+}
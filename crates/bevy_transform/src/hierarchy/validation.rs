@@ -0,0 +1,146 @@
+use crate::components::Children;
+use bevy_ecs::{
+    entity::Entity,
+    prelude::Changed,
+    system::{Query, Res},
+};
+use bevy_utils::{tracing::warn, HashSet};
+
+/// Resource that enables and configures [`children_validation_system`].
+///
+/// The validation is opt-in: insert this resource to have every changed [`Children`] component
+/// checked for duplicate entries and unbounded growth.
+#[derive(Debug, Clone, Copy)]
+pub struct ChildrenValidation {
+    /// The number of children above which a [`Children`] list is reported as suspiciously large.
+    pub max_children: usize,
+}
+
+impl Default for ChildrenValidation {
+    fn default() -> Self {
+        Self { max_children: 1024 }
+    }
+}
+
+/// A problem found in a [`Children`] component by [`validate_children`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildrenValidationError {
+    /// `child` appears more than once in the [`Children`] of `parent`, so transform propagation
+    /// would visit it several times.
+    DuplicateChild {
+        /// The entity owning the [`Children`] component
+        parent: Entity,
+        /// The entity that was found more than once
+        child: Entity,
+    },
+    /// The [`Children`] of `parent` has grown beyond [`ChildrenValidation::max_children`].
+    TooManyChildren {
+        /// The entity owning the [`Children`] component
+        parent: Entity,
+        /// The number of children found
+        count: usize,
+        /// The configured threshold
+        max_children: usize,
+    },
+}
+
+/// Checks the [`Children`] of `parent` for duplicate entries and for exceeding `max_children`.
+pub fn validate_children(
+    parent: Entity,
+    children: &Children,
+    max_children: usize,
+) -> Vec<ChildrenValidationError> {
+    let mut errors = Vec::new();
+    if children.len() > max_children {
+        errors.push(ChildrenValidationError::TooManyChildren {
+            parent,
+            count: children.len(),
+            max_children,
+        });
+    }
+
+    let mut seen = HashSet::with_capacity_and_hasher(children.len(), Default::default());
+    let mut reported = HashSet::default();
+    for child in children.iter() {
+        if !seen.insert(*child) && reported.insert(*child) {
+            errors.push(ChildrenValidationError::DuplicateChild {
+                parent,
+                child: *child,
+            });
+        }
+    }
+
+    errors
+}
+
+/// Warns about duplicate or excessive entries in changed [`Children`] components.
+///
+/// Does nothing unless the [`ChildrenValidation`] resource is present.
+pub fn children_validation_system(
+    validation: Option<Res<ChildrenValidation>>,
+    children_query: Query<(Entity, &Children), Changed<Children>>,
+) {
+    let validation = match validation {
+        Some(validation) => validation,
+        None => return,
+    };
+
+    for (parent, children) in children_query.iter() {
+        for error in validate_children(parent, children, validation.max_children) {
+            match error {
+                ChildrenValidationError::DuplicateChild { parent, child } => warn!(
+                    "Entity {:?} is listed more than once in the Children of {:?}",
+                    child, parent
+                ),
+                ChildrenValidationError::TooManyChildren {
+                    parent,
+                    count,
+                    max_children,
+                } => warn!(
+                    "Entity {:?} has {} children, more than the configured maximum of {}",
+                    parent, count, max_children
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hierarchy::BuildWorldChildren;
+    use bevy_ecs::world::World;
+
+    #[test]
+    fn duplicate_push_is_reported() {
+        let mut world = World::default();
+        let child = world.spawn().id();
+        let parent = world.spawn().id();
+        world.entity_mut(parent).push_children(&[child]);
+        world.entity_mut(parent).push_children(&[child]);
+
+        let children = world.get::<Children>(parent).unwrap();
+        assert_eq!(
+            validate_children(parent, children, 8),
+            vec![ChildrenValidationError::DuplicateChild { parent, child }]
+        );
+    }
+
+    #[test]
+    fn over_threshold_is_reported() {
+        let mut world = World::default();
+        let children = (0..4).map(|_| world.spawn().id()).collect::<Vec<_>>();
+        let parent = world.spawn().push_children(&children).id();
+
+        let children = world.get::<Children>(parent).unwrap();
+        assert!(validate_children(parent, children, 4).is_empty());
+        assert_eq!(
+            validate_children(parent, children, 3),
+            vec![ChildrenValidationError::TooManyChildren {
+                parent,
+                count: 4,
+                max_children: 3,
+            }]
+        );
+    }
+}
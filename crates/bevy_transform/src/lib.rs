@@ -17,9 +17,14 @@ pub mod prelude {
 use bevy_app::prelude::*;
 use bevy_ecs::{
     bundle::Bundle,
-    schedule::{ParallelSystemDescriptorCoercion, SystemLabel},
+    schedule::{ExclusiveSystemDescriptorCoercion, ParallelSystemDescriptorCoercion, SystemLabel},
+    system::IntoExclusiveSystem,
+};
+use prelude::{
+    children_update_system, children_validation_system, insert_missing_transforms_system,
+    parent_update_system, Children, GlobalTransform, Parent, PreviousGlobalTransform,
+    PreviousParent, StaticHierarchy, Transform, TransformInterpolation,
 };
-use prelude::{parent_update_system, Children, GlobalTransform, Parent, PreviousParent, Transform};
 
 /// A [`Bundle`] of the [`Transform`] and [`GlobalTransform`]
 /// [`Component`](bevy_ecs::component::Component)s, which describe the position of an entity.
@@ -92,6 +97,8 @@ pub enum TransformSystem {
     TransformPropagate,
     /// Updates [`Parent`] when changes in the hierarchy occur
     ParentUpdate,
+    /// Updates [`Parent`] from changed [`Children`]
+    ChildrenUpdate,
 }
 
 impl Plugin for TransformPlugin {
@@ -99,9 +106,24 @@ impl Plugin for TransformPlugin {
         app.register_type::<Children>()
             .register_type::<Parent>()
             .register_type::<PreviousParent>()
+            .register_type::<StaticHierarchy>()
             .register_type::<Transform>()
             .register_type::<GlobalTransform>()
+            .register_type::<PreviousGlobalTransform>()
+            .init_resource::<TransformInterpolation>()
             // add transform systems to startup so the first update is "correct"
+            .add_startup_system_to_stage(
+                StartupStage::PostStartup,
+                insert_missing_transforms_system
+                    .exclusive_system()
+                    .at_start(),
+            )
+            .add_startup_system_to_stage(
+                StartupStage::PostStartup,
+                children_update_system
+                    .label(TransformSystem::ChildrenUpdate)
+                    .before(TransformSystem::ParentUpdate),
+            )
             .add_startup_system_to_stage(
                 StartupStage::PostStartup,
                 parent_update_system.label(TransformSystem::ParentUpdate),
@@ -112,6 +134,18 @@ impl Plugin for TransformPlugin {
                     .label(TransformSystem::TransformPropagate)
                     .after(TransformSystem::ParentUpdate),
             )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                insert_missing_transforms_system
+                    .exclusive_system()
+                    .at_start(),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                children_update_system
+                    .label(TransformSystem::ChildrenUpdate)
+                    .before(TransformSystem::ParentUpdate),
+            )
             .add_system_to_stage(
                 CoreStage::PostUpdate,
                 parent_update_system.label(TransformSystem::ParentUpdate),
@@ -121,6 +155,10 @@ impl Plugin for TransformPlugin {
                 transform_propagate_system::transform_propagate_system
                     .label(TransformSystem::TransformPropagate)
                     .after(TransformSystem::ParentUpdate),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                children_validation_system.after(TransformSystem::ParentUpdate),
             );
     }
 }
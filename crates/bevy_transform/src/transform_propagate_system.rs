@@ -1,19 +1,32 @@
-use crate::components::{Children, GlobalTransform, Parent, Transform};
+use crate::{
+    components::{Children, GlobalTransform, Parent, Transform},
+    hierarchy::StaticHierarchy,
+};
 use bevy_ecs::{
     entity::Entity,
-    query::{Changed, With, Without},
+    query::{Changed, Or, With, Without},
     system::Query,
+    world::World,
 };
 
+type ChangedTransformOrChildren = Or<(Changed<Transform>, Changed<Children>)>;
+
 /// Update [`GlobalTransform`] component of entities based on entity hierarchy and
 /// [`Transform`] component.
+///
+/// Only the subtrees below a changed [`Transform`] or a changed [`Children`] are recomputed, so
+/// children that were reparented are placed relative to their new parent. Subtrees rooted at an
+/// entity marked with [`StaticHierarchy`] are skipped.
 pub fn transform_propagate_system(
     mut root_query: Query<
         (Entity, Option<&Children>, &Transform, &mut GlobalTransform),
-        Without<Parent>,
+        (Without<Parent>, Without<StaticHierarchy>),
+    >,
+    mut transform_query: Query<
+        (&Transform, &mut GlobalTransform),
+        (With<Parent>, Without<StaticHierarchy>),
     >,
-    mut transform_query: Query<(&Transform, &mut GlobalTransform), With<Parent>>,
-    changed_transform_query: Query<Entity, Changed<Transform>>,
+    changed_transform_query: Query<Entity, ChangedTransformOrChildren>,
     children_query: Query<Option<&Children>, (With<Parent>, With<GlobalTransform>)>,
 ) {
     for (entity, children, transform, mut global_transform) in root_query.iter_mut() {
@@ -40,8 +53,11 @@ pub fn transform_propagate_system(
 
 fn propagate_recursive(
     parent: &GlobalTransform,
-    changed_transform_query: &Query<Entity, Changed<Transform>>,
-    transform_query: &mut Query<(&Transform, &mut GlobalTransform), With<Parent>>,
+    changed_transform_query: &Query<Entity, ChangedTransformOrChildren>,
+    transform_query: &mut Query<
+        (&Transform, &mut GlobalTransform),
+        (With<Parent>, Without<StaticHierarchy>),
+    >,
     children_query: &Query<Option<&Children>, (With<Parent>, With<GlobalTransform>)>,
     entity: Entity,
     mut changed: bool,
@@ -73,6 +89,45 @@ fn propagate_recursive(
     }
 }
 
+/// Recomputes the [`GlobalTransform`]s of `root` and all its descendants, leaving the rest of the
+/// hierarchy untouched.
+///
+/// This is cheaper than waiting for [`transform_propagate_system`] after a local edit, e.g. to
+/// read the updated [`GlobalTransform`]s later in the same stage. The subtree is placed relative
+/// to the current [`GlobalTransform`] of the parent of `root`, which isn't recomputed, so it must
+/// already be up to date. Unlike [`transform_propagate_system`], the whole subtree is recomputed
+/// whether its [`Transform`]s changed or not, and [`StaticHierarchy`] markers are ignored.
+pub fn propagate_subtree(world: &mut World, root: Entity) {
+    let parent_global_transform = world
+        .get::<Parent>(root)
+        .and_then(|parent| world.get::<GlobalTransform>(parent.0))
+        .copied()
+        .unwrap_or_default();
+    propagate_subtree_recursive(world, &parent_global_transform, root);
+}
+
+fn propagate_subtree_recursive(world: &mut World, parent: &GlobalTransform, entity: Entity) {
+    let transform = match world.get::<Transform>(entity) {
+        Some(transform) => *transform,
+        None => return,
+    };
+    let global_transform = match world.get_mut::<GlobalTransform>(entity) {
+        Some(mut global_transform) => {
+            *global_transform = parent.mul_transform(transform);
+            *global_transform
+        }
+        None => return,
+    };
+
+    let children = match world.get::<Children>(entity) {
+        Some(children) => children.0.to_vec(),
+        None => return,
+    };
+    for child in children {
+        propagate_subtree_recursive(world, &global_transform, child);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use bevy_ecs::{
@@ -80,6 +135,7 @@ mod test {
         system::{CommandQueue, Commands},
         world::World,
     };
+    use bevy_math::Vec3;
 
     use super::*;
     use crate::{
@@ -174,4 +230,108 @@ mod test {
             GlobalTransform::from_xyz(1.0, 0.0, 0.0) * Transform::from_xyz(0.0, 0.0, 3.0)
         );
     }
+
+    #[test]
+    fn reparented_child_follows_its_new_parent() {
+        let mut world = World::default();
+
+        let mut update_stage = SystemStage::parallel();
+        update_stage.add_system(parent_update_system);
+        update_stage.add_system(transform_propagate_system);
+
+        let mut schedule = Schedule::default();
+        schedule.add_stage("update", update_stage);
+
+        let mut child = None;
+        world
+            .spawn()
+            .insert_bundle(TransformBundle::from(Transform::from_xyz(1.0, 0.0, 0.0)))
+            .with_children(|parent| {
+                child = Some(
+                    parent
+                        .spawn_bundle(TransformBundle::from(Transform::from_xyz(0.0, 2.0, 0.0)))
+                        .id(),
+                );
+            });
+        let child = child.unwrap();
+        let new_parent = world
+            .spawn()
+            .insert_bundle(TransformBundle::from(Transform::from_xyz(0.0, 0.0, 3.0)))
+            .id();
+        schedule.run(&mut world);
+        assert_eq!(
+            world.get::<GlobalTransform>(child).unwrap().translation,
+            Vec3::new(1.0, 2.0, 0.0)
+        );
+
+        // Neither the child's nor the new parent's `Transform` changes.
+        world.entity_mut(new_parent).push_children(&[child]);
+        schedule.run(&mut world);
+        assert_eq!(
+            world.get::<GlobalTransform>(child).unwrap().translation,
+            Vec3::new(0.0, 2.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn propagate_subtree_only_updates_subtree() {
+        let mut world = World::default();
+
+        let mut update_stage = SystemStage::parallel();
+        update_stage.add_system(parent_update_system);
+        update_stage.add_system(transform_propagate_system);
+
+        let mut schedule = Schedule::default();
+        schedule.add_stage("update", update_stage);
+
+        let mut arm = None;
+        let mut hand = None;
+        let mut leg = None;
+        let body = world
+            .spawn()
+            .insert_bundle(TransformBundle::from(Transform::from_xyz(1.0, 0.0, 0.0)))
+            .with_children(|parent| {
+                arm = Some(
+                    parent
+                        .spawn_bundle(TransformBundle::from(Transform::from_xyz(0.0, 2.0, 0.0)))
+                        .with_children(|parent| {
+                            hand = Some(
+                                parent
+                                    .spawn_bundle(TransformBundle::from(Transform::from_xyz(
+                                        0.0, 0.0, 3.0,
+                                    )))
+                                    .id(),
+                            );
+                        })
+                        .id(),
+                );
+                leg = Some(
+                    parent
+                        .spawn_bundle(TransformBundle::from(Transform::from_xyz(0.0, -2.0, 0.0)))
+                        .id(),
+                );
+            })
+            .id();
+        let (arm, hand, leg) = (arm.unwrap(), hand.unwrap(), leg.unwrap());
+        schedule.run(&mut world);
+
+        // Edits outside of the subtree must not be picked up.
+        world.get_mut::<Transform>(body).unwrap().translation.x = 5.0;
+        world.get_mut::<Transform>(leg).unwrap().translation.y = -4.0;
+        world.get_mut::<Transform>(arm).unwrap().translation.y = 3.0;
+        let body_global = *world.get::<GlobalTransform>(body).unwrap();
+        let leg_global = *world.get::<GlobalTransform>(leg).unwrap();
+
+        propagate_subtree(&mut world, arm);
+
+        assert_eq!(*world.get::<GlobalTransform>(body).unwrap(), body_global);
+        assert_eq!(*world.get::<GlobalTransform>(leg).unwrap(), leg_global);
+        let arm_global =
+            GlobalTransform::from_xyz(1.0, 0.0, 0.0) * Transform::from_xyz(0.0, 3.0, 0.0);
+        assert_eq!(*world.get::<GlobalTransform>(arm).unwrap(), arm_global);
+        assert_eq!(
+            *world.get::<GlobalTransform>(hand).unwrap(),
+            arm_global * Transform::from_xyz(0.0, 0.0, 3.0)
+        );
+    }
 }
@@ -1,6 +1,6 @@
 mod convert;
 
-use crate::{CalculatedSize, Node, Style};
+use crate::{Anchor, CalculatedSize, Node, Style};
 use bevy_app::EventReader;
 use bevy_ecs::{
     entity::Entity,
@@ -210,7 +210,13 @@ pub fn flex_node_system(
         (With<Node>, Changed<CalculatedSize>),
     >,
     children_query: Query<(Entity, &Children), (With<Node>, Changed<Children>)>,
-    mut node_transform_query: Query<(Entity, &mut Node, &mut Transform, Option<&Parent>)>,
+    mut node_transform_query: Query<(
+        Entity,
+        &mut Node,
+        &mut Transform,
+        Option<&Parent>,
+        Option<&Anchor>,
+    )>,
 ) {
     // update window root nodes
     for window in windows.iter() {
@@ -275,8 +281,12 @@ pub fn flex_node_system(
 
     let to_logical = |v| (physical_to_logical_factor * v as f64) as f32;
 
+    let window_size = windows
+        .get_primary()
+        .map(|window| Vec2::new(window.width(), window.height()));
+
     // PERF: try doing this incrementally
-    for (entity, mut node, mut transform, parent) in node_transform_query.iter_mut() {
+    for (entity, mut node, mut transform, parent, anchor) in node_transform_query.iter_mut() {
         let layout = flex_surface.get_layout(entity).unwrap();
         let new_size = Vec2::new(
             to_logical(layout.size.width),
@@ -288,11 +298,24 @@ pub fn flex_node_system(
         let position = &mut transform.translation;
         position.x = to_logical(layout.location.x + layout.size.width / 2.0);
         position.y = to_logical(layout.location.y + layout.size.height / 2.0);
-        if let Some(parent) = parent {
-            if let Ok(parent_layout) = flex_surface.get_layout(parent.0) {
-                position.x -= to_logical(parent_layout.size.width / 2.0);
-                position.y -= to_logical(parent_layout.size.height / 2.0);
+        let parent_size = parent
+            .and_then(|parent| flex_surface.get_layout(parent.0).ok())
+            .map(|parent_layout| {
+                Vec2::new(
+                    to_logical(parent_layout.size.width),
+                    to_logical(parent_layout.size.height),
+                )
+            });
+        if let Some(anchor) = anchor {
+            if let Some(container_size) = parent_size.or(window_size) {
+                let center = anchor.node_center(container_size, new_size);
+                position.x = center.x;
+                position.y = center.y;
             }
         }
+        if let Some(parent_size) = parent_size {
+            position.x -= parent_size.x / 2.0;
+            position.y -= parent_size.y / 2.0;
+        }
     }
 }
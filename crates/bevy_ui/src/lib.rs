@@ -29,7 +29,7 @@ use bevy_ecs::schedule::{ParallelSystemDescriptorCoercion, SystemLabel};
 use bevy_input::InputSystem;
 use bevy_math::{Rect, Size};
 use bevy_transform::TransformSystem;
-use update::{ui_z_system, update_clipping_system};
+use update::{ui_z_system, update_clipping_system, update_mask_system};
 
 /// The basic plugin for Bevy UI
 #[derive(Default)]
@@ -48,8 +48,12 @@ impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<FlexSurface>()
             .register_type::<AlignContent>()
+            .register_type::<Anchor>()
+            .register_type::<AnchorPoint>()
             .register_type::<AlignItems>()
             .register_type::<AlignSelf>()
+            .register_type::<BorderRadius>()
+            .register_type::<CalculatedMask>()
             .register_type::<CalculatedSize>()
             .register_type::<Direction>()
             .register_type::<Display>()
@@ -58,6 +62,7 @@ impl Plugin for UiPlugin {
             .register_type::<FocusPolicy>()
             .register_type::<Interaction>()
             .register_type::<JustifyContent>()
+            .register_type::<NinePatch>()
             .register_type::<Node>()
             // NOTE: used by Style::aspect_ratio
             .register_type::<Option<f32>>()
@@ -67,8 +72,13 @@ impl Plugin for UiPlugin {
             .register_type::<Size<Val>>()
             .register_type::<Rect<Val>>()
             .register_type::<Style>()
+            .register_type::<UiAtlasRect>()
+            .register_type::<UiBlendMode>()
             .register_type::<UiColor>()
             .register_type::<UiImage>()
+            .register_type::<UiMask>()
+            .register_type::<UiSampler>()
+            .register_type::<UiShader>()
             .register_type::<Val>()
             .register_type::<widget::Button>()
             .register_type::<widget::ImageMode>()
@@ -100,7 +110,12 @@ impl Plugin for UiPlugin {
             .add_system_to_stage(
                 CoreStage::PostUpdate,
                 update_clipping_system.after(TransformSystem::TransformPropagate),
-            );
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_mask_system.after(TransformSystem::ParentUpdate),
+            )
+            .add_system_to_stage(CoreStage::PostUpdate, spawn_fallback_ui_camera);
 
         crate::render::build_ui_render(app);
     }
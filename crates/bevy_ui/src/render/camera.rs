@@ -1,9 +1,32 @@
+use crate::{entity::UiCameraBundle, Node};
 use bevy_ecs::prelude::*;
-use bevy_render::{camera::ActiveCameras, render_phase::RenderPhase};
+use bevy_render::{
+    camera::{ActiveCameras, Camera},
+    render_phase::RenderPhase,
+};
 
 /// The name of the UI camera
 pub const CAMERA_UI: &str = "camera_ui";
 
+/// Spawns a [`UiCameraBundle`] if there are UI nodes but no UI camera to render them with.
+///
+/// The camera's orthographic projection maps window pixels to clip space and follows window
+/// resizes, so UI coordinates are well defined even if the app never spawned a UI camera.
+pub fn spawn_fallback_ui_camera(
+    mut commands: Commands,
+    nodes: Query<(), With<Node>>,
+    cameras: Query<&Camera>,
+) {
+    if nodes.is_empty()
+        || cameras
+            .iter()
+            .any(|camera| camera.name.as_deref() == Some(CAMERA_UI))
+    {
+        return;
+    }
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
 /// Inserts the [`RenderPhase`] into the UI camera
 pub fn extract_ui_camera_phases(mut commands: Commands, active_cameras: Res<ActiveCameras>) {
     if let Some(camera_ui) = active_cameras.get(CAMERA_UI) {
@@ -14,3 +37,45 @@ pub fn extract_ui_camera_phases(mut commands: Commands, active_cameras: Res<Acti
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::schedule::{Stage, SystemStage};
+    use bevy_math::{Vec2, Vec3};
+    use bevy_render::camera::{CameraProjection, OrthographicProjection};
+
+    #[test]
+    fn fallback_camera_is_spawned_once_for_ui_nodes() {
+        let mut world = World::default();
+        let mut stage = SystemStage::parallel();
+        stage.add_system(spawn_fallback_ui_camera);
+
+        stage.run(&mut world);
+        assert_eq!(world.query::<&Camera>().iter(&world).count(), 0);
+
+        world.spawn().insert(Node::default());
+        stage.run(&mut world);
+        stage.run(&mut world);
+        let cameras = world
+            .query::<&Camera>()
+            .iter(&world)
+            .map(|camera| camera.name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(cameras, vec![Some(CAMERA_UI.to_string())]);
+    }
+
+    #[test]
+    fn ui_projection_maps_window_corners_to_clip_corners() {
+        let camera = UiCameraBundle::default();
+        let mut projection: OrthographicProjection = camera.orthographic_projection;
+        projection.update(800.0, 600.0);
+        let view_proj =
+            projection.get_projection_matrix() * camera.transform.compute_matrix().inverse();
+
+        let bottom_left = view_proj.project_point3(Vec3::new(0.0, 0.0, 0.0));
+        let top_right = view_proj.project_point3(Vec3::new(800.0, 600.0, 0.0));
+        assert!((bottom_left.truncate() - Vec2::new(-1.0, -1.0)).length() < 1e-5);
+        assert!((top_right.truncate() - Vec2::new(1.0, 1.0)).length() < 1e-5);
+    }
+}
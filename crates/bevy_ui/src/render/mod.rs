@@ -9,7 +9,7 @@ pub use render_pass::*;
 use std::ops::Range;
 
 use bevy_app::prelude::*;
-use bevy_asset::{AssetEvent, Assets, Handle, HandleUntyped};
+use bevy_asset::{Assets, Handle, HandleUntyped};
 use bevy_core::FloatOrd;
 use bevy_ecs::prelude::*;
 use bevy_math::{const_vec3, Mat4, Vec2, Vec3, Vec4Swizzles};
@@ -22,19 +22,22 @@ use bevy_render::{
     render_phase::{sort_phase_system, AddRenderCommand, DrawFunctions, RenderPhase},
     render_resource::*,
     renderer::{RenderDevice, RenderQueue},
-    texture::Image,
-    view::{ViewUniforms, Visibility},
+    texture::{GpuImage, Image, TextureCache},
+    view::{ExtractedView, ViewUniforms, Visibility},
     RenderApp, RenderStage, RenderWorld,
 };
-use bevy_sprite::{Rect, SpriteAssetEvents, TextureAtlas};
+use bevy_sprite::{Pivot, Rect, SpriteAssetEvents, TextureAtlas};
 use bevy_text::{DefaultTextPipeline, Text};
 use bevy_transform::components::GlobalTransform;
-use bevy_utils::HashMap;
+use bevy_utils::{tracing::warn, HashMap};
 use bevy_window::Windows;
 
 use bytemuck::{Pod, Zeroable};
 
-use crate::{CalculatedClip, Node, UiColor, UiImage};
+use crate::{
+    BorderRadius, CalculatedClip, CalculatedMask, NinePatch, Node, UiAtlasRect, UiBlendMode,
+    UiColor, UiImage, UiMask, UiSampler, UiShader,
+};
 
 pub mod node {
     pub const UI_PASS_DRIVER: &str = "ui_pass_driver";
@@ -66,12 +69,19 @@ pub fn build_ui_render(app: &mut App) {
     let mut active_cameras = app.world.get_resource_mut::<ActiveCameras>().unwrap();
     active_cameras.add(CAMERA_UI);
 
+    let depth_mode = app
+        .world
+        .get_resource::<UiDepthMode>()
+        .copied()
+        .unwrap_or_default();
+
     let render_app = match app.get_sub_app_mut(RenderApp) {
         Ok(render_app) => render_app,
         Err(_) => return,
     };
 
     render_app
+        .insert_resource(depth_mode)
         .init_resource::<UiPipeline>()
         .init_resource::<SpecializedPipelines<UiPipeline>>()
         .init_resource::<UiImageBindGroups>()
@@ -89,6 +99,7 @@ pub fn build_ui_render(app: &mut App) {
             extract_text_uinodes.after(RenderUiSystem::ExtractNode),
         )
         .add_system_to_stage(RenderStage::Prepare, prepare_uinodes)
+        .add_system_to_stage(RenderStage::Prepare, prepare_ui_depth_textures)
         .add_system_to_stage(RenderStage::Queue, queue_uinodes)
         .add_system_to_stage(RenderStage::PhaseSort, sort_phase_system::<TransparentUi>);
 
@@ -127,7 +138,19 @@ pub struct ExtractedUiNode {
     pub rect: Rect,
     pub image: Handle<Image>,
     pub atlas_size: Option<Vec2>,
+    /// The region of the image drawn on the node
+    pub atlas_rect: UiAtlasRect,
     pub clip: Option<Rect>,
+    pub blend_mode: UiBlendMode,
+    /// The radius of the corners, already clamped to the size of the node
+    pub border_radius: f32,
+    /// The fragment shader replacing the default UI shader, if any
+    pub shader: Option<Handle<Shader>>,
+    /// The sampler replacing the one of the image, if any
+    pub sampler: Option<UiSampler>,
+    pub stencil_role: UiStencilRole,
+    /// The stencil reference of the [`UiMask`] the node is or is masked by
+    pub stencil_reference: u32,
 }
 
 #[derive(Default)]
@@ -135,6 +158,30 @@ pub struct ExtractedUiNodes {
     pub uinodes: Vec<ExtractedUiNode>,
 }
 
+impl ExtractedUiNodes {
+    /// Whether any node is masked, which requires a stencil buffer in the UI pass
+    pub fn uses_stencil(&self) -> bool {
+        self.uinodes
+            .iter()
+            .any(|uinode| uinode.stencil_role != UiStencilRole::Ignore)
+    }
+}
+
+/// The stencil role and reference of a node, depending on whether it is a [`UiMask`] itself and
+/// its [`CalculatedMask`]
+fn stencil(
+    mask: Option<&UiMask>,
+    calculated_mask: Option<&CalculatedMask>,
+) -> (UiStencilRole, u32) {
+    match (mask, calculated_mask) {
+        (_, None) => (UiStencilRole::Ignore, 0),
+        (Some(_), Some(calculated_mask)) => {
+            (UiStencilRole::WriteMask, calculated_mask.reference.into())
+        }
+        (None, Some(calculated_mask)) => (UiStencilRole::Masked, calculated_mask.reference.into()),
+    }
+}
+
 pub fn extract_uinodes(
     mut render_world: ResMut<RenderWorld>,
     images: Res<Assets<Image>>,
@@ -145,17 +192,75 @@ pub fn extract_uinodes(
         &UiImage,
         &Visibility,
         Option<&CalculatedClip>,
+        Option<&UiBlendMode>,
+        Option<&Pivot>,
+        Option<&BorderRadius>,
+        Option<&UiAtlasRect>,
+        Option<&NinePatch>,
+        Option<&UiShader>,
+        Option<&UiSampler>,
+        Option<&UiMask>,
+        Option<&CalculatedMask>,
     )>,
 ) {
     let mut extracted_uinodes = render_world.get_resource_mut::<ExtractedUiNodes>().unwrap();
     extracted_uinodes.uinodes.clear();
-    for (uinode, transform, color, image, visibility, clip) in uinode_query.iter() {
+    for (
+        uinode,
+        transform,
+        color,
+        image,
+        visibility,
+        clip,
+        blend_mode,
+        pivot,
+        border_radius,
+        atlas_rect,
+        nine_patch,
+        shader,
+        sampler,
+        mask,
+        calculated_mask,
+    ) in uinode_query.iter()
+    {
         if !visibility.is_visible {
             continue;
         }
         let image = image.0.clone_weak();
+        let shader = shader.map(|shader| shader.0.clone_weak());
+        let sampler = sampler.copied();
+        let (stencil_role, stencil_reference) = stencil(mask, calculated_mask);
         // Skip loading images
-        if !images.contains(image.clone_weak()) {
+        let image_size = match images.get(image.clone_weak()) {
+            Some(image) => image.size(),
+            None => continue,
+        };
+        let transform = pivot
+            .copied()
+            .unwrap_or_default()
+            .transform_quad(transform, uinode.size);
+        if let Some(nine_patch) = nine_patch {
+            let transform = transform.compute_matrix();
+            for slice in nine_patch.slices(uinode.size, image_size) {
+                extracted_uinodes.uinodes.push(ExtractedUiNode {
+                    transform: transform * Mat4::from_translation(slice.center.extend(0.0)),
+                    color: color.0,
+                    rect: bevy_sprite::Rect {
+                        min: Vec2::ZERO,
+                        max: slice.size,
+                    },
+                    image: image.clone_weak(),
+                    atlas_size: None,
+                    atlas_rect: slice.atlas_rect,
+                    clip: clip.map(|clip| clip.clip),
+                    blend_mode: blend_mode.copied().unwrap_or_default(),
+                    border_radius: 0.0,
+                    shader: shader.clone(),
+                    sampler,
+                    stencil_role,
+                    stencil_reference,
+                });
+            }
             continue;
         }
         extracted_uinodes.uinodes.push(ExtractedUiNode {
@@ -167,7 +272,17 @@ pub fn extract_uinodes(
             },
             image,
             atlas_size: None,
+            atlas_rect: atlas_rect.copied().unwrap_or_default(),
             clip: clip.map(|clip| clip.clip),
+            blend_mode: blend_mode.copied().unwrap_or_default(),
+            border_radius: border_radius
+                .copied()
+                .unwrap_or_default()
+                .clamped(uinode.size),
+            shader,
+            sampler,
+            stencil_role,
+            stencil_reference,
         });
     }
 }
@@ -184,6 +299,8 @@ pub fn extract_text_uinodes(
         &Text,
         &Visibility,
         Option<&CalculatedClip>,
+        Option<&UiMask>,
+        Option<&CalculatedMask>,
     )>,
 ) {
     let mut extracted_uinodes = render_world.get_resource_mut::<ExtractedUiNodes>().unwrap();
@@ -194,7 +311,9 @@ pub fn extract_text_uinodes(
         1.
     };
 
-    for (entity, uinode, transform, text, visibility, clip) in uinode_query.iter() {
+    for (entity, uinode, transform, text, visibility, clip, mask, calculated_mask) in
+        uinode_query.iter()
+    {
         if !visibility.is_visible {
             continue;
         }
@@ -202,6 +321,7 @@ pub fn extract_text_uinodes(
         if uinode.size == Vec2::ZERO {
             continue;
         }
+        let (stencil_role, stencil_reference) = stencil(mask, calculated_mask);
         if let Some(text_layout) = text_pipeline.get_glyphs(&entity) {
             let text_glyphs = &text_layout.glyphs;
             let alignment_offset = (uinode.size / -2.0).extend(0.0);
@@ -229,7 +349,14 @@ pub fn extract_text_uinodes(
                     rect,
                     image: texture,
                     atlas_size,
+                    atlas_rect: UiAtlasRect::default(),
                     clip: clip.map(|clip| clip.clip),
+                    blend_mode: UiBlendMode::default(),
+                    border_radius: 0.0,
+                    shader: None,
+                    sampler: None,
+                    stencil_role,
+                    stencil_reference,
                 });
             }
         }
@@ -242,6 +369,10 @@ struct UiVertex {
     pub position: [f32; 3],
     pub uv: [f32; 2],
     pub color: u32,
+    /// The position relative to the center of the node
+    pub local_position: [f32; 2],
+    /// The half size of the node, followed by the radius of its corners
+    pub border: [f32; 3],
 }
 
 pub struct UiMeta {
@@ -271,6 +402,11 @@ const QUAD_INDICES: [usize; 6] = [0, 2, 3, 0, 1, 2];
 pub struct UiBatch {
     pub range: Range<u32>,
     pub image: Handle<Image>,
+    pub blend_mode: UiBlendMode,
+    pub shader: Option<Handle<Shader>>,
+    pub sampler: Option<UiSampler>,
+    pub stencil_role: UiStencilRole,
+    pub stencil_reference: u32,
     pub z: f32,
 }
 
@@ -291,18 +427,43 @@ pub fn prepare_uinodes(
     let mut start = 0;
     let mut end = 0;
     let mut current_batch_handle = Default::default();
+    let mut current_blend_mode = UiBlendMode::default();
+    let mut current_shader = None;
+    let mut current_sampler = None;
+    let mut current_stencil = (UiStencilRole::Ignore, 0);
     let mut last_z = 0.0;
     for extracted_uinode in &extracted_uinodes.uinodes {
-        if current_batch_handle != extracted_uinode.image {
+        if current_batch_handle != extracted_uinode.image
+            || current_blend_mode != extracted_uinode.blend_mode
+            || current_shader != extracted_uinode.shader
+            || current_sampler != extracted_uinode.sampler
+            || current_stencil
+                != (
+                    extracted_uinode.stencil_role,
+                    extracted_uinode.stencil_reference,
+                )
+        {
             if start != end {
                 commands.spawn_bundle((UiBatch {
                     range: start..end,
                     image: current_batch_handle,
+                    blend_mode: current_blend_mode,
+                    shader: current_shader,
+                    sampler: current_sampler,
+                    stencil_role: current_stencil.0,
+                    stencil_reference: current_stencil.1,
                     z: last_z,
                 },));
                 start = end;
             }
             current_batch_handle = extracted_uinode.image.clone_weak();
+            current_blend_mode = extracted_uinode.blend_mode;
+            current_shader = extracted_uinode.shader.clone();
+            current_sampler = extracted_uinode.sampler;
+            current_stencil = (
+                extracted_uinode.stencil_role,
+                extracted_uinode.stencil_reference,
+            );
         }
 
         let uinode_rect = extracted_uinode.rect;
@@ -371,7 +532,7 @@ pub fn prepare_uinodes(
                 uinode_rect.min.y - positions_diff[3].y,
             ),
         ]
-        .map(|pos| pos / atlas_extent);
+        .map(|pos| extracted_uinode.atlas_rect.map_uv(pos / atlas_extent));
 
         let color = extracted_uinode.color.as_linear_rgba_f32();
         // encode color as a single u32 to save space
@@ -380,11 +541,19 @@ pub fn prepare_uinodes(
             | ((color[2] * 255.0) as u32) << 16
             | ((color[3] * 255.0) as u32) << 24;
 
+        let border = (rect_size.truncate() / 2.0)
+            .extend(extracted_uinode.border_radius)
+            .into();
+
         for i in QUAD_INDICES {
             ui_meta.vertices.push(UiVertex {
                 position: positions_clipped[i].into(),
                 uv: uvs[i].into(),
                 color,
+                local_position: (QUAD_VERTEX_POSITIONS[i].truncate() * rect_size.truncate()
+                    + positions_diff[i])
+                    .into(),
+                border,
             });
         }
 
@@ -397,6 +566,11 @@ pub fn prepare_uinodes(
         commands.spawn_bundle((UiBatch {
             range: start..end,
             image: current_batch_handle,
+            blend_mode: current_blend_mode,
+            shader: current_shader,
+            sampler: current_sampler,
+            stencil_role: current_stencil.0,
+            stencil_reference: current_stencil.1,
             z: last_z,
         },));
     }
@@ -406,7 +580,69 @@ pub fn prepare_uinodes(
 
 #[derive(Default)]
 pub struct UiImageBindGroups {
-    pub values: HashMap<Handle<Image>, BindGroup>,
+    /// The bind groups of each image, drawn with the sampler of the image or a [`UiSampler`]
+    values: HashMap<(Handle<Image>, Option<UiSampler>), BindGroup>,
+}
+
+impl UiImageBindGroups {
+    /// Returns the bind group of `image` drawn with `sampler`, if it is cached.
+    pub fn get(&self, image: &Handle<Image>, sampler: Option<UiSampler>) -> Option<&BindGroup> {
+        self.values.get(&(image.clone_weak(), sampler))
+    }
+
+    /// Caches the bind group of `image` drawn with `sampler`.
+    pub fn insert(
+        &mut self,
+        image: Handle<Image>,
+        sampler: Option<UiSampler>,
+        bind_group: BindGroup,
+    ) {
+        self.values.insert((image, sampler), bind_group);
+    }
+
+    /// Drops the bind groups of `image` with any sampler, so that new ones are created the next
+    /// time it is drawn.
+    pub fn remove(&mut self, image: &Handle<Image>) {
+        self.values
+            .retain(|(bound_image, _), _| bound_image != image);
+    }
+
+    /// Drops the bind groups of the images invalidated by `events`.
+    pub fn invalidate(&mut self, events: &SpriteAssetEvents) {
+        for image in events.invalidated_images() {
+            self.remove(image);
+        }
+    }
+
+    /// Drops all bind groups, so that they are all recreated the next time they are drawn.
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+/// Allocates the depth and stencil buffers of the UI views if [`UiDepthMode::DepthBuffer`] is used
+/// or any node is masked.
+pub fn prepare_ui_depth_textures(
+    mut commands: Commands,
+    mut texture_cache: ResMut<TextureCache>,
+    render_device: Res<RenderDevice>,
+    depth_mode: Res<UiDepthMode>,
+    extracted_uinodes: Res<ExtractedUiNodes>,
+    views: Query<(Entity, &ExtractedView), With<RenderPhase<TransparentUi>>>,
+) {
+    if *depth_mode != UiDepthMode::DepthBuffer && !extracted_uinodes.uses_stencil() {
+        return;
+    }
+    for (entity, view) in views.iter() {
+        let descriptor = ui_depth_texture_descriptor(view.width, view.height);
+        let sample_count = descriptor.sample_count;
+        let cached_texture = texture_cache.get(&render_device, descriptor);
+        commands.entity(entity).insert(ViewUiDepthTexture {
+            texture: cached_texture.texture,
+            view: cached_texture.default_view,
+            sample_count,
+        });
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -416,6 +652,7 @@ pub fn queue_uinodes(
     mut ui_meta: ResMut<UiMeta>,
     view_uniforms: Res<ViewUniforms>,
     ui_pipeline: Res<UiPipeline>,
+    depth_mode: Res<UiDepthMode>,
     mut pipelines: ResMut<SpecializedPipelines<UiPipeline>>,
     mut pipeline_cache: ResMut<RenderPipelineCache>,
     mut image_bind_groups: ResMut<UiImageBindGroups>,
@@ -425,13 +662,7 @@ pub fn queue_uinodes(
     events: Res<SpriteAssetEvents>,
 ) {
     // If an image has changed, the GpuImage has (probably) changed
-    for event in &events.images {
-        match event {
-            AssetEvent::Created { .. } => None,
-            AssetEvent::Modified { handle } => image_bind_groups.values.remove(handle),
-            AssetEvent::Removed { handle } => image_bind_groups.values.remove(handle),
-        };
-    }
+    image_bind_groups.invalidate(&events);
 
     if let Some(view_binding) = view_uniforms.uniforms.binding() {
         ui_meta.view_bind_group = Some(render_device.create_bind_group(&BindGroupDescriptor {
@@ -443,36 +674,63 @@ pub fn queue_uinodes(
             layout: &ui_pipeline.view_layout,
         }));
         let draw_ui_function = draw_functions.read().get_id::<DrawUi>().unwrap();
-        let pipeline = pipelines.specialize(&mut pipeline_cache, &ui_pipeline, UiPipelineKey {});
+        // Matches the stencil buffer allocated by `prepare_ui_depth_textures`
+        let stencil_buffer = ui_batches
+            .iter()
+            .any(|(_, batch)| batch.stencil_role != UiStencilRole::Ignore);
         for mut transparent_phase in views.iter_mut() {
             for (entity, batch) in ui_batches.iter() {
-                image_bind_groups
-                    .values
-                    .entry(batch.image.clone_weak())
-                    .or_insert_with(|| {
-                        let gpu_image = gpu_images.get(&batch.image).unwrap();
-                        render_device.create_bind_group(&BindGroupDescriptor {
-                            entries: &[
-                                BindGroupEntry {
-                                    binding: 0,
-                                    resource: BindingResource::TextureView(&gpu_image.texture_view),
-                                },
-                                BindGroupEntry {
-                                    binding: 1,
-                                    resource: BindingResource::Sampler(&gpu_image.sampler),
-                                },
-                            ],
-                            label: Some("ui_material_bind_group"),
-                            layout: &ui_pipeline.image_layout,
-                        })
+                if image_bind_groups.get(&batch.image, batch.sampler).is_none() {
+                    let gpu_image = match GpuImage::get(&gpu_images, &batch.image) {
+                        Ok(gpu_image) => gpu_image,
+                        Err(err) => {
+                            warn!("Skipping UI batch: {}", err);
+                            continue;
+                        }
+                    };
+                    let ui_sampler = batch
+                        .sampler
+                        .map(|sampler| render_device.create_sampler(&sampler.descriptor()));
+                    let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+                        entries: &[
+                            BindGroupEntry {
+                                binding: 0,
+                                resource: BindingResource::TextureView(&gpu_image.texture_view),
+                            },
+                            BindGroupEntry {
+                                binding: 1,
+                                resource: BindingResource::Sampler(
+                                    ui_sampler.as_ref().unwrap_or(&gpu_image.sampler),
+                                ),
+                            },
+                        ],
+                        label: Some("ui_material_bind_group"),
+                        layout: &ui_pipeline.image_layout,
                     });
-
-                transparent_phase.add(TransparentUi {
-                    draw_function: draw_ui_function,
-                    pipeline,
-                    entity,
-                    sort_key: FloatOrd(batch.z),
-                });
+                    image_bind_groups.insert(batch.image.clone_weak(), batch.sampler, bind_group);
+                }
+
+                // A mask is drawn twice in a row, which the stable sort of the phase preserves.
+                for &stencil_role in batch.stencil_role.draws() {
+                    let pipeline = pipelines.specialize(
+                        &mut pipeline_cache,
+                        &ui_pipeline,
+                        UiPipelineKey {
+                            blend_mode: batch.blend_mode,
+                            depth_mode: *depth_mode,
+                            msaa_samples: UI_SAMPLE_COUNT,
+                            fragment_shader: batch.shader.as_ref().map(|shader| shader.id),
+                            stencil_role,
+                            stencil_buffer,
+                        },
+                    );
+                    transparent_phase.add(TransparentUi {
+                        draw_function: draw_ui_function,
+                        pipeline,
+                        entity,
+                        sort_key: FloatOrd(batch.z),
+                    });
+                }
             }
         }
     }
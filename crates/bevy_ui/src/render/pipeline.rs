@@ -1,3 +1,5 @@
+use crate::UiBlendMode;
+use bevy_asset::{Handle, HandleId};
 use bevy_ecs::prelude::*;
 use bevy_render::{
     render_resource::{std140::AsStd140, *},
@@ -59,15 +61,183 @@ impl FromWorld for UiPipeline {
     }
 }
 
+/// The format of the depth and stencil buffer used by [`UiDepthMode::DepthBuffer`] and
+/// [`UiMask`](crate::UiMask)s
+pub const UI_DEPTH_FORMAT: TextureFormat = TextureFormat::Depth24PlusStencil8;
+
+/// The value the UI depth buffer is cleared to at the start of the UI pass, which is the far plane
+/// of the reversed depth range.
+pub const UI_DEPTH_CLEAR_VALUE: f32 = 0.0;
+
+/// The sample count of the UI pass, whatever the [`Msaa`](bevy_render::view::Msaa) setting of the
+/// main passes.
+///
+/// Each camera has its own multisampled texture, so the one of the UI camera doesn't hold what the
+/// main passes drew, and resolving it would overwrite them. The UI is thus drawn single-sampled
+/// into the swap chain texture the main passes resolved into.
+pub const UI_SAMPLE_COUNT: u32 = 1;
+
+/// The descriptor of the depth and stencil buffer of a UI view of `width` by `height` pixels
+pub fn ui_depth_texture_descriptor(width: u32, height: u32) -> TextureDescriptor<'static> {
+    TextureDescriptor {
+        label: Some("ui_depth_texture"),
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: UI_SAMPLE_COUNT,
+        dimension: TextureDimension::D2,
+        format: UI_DEPTH_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT,
+    }
+}
+
+/// Configures how UI nodes are ordered when they are rendered.
+///
+/// Insert this resource before adding the [`UiPlugin`](crate::UiPlugin) to change it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UiDepthMode {
+    /// Nodes are drawn back to front, sorted by their z coordinate, without a depth buffer.
+    SortOnly,
+    /// Nodes are also depth tested against a depth buffer of their own, which is cleared at the
+    /// start of every UI pass. The depth buffer of the 3D passes is never read or written, so UI
+    /// can't z-fight with 3D geometry and is drawn without a depth bias.
+    DepthBuffer,
+}
+
+impl Default for UiDepthMode {
+    fn default() -> Self {
+        UiDepthMode::SortOnly
+    }
+}
+
+impl UiDepthMode {
+    /// The [`DepthStencilState`] used by the UI pipeline for this depth mode
+    pub fn depth_stencil_state(self) -> Option<DepthStencilState> {
+        match self {
+            UiDepthMode::SortOnly => None,
+            UiDepthMode::DepthBuffer => Some(DepthStencilState {
+                format: UI_DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::GreaterEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+        }
+    }
+}
+
+/// How a UI node takes part in stencil masking, see [`UiMask`](crate::UiMask)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UiStencilRole {
+    /// The node isn't masked
+    Ignore,
+    /// The node is a mask, and writes the stencil reference where it's opaque without touching the
+    /// color target. Masks are drawn as usual first, see [`UiStencilRole::draws`].
+    WriteMask,
+    /// The node is only drawn where the stencil buffer holds the reference of its mask
+    Masked,
+}
+
+impl Default for UiStencilRole {
+    fn default() -> Self {
+        UiStencilRole::Ignore
+    }
+}
+
+impl UiStencilRole {
+    /// The roles of the draws of a node with this role, in order. A mask is drawn twice: once as
+    /// an unmasked node, so its soft edges keep their color, then once more to write the stencil.
+    pub fn draws(self) -> &'static [UiStencilRole] {
+        match self {
+            UiStencilRole::Ignore => &[UiStencilRole::Ignore],
+            UiStencilRole::WriteMask => &[UiStencilRole::Ignore, UiStencilRole::WriteMask],
+            UiStencilRole::Masked => &[UiStencilRole::Masked],
+        }
+    }
+
+    /// The color channels written by the UI pipeline for this role
+    pub fn color_writes(self) -> ColorWrites {
+        match self {
+            UiStencilRole::WriteMask => ColorWrites::empty(),
+            UiStencilRole::Ignore | UiStencilRole::Masked => ColorWrites::ALL,
+        }
+    }
+
+    /// The [`StencilState`] used by the UI pipeline for this role
+    pub fn stencil_state(self) -> StencilState {
+        let face = |compare, pass_op| StencilFaceState {
+            compare,
+            fail_op: StencilOperation::Keep,
+            depth_fail_op: StencilOperation::Keep,
+            pass_op,
+        };
+        match self {
+            UiStencilRole::Ignore => StencilState::default(),
+            UiStencilRole::WriteMask => StencilState {
+                front: face(CompareFunction::Always, StencilOperation::Replace),
+                back: face(CompareFunction::Always, StencilOperation::Replace),
+                read_mask: 0,
+                write_mask: !0,
+            },
+            UiStencilRole::Masked => StencilState {
+                front: face(CompareFunction::Equal, StencilOperation::Keep),
+                back: face(CompareFunction::Equal, StencilOperation::Keep),
+                read_mask: !0,
+                write_mask: 0,
+            },
+        }
+    }
+}
+
 #[derive(Clone, Copy, Hash, PartialEq, Eq)]
-pub struct UiPipelineKey {}
+pub struct UiPipelineKey {
+    pub blend_mode: UiBlendMode,
+    pub depth_mode: UiDepthMode,
+    /// The sample count of the UI pass, which is [`UI_SAMPLE_COUNT`]
+    pub msaa_samples: u32,
+    /// The [`UiShader`](crate::UiShader) replacing the fragment stage of the default UI shader
+    pub fragment_shader: Option<HandleId>,
+    pub stencil_role: UiStencilRole,
+    /// Whether the UI pass has a stencil buffer, which is the case as soon as any node is masked
+    pub stencil_buffer: bool,
+}
 
 impl SpecializedPipeline for UiPipeline {
     type Key = UiPipelineKey;
-    /// FIXME: there are no specialization for now, should this be removed?
-    fn specialize(&self, _key: Self::Key) -> RenderPipelineDescriptor {
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut descriptor = key.descriptor();
+        descriptor.layout = Some(vec![self.view_layout.clone(), self.image_layout.clone()]);
+        descriptor
+    }
+}
+
+impl UiPipelineKey {
+    /// The [`DepthStencilState`] of the UI pipeline for this key
+    fn depth_stencil_state(self) -> Option<DepthStencilState> {
+        if !self.stencil_buffer {
+            return self.depth_mode.depth_stencil_state();
+        }
+        let mut depth_stencil =
+            self.depth_mode
+                .depth_stencil_state()
+                .unwrap_or(DepthStencilState {
+                    format: UI_DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: CompareFunction::Always,
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                });
+        depth_stencil.stencil = self.stencil_role.stencil_state();
+        Some(depth_stencil)
+    }
+
+    /// The UI pipeline for this key, without the bind group layouts of the [`UiPipeline`]
+    fn descriptor(self) -> RenderPipelineDescriptor {
         let vertex_buffer_layout = VertexBufferLayout {
-            array_stride: 24,
+            array_stride: 44,
             step_mode: VertexStepMode::Vertex,
             attributes: vec![
                 // Position
@@ -87,9 +257,27 @@ impl SpecializedPipeline for UiPipeline {
                     offset: 20,
                     shader_location: 2,
                 },
+                // Position relative to the center of the node
+                VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: 24,
+                    shader_location: 3,
+                },
+                // Half size and corner radius of the node
+                VertexAttribute {
+                    format: VertexFormat::Float32x3,
+                    offset: 32,
+                    shader_location: 4,
+                },
             ],
         };
-        let shader_defs = Vec::new();
+        let mut shader_defs = Vec::new();
+        if self.blend_mode == UiBlendMode::PremultipliedAlpha {
+            shader_defs.push(String::from("PREMULTIPLIED_ALPHA"));
+        }
+        if self.stencil_role == UiStencilRole::WriteMask {
+            shader_defs.push(String::from("STENCIL_MASK"));
+        }
 
         RenderPipelineDescriptor {
             vertex: VertexState {
@@ -99,16 +287,18 @@ impl SpecializedPipeline for UiPipeline {
                 buffers: vec![vertex_buffer_layout],
             },
             fragment: Some(FragmentState {
-                shader: super::UI_SHADER_HANDLE.typed::<Shader>(),
+                shader: self
+                    .fragment_shader
+                    .map_or_else(|| super::UI_SHADER_HANDLE.typed::<Shader>(), Handle::weak),
                 shader_defs,
                 entry_point: "fragment".into(),
                 targets: vec![ColorTargetState {
                     format: TextureFormat::bevy_default(),
-                    blend: Some(BlendState::ALPHA_BLENDING),
-                    write_mask: ColorWrites::ALL,
+                    blend: Some(self.blend_mode.blend_state()),
+                    write_mask: self.stencil_role.color_writes(),
                 }],
             }),
-            layout: Some(vec![self.view_layout.clone(), self.image_layout.clone()]),
+            layout: None,
             primitive: PrimitiveState {
                 front_face: FrontFace::Ccw,
                 cull_mode: None,
@@ -118,9 +308,9 @@ impl SpecializedPipeline for UiPipeline {
                 topology: PrimitiveTopology::TriangleList,
                 strip_index_format: None,
             },
-            depth_stencil: None,
+            depth_stencil: self.depth_stencil_state(),
             multisample: MultisampleState {
-                count: 1,
+                count: self.msaa_samples,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
@@ -128,3 +318,222 @@ impl SpecializedPipeline for UiPipeline {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::UiCameraBundle;
+    use bevy_math::Vec3;
+    use bevy_render::{camera::CameraProjection, renderer::validate_sample_counts, view::Msaa};
+
+    #[test]
+    fn sort_only_has_no_depth_buffer() {
+        assert!(UiDepthMode::default().depth_stencil_state().is_none());
+    }
+
+    #[test]
+    fn ui_depth_orders_nodes_by_z() {
+        let depth_stencil = UiDepthMode::DepthBuffer.depth_stencil_state().unwrap();
+        assert_eq!(depth_stencil.format, UI_DEPTH_FORMAT);
+        assert!(depth_stencil.depth_write_enabled);
+        assert_eq!(depth_stencil.bias.constant, 0);
+        assert_eq!(depth_stencil.bias.slope_scale, 0.0);
+
+        let mut camera = UiCameraBundle::default();
+        camera.orthographic_projection.update(800.0, 600.0);
+        let view_proj = camera.orthographic_projection.get_projection_matrix()
+            * camera.transform.compute_matrix().inverse();
+        let depth = |z: f32| view_proj.project_point3(Vec3::new(400.0, 300.0, z)).z;
+        let passes = |new: f32, stored: f32| match depth_stencil.depth_compare {
+            CompareFunction::GreaterEqual => new >= stored,
+            CompareFunction::Greater => new > stored,
+            compare => panic!("unexpected depth compare {:?}", compare),
+        };
+
+        // Nodes in front pass over nodes behind, but not the other way around.
+        assert!(passes(depth(2.0), depth(1.0)));
+        assert!(!passes(depth(1.0), depth(2.0)));
+        // Since the UI depth buffer starts out cleared, whatever the 3D passes wrote to theirs, any
+        // node within the camera's range is drawn.
+        for z in [0.0, 1.0, 500.0, 999.0] {
+            assert!(passes(depth(z), UI_DEPTH_CLEAR_VALUE));
+        }
+    }
+
+    #[test]
+    fn multisampled_ui_pipeline() {
+        let descriptor = UiPipelineKey {
+            blend_mode: UiBlendMode::default(),
+            depth_mode: UiDepthMode::DepthBuffer,
+            msaa_samples: 4,
+            fragment_shader: None,
+            stencil_role: UiStencilRole::Ignore,
+            stencil_buffer: false,
+        }
+        .descriptor();
+        assert_eq!(descriptor.multisample.count, 4);
+        assert_eq!(descriptor.depth_stencil.unwrap().format, UI_DEPTH_FORMAT);
+    }
+
+    #[test]
+    fn ui_pass_of_a_multisampled_view_is_single_sampled() {
+        let msaa = Msaa { samples: 4 };
+        let key = UiPipelineKey {
+            blend_mode: UiBlendMode::default(),
+            depth_mode: UiDepthMode::DepthBuffer,
+            msaa_samples: UI_SAMPLE_COUNT,
+            fragment_shader: None,
+            stencil_role: UiStencilRole::Ignore,
+            stencil_buffer: false,
+        };
+
+        // The main passes of a 4x view target resolve into its swap chain texture, which the UI
+        // pass draws into with an unsampled color attachment, along with its own depth buffer.
+        let color_attachment = 1;
+        let depth_attachment = ui_depth_texture_descriptor(800, 600).sample_count;
+        let pipeline = key.descriptor().multisample.count;
+        assert_eq!(
+            validate_sample_counts(&[color_attachment], Some(depth_attachment), pipeline),
+            Ok(())
+        );
+
+        // Following the sample count of the main passes would need the multisampled texture of
+        // the view target, which doesn't hold what the main passes drew.
+        let multisampled = UiPipelineKey {
+            msaa_samples: msaa.samples,
+            ..key
+        };
+        assert!(validate_sample_counts(
+            &[color_attachment],
+            Some(depth_attachment),
+            multisampled.descriptor().multisample.count
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn nodes_of_a_batch_pack_into_one_vertex_buffer() {
+        use crate::render::UiVertex;
+
+        let colors = [0xff0000ffu32, 0xff00ff00, 0xffff0000];
+        let vertices: Vec<UiVertex> = colors
+            .iter()
+            .enumerate()
+            .flat_map(|(node, &color)| {
+                (0..6).map(move |_| UiVertex {
+                    position: [node as f32 * 10.0, 0.0, 0.0],
+                    uv: [0.0, 1.0],
+                    color,
+                    local_position: [0.0; 2],
+                    border: [5.0, 5.0, 0.0],
+                })
+            })
+            .collect();
+        let bytes: &[u8] = bytemuck::cast_slice(&vertices);
+
+        // A batch of three nodes is drawn with a single draw of 18 vertices
+        let descriptor = UiPipelineKey {
+            blend_mode: UiBlendMode::default(),
+            depth_mode: UiDepthMode::default(),
+            msaa_samples: 1,
+            fragment_shader: None,
+            stencil_role: UiStencilRole::Ignore,
+            stencil_buffer: false,
+        }
+        .descriptor();
+        let layout = &descriptor.vertex.buffers[0];
+        let stride = layout.array_stride as usize;
+        assert_eq!(stride, std::mem::size_of::<UiVertex>());
+        assert_eq!(bytes.len(), 3 * 6 * stride);
+
+        let color = &layout.attributes[2];
+        assert_eq!(color.format, VertexFormat::Uint32);
+        for (node, expected) in colors.iter().enumerate() {
+            let offset = node * 6 * stride + color.offset as usize;
+            assert_eq!(&bytes[offset..offset + 4], &expected.to_ne_bytes());
+        }
+    }
+
+    #[test]
+    fn ui_shader_replaces_the_fragment_stage() {
+        let default_key = UiPipelineKey {
+            blend_mode: UiBlendMode::default(),
+            depth_mode: UiDepthMode::default(),
+            msaa_samples: 1,
+            fragment_shader: None,
+            stencil_role: UiStencilRole::Ignore,
+            stencil_buffer: false,
+        };
+        let blur_shader = HandleId::random::<Shader>();
+        let blur_key = UiPipelineKey {
+            fragment_shader: Some(blur_shader),
+            ..default_key
+        };
+
+        let default_pipeline = default_key.descriptor();
+        let blur_pipeline = blur_key.descriptor();
+        let ui_shader = super::super::UI_SHADER_HANDLE.id;
+        assert_eq!(default_pipeline.fragment.unwrap().shader.id, ui_shader);
+        assert_eq!(blur_pipeline.fragment.unwrap().shader.id, blur_shader);
+        // The vertex stage is shared, so custom shaders receive the usual vertex output.
+        assert_eq!(blur_pipeline.vertex.shader.id, ui_shader);
+    }
+
+    #[test]
+    fn masked_nodes_test_the_stencil_written_by_their_mask() {
+        let key = |stencil_role| UiPipelineKey {
+            blend_mode: UiBlendMode::default(),
+            depth_mode: UiDepthMode::SortOnly,
+            msaa_samples: 1,
+            fragment_shader: None,
+            stencil_role,
+            stencil_buffer: true,
+        };
+        assert_eq!(
+            UiStencilRole::WriteMask.draws(),
+            &[UiStencilRole::Ignore, UiStencilRole::WriteMask]
+        );
+        let mask_color = key(UiStencilRole::Ignore).descriptor();
+        let mask = key(UiStencilRole::WriteMask).descriptor();
+        let masked = key(UiStencilRole::Masked).descriptor();
+        let unmasked = key(UiStencilRole::Ignore).descriptor();
+
+        let mask_stencil = mask.depth_stencil.unwrap().stencil;
+        assert_eq!(mask_stencil.front.compare, CompareFunction::Always);
+        assert_eq!(mask_stencil.front.pass_op, StencilOperation::Replace);
+        assert_eq!(mask_stencil.write_mask, !0);
+        assert!(mask
+            .vertex
+            .shader_defs
+            .contains(&"STENCIL_MASK".to_string()));
+        // The stencil draw discards the translucent parts of the mask, so it leaves the color to
+        // the first draw, which keeps them.
+        assert_eq!(
+            mask.fragment.unwrap().targets[0].write_mask,
+            ColorWrites::empty()
+        );
+        assert_eq!(
+            mask_color.fragment.unwrap().targets[0].write_mask,
+            ColorWrites::ALL
+        );
+        assert!(!mask_color
+            .vertex
+            .shader_defs
+            .contains(&"STENCIL_MASK".to_string()));
+
+        let masked_stencil = masked.depth_stencil.unwrap().stencil;
+        assert_eq!(masked_stencil.front.compare, CompareFunction::Equal);
+        assert_eq!(masked_stencil.write_mask, 0);
+        assert_eq!(masked_stencil.read_mask, !0);
+        assert!(!masked
+            .vertex
+            .shader_defs
+            .contains(&"STENCIL_MASK".to_string()));
+
+        // Everything drawn in the pass has to match its depth stencil attachment, masked or not.
+        let unmasked_depth_stencil = unmasked.depth_stencil.unwrap();
+        assert_eq!(unmasked_depth_stencil.format, UI_DEPTH_FORMAT);
+        assert!(!unmasked_depth_stencil.stencil.is_enabled());
+        assert!(!unmasked_depth_stencil.depth_write_enabled);
+    }
+}
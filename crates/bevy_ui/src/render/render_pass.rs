@@ -8,13 +8,26 @@ use bevy_render::{
     render_graph::*,
     render_phase::*,
     render_resource::{
-        CachedPipelineId, LoadOp, Operations, RenderPassColorAttachment, RenderPassDescriptor,
+        CachedPipelineId, LoadOp, Operations, RenderPassDepthStencilAttachment,
+        RenderPassDescriptor, Texture, TextureView,
     },
     renderer::*,
     view::*,
 };
 
-use super::{draw_ui_graph, UiBatch, UiImageBindGroups, UiMeta, CAMERA_UI};
+use super::{
+    draw_ui_graph, UiBatch, UiImageBindGroups, UiMeta, CAMERA_UI, UI_DEPTH_CLEAR_VALUE,
+    UI_SAMPLE_COUNT,
+};
+
+/// The depth and stencil buffer of a UI view, present if
+/// [`UiDepthMode::DepthBuffer`](super::UiDepthMode) is used or any node is masked.
+#[derive(Component)]
+pub struct ViewUiDepthTexture {
+    pub texture: Texture,
+    pub view: TextureView,
+    pub sample_count: u32,
+}
 
 pub struct UiPassDriverNode;
 
@@ -35,8 +48,14 @@ impl bevy_render::render_graph::Node for UiPassDriverNode {
 }
 
 pub struct UiPassNode {
-    query:
-        QueryState<(&'static RenderPhase<TransparentUi>, &'static ViewTarget), With<ExtractedView>>,
+    query: QueryState<
+        (
+            &'static RenderPhase<TransparentUi>,
+            &'static ViewTarget,
+            Option<&'static ViewUiDepthTexture>,
+        ),
+        With<ExtractedView>,
+    >,
 }
 
 impl UiPassNode {
@@ -65,21 +84,31 @@ impl bevy_render::render_graph::Node for UiPassNode {
         world: &World,
     ) -> Result<(), NodeRunError> {
         let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
-        let (transparent_phase, target) = self
-            .query
-            .get_manual(world, view_entity)
-            .expect("view entity should exist");
+        let (transparent_phase, target, depth) = match self.query.get_manual(world, view_entity) {
+            Ok(query) => query,
+            Err(_) => return Ok(()), // No window
+        };
+        // The unsampled color attachment always has a single sample
+        validate_sample_counts(&[1], depth.map(|depth| depth.sample_count), UI_SAMPLE_COUNT)?;
         let pass_descriptor = RenderPassDescriptor {
             label: Some("ui_pass"),
-            color_attachments: &[RenderPassColorAttachment {
-                view: &target.view,
-                resolve_target: None,
-                ops: Operations {
-                    load: LoadOp::Load,
-                    store: true,
-                },
-            }],
-            depth_stencil_attachment: None,
+            // The UI is drawn on top of what the main passes resolved into the swap chain texture,
+            // see `UI_SAMPLE_COUNT`.
+            color_attachments: &[target.get_unsampled_color_attachment(Operations {
+                load: LoadOp::Load,
+                store: true,
+            })],
+            depth_stencil_attachment: depth.map(|depth| RenderPassDepthStencilAttachment {
+                view: &depth.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(UI_DEPTH_CLEAR_VALUE),
+                    store: false,
+                }),
+                stencil_ops: Some(Operations {
+                    load: LoadOp::Clear(0),
+                    store: false,
+                }),
+            }),
         };
 
         let draw_functions = world
@@ -139,6 +168,7 @@ pub type DrawUi = (
     SetItemPipeline,
     SetUiViewBindGroup<0>,
     SetUiTextureBindGroup<1>,
+    SetUiStencilReference,
     DrawUiNode,
 );
 
@@ -174,7 +204,26 @@ impl<const I: usize> EntityRenderCommand for SetUiTextureBindGroup<I> {
         let batch = query_batch.get(item).unwrap();
         let image_bind_groups = image_bind_groups.into_inner();
 
-        pass.set_bind_group(I, image_bind_groups.values.get(&batch.image).unwrap(), &[]);
+        pass.set_bind_group(
+            I,
+            image_bind_groups.get(&batch.image, batch.sampler).unwrap(),
+            &[],
+        );
+        RenderCommandResult::Success
+    }
+}
+pub struct SetUiStencilReference;
+impl EntityRenderCommand for SetUiStencilReference {
+    type Param = SQuery<Read<UiBatch>>;
+
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        query_batch: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let batch = query_batch.get(item).unwrap();
+        pass.set_stencil_reference(batch.stencil_reference);
         RenderCommandResult::Success
     }
 }
@@ -195,3 +244,34 @@ impl EntityRenderCommand for DrawUiNode {
         RenderCommandResult::Success
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_transform::components::GlobalTransform;
+
+    #[test]
+    fn view_of_a_skipped_window_has_nothing_to_draw() {
+        let mut world = World::new();
+        // `prepare_windows` skips the frame of a window whose swap chain texture can't be
+        // acquired, so its views don't get a `ViewTarget`.
+        let view = world
+            .spawn()
+            .insert_bundle((
+                ExtractedView {
+                    projection: Default::default(),
+                    transform: GlobalTransform::identity(),
+                    width: 800,
+                    height: 600,
+                    near: 0.0,
+                    far: 1000.0,
+                },
+                RenderPhase::<TransparentUi>::default(),
+            ))
+            .id();
+
+        let mut node = UiPassNode::new(&mut world);
+        node.update(&mut world);
+        assert!(node.query.get_manual(&world, view).is_err());
+    }
+}
@@ -8,6 +8,8 @@ var<uniform> view: View;
 struct VertexOutput {
     [[location(0)]] uv: vec2<f32>;
     [[location(1)]] color: vec4<f32>;
+    [[location(2)]] local_position: vec2<f32>;
+    [[location(3)]] border: vec3<f32>;
     [[builtin(position)]] position: vec4<f32>;
 };
 
@@ -16,9 +18,13 @@ fn vertex(
     [[location(0)]] vertex_position: vec3<f32>,
     [[location(1)]] vertex_uv: vec2<f32>,
     [[location(2)]] vertex_color: u32,
+    [[location(3)]] vertex_local_position: vec2<f32>,
+    [[location(4)]] vertex_border: vec3<f32>,
 ) -> VertexOutput {
     var out: VertexOutput;
     out.uv = vertex_uv;
+    out.local_position = vertex_local_position;
+    out.border = vertex_border;
     out.position = view.view_proj * vec4<f32>(vertex_position, 1.0);
     out.color = vec4<f32>((vec4<u32>(vertex_color) >> vec4<u32>(0u, 8u, 16u, 24u)) & vec4<u32>(255u)) / 255.0;
     return out;
@@ -33,5 +39,25 @@ var sprite_sampler: sampler;
 fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     var color = textureSample(sprite_texture, sprite_sampler, in.uv); 
     color = in.color * color;
+    // Fade out the corners outside of the rounded rectangle, see `BorderRadius::coverage`
+    let radius = in.border.z;
+    if (radius > 0.0) {
+        let q = abs(in.local_position) - in.border.xy + vec2<f32>(radius);
+        let distance = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+        let t = clamp(distance + 0.5, 0.0, 1.0);
+        let coverage = 1.0 - t * t * (3.0 - 2.0 * t);
+#ifdef PREMULTIPLIED_ALPHA
+        color = color * coverage;
+#else
+        color.a = color.a * coverage;
+#endif
+    }
+#ifdef STENCIL_MASK
+    // Only the opaque parts of a mask let its descendants through. This draw only writes the
+    // stencil, the mask itself was drawn before without discarding anything.
+    if (color.a < 0.5) {
+        discard;
+    }
+#endif
     return color;
 }
\ No newline at end of file
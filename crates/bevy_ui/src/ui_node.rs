@@ -4,7 +4,8 @@ use bevy_math::{Rect, Size, Vec2};
 use bevy_reflect::{Reflect, ReflectDeserialize};
 use bevy_render::{
     color::Color,
-    texture::{Image, DEFAULT_IMAGE_HANDLE},
+    render_resource::{BlendMode, BlendState, FilterMode, SamplerDescriptor, Shader},
+    texture::{Image, ImageAddressModes, DEFAULT_IMAGE_HANDLE},
 };
 use serde::{Deserialize, Serialize};
 use std::ops::{Add, AddAssign};
@@ -378,12 +379,313 @@ impl Default for UiImage {
     }
 }
 
+/// A fragment shader drawing the node instead of the default UI shader, e.g. for blurred panels or
+/// animated gradients
+///
+/// The shader must have a `fragment` entry point taking the vertex output of the default UI shader,
+/// and may use the same view and image bind groups. Nodes are batched by shader, so nodes sharing a
+/// shader are still drawn together.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct UiShader(pub Handle<Shader>);
+
+/// The sampler the [`UiImage`] of the node is drawn with, e.g. to tile a background with
+/// [`ImageAddressModes::repeat_all`] or keep pixel art crisp with [`FilterMode::Nearest`]
+///
+/// Nodes without this component use the sampler of their image.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Reflect)]
+#[reflect_value(Component, PartialEq, Hash)]
+pub struct UiSampler {
+    /// How texture coordinates outside of the image are sampled
+    pub address_modes: ImageAddressModes,
+    /// How the image is filtered when magnified, minified and between mip levels
+    pub filter: FilterMode,
+}
+
+impl UiSampler {
+    /// The descriptor of the sampler replacing the one of the image
+    pub fn descriptor(&self) -> SamplerDescriptor<'static> {
+        let mut descriptor = SamplerDescriptor {
+            label: Some("ui_sampler"),
+            mag_filter: self.filter,
+            min_filter: self.filter,
+            mipmap_filter: self.filter,
+            ..Default::default()
+        };
+        self.address_modes.apply_to(&mut descriptor);
+        descriptor
+    }
+}
+
+/// The region of the [`UiImage`] drawn on the node, e.g. an icon packed into a texture atlas
+///
+/// The corners are given in normalized texture coordinates, from `(0.0, 0.0)` at the top left of
+/// the image to `(1.0, 1.0)` at its bottom right. Nodes without this component draw the whole image.
+#[derive(Component, Copy, Clone, PartialEq, Debug, Reflect)]
+#[reflect(Component, PartialEq)]
+pub struct UiAtlasRect {
+    /// The top left corner of the region
+    pub min_uv: Vec2,
+    /// The bottom right corner of the region
+    pub max_uv: Vec2,
+}
+
+impl Default for UiAtlasRect {
+    fn default() -> Self {
+        Self {
+            min_uv: Vec2::ZERO,
+            max_uv: Vec2::ONE,
+        }
+    }
+}
+
+impl UiAtlasRect {
+    /// Returns the region of the cell at `index` of a grid of `columns` by `rows` equally sized
+    /// cells, counted row by row from the top left
+    pub fn from_grid(index: usize, columns: usize, rows: usize) -> Self {
+        let cell_size = Vec2::new(1.0 / columns as f32, 1.0 / rows as f32);
+        let min_uv = Vec2::new((index % columns) as f32, (index / columns) as f32) * cell_size;
+        Self {
+            min_uv,
+            max_uv: min_uv + cell_size,
+        }
+    }
+
+    /// Maps `uv`, relative to the whole node, into the region
+    pub fn map_uv(&self, uv: Vec2) -> Vec2 {
+        self.min_uv + uv * (self.max_uv - self.min_uv)
+    }
+}
+
+/// Draws the [`UiImage`] of a node as a nine-patch, so that its borders keep their size when the
+/// node is resized
+///
+/// The image is cut into a 3x3 grid by the `border` insets, in pixels of the image. The corners
+/// are drawn at the size of the image, one logical pixel per image pixel, the edges are stretched
+/// along the node's sides and the center is stretched in both directions. If the node is smaller
+/// than its borders, the borders shrink proportionally. The whole image is used, ignoring any
+/// [`UiAtlasRect`] of the node.
+#[derive(Component, Copy, Clone, PartialEq, Debug, Default, Reflect)]
+#[reflect(Component, PartialEq)]
+pub struct NinePatch {
+    /// The width of the left and right borders and the height of the top and bottom borders
+    pub border: Rect<f32>,
+}
+
+/// One of the nine regions of a [`NinePatch`], as returned by [`NinePatch::slices`]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct NinePatchSlice {
+    /// The center of the region, relative to the center of the node, with y pointing up
+    pub center: Vec2,
+    /// The size of the region on the node
+    pub size: Vec2,
+    /// The region of the image drawn onto it
+    pub atlas_rect: UiAtlasRect,
+}
+
+impl NinePatch {
+    /// Cuts a node of `node_size`, drawing an image of `image_size`, into its nine regions, row by
+    /// row from the bottom left. Regions without an area, e.g. the edges of a border of width
+    /// zero, are skipped.
+    pub fn slices(&self, node_size: Vec2, image_size: Vec2) -> Vec<NinePatchSlice> {
+        let horizontal_scale = (node_size.x / (self.border.left + self.border.right)).min(1.0);
+        let vertical_scale = (node_size.y / (self.border.top + self.border.bottom)).min(1.0);
+        let (left, right) = (
+            self.border.left * horizontal_scale,
+            self.border.right * horizontal_scale,
+        );
+        let (bottom, top) = (
+            self.border.bottom * vertical_scale,
+            self.border.top * vertical_scale,
+        );
+
+        let half_size = node_size / 2.0;
+        let xs = [
+            -half_size.x,
+            -half_size.x + left,
+            half_size.x - right,
+            half_size.x,
+        ];
+        let ys = [
+            -half_size.y,
+            -half_size.y + bottom,
+            half_size.y - top,
+            half_size.y,
+        ];
+        // Texture coordinates point down, so the bottom row starts at the bottom of the image.
+        let us = [
+            0.0,
+            self.border.left / image_size.x,
+            1.0 - self.border.right / image_size.x,
+            1.0,
+        ];
+        let vs = [
+            1.0,
+            1.0 - self.border.bottom / image_size.y,
+            self.border.top / image_size.y,
+            0.0,
+        ];
+
+        let mut slices = Vec::with_capacity(9);
+        for row in 0..3 {
+            for column in 0..3 {
+                let min = Vec2::new(xs[column], ys[row]);
+                let max = Vec2::new(xs[column + 1], ys[row + 1]);
+                let size = max - min;
+                if size.x <= 0.0 || size.y <= 0.0 {
+                    continue;
+                }
+                slices.push(NinePatchSlice {
+                    center: (min + max) / 2.0,
+                    size,
+                    atlas_rect: UiAtlasRect {
+                        min_uv: Vec2::new(us[column], vs[row + 1]),
+                        max_uv: Vec2::new(us[column + 1], vs[row]),
+                    },
+                });
+            }
+        }
+        slices
+    }
+}
+
 impl From<Handle<Image>> for UiImage {
     fn from(handle: Handle<Image>) -> Self {
         Self(handle)
     }
 }
 
+/// How the color output of a node is blended with what has already been drawn behind it
+///
+/// With straight alpha the color channels of an image are independent of its alpha channel, and
+/// the blending multiplies them by alpha. Premultiplied images have already had their color
+/// channels multiplied by alpha, which keeps edges of filtered or composited images free of dark
+/// fringes, but must be drawn with [`UiBlendMode::PremultipliedAlpha`] to avoid applying alpha twice.
+///
+/// Nodes without this component use [`UiBlendMode::AlphaBlend`].
+#[derive(Component, Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize, Reflect)]
+#[reflect_value(Component, PartialEq, Serialize, Deserialize)]
+pub enum UiBlendMode {
+    /// Straight (non-premultiplied) alpha blending
+    AlphaBlend,
+    /// Blending for images whose color channels are premultiplied by their alpha
+    PremultipliedAlpha,
+}
+
+impl Default for UiBlendMode {
+    fn default() -> Self {
+        UiBlendMode::AlphaBlend
+    }
+}
+
+impl UiBlendMode {
+    /// The [`BlendState`] used by the UI pipeline for this blend mode
+    pub fn blend_state(self) -> BlendState {
+        BlendMode::from(self).blend_state()
+    }
+}
+
+impl From<UiBlendMode> for BlendMode {
+    fn from(blend_mode: UiBlendMode) -> Self {
+        match blend_mode {
+            UiBlendMode::AlphaBlend => BlendMode::StraightAlpha,
+            UiBlendMode::PremultipliedAlpha => BlendMode::Premultiplied,
+        }
+    }
+}
+
+/// Pins a node to an anchor point of its parent, or of the window for root nodes
+///
+/// After the flex layout is computed, the node is moved so that its matching edge (or center)
+/// lines up with the anchor point, plus the [`Anchor::offset`]. The anchored position is computed
+/// again whenever the layout is, so e.g. a node anchored to the [`AnchorPoint::End`] horizontally
+/// stays pinned to the right edge when its parent is resized.
+///
+/// The node still takes up space in the flex layout of its siblings, unless it uses
+/// [`PositionType::Absolute`].
+#[derive(Component, Copy, Clone, PartialEq, Debug, Default, Reflect)]
+#[reflect(Component, PartialEq)]
+pub struct Anchor {
+    /// The horizontal anchor point, where [`AnchorPoint::Start`] is the left edge
+    pub horizontal: AnchorPoint,
+    /// The vertical anchor point, where [`AnchorPoint::Start`] is the bottom edge
+    pub vertical: AnchorPoint,
+    /// The offset from the anchor point, in logical pixels, with y pointing up
+    pub offset: Vec2,
+}
+
+impl Anchor {
+    /// Returns the center of a node of `node_size` anchored within a container of
+    /// `container_size`, relative to the bottom left corner of the container
+    pub fn node_center(&self, container_size: Vec2, node_size: Vec2) -> Vec2 {
+        Vec2::new(
+            self.horizontal.position(container_size.x, node_size.x),
+            self.vertical.position(container_size.y, node_size.y),
+        ) + self.offset
+    }
+}
+
+/// A point along one axis of a container, used by [`Anchor`]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize, Reflect)]
+#[reflect_value(PartialEq, Serialize, Deserialize)]
+pub enum AnchorPoint {
+    /// The left or bottom edge
+    Start,
+    /// The center
+    Center,
+    /// The right or top edge
+    End,
+}
+
+impl Default for AnchorPoint {
+    fn default() -> AnchorPoint {
+        AnchorPoint::Start
+    }
+}
+
+impl AnchorPoint {
+    /// Returns the center of a node of `node_length` anchored to this point, along an axis of
+    /// `container_length`
+    fn position(self, container_length: f32, node_length: f32) -> f32 {
+        match self {
+            AnchorPoint::Start => node_length / 2.0,
+            AnchorPoint::Center => container_length / 2.0,
+            AnchorPoint::End => container_length - node_length / 2.0,
+        }
+    }
+}
+
+/// Rounds the corners of a node, by a radius in logical pixels
+///
+/// The radius is clamped to half of the shorter side of the node, and the rounded edge fades out
+/// over one pixel. Nodes without this component, or with a radius of zero, are drawn with square
+/// corners.
+#[derive(Component, Copy, Clone, PartialEq, Debug, Default, Reflect)]
+#[reflect(Component, PartialEq)]
+pub struct BorderRadius(pub f32);
+
+impl BorderRadius {
+    /// Returns the radius used for a node of `size`
+    pub fn clamped(self, size: Vec2) -> f32 {
+        self.0.min(size.min_element() / 2.0).max(0.0)
+    }
+
+    /// Returns how much of the pixel at `position`, relative to the center of a node of `size`,
+    /// is covered by the node, from `0.0` outside its rounded corners to `1.0` inside them
+    ///
+    /// This matches the coverage computed by the UI shader.
+    pub fn coverage(self, position: Vec2, size: Vec2) -> f32 {
+        let radius = self.clamped(size);
+        if radius <= 0.0 {
+            return 1.0;
+        }
+        let q = position.abs() - size / 2.0 + Vec2::splat(radius);
+        let distance = q.max(Vec2::ZERO).length() + q.max_element().min(0.0) - radius;
+        let t = (distance + 0.5).clamp(0.0, 1.0);
+        1.0 - t * t * (3.0 - 2.0 * t)
+    }
+}
+
 /// The calculated clip of the node
 #[derive(Component, Default, Copy, Clone, Debug, Reflect)]
 #[reflect(Component)]
@@ -391,3 +693,156 @@ pub struct CalculatedClip {
     /// The rect of the clip
     pub clip: bevy_sprite::Rect,
 }
+
+/// Masks the descendants of the node to its shape
+///
+/// The node itself is drawn as usual, and its descendants are only drawn where it is opaque. Unlike
+/// [`Overflow::Hidden`], this follows the rounded corners and the transparent parts of the image of
+/// the node, e.g. for circular avatars. Masks don't nest: nodes are only masked by their nearest
+/// ancestor with a `UiMask`, and at most 255 masks are told apart. Pixels of the mask with an
+/// alpha of at least 0.5 let its descendants through, while the mask itself keeps its soft edges.
+#[derive(Component, Default, Copy, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct UiMask;
+
+/// The calculated stencil mask of a [`UiMask`] node and its descendants
+#[derive(Component, Default, Copy, Clone, PartialEq, Debug, Reflect)]
+#[reflect(Component, PartialEq)]
+pub struct CalculatedMask {
+    /// The stencil reference value the mask writes, and its descendants are drawn on
+    pub reference: u8,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Anchor, AnchorPoint, BorderRadius, NinePatch, UiAtlasRect, UiBlendMode, UiSampler,
+    };
+    use bevy_math::{Rect, Vec2};
+    use bevy_render::{
+        render_resource::{
+            AddressMode, BlendComponent, BlendFactor, BlendMode, BlendOperation, FilterMode,
+        },
+        texture::ImageAddressModes,
+    };
+
+    #[test]
+    fn premultiplied_alpha_does_not_scale_source_color() {
+        let blend = UiBlendMode::PremultipliedAlpha.blend_state();
+        let expected = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add,
+        };
+        assert_eq!(blend.color, expected);
+        assert_eq!(blend.alpha, expected);
+    }
+
+    #[test]
+    fn alpha_blend_scales_source_color_by_alpha() {
+        let blend = UiBlendMode::default().blend_state();
+        assert_eq!(blend.color.src_factor, BlendFactor::SrcAlpha);
+        assert_eq!(blend.color.dst_factor, BlendFactor::OneMinusSrcAlpha);
+        assert_ne!(blend, UiBlendMode::PremultipliedAlpha.blend_state());
+        assert_eq!(
+            BlendMode::from(UiBlendMode::default()),
+            BlendMode::default()
+        );
+    }
+
+    #[test]
+    fn right_anchored_node_tracks_parent_width() {
+        let anchor = Anchor {
+            horizontal: AnchorPoint::End,
+            vertical: AnchorPoint::Center,
+            offset: Vec2::new(-10.0, 0.0),
+        };
+        let node_size = Vec2::new(40.0, 20.0);
+
+        let narrow = anchor.node_center(Vec2::new(200.0, 100.0), node_size);
+        assert_eq!(narrow, Vec2::new(170.0, 50.0));
+        let wide = anchor.node_center(Vec2::new(500.0, 100.0), node_size);
+        assert_eq!(wide, Vec2::new(470.0, 50.0));
+
+        let left = Anchor::default().node_center(Vec2::new(500.0, 100.0), node_size);
+        assert_eq!(left, Vec2::new(20.0, 10.0));
+    }
+
+    #[test]
+    fn rounded_corners_are_transparent() {
+        let size = Vec2::new(100.0, 50.0);
+        let corner = Vec2::new(49.5, 24.5);
+
+        let rounded = BorderRadius(10.0);
+        assert_eq!(rounded.coverage(corner, size), 0.0);
+        assert_eq!(rounded.coverage(Vec2::ZERO, size), 1.0);
+        assert_eq!(rounded.coverage(Vec2::new(49.5, 0.0), size), 1.0);
+
+        assert_eq!(BorderRadius(0.0).coverage(corner, size), 1.0);
+        assert_eq!(BorderRadius(100.0).clamped(size), 25.0);
+    }
+
+    #[test]
+    fn atlas_rect_maps_node_uvs_into_its_cell() {
+        let full = UiAtlasRect::default();
+        assert_eq!(full.map_uv(Vec2::new(0.25, 0.75)), Vec2::new(0.25, 0.75));
+
+        // The second cell of the second row of a 4x2 grid
+        let icon = UiAtlasRect::from_grid(5, 4, 2);
+        assert_eq!(icon.min_uv, Vec2::new(0.25, 0.5));
+        assert_eq!(icon.max_uv, Vec2::new(0.5, 1.0));
+        assert_eq!(icon.map_uv(Vec2::ZERO), icon.min_uv);
+        assert_eq!(icon.map_uv(Vec2::ONE), icon.max_uv);
+        assert_eq!(icon.map_uv(Vec2::splat(0.5)), Vec2::new(0.375, 0.75));
+    }
+
+    #[test]
+    fn nine_patch_keeps_corners_at_image_size() {
+        let nine_patch = NinePatch {
+            border: Rect {
+                left: 8.0,
+                right: 8.0,
+                top: 4.0,
+                bottom: 4.0,
+            },
+        };
+        let slices = nine_patch.slices(Vec2::new(100.0, 40.0), Vec2::new(32.0, 16.0));
+        assert_eq!(slices.len(), 9);
+
+        let bottom_left = slices[0];
+        assert_eq!(bottom_left.size, Vec2::new(8.0, 4.0));
+        assert_eq!(bottom_left.center, Vec2::new(-46.0, -18.0));
+        assert_eq!(bottom_left.atlas_rect.min_uv, Vec2::new(0.0, 0.75));
+        assert_eq!(bottom_left.atlas_rect.max_uv, Vec2::new(0.25, 1.0));
+
+        let center = slices[4];
+        assert_eq!(center.size, Vec2::new(84.0, 32.0));
+        assert_eq!(center.center, Vec2::ZERO);
+        assert_eq!(center.atlas_rect.min_uv, Vec2::new(0.25, 0.25));
+        assert_eq!(center.atlas_rect.max_uv, Vec2::new(0.75, 0.75));
+
+        let top_right = slices[8];
+        assert_eq!(top_right.size, Vec2::new(8.0, 4.0));
+        assert_eq!(top_right.center, Vec2::new(46.0, 18.0));
+        assert_eq!(top_right.atlas_rect.min_uv, Vec2::new(0.75, 0.0));
+        assert_eq!(top_right.atlas_rect.max_uv, Vec2::new(1.0, 0.25));
+
+        // Borders wider than the node shrink, leaving no room for the center column.
+        let narrow = nine_patch.slices(Vec2::new(8.0, 40.0), Vec2::new(32.0, 16.0));
+        assert_eq!(narrow.len(), 6);
+        assert_eq!(narrow[0].size, Vec2::new(4.0, 4.0));
+    }
+
+    #[test]
+    fn ui_sampler_repeats_pixel_art() {
+        let descriptor = UiSampler {
+            address_modes: ImageAddressModes::repeat_all(),
+            filter: FilterMode::Nearest,
+        }
+        .descriptor();
+        assert_eq!(descriptor.address_mode_u, AddressMode::Repeat);
+        assert_eq!(descriptor.address_mode_v, AddressMode::Repeat);
+        assert_eq!(descriptor.mag_filter, FilterMode::Nearest);
+        assert_eq!(descriptor.min_filter, FilterMode::Nearest);
+    }
+}
@@ -1,6 +1,6 @@
 //! This module contains systems that update the UI when something changes
 
-use crate::{CalculatedClip, Overflow, Style};
+use crate::{CalculatedClip, CalculatedMask, Overflow, Style, UiMask};
 
 use super::Node;
 use bevy_ecs::{
@@ -129,6 +129,72 @@ fn update_clipping(
     }
 }
 
+/// Updates the [`CalculatedMask`] of [`UiMask`] nodes and their descendants
+pub fn update_mask_system(
+    mut commands: Commands,
+    root_node_query: Query<Entity, (With<Node>, Without<Parent>)>,
+    mut node_query: Query<(Option<&UiMask>, Option<&mut CalculatedMask>), With<Node>>,
+    children_query: Query<&Children>,
+) {
+    // The stencil buffer is cleared to 0, which is never used as a reference.
+    let mut next_reference = 1;
+    for root_node in root_node_query.iter() {
+        update_mask(
+            &mut commands,
+            &children_query,
+            &mut node_query,
+            root_node,
+            None,
+            &mut next_reference,
+        );
+    }
+}
+
+fn update_mask(
+    commands: &mut Commands,
+    children_query: &Query<&Children>,
+    node_query: &mut Query<(Option<&UiMask>, Option<&mut CalculatedMask>), With<Node>>,
+    entity: Entity,
+    mut mask: Option<CalculatedMask>,
+    next_reference: &mut u8,
+) {
+    if let Ok((ui_mask, calculated_mask)) = node_query.get_mut(entity) {
+        if ui_mask.is_some() {
+            mask = Some(CalculatedMask {
+                reference: *next_reference,
+            });
+            *next_reference = next_reference.checked_add(1).unwrap_or(1);
+        }
+        match (mask, calculated_mask) {
+            (None, None) => {}
+            (None, Some(_)) => {
+                commands.entity(entity).remove::<CalculatedMask>();
+            }
+            (Some(mask), None) => {
+                commands.entity(entity).insert(mask);
+            }
+            (Some(mask), Some(mut old_mask)) => {
+                if *old_mask != mask {
+                    *old_mask = mask;
+                }
+            }
+        }
+    }
+
+    if let Ok(children) = children_query.get(entity) {
+        for child in children.iter().cloned() {
+            update_mask(
+                commands,
+                children_query,
+                node_query,
+                child,
+                mask,
+                next_reference,
+            );
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use bevy_ecs::{
@@ -137,11 +203,15 @@ mod tests {
         system::{CommandQueue, Commands},
         world::World,
     };
-    use bevy_transform::{components::Transform, hierarchy::BuildChildren};
+    use bevy_math::Vec2;
+    use bevy_transform::{
+        components::{GlobalTransform, Transform},
+        hierarchy::BuildChildren,
+    };
 
-    use crate::Node;
+    use crate::{CalculatedClip, CalculatedMask, Node, Overflow, Style, UiMask};
 
-    use super::{ui_z_system, UI_Z_STEP};
+    use super::{ui_z_system, update_clipping_system, update_mask_system, UI_Z_STEP};
 
     #[derive(Component, PartialEq, Debug, Clone)]
     struct Label(&'static str);
@@ -236,4 +306,105 @@ mod tests {
         ];
         assert_eq!(actual_result, expected_result);
     }
+
+    #[test]
+    fn nested_clips_intersect() {
+        let mut world = World::default();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let clipping_node = |x: f32, y: f32, overflow: Overflow| {
+            (
+                Node {
+                    size: Vec2::new(100.0, 100.0),
+                },
+                GlobalTransform::from_xyz(x, y, 0.0),
+                Style {
+                    overflow,
+                    ..Default::default()
+                },
+            )
+        };
+        let mut inner = None;
+        let mut leaf = None;
+        let outer = commands
+            .spawn_bundle(clipping_node(50.0, 50.0, Overflow::Hidden))
+            .with_children(|parent| {
+                inner = Some(
+                    parent
+                        .spawn_bundle(clipping_node(80.0, 70.0, Overflow::Hidden))
+                        .with_children(|parent| {
+                            leaf = Some(
+                                parent
+                                    .spawn_bundle(clipping_node(0.0, 0.0, Overflow::Visible))
+                                    .id(),
+                            );
+                        })
+                        .id(),
+                );
+            })
+            .id();
+        queue.apply(&mut world);
+
+        SystemStage::single(update_clipping_system).run(&mut world);
+
+        assert!(world.get::<CalculatedClip>(outer).is_none());
+        let clip = |entity| world.get::<CalculatedClip>(entity).unwrap().clip;
+        let inner_clip = clip(inner.unwrap());
+        assert_eq!(inner_clip.min, Vec2::new(0.0, 0.0));
+        assert_eq!(inner_clip.max, Vec2::new(100.0, 100.0));
+        // The leaf is clipped to the part of the inner node inside the outer node.
+        let leaf_clip = clip(leaf.unwrap());
+        assert_eq!(leaf_clip.min, Vec2::new(30.0, 20.0));
+        assert_eq!(leaf_clip.max, Vec2::new(100.0, 100.0));
+    }
+
+    #[test]
+    fn masks_apply_to_their_descendants() {
+        let mut world = World::default();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut picture = None;
+        let mut badge = None;
+        let mut icon = None;
+        let unmasked = commands.spawn_bundle(node_with_transform("unmasked")).id();
+        let avatar = commands
+            .spawn_bundle(node_with_transform("avatar"))
+            .insert(UiMask)
+            .with_children(|parent| {
+                picture = Some(parent.spawn_bundle(node_with_transform("picture")).id());
+                badge = Some(
+                    parent
+                        .spawn_bundle(node_with_transform("badge"))
+                        .insert(UiMask)
+                        .with_children(|parent| {
+                            icon = Some(parent.spawn_bundle(node_with_transform("icon")).id());
+                        })
+                        .id(),
+                );
+            })
+            .id();
+        let (picture, badge, icon) = (picture.unwrap(), badge.unwrap(), icon.unwrap());
+        queue.apply(&mut world);
+
+        let mut stage = SystemStage::single(update_mask_system);
+        stage.run(&mut world);
+
+        let reference = |world: &World, entity| {
+            world
+                .get::<CalculatedMask>(entity)
+                .map(|mask| mask.reference)
+        };
+        assert_eq!(reference(&world, unmasked), None);
+        assert_eq!(reference(&world, avatar), Some(1));
+        assert_eq!(reference(&world, picture), Some(1));
+        // Masks don't nest, the badge masks its icon on its own.
+        assert_eq!(reference(&world, badge), Some(2));
+        assert_eq!(reference(&world, icon), Some(2));
+
+        world.entity_mut(avatar).remove::<UiMask>();
+        stage.run(&mut world);
+        assert_eq!(reference(&world, avatar), None);
+        assert_eq!(reference(&world, picture), None);
+        assert_eq!(reference(&world, icon), Some(1));
+    }
 }
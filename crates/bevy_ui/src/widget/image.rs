@@ -26,6 +26,9 @@ impl Default for ImageMode {
 }
 
 /// Updates calculated size of the node based on the image provided
+///
+/// Nodes are thus laid out at the native size of their image, unless their [`Style`](crate::Style)
+/// sets a size. The size follows the image when it is modified or replaced.
 pub fn image_node_system(
     textures: Res<Assets<Image>>,
     mut query: Query<(&mut CalculatedSize, &UiImage), With<ImageMode>>,
@@ -43,3 +46,64 @@ pub fn image_node_system(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_app::App;
+    use bevy_asset::{AddAsset, AssetPlugin};
+    use bevy_core::CorePlugin;
+    use bevy_ecs::schedule::{Stage, SystemStage};
+    use bevy_render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+    fn image(width: u32, height: u32) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255, 255, 255, 255],
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    #[test]
+    fn image_node_adopts_texture_size() {
+        let mut app = App::new();
+        app.add_plugin(CorePlugin)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Image>();
+        let world = &mut app.world;
+        let handle = world
+            .get_resource_mut::<Assets<Image>>()
+            .unwrap()
+            .add(image(64, 32));
+        let node = world
+            .spawn()
+            .insert_bundle((
+                CalculatedSize::default(),
+                UiImage(handle.clone()),
+                ImageMode::KeepAspect,
+            ))
+            .id();
+
+        let mut stage = SystemStage::single(image_node_system);
+        stage.run(world);
+        assert_eq!(
+            world.get::<CalculatedSize>(node).unwrap().size,
+            Size::new(64.0, 32.0)
+        );
+
+        world
+            .get_resource_mut::<Assets<Image>>()
+            .unwrap()
+            .set_untracked(&handle, image(16, 48));
+        stage.run(world);
+        assert_eq!(
+            world.get::<CalculatedSize>(node).unwrap().size,
+            Size::new(16.0, 48.0)
+        );
+    }
+}
@@ -135,13 +135,13 @@ impl FromWorld for GameOfLifePipeline {
             bind_group_layouts: &[&texture_bind_group_layout],
             push_constant_ranges: &[],
         });
-        let init_pipeline = render_device.create_compute_pipeline(&ComputePipelineDescriptor {
+        let init_pipeline = render_device.create_compute_pipeline(&RawComputePipelineDescriptor {
             label: None,
             layout: Some(&pipeline_layout),
             module: &shader,
             entry_point: "init",
         });
-        let sim_pipeline = render_device.create_compute_pipeline(&ComputePipelineDescriptor {
+        let sim_pipeline = render_device.create_compute_pipeline(&RawComputePipelineDescriptor {
             label: None,
             layout: Some(&pipeline_layout),
             module: &shader,